@@ -16,24 +16,26 @@ use solana_client::{
 };
 use solana_program::{
     borsh::try_from_slice_unchecked,
-    msg, program_pack::Pack, pubkey::Pubkey, rent::Rent,
+    clock::Slot,
+    msg, pubkey::Pubkey,
 };
 use solana_sdk::{
     self, commitment_config::CommitmentConfig, signature::Keypair, signature::Signer,
     account::Account,
-    system_instruction,
-    instruction::Instruction,
     transaction::Transaction,
 };
 use spl_associated_token_account::{get_associated_token_address};
 use std::convert::TryInto;
 use spl_governance_addin_vesting::{
-    state::{ VestingRecord, VestingSchedule },
+    state::{ VestingAccountType, VestingRecord, VestingRecordV1, VestingSchedule, get_vesting_token_address },
     instruction::{
-        deposit, deposit_with_realm, withdraw, withdraw_with_realm, change_owner, change_owner_with_realm,
+        deposit, deposit_with_realm, withdraw, withdraw_with_realm, withdraw_out_of_realm,
+        change_owner, change_owner_with_realm,
         create_voter_weight_record, set_vote_percentage_with_realm, split, split_with_realm,
+        attach_realm, detach_realm, accelerate_release, set_label, set_tge_timestamp,
+        update_vesting_index, with_created_vesting_token_accounts, migrate_vesting_record,
     },
-    voter_weight::get_voter_weight_record_address,
+    vesting_index::{ VestingIndex, get_vesting_index_address },
 };
 
 // Lock the vesting contract
@@ -47,6 +49,7 @@ fn command_deposit_svc(
     vesting_owner_pubkey: Pubkey,
     mint_pubkey: Pubkey,
     schedules: Vec<VestingSchedule>,
+    label: Option<String>,
     confirm: bool,
 ) {
     // If no source token account was given, use the associated source account
@@ -55,34 +58,28 @@ fn command_deposit_svc(
         _ => possible_source_token_pubkey.unwrap(),
     };
 
-    let vesting_token_keypair = Keypair::new();
-    let vesting_token_pubkey = vesting_token_keypair.pubkey();
+    // The vesting token account is created by the vesting program, the seed only has to be unique
+    let vesting_token_seed = Keypair::new().pubkey();
+    let vesting_token_pubkey = get_vesting_token_address(&vesting_addin_program_id, &vesting_token_seed);
 
     let (vesting_pubkey,_) = Pubkey::find_program_address(&[vesting_token_pubkey.as_ref()], &vesting_addin_program_id);
 
     let instructions = [
-        system_instruction::create_account(
-            &source_token_owner.pubkey(),
-            &vesting_token_pubkey,
-            Rent::default().minimum_balance(spl_token::state::Account::LEN),
-            spl_token::state::Account::LEN as u64,
-            &spl_token::id()
-        ),
-        spl_token::instruction::initialize_account(
-            &spl_token::id(), 
-            &vesting_token_pubkey,
-            &mint_pubkey, 
-            &vesting_pubkey
-        ).unwrap(),
-        deposit(
-            &vesting_addin_program_id,
-            &spl_token::id(),
-            &vesting_token_pubkey,
-            &source_token_owner.pubkey(),
-            &source_token_pubkey,
-            &vesting_owner_pubkey,
-            &payer.pubkey(),
-            schedules,
+        with_created_vesting_token_accounts(
+            deposit(
+                &vesting_addin_program_id,
+                &spl_token::id(),
+                &vesting_token_pubkey,
+                &source_token_owner.pubkey(),
+                &source_token_pubkey,
+                &vesting_owner_pubkey,
+                &payer.pubkey(),
+                schedules,
+                label,
+            )
+            .unwrap(),
+            &mint_pubkey,
+            &[vesting_token_seed],
         )
         .unwrap(),
     ];
@@ -90,7 +87,7 @@ fn command_deposit_svc(
     let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
 
     let latest_blockhash = rpc_client.get_latest_blockhash().unwrap();
-    transaction.sign(&[&payer, &vesting_token_keypair, &source_token_owner], latest_blockhash);
+    transaction.sign(&[&payer, &source_token_owner], latest_blockhash);
 
     msg!("Vesting addin program id: {:?}", vesting_addin_program_id,);
     msg!("SPL Token program id: {:?}", spl_token::id(),);
@@ -126,6 +123,8 @@ fn command_deposit_with_realm_svc(
     mint_pubkey: Pubkey,
     realm_pubkey: Pubkey,
     schedules: Vec<VestingSchedule>,
+    label: Option<String>,
+    tge_relative: bool,
     confirm: bool,
 ) {
     // If no source token account was given, use the associated source account
@@ -134,36 +133,32 @@ fn command_deposit_with_realm_svc(
         _ => possible_source_token_pubkey.unwrap(),
     };
 
-    let vesting_token_keypair = Keypair::new();
-    let vesting_token_pubkey = vesting_token_keypair.pubkey();
+    // The vesting token account is created by the vesting program, the seed only has to be unique
+    let vesting_token_seed = Keypair::new().pubkey();
+    let vesting_token_pubkey = get_vesting_token_address(&vesting_addin_program_id, &vesting_token_seed);
 
     let (vesting_pubkey,_) = Pubkey::find_program_address(&[vesting_token_pubkey.as_ref()], &vesting_addin_program_id);
 
     let instructions = [
-        system_instruction::create_account(
-            &source_token_owner.pubkey(),
-            &vesting_token_pubkey,
-            Rent::default().minimum_balance(spl_token::state::Account::LEN),
-            spl_token::state::Account::LEN as u64,
-            &spl_token::id()
-        ),
-        spl_token::instruction::initialize_account(
-            &spl_token::id(), 
-            &vesting_token_pubkey,
-            &mint_pubkey, 
-            &vesting_pubkey
-        ).unwrap(),
-        deposit_with_realm(
-            &vesting_addin_program_id,
-            &spl_token::id(),
-            &vesting_token_pubkey,
-            &source_token_owner.pubkey(),
-            &source_token_pubkey,
-            &vesting_owner_pubkey,
-            &payer.pubkey(),
-            schedules,
-            &realm_pubkey,
+        with_created_vesting_token_accounts(
+            deposit_with_realm(
+                &vesting_addin_program_id,
+                &spl_token::id(),
+                &vesting_token_pubkey,
+                &source_token_owner.pubkey(),
+                &source_token_pubkey,
+                &vesting_owner_pubkey,
+                &payer.pubkey(),
+                schedules,
+                &realm_pubkey,
+                &mint_pubkey,
+                &governance_program_id,
+                label,
+                tge_relative,
+            )
+            .unwrap(),
             &mint_pubkey,
+            &[vesting_token_seed],
         )
         .unwrap(),
     ];
@@ -171,7 +166,7 @@ fn command_deposit_with_realm_svc(
     let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
 
     let latest_blockhash = rpc_client.get_latest_blockhash().unwrap();
-    transaction.sign(&[&payer, &vesting_token_keypair, &source_token_owner], latest_blockhash);
+    transaction.sign(&[&payer, &source_token_owner], latest_blockhash);
 
     msg!("Vesting addin program id: {:?}", vesting_addin_program_id,);
     msg!("SPL Token program id: {:?}", spl_token::id(),);
@@ -203,6 +198,7 @@ fn command_withdraw_svc(
     vesting_owner: Keypair,
     vesting_token_pubkey: Pubkey,
     destination_token_pubkey: Pubkey,
+    release_proposals: Vec<Pubkey>,
 ) {
 
     let withdraw_instruction = withdraw(
@@ -211,6 +207,7 @@ fn command_withdraw_svc(
         &vesting_token_pubkey,
         &destination_token_pubkey,
         &vesting_owner.pubkey(),
+        &release_proposals,
     )
     .unwrap();
 
@@ -233,6 +230,7 @@ fn command_withdraw_with_realm_svc(
     mint_pubkey: Pubkey,
     realm_pubkey: Pubkey,
     destination_token_pubkey: Pubkey,
+    release_proposals: Vec<Pubkey>,
 ) {
 
     let withdraw_instruction = withdraw_with_realm(
@@ -244,6 +242,49 @@ fn command_withdraw_with_realm_svc(
         &governance_program_id,
         &realm_pubkey,
         &mint_pubkey,
+        &release_proposals,
+        Some(&payer.pubkey()),
+    )
+    .unwrap();
+
+    let mut transaction = Transaction::new_with_payer(&[withdraw_instruction], Some(&payer.pubkey()));
+
+    let latest_blockhash = rpc_client.get_latest_blockhash().unwrap();
+    transaction.sign(&[&payer, &vesting_owner], latest_blockhash);
+
+    rpc_client.send_transaction(&transaction).unwrap();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn command_withdraw_out_of_realm_svc(
+    rpc_client: RpcClient,
+    governance_program_id: Pubkey,
+    vesting_addin_program_id: Pubkey,
+    payer: Keypair,
+    vesting_owner: Keypair,
+    vesting_token_pubkey: Pubkey,
+    mint_pubkey: Pubkey,
+    realm_pubkey: Pubkey,
+    destination_token_pubkey: Pubkey,
+    release_proposals: Vec<Pubkey>,
+) {
+
+    // The rent released by the vesting account is returned to its rent payer
+    let (vesting_pubkey,_) = Pubkey::find_program_address(&[vesting_token_pubkey.as_ref()], &vesting_addin_program_id);
+    let vesting_record_account_data = rpc_client.get_account_data(&vesting_pubkey).unwrap();
+    let vesting_record: VestingRecord = try_from_slice_unchecked(&vesting_record_account_data).unwrap();
+
+    let withdraw_instruction = withdraw_out_of_realm(
+        &vesting_addin_program_id,
+        &spl_token::id(),
+        &vesting_token_pubkey,
+        &destination_token_pubkey,
+        &vesting_owner.pubkey(),
+        &vesting_record.rent_payer,
+        &governance_program_id,
+        &realm_pubkey,
+        &mint_pubkey,
+        &release_proposals,
     )
     .unwrap();
 
@@ -272,7 +313,18 @@ fn command_change_owner(
     )
     .unwrap();
 
-    let mut transaction = Transaction::new_with_payer(&[change_owner_instruction], Some(&payer.pubkey()));
+    let instructions = [
+        change_owner_instruction,
+        // The new owner adds the vesting to the VestingIndex by the update-index command
+        update_vesting_index(
+            &vesting_addin_program_id,
+            &vesting_owner.pubkey(),
+            &payer.pubkey(),
+            &[vesting_token_pubkey],
+        ).unwrap(),
+    ];
+
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
 
     let latest_blockhash = rpc_client.get_latest_blockhash().unwrap();
     transaction.sign(
@@ -296,24 +348,6 @@ fn command_change_owner_with_realm(
     new_vesting_owner_pubkey: Pubkey,
 ) {
 
-    let mut instructions: Vec<Instruction> = Vec::new();
-
-    let new_voter_weight_record_pubkey = get_voter_weight_record_address(&vesting_addin_program_id, &realm_pubkey, &mint_pubkey, &new_vesting_owner_pubkey);
-
-    let new_voter_weight_record_data_result = rpc_client.get_account_data(&new_voter_weight_record_pubkey);
-    if new_voter_weight_record_data_result.is_err() || new_voter_weight_record_data_result.unwrap().is_empty() {
-
-        let create_voter_weight_record_instruction = create_voter_weight_record(
-            &vesting_addin_program_id,
-            &new_vesting_owner_pubkey,
-            &payer.pubkey(),
-            &realm_pubkey,
-            &mint_pubkey,
-        )
-        .unwrap();
-        instructions.push(create_voter_weight_record_instruction);
-    }
-
     let change_owner_instruction = change_owner_with_realm(
         &vesting_addin_program_id,
         &vesting_token_pubkey,
@@ -322,9 +356,21 @@ fn command_change_owner_with_realm(
         &governance_program_id,
         &realm_pubkey,
         &mint_pubkey,
+        Some(&payer.pubkey()),
+        Some(&payer.pubkey()),
     )
     .unwrap();
-    instructions.push(change_owner_instruction);
+
+    let instructions = [
+        change_owner_instruction,
+        // The new owner adds the vesting to the VestingIndex by the update-index command
+        update_vesting_index(
+            &vesting_addin_program_id,
+            &vesting_owner.pubkey(),
+            &payer.pubkey(),
+            &[vesting_token_pubkey],
+        ).unwrap(),
+    ];
 
     let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
 
@@ -377,16 +423,285 @@ fn command_set_vote_percentage_with_realm(
     mint_pubkey: Pubkey,
     realm_pubkey: Pubkey,
     percentage: u16,
+    valid_until: Option<Slot>,
 ) {
 
     let instruction = set_vote_percentage_with_realm(
         &vesting_addin_program_id,
-        &vesting_owner_pubkey,
-        &vesting_authority.pubkey(),
+        &vesting_owner_pubkey,
+        &vesting_authority.pubkey(),
+        &governance_program_id,
+        &realm_pubkey,
+        &mint_pubkey,
+        percentage,
+        valid_until,
+    )
+    .unwrap();
+
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+
+    let latest_blockhash = rpc_client.get_latest_blockhash().unwrap();
+    transaction.sign(
+        &[&payer,&vesting_authority],
+        latest_blockhash,
+    );
+
+    rpc_client.send_transaction(&transaction).unwrap();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn command_split(
+    rpc_client: RpcClient,
+    governance_program_id: Pubkey,
+    vesting_addin_program_id: Pubkey,
+    payer: Keypair,
+    vesting_owner: Keypair,
+    vesting_token_pubkey: Pubkey,
+    new_vesting_owner_pubkey: Pubkey,
+    schedules: Vec<VestingSchedule>,
+    label: Option<String>,
+) {
+    let (vesting_pubkey,_) = Pubkey::find_program_address(&[vesting_token_pubkey.as_ref()], &vesting_addin_program_id);
+
+    let vesting_record_account_data = rpc_client.get_account_data(&vesting_pubkey).unwrap();
+    let vesting_record: VestingRecord = try_from_slice_unchecked(&vesting_record_account_data).unwrap();
+
+    let new_vesting_token_seed = Keypair::new().pubkey();
+    let new_vesting_token_pubkey = get_vesting_token_address(&vesting_addin_program_id, &new_vesting_token_seed);
+
+    let (new_vesting_pubkey, _) = Pubkey::find_program_address(
+        &[new_vesting_token_pubkey.as_ref()],
+        &vesting_addin_program_id,
+    );
+
+    msg!("Vesting addin program id: {:?}", vesting_addin_program_id);
+    msg!("SPL Token program id: {:?}", spl_token::id());
+    msg!("Payer: {:?}", payer.pubkey(),);
+    msg!("New vesting owner pubkey: {:?}", new_vesting_owner_pubkey);
+    msg!("New vesting account pubkey: {:?}", new_vesting_pubkey);
+    msg!("New vesting token pubkey: {:?}", new_vesting_token_pubkey);
+    report_schedules(&schedules);
+
+    let split_instruction = if let Some(realm_pubkey) = vesting_record.realm {
+        split_with_realm(
+            &vesting_addin_program_id,
+            &spl_token::id(),
+            &vesting_token_pubkey,
+            &vesting_owner.pubkey(),
+            &new_vesting_token_pubkey,
+            &new_vesting_owner_pubkey,
+            &payer.pubkey(),
+            schedules,
+            label,
+            &governance_program_id,
+            &realm_pubkey,
+            &vesting_record.mint,
+            Some(&payer.pubkey()),
+        )
+    } else {
+        split(
+            &vesting_addin_program_id,
+            &spl_token::id(),
+            &vesting_token_pubkey,
+            &vesting_owner.pubkey(),
+            &new_vesting_token_pubkey,
+            &new_vesting_owner_pubkey,
+            &payer.pubkey(),
+            schedules,
+            label,
+        )
+    }.unwrap();
+
+    // The new owner adds the vesting to the VestingIndex by the update-index command
+    let instructions = [
+        with_created_vesting_token_accounts(split_instruction, &vesting_record.mint, &[new_vesting_token_seed]).unwrap(),
+    ];
+
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+
+    let latest_blockhash = rpc_client.get_latest_blockhash().unwrap();
+    transaction.sign(
+        &[&payer, &vesting_owner],
+        latest_blockhash,
+    );
+
+    rpc_client.send_transaction(&transaction).unwrap();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn command_attach_realm(
+    rpc_client: RpcClient,
+    governance_program_id: Pubkey,
+    vesting_addin_program_id: Pubkey,
+    payer: Keypair,
+    vesting_owner: Keypair,
+    vesting_token_pubkey: Pubkey,
+    mint_pubkey: Pubkey,
+    realm_pubkey: Pubkey,
+) {
+
+    let instruction = attach_realm(
+        &vesting_addin_program_id,
+        &vesting_token_pubkey,
+        &vesting_owner.pubkey(),
+        &payer.pubkey(),
+        &governance_program_id,
+        &realm_pubkey,
+        &mint_pubkey,
+    )
+    .unwrap();
+
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+
+    let latest_blockhash = rpc_client.get_latest_blockhash().unwrap();
+    transaction.sign(
+        &[&payer, &vesting_owner],
+        latest_blockhash,
+    );
+
+    rpc_client.send_transaction(&transaction).unwrap();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn command_detach_realm(
+    rpc_client: RpcClient,
+    governance_program_id: Pubkey,
+    vesting_addin_program_id: Pubkey,
+    payer: Keypair,
+    vesting_owner: Keypair,
+    vesting_token_pubkey: Pubkey,
+    mint_pubkey: Pubkey,
+    realm_pubkey: Pubkey,
+) {
+
+    // The rent released by the vesting account is returned to its rent payer
+    let (vesting_pubkey,_) = Pubkey::find_program_address(&[vesting_token_pubkey.as_ref()], &vesting_addin_program_id);
+    let vesting_record_account_data = rpc_client.get_account_data(&vesting_pubkey).unwrap();
+    let vesting_record: VestingRecord = try_from_slice_unchecked(&vesting_record_account_data).unwrap();
+
+    let instruction = detach_realm(
+        &vesting_addin_program_id,
+        &vesting_token_pubkey,
+        &vesting_owner.pubkey(),
+        &vesting_record.rent_payer,
+        &governance_program_id,
+        &realm_pubkey,
+        &mint_pubkey,
+    )
+    .unwrap();
+
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+
+    let latest_blockhash = rpc_client.get_latest_blockhash().unwrap();
+    transaction.sign(
+        &[&payer, &vesting_owner],
+        latest_blockhash,
+    );
+
+    rpc_client.send_transaction(&transaction).unwrap();
+}
+
+fn command_migrate_vesting_record(
+    rpc_client: RpcClient,
+    vesting_addin_program_id: Pubkey,
+    payer: Keypair,
+    vesting_owner: Keypair,
+    vesting_token_pubkey: Pubkey,
+    realm_pubkey: Option<Pubkey>,
+) {
+    let instruction = migrate_vesting_record(
+        &vesting_addin_program_id,
+        &vesting_token_pubkey,
+        &vesting_owner.pubkey(),
+        &payer.pubkey(),
+        realm_pubkey.as_ref(),
+    )
+    .unwrap();
+
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+
+    let latest_blockhash = rpc_client.get_latest_blockhash().unwrap();
+    transaction.sign(
+        &[&payer, &vesting_owner],
+        latest_blockhash,
+    );
+
+    rpc_client.send_transaction(&transaction).unwrap();
+}
+
+fn command_update_vesting_index(
+    rpc_client: RpcClient,
+    vesting_addin_program_id: Pubkey,
+    payer: Keypair,
+    vesting_owner: Keypair,
+    vesting_token_pubkeys: Vec<Pubkey>,
+) {
+    let instruction = update_vesting_index(
+        &vesting_addin_program_id,
+        &vesting_owner.pubkey(),
+        &payer.pubkey(),
+        &vesting_token_pubkeys,
+    )
+    .unwrap();
+
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+
+    let latest_blockhash = rpc_client.get_latest_blockhash().unwrap();
+    transaction.sign(
+        &[&payer, &vesting_owner],
+        latest_blockhash,
+    );
+
+    rpc_client.send_transaction(&transaction).unwrap();
+}
+
+fn command_set_label(
+    rpc_client: RpcClient,
+    vesting_addin_program_id: Pubkey,
+    payer: Keypair,
+    grantor: Keypair,
+    vesting_token_pubkey: Pubkey,
+    label: Option<String>,
+) {
+    let instruction = set_label(
+        &vesting_addin_program_id,
+        &vesting_token_pubkey,
+        &grantor.pubkey(),
+        &payer.pubkey(),
+        label,
+    )
+    .unwrap();
+
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+
+    let latest_blockhash = rpc_client.get_latest_blockhash().unwrap();
+    transaction.sign(
+        &[&payer, &grantor],
+        latest_blockhash,
+    );
+
+    rpc_client.send_transaction(&transaction).unwrap();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn command_set_tge_timestamp(
+    rpc_client: RpcClient,
+    governance_program_id: Pubkey,
+    vesting_addin_program_id: Pubkey,
+    payer: Keypair,
+    realm_authority: Keypair,
+    realm_pubkey: Pubkey,
+    mint_pubkey: Pubkey,
+    tge_timestamp: u64,
+) {
+    let instruction = set_tge_timestamp(
+        &vesting_addin_program_id,
         &governance_program_id,
         &realm_pubkey,
+        &realm_authority.pubkey(),
+        &payer.pubkey(),
         &mint_pubkey,
-        percentage,
+        tge_timestamp,
     )
     .unwrap();
 
@@ -394,7 +709,7 @@ fn command_set_vote_percentage_with_realm(
 
     let latest_blockhash = rpc_client.get_latest_blockhash().unwrap();
     transaction.sign(
-        &[&payer,&vesting_authority],
+        &[&payer, &realm_authority],
         latest_blockhash,
     );
 
@@ -402,86 +717,45 @@ fn command_set_vote_percentage_with_realm(
 }
 
 #[allow(clippy::too_many_arguments)]
-fn command_split(
+fn command_accelerate_release(
     rpc_client: RpcClient,
     governance_program_id: Pubkey,
     vesting_addin_program_id: Pubkey,
     payer: Keypair,
-    vesting_owner: Keypair,
-    vesting_token_pubkey: Pubkey,
-    new_vesting_owner_pubkey: Pubkey,
-    schedules: Vec<VestingSchedule>,
+    realm_authority: Keypair,
+    realm_pubkey: Pubkey,
+    vesting_token_pubkeys: Vec<Pubkey>,
+    release_time: u64,
+    include_milestones: bool,
 ) {
-    let (vesting_pubkey,_) = Pubkey::find_program_address(&[vesting_token_pubkey.as_ref()], &vesting_addin_program_id);
-
-    let vesting_record_account_data = rpc_client.get_account_data(&vesting_pubkey).unwrap();
-    let vesting_record: VestingRecord = try_from_slice_unchecked(&vesting_record_account_data).unwrap();
 
-    let new_vesting_token_keypair = Keypair::new();
-    let new_vesting_token_pubkey = new_vesting_token_keypair.pubkey();
+    let mut mint_pubkey = None;
+    let mut vestings = Vec::with_capacity(vesting_token_pubkeys.len());
+    for vesting_token_pubkey in vesting_token_pubkeys {
+        let (vesting_pubkey,_) = Pubkey::find_program_address(&[vesting_token_pubkey.as_ref()], &vesting_addin_program_id);
+        let vesting_record_account_data = rpc_client.get_account_data(&vesting_pubkey).unwrap();
+        let vesting_record: VestingRecord = try_from_slice_unchecked(&vesting_record_account_data).unwrap();
+        mint_pubkey.get_or_insert(vesting_record.mint);
+        vestings.push((vesting_token_pubkey, vesting_record.rent_payer));
+    }
 
-    let (new_vesting_pubkey, _) = Pubkey::find_program_address(
-        &[new_vesting_token_pubkey.as_ref()],
+    let instruction = accelerate_release(
         &vesting_addin_program_id,
-    );
-
-    msg!("Vesting addin program id: {:?}", vesting_addin_program_id);
-    msg!("SPL Token program id: {:?}", spl_token::id());
-    msg!("Payer: {:?}", payer.pubkey(),);
-    msg!("New vesting owner pubkey: {:?}", new_vesting_owner_pubkey);
-    msg!("New vesting account pubkey: {:?}", new_vesting_pubkey);
-    msg!("New vesting token pubkey: {:?}", new_vesting_token_pubkey);
-    report_schedules(&schedules);
-
-    let instructions = [
-        system_instruction::create_account(
-            &vesting_owner.pubkey(),
-            &new_vesting_token_pubkey,
-            Rent::default().minimum_balance(spl_token::state::Account::LEN),
-            spl_token::state::Account::LEN as u64,
-            &spl_token::id()
-        ),
-
-        spl_token::instruction::initialize_account(
-            &spl_token::id(),
-            &new_vesting_token_pubkey,
-            &vesting_record.mint,
-            &new_vesting_pubkey,
-        ).unwrap(),
-
-        if let Some(realm_pubkey) = vesting_record.realm {
-            split_with_realm(
-                &vesting_addin_program_id,
-                &spl_token::id(),
-                &vesting_token_pubkey,
-                &vesting_owner.pubkey(),
-                &new_vesting_token_pubkey,
-                &new_vesting_owner_pubkey,
-                &payer.pubkey(),
-                schedules,
-                &governance_program_id,
-                &realm_pubkey,
-                &vesting_record.mint,
-            )
-        } else {
-            split(
-                &vesting_addin_program_id,
-                &spl_token::id(),
-                &vesting_token_pubkey,
-                &vesting_owner.pubkey(),
-                &new_vesting_token_pubkey,
-                &new_vesting_owner_pubkey,
-                &payer.pubkey(),
-                schedules,
-            )
-        }.unwrap(),
-    ];
+        &governance_program_id,
+        &realm_pubkey,
+        &realm_authority.pubkey(),
+        &mint_pubkey.expect("No vesting addresses"),
+        &vestings,
+        release_time,
+        include_milestones,
+    )
+    .unwrap();
 
-    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
 
     let latest_blockhash = rpc_client.get_latest_blockhash().unwrap();
     transaction.sign(
-        &[&payer, &vesting_owner, &new_vesting_token_keypair],
+        &[&payer, &realm_authority],
         latest_blockhash,
     );
 
@@ -510,7 +784,16 @@ fn report_vesting_record_info(vesting_record: &VestingRecord) {
     msg!("Vesting Owner Address: {:?}", &vesting_record.owner);
     msg!("Vesting Mint Address:  {:?}", &vesting_record.mint);
     msg!("Vesting Token Address: {:?}", &vesting_record.token);
+    msg!("Vesting Grantor: {:?}", &vesting_record.grantor);
+    msg!("Vesting Rent Payer: {:?}", &vesting_record.rent_payer);
     msg!("Vesting Realm: {:?}", &vesting_record.realm);
+    msg!("Vesting Governance Program: {:?}", &vesting_record.governance);
+    if let Some(label) = &vesting_record.label {
+        msg!("Vesting Label: {}", label);
+    }
+    if vesting_record.tge_relative {
+        msg!("Release times are relative to the TGE timestamp of the Realm");
+    }
 
     report_schedules(&vesting_record.schedule);
 }
@@ -526,6 +809,9 @@ fn report_schedules(schedules: &[VestingSchedule]) {
                 &item.release_time,
                 NaiveDateTime::from_timestamp(item.release_time.try_into().unwrap(), 0u32),
             );
+            if let Some(release_proposal) = item.release_proposal {
+                msg!("      released by proposal {}", release_proposal);
+            }
             item.amount
         }).sum();
     msg!("Total amount: {}", total_amount);
@@ -600,6 +886,7 @@ fn parse_schedules(arg_matches: &ArgMatches) -> Vec<VestingSchedule> {
         schedules.push(VestingSchedule {
             release_time: h,
             amount: a,
+            release_proposal: None,
         });
     }
 
@@ -628,6 +915,7 @@ trait ArgsHelper {
     fn arg_realm_address(self, required: bool) -> Self;
     fn arg_mint_address(self, required: bool) -> Self;
     fn arg_schedules(self) -> Self;
+    fn arg_label(self) -> Self;
 }
 
 impl ArgsHelper for App<'_, '_> {
@@ -718,6 +1006,16 @@ impl ArgsHelper for App<'_, '_> {
         )
     }
 
+    fn arg_label(self) -> Self {
+        self.arg(
+            Arg::with_name("label")
+                .long("label")
+                .value_name("LABEL")
+                .takes_value(true)
+                .help("Specify the label of the vesting (e.g. \"Seed round\" or URI)."),
+        )
+    }
+
     fn arg_schedules(self) -> Self {
         self
             // scheduled vesting
@@ -872,6 +1170,14 @@ fn main() {
                 .arg_mint_address(true)
                 .arg_realm_address(true)
                 .arg_schedules()
+                .arg_label()
+                .arg(
+                    Arg::with_name("tge_relative")
+                        .long("tge_relative")
+                        .takes_value(false)
+                        .requires("realm_address")
+                        .help("Release times are offsets from the TGE timestamp of the Realm."),
+                )
                 .arg_optional_payer()
                 .arg(
                     Arg::with_name("confirm")
@@ -898,6 +1204,13 @@ fn main() {
                         .takes_value(true)
                         .help("Specify the destination token address (publickey)."),
                 )
+                .arg(
+                    Arg::with_name("out_of_realm")
+                        .long("out_of_realm")
+                        .takes_value(false)
+                        .help("Withdraw and detach the vesting from the Realm \
+                               which doesn't use the vesting addin anymore."),
+                )
         )
         .subcommand(
             SubCommand::with_name("change-owner")
@@ -952,6 +1265,14 @@ fn main() {
                         .takes_value(true)
                         .help("Deposited tokens percentage of voting."),
                 )
+                .arg(
+                    Arg::with_name("valid_until")
+                        .long("valid_until")
+                        .value_name("SLOT")
+                        .validator(is_slot)
+                        .takes_value(true)
+                        .help("The last slot when the percentage is valid. Reverts to the default vote percentage of the Realm after the slot."),
+                )
         )
         .subcommand(
             SubCommand::with_name("split")
@@ -961,6 +1282,137 @@ fn main() {
                 .arg_vesting_address()
                 .arg_new_vesting_owner()
                 .arg_schedules()
+                .arg_label()
+        )
+        .subcommand(
+            SubCommand::with_name("attach-realm")
+                .about("Attach a vesting contract created without Realm to the Realm")
+                .arg_optional_payer()
+                .arg_vesting_owner_keypair()
+                .arg_vesting_address()
+                .arg_realm_address(true)
+        )
+        .subcommand(
+            SubCommand::with_name("detach-realm")
+                .about("Detach a vesting contract from the Realm")
+                .arg_optional_payer()
+                .arg_vesting_owner_keypair()
+                .arg_vesting_address()
+        )
+        .subcommand(
+            SubCommand::with_name("migrate")
+                .about("Convert a vesting contract created by the previous version of the addin to the current layout")
+                .arg_optional_payer()
+                .arg_vesting_owner_keypair()
+                .arg_vesting_address()
+        )
+        .subcommand(
+            SubCommand::with_name("update-index")
+                .about("Add the vesting contracts of the owner to the owner's index (and remove the stale ones)")
+                .arg_optional_payer()
+                .arg_vesting_owner_keypair()
+                .arg(
+                    Arg::with_name("vesting_addresses")
+                        .long("vesting_addresses")
+                        .value_name("ADDRESS")
+                        .required(true)
+                        .validator(is_pubkey)
+                        .takes_value(true)
+                        .multiple(true)
+                        .use_delimiter(true)
+                        .help("Specify the vesting token addresses (publickeys) separated by a comma."),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("accelerate-release")
+                .about("Pull the remaining schedules of vesting contracts under the Realm forward to the release time")
+                .arg_optional_payer()
+                .arg(
+                    Arg::with_name("realm_authority")
+                        .long("realm_authority")
+                        .value_name("KEYPAIR")
+                        .required(true)
+                        .validator(is_keypair)
+                        .takes_value(true)
+                        .help(
+                            "Specify the realm authority account address. \
+                            This may be a keypair file, the ASK keyword.",
+                        ),
+                )
+                .arg_realm_address(true)
+                .arg(
+                    Arg::with_name("vesting_addresses")
+                        .long("vesting_addresses")
+                        .value_name("ADDRESS")
+                        .required(true)
+                        .validator(is_pubkey)
+                        .takes_value(true)
+                        .multiple(true)
+                        .use_delimiter(true)
+                        .help("Specify the vesting token addresses (publickeys) separated by a comma."),
+                )
+                .arg(
+                    Arg::with_name("release_time")
+                        .long("release_time")
+                        .value_name("SLOT")
+                        .required(true)
+                        .validator(is_slot)
+                        .takes_value(true)
+                        .help("Release time in unix timestamp for the remaining schedules."),
+                )
+                .arg(
+                    Arg::with_name("include_milestones")
+                        .long("include_milestones")
+                        .takes_value(false)
+                        .help("Release the milestone schedules unconditionally too."),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("set-tge-timestamp")
+                .about("Set the TGE timestamp for the TGE-relative vesting contracts of the Realm (only once)")
+                .arg_optional_payer()
+                .arg(
+                    Arg::with_name("realm_authority")
+                        .long("realm_authority")
+                        .value_name("KEYPAIR")
+                        .required(true)
+                        .validator(is_keypair)
+                        .takes_value(true)
+                        .help(
+                            "Specify the realm authority account address. \
+                            This may be a keypair file, the ASK keyword.",
+                        ),
+                )
+                .arg_realm_address(true)
+                .arg_mint_address(true)
+                .arg(
+                    Arg::with_name("tge_timestamp")
+                        .long("tge_timestamp")
+                        .value_name("TIMESTAMP")
+                        .required(true)
+                        .validator(is_slot)
+                        .takes_value(true)
+                        .help("Token generation event time in unix timestamp."),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("set-label")
+                .about("Set the label of a vesting contract (by the grantor of the vesting)")
+                .arg_optional_payer()
+                .arg(
+                    Arg::with_name("grantor")
+                        .long("grantor")
+                        .value_name("KEYPAIR")
+                        .required(true)
+                        .validator(is_keypair)
+                        .takes_value(true)
+                        .help(
+                            "Specify the grantor (source account owner) of the vesting. \
+                            This may be a keypair file, the ASK keyword.",
+                        ),
+                )
+                .arg_vesting_address()
+                .arg_label()
         )
         .subcommand(
             SubCommand::with_name("info")
@@ -992,6 +1444,8 @@ fn main() {
             let payer_keypair = keypair_of(arg_matches, "payer").unwrap_or_else(|| keypair_of(arg_matches, "source_owner").unwrap() );
             let confirm: bool = value_of(arg_matches, "confirm").unwrap();
             let schedules = parse_schedules(arg_matches);
+            let label: Option<String> = value_of(arg_matches, "label");
+            let tge_relative = arg_matches.is_present("tge_relative");
 
             if let Some(realm_pubkey) = realm_opt {
                 command_deposit_with_realm_svc(
@@ -1005,6 +1459,8 @@ fn main() {
                     mint_pubkey,
                     realm_pubkey,
                     schedules,
+                    label,
+                    tge_relative,
                     confirm,
                 )
             } else {
@@ -1017,6 +1473,7 @@ fn main() {
                     vesting_owner_pubkey,
                     mint_pubkey,
                     schedules,
+                    label,
                     confirm,
                 )
             }
@@ -1034,8 +1491,25 @@ fn main() {
 
             let vesting_record_account_data = rpc_client.get_account_data(&vesting_pubkey).unwrap();
             let vesting_record: VestingRecord = try_from_slice_unchecked(&vesting_record_account_data).unwrap();
+            let release_proposals: Vec<Pubkey> = vesting_record.schedule.iter()
+                .filter(|s| s.amount != 0)
+                .filter_map(|s| s.release_proposal)
+                .collect();
 
-            if let Some(realm_pubkey) = vesting_record.realm {
+            if let (Some(realm_pubkey), true) = (vesting_record.realm, arg_matches.is_present("out_of_realm")) {
+                command_withdraw_out_of_realm_svc(
+                    rpc_client,
+                    governance_program_id,
+                    vesting_addin_program_id,
+                    payer_keypair,
+                    vesting_owner_keypair,
+                    vesting_token_pubkey,
+                    vesting_record.mint,
+                    realm_pubkey,
+                    destination_token_pubkey,
+                    release_proposals,
+                )
+            } else if let Some(realm_pubkey) = vesting_record.realm {
                 let mint_pubkey: Pubkey = vesting_record.mint;
 
                 command_withdraw_with_realm_svc(
@@ -1048,6 +1522,7 @@ fn main() {
                     mint_pubkey,
                     realm_pubkey,
                     destination_token_pubkey,
+                    release_proposals,
                 )
             } else {
                 command_withdraw_svc(
@@ -1057,6 +1532,7 @@ fn main() {
                     vesting_owner_keypair,
                     vesting_token_pubkey,
                     destination_token_pubkey,
+                    release_proposals,
                 )
             };
         }
@@ -1126,6 +1602,7 @@ fn main() {
             let vesting_owner_pubkey = pubkey_of(arg_matches, "vesting_owner").unwrap();
             
             let percentage: u16 = value_of(arg_matches, "percentage").unwrap();
+            let valid_until: Option<Slot> = value_of(arg_matches, "valid_until");
 
             let payer_keypair = keypair_of(arg_matches, "payer").unwrap_or_else(|| keypair_of(arg_matches, "vesting_authority").unwrap() );
 
@@ -1139,6 +1616,7 @@ fn main() {
                 mint_pubkey,
                 realm_pubkey,
                 percentage,
+                valid_until,
             )
         }
         ("split", Some(arg_matches)) => {
@@ -1147,6 +1625,7 @@ fn main() {
             let vesting_token_pubkey = pubkey_of(arg_matches, "vesting_address").unwrap();
             let new_vesting_owner_pubkey = pubkey_of(arg_matches, "new_vesting_owner").unwrap();
             let schedules = parse_schedules(arg_matches);
+            let label: Option<String> = value_of(arg_matches, "label");
 
             command_split(
                 rpc_client,
@@ -1157,6 +1636,139 @@ fn main() {
                 vesting_token_pubkey,
                 new_vesting_owner_pubkey,
                 schedules,
+                label,
+            )
+        }
+        ("set-tge-timestamp", Some(arg_matches)) => {
+            let payer_keypair = keypair_of(arg_matches, "payer").unwrap_or_else(|| keypair_of(arg_matches, "realm_authority").unwrap() );
+            let realm_authority_keypair = keypair_of(arg_matches, "realm_authority").unwrap();
+            let realm_pubkey = pubkey_of(arg_matches, "realm_address").unwrap();
+            let mint_pubkey = pubkey_of(arg_matches, "mint_address").unwrap();
+            let tge_timestamp: u64 = value_of(arg_matches, "tge_timestamp").unwrap();
+
+            command_set_tge_timestamp(
+                rpc_client,
+                governance_program_id,
+                vesting_addin_program_id,
+                payer_keypair,
+                realm_authority_keypair,
+                realm_pubkey,
+                mint_pubkey,
+                tge_timestamp,
+            )
+        }
+        ("set-label", Some(arg_matches)) => {
+            let payer_keypair = keypair_of(arg_matches, "payer").unwrap_or_else(|| keypair_of(arg_matches, "grantor").unwrap() );
+            let grantor_keypair = keypair_of(arg_matches, "grantor").unwrap();
+            let vesting_token_pubkey = pubkey_of(arg_matches, "vesting_address").unwrap();
+            let label: Option<String> = value_of(arg_matches, "label");
+
+            command_set_label(
+                rpc_client,
+                vesting_addin_program_id,
+                payer_keypair,
+                grantor_keypair,
+                vesting_token_pubkey,
+                label,
+            )
+        }
+        ("attach-realm", Some(arg_matches)) => {
+            let payer_keypair = keypair_of(arg_matches, "payer").unwrap_or_else(|| keypair_of(arg_matches, "vesting_owner").unwrap() );
+            let vesting_owner_keypair = keypair_of(arg_matches, "vesting_owner").unwrap();
+            let vesting_token_pubkey = pubkey_of(arg_matches, "vesting_address").unwrap();
+            let realm_pubkey = pubkey_of(arg_matches, "realm_address").unwrap();
+
+            let (vesting_pubkey,_) = Pubkey::find_program_address(&[vesting_token_pubkey.as_ref()], &vesting_addin_program_id);
+
+            let vesting_record_account_data = rpc_client.get_account_data(&vesting_pubkey).unwrap();
+            let vesting_record: VestingRecord = try_from_slice_unchecked(&vesting_record_account_data).unwrap();
+
+            command_attach_realm(
+                rpc_client,
+                governance_program_id,
+                vesting_addin_program_id,
+                payer_keypair,
+                vesting_owner_keypair,
+                vesting_token_pubkey,
+                vesting_record.mint,
+                realm_pubkey,
+            )
+        }
+        ("detach-realm", Some(arg_matches)) => {
+            let payer_keypair = keypair_of(arg_matches, "payer").unwrap_or_else(|| keypair_of(arg_matches, "vesting_owner").unwrap() );
+            let vesting_owner_keypair = keypair_of(arg_matches, "vesting_owner").unwrap();
+            let vesting_token_pubkey = pubkey_of(arg_matches, "vesting_address").unwrap();
+
+            let (vesting_pubkey,_) = Pubkey::find_program_address(&[vesting_token_pubkey.as_ref()], &vesting_addin_program_id);
+
+            let vesting_record_account_data = rpc_client.get_account_data(&vesting_pubkey).unwrap();
+            let vesting_record: VestingRecord = try_from_slice_unchecked(&vesting_record_account_data).unwrap();
+            let realm_pubkey = vesting_record.realm.expect("Vesting is not under Realm");
+
+            command_detach_realm(
+                rpc_client,
+                governance_program_id,
+                vesting_addin_program_id,
+                payer_keypair,
+                vesting_owner_keypair,
+                vesting_token_pubkey,
+                vesting_record.mint,
+                realm_pubkey,
+            )
+        }
+        ("migrate", Some(arg_matches)) => {
+            let payer_keypair = keypair_of(arg_matches, "payer").unwrap_or_else(|| keypair_of(arg_matches, "vesting_owner").unwrap() );
+            let vesting_owner_keypair = keypair_of(arg_matches, "vesting_owner").unwrap();
+            let vesting_token_pubkey = pubkey_of(arg_matches, "vesting_address").unwrap();
+
+            let (vesting_pubkey,_) = Pubkey::find_program_address(&[vesting_token_pubkey.as_ref()], &vesting_addin_program_id);
+
+            let vesting_record_account_data = rpc_client.get_account_data(&vesting_pubkey).unwrap();
+            if vesting_record_account_data.first() != Some(&(VestingAccountType::VestingRecordV1 as u8)) {
+                panic!("Vesting account has the current layout");
+            }
+            let vesting_record: VestingRecordV1 = try_from_slice_unchecked(&vesting_record_account_data).unwrap();
+
+            command_migrate_vesting_record(
+                rpc_client,
+                vesting_addin_program_id,
+                payer_keypair,
+                vesting_owner_keypair,
+                vesting_token_pubkey,
+                vesting_record.realm,
+            )
+        }
+        ("update-index", Some(arg_matches)) => {
+            let payer_keypair = keypair_of(arg_matches, "payer").unwrap_or_else(|| keypair_of(arg_matches, "vesting_owner").unwrap() );
+            let vesting_owner_keypair = keypair_of(arg_matches, "vesting_owner").unwrap();
+            let vesting_token_pubkeys: Vec<Pubkey> = values_of(arg_matches, "vesting_addresses").unwrap();
+
+            command_update_vesting_index(
+                rpc_client,
+                vesting_addin_program_id,
+                payer_keypair,
+                vesting_owner_keypair,
+                vesting_token_pubkeys,
+            )
+        }
+        ("accelerate-release", Some(arg_matches)) => {
+            let payer_keypair = keypair_of(arg_matches, "payer").unwrap_or_else(|| keypair_of(arg_matches, "realm_authority").unwrap() );
+            let realm_authority_keypair = keypair_of(arg_matches, "realm_authority").unwrap();
+            let realm_pubkey = pubkey_of(arg_matches, "realm_address").unwrap();
+            let vesting_token_pubkeys: Vec<Pubkey> = values_of(arg_matches, "vesting_addresses").unwrap();
+            let release_time: u64 = value_of(arg_matches, "release_time").unwrap();
+            let include_milestones = arg_matches.is_present("include_milestones");
+
+            command_accelerate_release(
+                rpc_client,
+                governance_program_id,
+                vesting_addin_program_id,
+                payer_keypair,
+                realm_authority_keypair,
+                realm_pubkey,
+                vesting_token_pubkeys,
+                release_time,
+                include_milestones,
             )
         }
         ("info", Some(arg_matches)) => {
@@ -1166,6 +1778,28 @@ fn main() {
         ("info-owner", Some(arg_matches)) => {
             let vesting_owner_pubkey = pubkey_of(arg_matches, "vesting_owner").unwrap();
 
+            // Use the VestingIndex of the owner (listing the vestings added by the owner) if it exists
+            // and fall back to scanning the program accounts
+            let vesting_index_pubkey = get_vesting_index_address(&vesting_addin_program_id, &vesting_owner_pubkey);
+            if let Ok(vesting_index_account_data) = rpc_client.get_account_data(&vesting_index_pubkey) {
+                let vesting_index: VestingIndex = try_from_slice_unchecked(&vesting_index_account_data).unwrap();
+                let vesting_accounts = rpc_client.get_multiple_accounts(&vesting_index.vestings).unwrap();
+                for (vesting_account_pubkey, vesting_account) in vesting_index.vestings.iter().zip(vesting_accounts) {
+                    // The index can be stale until the next UpdateVestingIndex
+                    let vesting_record: VestingRecord = match vesting_account {
+                        Some(vesting_account) if vesting_account.owner == vesting_addin_program_id =>
+                            try_from_slice_unchecked(&vesting_account.data).unwrap(),
+                        _ => continue,
+                    };
+                    if vesting_record.owner != vesting_owner_pubkey {
+                        continue;
+                    }
+                    msg!("\nVesting Account Pubkey: {:?}", vesting_account_pubkey);
+                    report_vesting_record_info(&vesting_record);
+                }
+                return;
+            }
+
             let records: Vec<(Pubkey,Account)> =
                 rpc_client.get_program_accounts_with_config(
                     &vesting_addin_program_id,
@@ -1176,7 +1810,7 @@ fn main() {
                                 rpc_filter::Memcmp {
                                     offset: 0,
                                     bytes: rpc_filter::MemcmpEncodedBytes::Bytes({
-                                        let mut fd: Vec<u8> = vec![1];
+                                        let mut fd: Vec<u8> = vec![VestingAccountType::VestingRecordV2 as u8];
                                         fd.append(&mut vesting_owner_pubkey.to_bytes().to_vec());
                                         fd
                                     }),
@@ -1,12 +1,10 @@
 import { Connection, PublicKey, Keypair } from '@solana/web3.js';
 import fs from 'fs';
-import {
-  Numberu64,
-  generateRandomSeed,
-  signTransactionInstructions,
-} from './utils';
+import { TOKEN_PROGRAM_ID } from '@solana/spl-token';
+import { Numberu64, signTransactionInstructions } from './utils';
 import { Schedule } from './state';
-import { create, TOKEN_VESTING_PROGRAM_ID } from './main';
+import { createDepositInstruction } from './instructions';
+import { getVestingTokenAddress } from './main';
 
 /**
  *
@@ -50,9 +48,11 @@ const DATES = [
   new Date(2024, 12),
 ];
 
+/** The vesting addin program */
+const VESTING_PROGRAM_ID = new PublicKey('');
+
 /** Info about the desintation */
 const DESTINATION_OWNER = new PublicKey('');
-const DESTINATION_TOKEN_ACCOUNT = new PublicKey('');
 
 /** Token info */
 const MINT = new PublicKey('');
@@ -69,18 +69,13 @@ const connection = new Connection('');
 
 /** Do some checks before sending the tokens */
 const checks = async () => {
-  const tokenInfo = await connection.getParsedAccountInfo(
-    DESTINATION_TOKEN_ACCOUNT,
-  );
+  const tokenInfo = await connection.getParsedAccountInfo(SOURCE_TOKEN_ACCOUNT);
 
   // @ts-ignore
   const parsed = tokenInfo.value.data.parsed;
   if (parsed.info.mint !== MINT.toBase58()) {
     throw new Error('Invalid mint');
   }
-  if (parsed.info.owner !== DESTINATION_OWNER.toBase58()) {
-    throw new Error('Invalid owner');
-  }
   if (parsed.info.tokenAmount.decimals !== DECIMALS) {
     throw new Error('Invalid decimals');
   }
@@ -100,44 +95,35 @@ const lock = async () => {
       ),
     );
   }
-  const seed = generateRandomSeed();
-
-  console.log(`Seed: ${seed}`);
-
-  const instruction = await create(
-    connection,
-    TOKEN_VESTING_PROGRAM_ID,
-    Buffer.from(seed),
-    wallet.publicKey,
+  /** The vesting spl-token account is created by the vesting program, the seed only has to be unique */
+  const vestingTokenSeed = Keypair.generate().publicKey;
+  const vestingTokenAccount = await getVestingTokenAddress(
+    VESTING_PROGRAM_ID,
+    vestingTokenSeed,
+  );
+  const instruction = await createDepositInstruction(
+    VESTING_PROGRAM_ID,
+    TOKEN_PROGRAM_ID,
+    vestingTokenAccount,
     wallet.publicKey,
     SOURCE_TOKEN_ACCOUNT,
-    DESTINATION_TOKEN_ACCOUNT,
-    MINT,
+    DESTINATION_OWNER,
+    wallet.publicKey,
     schedules,
+    null,
+    false,
+    null,
+    { seed: vestingTokenSeed, mint: MINT },
   );
 
   const tx = await signTransactionInstructions(
     connection,
     [wallet],
     wallet.publicKey,
-    instruction,
+    [instruction],
   );
 
   console.log(`Transaction: ${tx}`);
-  
-  //waiting for tx confirmation
-  await new Promise(f => setTimeout(f, 60000));
-
-  //get seed for official bonfida UI
-  const txInfo = await connection.getConfirmedTransaction(tx,"confirmed")
-  if (txInfo && !txInfo.meta?.err) {
-    console.log(
-      txInfo?.transaction.instructions[2].data.slice(1, 32 + 1).toString("hex")
-    );
-  }
-  else{
-    throw new Error('Transaction not confirmed.')
-  }
 };
 
 lock();
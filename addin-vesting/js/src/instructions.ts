@@ -1,202 +1,576 @@
-import { PublicKey, SYSVAR_RENT_PUBKEY, TransactionInstruction } from '@solana/web3.js';
+import {
+  AccountMeta,
+  PublicKey,
+  SystemProgram,
+  TransactionInstruction,
+} from '@solana/web3.js';
 import { Schedule } from './state';
-import { Numberu32 } from './utils';
+import {
+  findVestingAccountAddress,
+  findVestingTokenAddress,
+  getMaxVoterWeightRecordAddress,
+  getRealmConfigAddress,
+  getTokenOwnerRecordAddress,
+  getVestingAccountAddress,
+  getVestingConfigAddress,
+  getVoterWeightRecordAddress,
+} from './main';
 
-export enum Instruction {
-  Init,
-  Create,
+// The order must match VestingInstruction of the program
+export enum VestingInstruction {
+  Deposit,
+  Withdraw,
+  SetVotePercentage,
+  ChangeOwner,
+  CreateVoterWeightRecord,
+  Close,
+  CloseVoterWeightRecord,
+  Split,
+  AttachRealm,
+  DetachRealm,
+  WithdrawOutOfRealm,
+  AccelerateRelease,
+  UpdateVoterWeightRecord,
+  SetVestingConfig,
+  RefreshMaxVoterWeight,
+  RefreshReleasedAmount,
+  SetVoteAmount,
+  CloseMaxVoterWeightRecord,
+  SplitPercent,
+  SplitMany,
+  SetLabel,
+  CreateVestingTemplate,
+  DepositFromTemplate,
+  SetTgeTimestamp,
+  UpdateVestingIndex,
+  GetVestingStatus,
+  MigrateVestingRecord,
+  MigrateVoterWeightRecord,
 }
 
-export function createInitInstruction(
-  systemProgramId: PublicKey,
+/**
+ * The vesting spl-token account created by Deposit or Split
+ * (the address is returned by getVestingTokenAddress for the seed)
+ */
+export interface CreatedVestingTokenAccount {
+  seed: PublicKey;
+  mint: PublicKey;
+}
+
+/** The Realm of the vesting (vesting for Realm) */
+export interface RealmAccounts {
+  governanceProgramId: PublicKey;
+  realm: PublicKey;
+  mint: PublicKey;
+}
+
+function account(
+  pubkey: PublicKey,
+  isWritable: boolean,
+  isSigner = false,
+): AccountMeta {
+  return { pubkey, isSigner, isWritable };
+}
+
+function encodeSchedules(schedules: Array<Schedule>): Buffer {
+  const len = Buffer.alloc(4);
+  len.writeUInt32LE(schedules.length);
+  return Buffer.concat([len, ...schedules.map(s => s.toBuffer())]);
+}
+
+// Encodes the optional VestingTokenSeed (the seed and the bump seed of the vesting spl-token account)
+async function encodeVestingTokenSeed(
   vestingProgramId: PublicKey,
-  payerKey: PublicKey,
-  vestingAccountKey: PublicKey,
-  seeds: Array<Buffer | Uint8Array>,
-  numberOfSchedules: number,
-): TransactionInstruction {
-  let buffers = [
-    Buffer.from(Int8Array.from([0]).buffer),
-    Buffer.concat(seeds),
-    // @ts-ignore
-    new Numberu32(numberOfSchedules).toBuffer(),
-  ];
+  createdTokenAccount: CreatedVestingTokenAccount | null,
+): Promise<Buffer> {
+  if (!createdTokenAccount) {
+    return Buffer.from([0]);
+  }
+  const [, bumpSeed] = await findVestingTokenAddress(
+    vestingProgramId,
+    createdTokenAccount.seed,
+  );
+  return Buffer.concat([
+    Buffer.from([1]),
+    createdTokenAccount.seed.toBuffer(),
+    Buffer.from([bumpSeed]),
+  ]);
+}
 
-  const data = Buffer.concat(buffers);
+function encodeLabel(label: string | null): Buffer {
+  if (label === null) {
+    return Buffer.from([0]);
+  }
+  const bytes = Buffer.from(label, 'utf8');
+  const len = Buffer.alloc(4);
+  len.writeUInt32LE(bytes.length);
+  return Buffer.concat([Buffer.from([1]), len, bytes]);
+}
+
+export async function createDepositInstruction(
+  vestingProgramId: PublicKey,
+  tokenProgramId: PublicKey,
+  vestingTokenAccountKey: PublicKey,
+  sourceTokenAccountOwnerKey: PublicKey,
+  sourceTokenAccountKey: PublicKey,
+  vestingOwnerKey: PublicKey,
+  payerKey: PublicKey,
+  schedules: Array<Schedule>,
+  label: string | null,
+  tgeRelative: boolean,
+  realm: RealmAccounts | null,
+  createdTokenAccount: CreatedVestingTokenAccount | null = null,
+): Promise<TransactionInstruction> {
+  const [vestingAccountKey, vestingBumpSeed] = await findVestingAccountAddress(
+    vestingProgramId,
+    vestingTokenAccountKey,
+  );
   const keys = [
-    {
-      pubkey: systemProgramId,
-      isSigner: false,
-      isWritable: false,
-    },
-    {
-      pubkey: SYSVAR_RENT_PUBKEY,
-      isSigner: false,
-      isWritable: false,
-    },
-    {
-      pubkey: payerKey,
-      isSigner: true,
-      isWritable: true,
-    },
-    {
-      pubkey: vestingAccountKey,
-      isSigner: false,
-      isWritable: true,
-    },
+    account(SystemProgram.programId, false),
+    account(tokenProgramId, false),
+    account(vestingAccountKey, true),
+    account(vestingTokenAccountKey, true),
+    account(sourceTokenAccountOwnerKey, false, true),
+    account(sourceTokenAccountKey, true),
+    account(vestingOwnerKey, false),
+    account(payerKey, true, true),
   ];
+  if (createdTokenAccount) {
+    keys.push(account(createdTokenAccount.mint, false));
+  }
+  if (realm) {
+    keys.push(
+      account(realm.governanceProgramId, false),
+      account(realm.realm, false),
+      account(
+        await getRealmConfigAddress(realm.governanceProgramId, realm.realm),
+        false,
+      ),
+      account(
+        await getVestingConfigAddress(vestingProgramId, realm.realm, realm.mint),
+        false,
+      ),
+      account(
+        await getVoterWeightRecordAddress(
+          vestingProgramId,
+          realm.realm,
+          realm.mint,
+          vestingOwnerKey,
+        ),
+        true,
+      ),
+      account(
+        await getMaxVoterWeightRecordAddress(
+          vestingProgramId,
+          realm.realm,
+          realm.mint,
+        ),
+        true,
+      ),
+    );
+  }
 
-  return new TransactionInstruction({
-    keys,
-    programId: vestingProgramId,
-    data,
-  });
+  const data = Buffer.concat([
+    Buffer.from([VestingInstruction.Deposit]),
+    encodeSchedules(schedules),
+    encodeLabel(label),
+    Buffer.from([tgeRelative ? 1 : 0]),
+    await encodeVestingTokenSeed(vestingProgramId, createdTokenAccount),
+    Buffer.from([vestingBumpSeed]),
+  ]);
+  return new TransactionInstruction({ keys, programId: vestingProgramId, data });
 }
 
-export function createCreateInstruction(
+export async function createWithdrawInstruction(
   vestingProgramId: PublicKey,
   tokenProgramId: PublicKey,
-  vestingAccountKey: PublicKey,
   vestingTokenAccountKey: PublicKey,
-  sourceTokenAccountOwnerKey: PublicKey,
-  sourceTokenAccountKey: PublicKey,
   destinationTokenAccountKey: PublicKey,
-  mintAddress: PublicKey,
-  schedules: Array<Schedule>,
-  seeds: Array<Buffer | Uint8Array>,
-): TransactionInstruction {
-  let buffers = [
-    Buffer.from(Int8Array.from([1]).buffer),
-    Buffer.concat(seeds),
-    mintAddress.toBuffer(),
-    destinationTokenAccountKey.toBuffer(),
+  vestingOwnerKey: PublicKey,
+  realm: RealmAccounts | null,
+  releaseProposals: Array<PublicKey> = [],
+  spill: PublicKey | null = null,
+): Promise<TransactionInstruction> {
+  const vestingAccountKey = await getVestingAccountAddress(
+    vestingProgramId,
+    vestingTokenAccountKey,
+  );
+  const keys = [
+    account(tokenProgramId, false),
+    account(vestingAccountKey, true),
+    account(vestingTokenAccountKey, true),
+    account(destinationTokenAccountKey, true),
+    account(vestingOwnerKey, false, true),
   ];
+  if (realm) {
+    keys.push(
+      account(realm.governanceProgramId, false),
+      account(realm.realm, false),
+      account(
+        await getTokenOwnerRecordAddress(
+          realm.governanceProgramId,
+          realm.realm,
+          realm.mint,
+          vestingOwnerKey,
+        ),
+        false,
+      ),
+      account(
+        await getVestingConfigAddress(vestingProgramId, realm.realm, realm.mint),
+        false,
+      ),
+      account(
+        await getVoterWeightRecordAddress(
+          vestingProgramId,
+          realm.realm,
+          realm.mint,
+          vestingOwnerKey,
+        ),
+        true,
+      ),
+      account(
+        await getMaxVoterWeightRecordAddress(
+          vestingProgramId,
+          realm.realm,
+          realm.mint,
+        ),
+        true,
+      ),
+      ...releaseProposals.map(proposal => account(proposal, false)),
+    );
+    // The spill account (the last one) closes the VoterWeightRecord left empty
+    if (spill) {
+      keys.push(account(spill, true));
+    }
+  }
 
-  schedules.forEach(s => {
-    buffers.push(s.toBuffer());
-  });
+  const data = Buffer.from([VestingInstruction.Withdraw]);
+  return new TransactionInstruction({ keys, programId: vestingProgramId, data });
+}
 
-  const data = Buffer.concat(buffers);
+export async function createChangeOwnerInstruction(
+  vestingProgramId: PublicKey,
+  vestingTokenAccountKey: PublicKey,
+  vestingOwnerKey: PublicKey,
+  newVestingOwnerKey: PublicKey,
+  realm: RealmAccounts | null,
+  spill: PublicKey | null = null,
+  payerKey: PublicKey | null = null,
+): Promise<TransactionInstruction> {
+  const vestingAccountKey = await getVestingAccountAddress(
+    vestingProgramId,
+    vestingTokenAccountKey,
+  );
   const keys = [
-    {
-      pubkey: tokenProgramId,
-      isSigner: false,
-      isWritable: false,
-    },
-    {
-      pubkey: vestingAccountKey,
-      isSigner: false,
-      isWritable: true,
-    },
-    {
-      pubkey: vestingTokenAccountKey,
-      isSigner: false,
-      isWritable: true,
-    },
-    {
-      pubkey: sourceTokenAccountOwnerKey,
-      isSigner: true,
-      isWritable: false,
-    },
-    {
-      pubkey: sourceTokenAccountKey,
-      isSigner: false,
-      isWritable: true,
-    },
+    account(vestingAccountKey, true),
+    account(vestingOwnerKey, false, true),
+    account(newVestingOwnerKey, false),
   ];
-  return new TransactionInstruction({
-    keys,
-    programId: vestingProgramId,
-    data,
-  });
+  if (realm) {
+    keys.push(
+      account(realm.governanceProgramId, false),
+      account(realm.realm, false),
+      account(
+        await getTokenOwnerRecordAddress(
+          realm.governanceProgramId,
+          realm.realm,
+          realm.mint,
+          vestingOwnerKey,
+        ),
+        false,
+      ),
+      account(
+        await getVestingConfigAddress(vestingProgramId, realm.realm, realm.mint),
+        false,
+      ),
+      account(
+        await getVoterWeightRecordAddress(
+          vestingProgramId,
+          realm.realm,
+          realm.mint,
+          vestingOwnerKey,
+        ),
+        true,
+      ),
+      account(
+        await getVoterWeightRecordAddress(
+          vestingProgramId,
+          realm.realm,
+          realm.mint,
+          newVestingOwnerKey,
+        ),
+        true,
+      ),
+      account(
+        await getMaxVoterWeightRecordAddress(
+          vestingProgramId,
+          realm.realm,
+          realm.mint,
+        ),
+        true,
+      ),
+    );
+    // The payer creates the VoterWeightRecord of the new owner if it doesn't exist
+    if (payerKey) {
+      keys.push(
+        account(SystemProgram.programId, false),
+        account(payerKey, true, true),
+      );
+    }
+    // The spill account closes the VoterWeightRecord of the current owner left empty
+    if (spill) {
+      keys.push(account(spill, true));
+    }
+  }
+
+  const data = Buffer.from([VestingInstruction.ChangeOwner]);
+  return new TransactionInstruction({ keys, programId: vestingProgramId, data });
 }
 
-export function createUnlockInstruction(
+export async function createSplitInstruction(
   vestingProgramId: PublicKey,
   tokenProgramId: PublicKey,
-  clockSysvarId: PublicKey,
-  vestingAccountKey: PublicKey,
   vestingTokenAccountKey: PublicKey,
-  destinationTokenAccountKey: PublicKey,
-  seeds: Array<Buffer | Uint8Array>,
-): TransactionInstruction {
+  vestingOwnerKey: PublicKey,
+  newVestingTokenAccountKey: PublicKey,
+  newVestingOwnerKey: PublicKey,
+  payerKey: PublicKey,
+  schedules: Array<Schedule>,
+  label: string | null,
+  realm: RealmAccounts | null,
+  spill: PublicKey | null = null,
+  createdTokenAccount: CreatedVestingTokenAccount | null = null,
+): Promise<TransactionInstruction> {
+  const vestingAccountKey = await getVestingAccountAddress(
+    vestingProgramId,
+    vestingTokenAccountKey,
+  );
+  const [newVestingAccountKey, newVestingBumpSeed] = await findVestingAccountAddress(
+    vestingProgramId,
+    newVestingTokenAccountKey,
+  );
+  const keys = [
+    account(SystemProgram.programId, false),
+    account(tokenProgramId, false),
+    account(vestingAccountKey, true),
+    account(vestingTokenAccountKey, true),
+    account(vestingOwnerKey, false, true),
+    account(newVestingAccountKey, true),
+    account(newVestingTokenAccountKey, true),
+    account(newVestingOwnerKey, false),
+    account(payerKey, true, true),
+  ];
+  if (createdTokenAccount) {
+    keys.push(account(createdTokenAccount.mint, false));
+  }
+  if (realm) {
+    keys.push(
+      account(realm.governanceProgramId, false),
+      account(realm.realm, false),
+      account(
+        await getTokenOwnerRecordAddress(
+          realm.governanceProgramId,
+          realm.realm,
+          realm.mint,
+          vestingOwnerKey,
+        ),
+        false,
+      ),
+      account(
+        await getVestingConfigAddress(vestingProgramId, realm.realm, realm.mint),
+        false,
+      ),
+      account(
+        await getVoterWeightRecordAddress(
+          vestingProgramId,
+          realm.realm,
+          realm.mint,
+          vestingOwnerKey,
+        ),
+        true,
+      ),
+      account(
+        await getVoterWeightRecordAddress(
+          vestingProgramId,
+          realm.realm,
+          realm.mint,
+          newVestingOwnerKey,
+        ),
+        true,
+      ),
+      account(
+        await getMaxVoterWeightRecordAddress(
+          vestingProgramId,
+          realm.realm,
+          realm.mint,
+        ),
+        true,
+      ),
+    );
+    if (spill) {
+      keys.push(account(spill, true));
+    }
+  }
+
   const data = Buffer.concat([
-    Buffer.from(Int8Array.from([2]).buffer),
-    Buffer.concat(seeds),
+    Buffer.from([VestingInstruction.Split]),
+    encodeSchedules(schedules),
+    encodeLabel(label),
+    await encodeVestingTokenSeed(vestingProgramId, createdTokenAccount),
+    Buffer.from([newVestingBumpSeed]),
   ]);
+  return new TransactionInstruction({ keys, programId: vestingProgramId, data });
+}
 
+export async function createAttachRealmInstruction(
+  vestingProgramId: PublicKey,
+  vestingTokenAccountKey: PublicKey,
+  vestingOwnerKey: PublicKey,
+  payerKey: PublicKey,
+  realm: RealmAccounts,
+): Promise<TransactionInstruction> {
+  const vestingAccountKey = await getVestingAccountAddress(
+    vestingProgramId,
+    vestingTokenAccountKey,
+  );
   const keys = [
-    {
-      pubkey: tokenProgramId,
-      isSigner: false,
-      isWritable: false,
-    },
-    {
-      pubkey: clockSysvarId,
-      isSigner: false,
-      isWritable: false,
-    },
-    {
-      pubkey: vestingAccountKey,
-      isSigner: false,
-      isWritable: true,
-    },
-    {
-      pubkey: vestingTokenAccountKey,
-      isSigner: false,
-      isWritable: true,
-    },
-    {
-      pubkey: destinationTokenAccountKey,
-      isSigner: false,
-      isWritable: true,
-    },
+    account(SystemProgram.programId, false),
+    account(vestingAccountKey, true),
+    account(vestingOwnerKey, false, true),
+    account(payerKey, true, true),
+    account(realm.governanceProgramId, false),
+    account(realm.realm, false),
+    account(
+      await getRealmConfigAddress(realm.governanceProgramId, realm.realm),
+      false,
+    ),
+    account(
+      await getVestingConfigAddress(vestingProgramId, realm.realm, realm.mint),
+      false,
+    ),
+    account(
+      await getVoterWeightRecordAddress(
+        vestingProgramId,
+        realm.realm,
+        realm.mint,
+        vestingOwnerKey,
+      ),
+      true,
+    ),
+    account(
+      await getMaxVoterWeightRecordAddress(
+        vestingProgramId,
+        realm.realm,
+        realm.mint,
+      ),
+      true,
+    ),
   ];
-  return new TransactionInstruction({
-    keys,
-    programId: vestingProgramId,
-    data,
-  });
+
+  const data = Buffer.from([VestingInstruction.AttachRealm]);
+  return new TransactionInstruction({ keys, programId: vestingProgramId, data });
 }
 
-export function createChangeDestinationInstruction(
+export async function createDetachRealmInstruction(
   vestingProgramId: PublicKey,
-  vestingAccountKey: PublicKey,
-  currentDestinationTokenAccountOwner: PublicKey,
-  currentDestinationTokenAccount: PublicKey,
-  targetDestinationTokenAccount: PublicKey,
-  seeds: Array<Buffer | Uint8Array>,
-): TransactionInstruction {
-  const data = Buffer.concat([
-    Buffer.from(Int8Array.from([3]).buffer),
-    Buffer.concat(seeds),
-  ]);
+  vestingTokenAccountKey: PublicKey,
+  vestingOwnerKey: PublicKey,
+  rentPayerKey: PublicKey,
+  realm: RealmAccounts,
+): Promise<TransactionInstruction> {
+  const vestingAccountKey = await getVestingAccountAddress(
+    vestingProgramId,
+    vestingTokenAccountKey,
+  );
+  const keys = [
+    account(vestingAccountKey, true),
+    account(vestingOwnerKey, false, true),
+    // The rent payer of the vesting account receives the rent released by the shrunk account
+    account(rentPayerKey, true),
+    account(realm.governanceProgramId, false),
+    account(realm.realm, false),
+    account(
+      await getTokenOwnerRecordAddress(
+        realm.governanceProgramId,
+        realm.realm,
+        realm.mint,
+        vestingOwnerKey,
+      ),
+      false,
+    ),
+    account(
+      await getVestingConfigAddress(vestingProgramId, realm.realm, realm.mint),
+      false,
+    ),
+    account(
+      await getVoterWeightRecordAddress(
+        vestingProgramId,
+        realm.realm,
+        realm.mint,
+        vestingOwnerKey,
+      ),
+      true,
+    ),
+    account(
+      await getMaxVoterWeightRecordAddress(
+        vestingProgramId,
+        realm.realm,
+        realm.mint,
+      ),
+      true,
+    ),
+  ];
+
+  const data = Buffer.from([VestingInstruction.DetachRealm]);
+  return new TransactionInstruction({ keys, programId: vestingProgramId, data });
+}
 
+/**
+ * Converts the legacy vesting account to the current layout
+ * (the Realm is required for the vesting under Realm)
+ */
+export async function createMigrateVestingRecordInstruction(
+  vestingProgramId: PublicKey,
+  vestingTokenAccountKey: PublicKey,
+  vestingOwnerKey: PublicKey,
+  payerKey: PublicKey,
+  realm: PublicKey | null = null,
+): Promise<TransactionInstruction> {
+  const [vestingAccountKey, bumpSeed] = await findVestingAccountAddress(
+    vestingProgramId,
+    vestingTokenAccountKey,
+  );
   const keys = [
-    {
-      pubkey: vestingAccountKey,
-      isSigner: false,
-      isWritable: true,
-    },
-    {
-      pubkey: currentDestinationTokenAccount,
-      isSigner: false,
-      isWritable: false,
-    },
-    {
-      pubkey: currentDestinationTokenAccountOwner,
-      isSigner: true,
-      isWritable: false,
-    },
-    {
-      pubkey: targetDestinationTokenAccount,
-      isSigner: false,
-      isWritable: false,
-    },
+    account(SystemProgram.programId, false),
+    account(vestingAccountKey, true),
+    account(vestingOwnerKey, false, true),
+    account(payerKey, true, true),
   ];
-  return new TransactionInstruction({
-    keys,
-    programId: vestingProgramId,
-    data,
-  });
+  if (realm) {
+    keys.push(account(realm, false));
+  }
+
+  const data = Buffer.from([VestingInstruction.MigrateVestingRecord, bumpSeed]);
+  return new TransactionInstruction({ keys, programId: vestingProgramId, data });
+}
+
+/** Converts the legacy VoterWeightRecord of the owner to the current layout */
+export async function createMigrateVoterWeightRecordInstruction(
+  vestingProgramId: PublicKey,
+  ownerKey: PublicKey,
+  realm: PublicKey,
+  mint: PublicKey,
+  payerKey: PublicKey,
+): Promise<TransactionInstruction> {
+  const keys = [
+    account(SystemProgram.programId, false),
+    account(
+      await getVoterWeightRecordAddress(vestingProgramId, realm, mint, ownerKey),
+      true,
+    ),
+    account(payerKey, true, true),
+  ];
+
+  const data = Buffer.from([VestingInstruction.MigrateVoterWeightRecord]);
+  return new TransactionInstruction({ keys, programId: vestingProgramId, data });
 }
@@ -1,197 +1,142 @@
-import {
-  Account,
-  PublicKey,
-  SystemProgram,
-  SYSVAR_CLOCK_PUBKEY,
-  TransactionInstruction,
-  Connection,
-} from '@solana/web3.js';
-import { TOKEN_PROGRAM_ID } from '@solana/spl-token';
-import {
-  createChangeDestinationInstruction,
-  createCreateInstruction,
-  createInitInstruction,
-  createUnlockInstruction,
-} from './instructions';
-import {
-  findAssociatedTokenAddress,
-  createAssociatedTokenAccount,
-} from './utils';
-import { ContractInfo, Schedule } from './state';
-import { assert } from 'console';
-import bs58 from 'bs58';
-
-export const TOKEN_VESTING_PROGRAM_ID = new PublicKey(
-  'CChTq6PthWU82YZkbveA3WDf7s97BWhBK4Vx9bmsT743',
-);
-
-export async function create(
-  connection: Connection,
-  programId: PublicKey,
-  seedWord: Buffer | Uint8Array,
-  payer: PublicKey,
-  sourceTokenOwner: PublicKey,
-  possibleSourceTokenPubkey: PublicKey | null,
-  destinationTokenPubkey: PublicKey,
-  mintAddress: PublicKey,
-  schedules: Array<Schedule>,
-): Promise<Array<TransactionInstruction>> {
-  // If no source token account was given, use the associated source account
-  if (possibleSourceTokenPubkey == null) {
-    possibleSourceTokenPubkey = await findAssociatedTokenAddress(
-      sourceTokenOwner,
-      mintAddress,
-    );
-  }
-
-  // Find the non reversible public key for the vesting contract via the seed
-  seedWord = seedWord.slice(0, 31);
-  const [vestingAccountKey, bump] = await PublicKey.findProgramAddress(
-    [seedWord],
-    programId,
+import { Connection, PublicKey } from '@solana/web3.js';
+import { VestingAccountType, VestingRecord } from './state';
+
+/** Returns the vesting account PDA (seeds: [vesting spl-token account]) and its bump seed */
+export async function findVestingAccountAddress(
+  vestingProgramId: PublicKey,
+  vestingTokenAccountKey: PublicKey,
+): Promise<[PublicKey, number]> {
+  return PublicKey.findProgramAddress(
+    [vestingTokenAccountKey.toBuffer()],
+    vestingProgramId,
   );
+}
 
-  const vestingTokenAccountKey = await findAssociatedTokenAddress(
-    vestingAccountKey,
-    mintAddress,
+/** Returns the vesting account PDA (seeds: [vesting spl-token account]) */
+export async function getVestingAccountAddress(
+  vestingProgramId: PublicKey,
+  vestingTokenAccountKey: PublicKey,
+): Promise<PublicKey> {
+  const [address] = await findVestingAccountAddress(
+    vestingProgramId,
+    vestingTokenAccountKey,
   );
+  return address;
+}
 
-  seedWord = Buffer.from(seedWord.toString('hex') + bump.toString(16), 'hex');
-
-  console.log(
-    'Vesting contract account pubkey: ',
-    vestingAccountKey.toBase58(),
+/**
+ * Returns the vesting spl-token account PDA created by Deposit or Split (seeds: ['vesting-token', seed])
+ * and its bump seed
+ */
+export async function findVestingTokenAddress(
+  vestingProgramId: PublicKey,
+  seed: PublicKey,
+): Promise<[PublicKey, number]> {
+  return PublicKey.findProgramAddress(
+    [Buffer.from('vesting-token'), seed.toBuffer()],
+    vestingProgramId,
   );
+}
 
-  console.log('contract ID: ', bs58.encode(seedWord));
-
-  const check_existing = await connection.getAccountInfo(vestingAccountKey);
-  if (!!check_existing) {
-    throw 'Contract already exists.';
-  }
-
-  let instruction = [
-    createInitInstruction(
-      SystemProgram.programId,
-      programId,
-      payer,
-      vestingAccountKey,
-      [seedWord],
-      schedules.length,
-    ),
-    await createAssociatedTokenAccount(
-      SystemProgram.programId,
-      SYSVAR_CLOCK_PUBKEY,
-      payer,
-      vestingAccountKey,
-      mintAddress,
-    ),
-    createCreateInstruction(
-      programId,
-      TOKEN_PROGRAM_ID,
-      vestingAccountKey,
-      vestingTokenAccountKey,
-      sourceTokenOwner,
-      possibleSourceTokenPubkey,
-      destinationTokenPubkey,
-      mintAddress,
-      schedules,
-      [seedWord],
-    ),
-  ];
-  return instruction;
+/** Returns the vesting spl-token account PDA created by Deposit or Split (seeds: ['vesting-token', seed]) */
+export async function getVestingTokenAddress(
+  vestingProgramId: PublicKey,
+  seed: PublicKey,
+): Promise<PublicKey> {
+  const [address] = await findVestingTokenAddress(vestingProgramId, seed);
+  return address;
 }
 
-export async function unlock(
-  connection: Connection,
-  programId: PublicKey,
-  seedWord: Buffer | Uint8Array,
-  mintAddress: PublicKey,
-): Promise<Array<TransactionInstruction>> {
-  seedWord = seedWord.slice(0, 31);
-  const [vestingAccountKey, bump] = await PublicKey.findProgramAddress(
-    [seedWord],
-    programId,
+/** Returns the VestingConfig PDA of the Realm and the governing token mint */
+export async function getVestingConfigAddress(
+  vestingProgramId: PublicKey,
+  realm: PublicKey,
+  mint: PublicKey,
+): Promise<PublicKey> {
+  const [address] = await PublicKey.findProgramAddress(
+    [Buffer.from('vesting-config'), realm.toBuffer(), mint.toBuffer()],
+    vestingProgramId,
   );
-  seedWord = Buffer.from(seedWord.toString('hex') + bump.toString(16), 'hex');
+  return address;
+}
 
-  const vestingTokenAccountKey = await findAssociatedTokenAddress(
-    vestingAccountKey,
-    mintAddress,
+/** Returns the VoterWeightRecord PDA of the token owner */
+export async function getVoterWeightRecordAddress(
+  vestingProgramId: PublicKey,
+  realm: PublicKey,
+  mint: PublicKey,
+  owner: PublicKey,
+): Promise<PublicKey> {
+  const [address] = await PublicKey.findProgramAddress(
+    [
+      Buffer.from('voter-weight-record'),
+      realm.toBuffer(),
+      mint.toBuffer(),
+      owner.toBuffer(),
+    ],
+    vestingProgramId,
   );
+  return address;
+}
 
-  const vestingInfo = await getContractInfo(connection, vestingAccountKey);
+/** Returns the MaxVoterWeightRecord PDA of the Realm and the governing token mint */
+export async function getMaxVoterWeightRecordAddress(
+  vestingProgramId: PublicKey,
+  realm: PublicKey,
+  mint: PublicKey,
+): Promise<PublicKey> {
+  const [address] = await PublicKey.findProgramAddress(
+    [Buffer.from('max-voter-weight-record'), realm.toBuffer(), mint.toBuffer()],
+    vestingProgramId,
+  );
+  return address;
+}
 
-  let instruction = [
-    createUnlockInstruction(
-      programId,
-      TOKEN_PROGRAM_ID,
-      SYSVAR_CLOCK_PUBKEY,
-      vestingAccountKey,
-      vestingTokenAccountKey,
-      vestingInfo.destinationAddress,
-      [seedWord],
-    ),
-  ];
+/** Returns the TokenOwnerRecord PDA of the spl-governance program */
+export async function getTokenOwnerRecordAddress(
+  governanceProgramId: PublicKey,
+  realm: PublicKey,
+  mint: PublicKey,
+  owner: PublicKey,
+): Promise<PublicKey> {
+  const [address] = await PublicKey.findProgramAddress(
+    [
+      Buffer.from('governance'),
+      realm.toBuffer(),
+      mint.toBuffer(),
+      owner.toBuffer(),
+    ],
+    governanceProgramId,
+  );
+  return address;
+}
 
-  return instruction;
+/** Returns the RealmConfig PDA of the spl-governance program */
+export async function getRealmConfigAddress(
+  governanceProgramId: PublicKey,
+  realm: PublicKey,
+): Promise<PublicKey> {
+  const [address] = await PublicKey.findProgramAddress(
+    [Buffer.from('realm-config'), realm.toBuffer()],
+    governanceProgramId,
+  );
+  return address;
 }
 
-export async function getContractInfo(
+export async function getVestingRecord(
   connection: Connection,
   vestingAccountKey: PublicKey,
-): Promise<ContractInfo> {
-  console.log('Fetching contract ', vestingAccountKey.toBase58());
-  const vestingInfo = await connection.getAccountInfo(
-    vestingAccountKey,
-    'single',
-  );
+): Promise<VestingRecord> {
+  const vestingInfo = await connection.getAccountInfo(vestingAccountKey);
   if (!vestingInfo) {
-    throw 'Vesting contract account is unavailable';
+    throw 'Vesting account is unavailable';
   }
-  const info = ContractInfo.fromBuffer(vestingInfo!.data);
-  if (!info) {
-    throw 'Vesting contract account is not initialized';
+  if (vestingInfo.data[0] == VestingAccountType.VestingRecordV1) {
+    throw 'Vesting account has the legacy layout (see createMigrateVestingRecordInstruction)';
   }
-  return info!;
-}
-
-export async function changeDestination(
-  connection: Connection,
-  programId: PublicKey,
-  currentDestinationTokenAccountPublicKey: PublicKey,
-  newDestinationTokenAccountOwner: PublicKey | undefined,
-  newDestinationTokenAccount: PublicKey | undefined,
-  vestingSeed: Array<Buffer | Uint8Array>,
-): Promise<Array<TransactionInstruction>> {
-  let seedWord = vestingSeed[0];
-  seedWord = seedWord.slice(0, 31);
-  const [vestingAccountKey, bump] = await PublicKey.findProgramAddress(
-    [seedWord],
-    programId,
-  );
-  seedWord = Buffer.from(seedWord.toString('hex') + bump.toString(16), 'hex');
-
-  const contractInfo = await getContractInfo(connection, vestingAccountKey);
-  if (!newDestinationTokenAccount) {
-    assert(
-      !!newDestinationTokenAccountOwner,
-      'At least one of newDestinationTokenAccount and newDestinationTokenAccountOwner must be provided!',
-    );
-    newDestinationTokenAccount = await findAssociatedTokenAddress(
-      newDestinationTokenAccountOwner!,
-      contractInfo.mintAddress,
-    );
+  const record = VestingRecord.fromBuffer(vestingInfo.data);
+  if (!record) {
+    throw 'Vesting account is not initialized';
   }
-
-  return [
-    createChangeDestinationInstruction(
-      programId,
-      vestingAccountKey,
-      currentDestinationTokenAccountPublicKey,
-      contractInfo.destinationAddress,
-      newDestinationTokenAccount,
-      [seedWord],
-    ),
-  ];
+  return record;
 }
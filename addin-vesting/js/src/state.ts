@@ -1,83 +1,137 @@
 import { PublicKey } from '@solana/web3.js';
 import { Numberu64 } from './utils';
 
+export enum VestingAccountType {
+  Unitialized,
+  // The legacy layout to be migrated by MigrateVestingRecord
+  VestingRecordV1,
+  VestingConfig,
+  VestingTemplate,
+  VestingIndex,
+  VestingRecordV2,
+}
+
 export class Schedule {
   // Release time in unix timestamp
   releaseTime!: Numberu64;
   amount!: Numberu64;
+  // The spl-governance Proposal which must succeed to release the amount (milestone release)
+  releaseProposal!: PublicKey | null;
 
-  constructor(releaseTime: Numberu64, amount: Numberu64) {
+  constructor(
+    releaseTime: Numberu64,
+    amount: Numberu64,
+    releaseProposal: PublicKey | null = null,
+  ) {
     this.releaseTime = releaseTime;
     this.amount = amount;
+    this.releaseProposal = releaseProposal;
   }
 
   public toBuffer(): Buffer {
-    return Buffer.concat([this.releaseTime.toBuffer(), this.amount.toBuffer()]);
+    return Buffer.concat([
+      this.releaseTime.toBuffer(),
+      this.amount.toBuffer(),
+      this.releaseProposal
+        ? Buffer.concat([Buffer.from([1]), this.releaseProposal.toBuffer()])
+        : Buffer.from([0]),
+    ]);
   }
 
-  static fromBuffer(buf: Buffer): Schedule {
+  // Returns the schedule and the number of bytes read
+  static fromBuffer(buf: Buffer): [Schedule, number] {
     const releaseTime: Numberu64 = Numberu64.fromBuffer(buf.slice(0, 8));
     const amount: Numberu64 = Numberu64.fromBuffer(buf.slice(8, 16));
-    return new Schedule(releaseTime, amount);
+    if (buf[16] == 0) {
+      return [new Schedule(releaseTime, amount), 17];
+    }
+    const releaseProposal = new PublicKey(buf.slice(17, 49));
+    return [new Schedule(releaseTime, amount, releaseProposal), 49];
   }
 }
 
-export class VestingScheduleHeader {
-  destinationAddress!: PublicKey;
-  mintAddress!: PublicKey;
-  isInitialized!: boolean;
+/** Reads borsh encoded values of the account data */
+class Reader {
+  offset = 0;
 
-  constructor(
-    destinationAddress: PublicKey,
-    mintAddress: PublicKey,
-    isInitialized: boolean,
-  ) {
-    this.destinationAddress = destinationAddress;
-    this.mintAddress = mintAddress;
-    this.isInitialized = isInitialized;
+  constructor(private buf: Buffer) {}
+
+  u8(): number {
+    return this.buf[this.offset++];
   }
 
-  static fromBuffer(buf: Buffer): VestingScheduleHeader {
-    const destinationAddress = new PublicKey(buf.slice(0, 32));
-    const mintAddress = new PublicKey(buf.slice(32, 64));
-    const isInitialized = buf[64] == 1;
-    const header: VestingScheduleHeader = {
-      destinationAddress,
-      mintAddress,
-      isInitialized,
-    };
-    return header;
+  u32(): number {
+    const value = this.buf.readUInt32LE(this.offset);
+    this.offset += 4;
+    return value;
+  }
+
+  u64(): Numberu64 {
+    const value = Numberu64.fromBuffer(this.buf.slice(this.offset, this.offset + 8));
+    this.offset += 8;
+    return value;
   }
-}
 
-export class ContractInfo {
-  destinationAddress!: PublicKey;
-  mintAddress!: PublicKey;
-  schedules!: Array<Schedule>;
+  pubkey(): PublicKey {
+    const value = new PublicKey(this.buf.slice(this.offset, this.offset + 32));
+    this.offset += 32;
+    return value;
+  }
 
-  constructor(
-    destinationAddress: PublicKey,
-    mintAddress: PublicKey,
-    schedules: Array<Schedule>,
-  ) {
-    this.destinationAddress = destinationAddress;
-    this.mintAddress = mintAddress;
-    this.schedules = schedules;
+  option<T>(read: () => T): T | null {
+    return this.u8() == 0 ? null : read();
+  }
+
+  string(): string {
+    const len = this.u32();
+    const value = this.buf.slice(this.offset, this.offset + len).toString('utf8');
+    this.offset += len;
+    return value;
+  }
+
+  schedule(): Schedule {
+    const [schedule, len] = Schedule.fromBuffer(this.buf.slice(this.offset));
+    this.offset += len;
+    return schedule;
   }
+}
+
+export class VestingRecord {
+  owner!: PublicKey;
+  mint!: PublicKey;
+  token!: PublicKey;
+  grantor!: PublicKey;
+  rentPayer!: PublicKey;
+  bumpSeed!: number;
+  realm!: PublicKey | null;
+  governance!: PublicKey | null;
+  excludedAmount!: Numberu64;
+  schedule!: Array<Schedule>;
+  tgeRelative!: boolean;
+  label!: string | null;
 
-  static fromBuffer(buf: Buffer): ContractInfo | undefined {
-    const header = VestingScheduleHeader.fromBuffer(buf.slice(0, 65));
-    if (!header.isInitialized) {
+  static fromBuffer(buf: Buffer): VestingRecord | undefined {
+    const reader = new Reader(buf);
+    if (reader.u8() != VestingAccountType.VestingRecordV2) {
       return undefined;
     }
-    const schedules: Array<Schedule> = [];
-    for (let i = 65; i < buf.length; i += 16) {
-      schedules.push(Schedule.fromBuffer(buf.slice(i, i + 16)));
+    const record = new VestingRecord();
+    record.owner = reader.pubkey();
+    record.mint = reader.pubkey();
+    record.token = reader.pubkey();
+    record.grantor = reader.pubkey();
+    record.rentPayer = reader.pubkey();
+    record.bumpSeed = reader.u8();
+    record.realm = reader.option(() => reader.pubkey());
+    record.governance = reader.option(() => reader.pubkey());
+    record.excludedAmount = reader.u64();
+    const scheduleLen = reader.u32();
+    record.schedule = [];
+    for (let i = 0; i < scheduleLen; i++) {
+      record.schedule.push(reader.schedule());
     }
-    return new ContractInfo(
-      header.destinationAddress,
-      header.mintAddress,
-      schedules,
-    );
+    record.tgeRelative = reader.u8() == 1;
+    record.label = reader.option(() => reader.string());
+    return record;
   }
 }
@@ -0,0 +1,390 @@
+use crate::{
+    error::VestingError,
+    state::VestingAccountType,
+    voter_weight::DEFAULT_VOTE_PERCENTAGE,
+};
+use std::convert::TryInto;
+use solana_program::{
+    pubkey::Pubkey,
+    program_error::ProgramError,
+    program_pack::IsInitialized,
+    account_info::AccountInfo,
+};
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use spl_governance_tools::account::{
+    AccountMaxSize,
+    get_account_data,
+};
+
+/// Maximum number of points in the piecewise-linear weight curve
+pub const MAX_WEIGHT_CURVE_POINTS: usize = 8;
+
+/// Supply fraction which corresponds to 100% of the mint supply
+pub const SUPPLY_FRACTION_BASE: u64 = 10_000_000_000;
+
+/// Maximum number of the token accounts excluded from the mint supply
+pub const MAX_EXCLUDED_TOKEN_ACCOUNTS: usize = 4;
+
+/// Point of the piecewise-linear weight curve
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct WeightCurvePoint {
+    pub amount: u64,
+    pub weight: u64,
+}
+
+/// Curve to convert the total amount of deposited tokens to the voter weight
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub enum WeightCurve {
+    /// Voter weight is equal to the amount
+    Linear,
+
+    /// Voter weight is the square root of the amount (quadratic voting)
+    SquareRoot,
+
+    /// Voter weight is interpolated between the points starting from (0, 0) and rounded up,
+    /// so any positive amount has positive weight. The weight of the last point is the cap for the greater amounts
+    PiecewiseLinear {
+        points: Vec<WeightCurvePoint>,
+    },
+}
+
+impl WeightCurve {
+    /// Checks the points of the piecewise-linear curve: amounts are strictly increasing
+    /// and weights are positive and not decreasing
+    pub fn validate(&self) -> Result<(), ProgramError> {
+        if let WeightCurve::PiecewiseLinear {points} = self {
+            if points.is_empty() || points.len() > MAX_WEIGHT_CURVE_POINTS || points[0].weight == 0 {
+                return Err(VestingError::InvalidWeightCurve.into());
+            }
+            let mut previous = WeightCurvePoint::default();
+            for point in points {
+                if point.amount <= previous.amount || point.weight < previous.weight {
+                    return Err(VestingError::InvalidWeightCurve.into());
+                }
+                previous = *point;
+            }
+        }
+        Ok(())
+    }
+
+    /// Converts the amount of tokens to the voter weight
+    pub fn get_weight(&self, amount: u64) -> Result<u64, ProgramError> {
+        match self {
+            WeightCurve::Linear => Ok(amount),
+            WeightCurve::SquareRoot => Ok(integer_sqrt(amount)),
+            WeightCurve::PiecewiseLinear {points} => {
+                let mut previous = WeightCurvePoint::default();
+                for point in points {
+                    if amount <= point.amount {
+                        let step = (point.amount - previous.amount) as u128;
+                        let weight = ((amount - previous.amount) as u128 * (point.weight - previous.weight) as u128
+                                + step - 1) / step
+                                + previous.weight as u128;
+                        return weight.try_into().map_err(|_| VestingError::OverflowAmount.into());
+                    }
+                    previous = *point;
+                }
+                Ok(previous.weight)
+            },
+        }
+    }
+}
+
+/// Source of the max voter weight reported by MaxVoterWeightRecord
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub enum MaxVoterWeightSource {
+    /// Max voter weight is the sum of the weighted amounts of all deposits
+    Deposits,
+
+    /// Max voter weight is the fraction of the mint supply without the tokens held by
+    /// the excluded (treasury) token accounts. The weight curve is not applied to the supply,
+    /// so it must be linear. MaxVoterWeightRecord is recalculated by RefreshMaxVoterWeight instruction
+    MintSupply {
+        /// Fraction of the mint supply (SUPPLY_FRACTION_BASE is 100%)
+        supply_fraction: u64,
+
+        /// Token accounts of the mint which tokens are not counted
+        excluded_token_accounts: Vec<Pubkey>,
+    },
+}
+
+impl MaxVoterWeightSource {
+    /// Checks the supply fraction and the list of the excluded token accounts
+    pub fn validate(&self) -> Result<(), ProgramError> {
+        if let MaxVoterWeightSource::MintSupply {supply_fraction, excluded_token_accounts} = self {
+            if *supply_fraction == 0 || *supply_fraction > SUPPLY_FRACTION_BASE ||
+               excluded_token_accounts.len() > MAX_EXCLUDED_TOKEN_ACCOUNTS {
+                return Err(VestingError::InvalidMaxVoterWeightSource.into());
+            }
+            for (i, token_account) in excluded_token_accounts.iter().enumerate() {
+                if excluded_token_accounts[..i].contains(token_account) {
+                    return Err(VestingError::InvalidMaxVoterWeightSource.into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns true if MaxVoterWeightRecord is changed by the deposits
+    pub fn is_deposits(&self) -> bool {
+        *self == MaxVoterWeightSource::Deposits
+    }
+}
+
+fn integer_sqrt(value: u64) -> u64 {
+    // Newton's method converges from above starting from any value not less than the root
+    let value = value as u128;
+    let mut x = value;
+    let mut y = (x + 1) / 2;
+    while y < x {
+        x = y;
+        y = (x + value / x) / 2;
+    }
+    x as u64
+}
+
+/// VestingConfig account
+/// Settings of the vesting addin for the Realm and the governing token mint.
+/// Default settings are used while the account is not created
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct VestingConfig {
+    pub account_type: VestingAccountType,
+    pub realm: Pubkey,
+    pub mint: Pubkey,
+
+    /// Curve to convert the total amount of deposited tokens to the voter weight
+    pub weight_curve: WeightCurve,
+
+    /// Maximum voter weight of a single voter as a share of the max voter weight
+    /// (in hundredths of a percent)
+    pub max_voter_weight_share: u16,
+
+    /// Source of the max voter weight
+    pub max_voter_weight_source: MaxVoterWeightSource,
+
+    /// Only the tokens not released by the schedules are counted toward the voter weight.
+    /// Released amounts are excluded by RefreshReleasedAmount instruction
+    pub count_unvested_only: bool,
+
+    /// Token generation event timestamp for the TGE-relative vestings.
+    /// Can be set by the Realm authority only once
+    pub tge_timestamp: Option<u64>,
+
+    /// Governance account (PDA) of the Realm which can accelerate the release of the vestings
+    /// in addition to the Realm authority
+    pub release_authority: Option<Pubkey>,
+
+    /// CastVote uses only the voter weight as of the voting start of the Proposal.
+    /// VoterWeightRecords report the voter weight for an action by UpdateVoterWeightRecord instruction only
+    pub snapshot_voting: bool,
+
+    /// Vote percentage of the VoterWeightRecords after the expiration of the temporary vote percentage
+    /// (in hundredths of a percent)
+    pub default_vote_percentage: u16,
+
+    /// Bump seed of the VestingConfig PDA (seeds: ['vesting-config', realm, mint])
+    pub bump_seed: u8,
+}
+
+impl VestingConfig {
+    /// Returns default settings for the Realm and the governing token mint
+    pub fn new(realm: &Pubkey, mint: &Pubkey) -> Self {
+        Self {
+            account_type: VestingAccountType::VestingConfig,
+            realm: *realm,
+            mint: *mint,
+            weight_curve: WeightCurve::Linear,
+            max_voter_weight_share: 10_000,
+            max_voter_weight_source: MaxVoterWeightSource::Deposits,
+            count_unvested_only: false,
+            tge_timestamp: None,
+            release_authority: None,
+            snapshot_voting: false,
+            default_vote_percentage: DEFAULT_VOTE_PERCENTAGE,
+            bump_seed: 0,
+        }
+    }
+
+    /// Returns true if the voter weight of a single voter is capped
+    pub fn has_voter_weight_cap(&self) -> bool {
+        self.max_voter_weight_share < 10_000
+    }
+
+    /// Returns the maximum voter weight of a single voter for the max voter weight
+    pub fn get_voter_weight_cap(&self, max_voter_weight: u64) -> Result<u64, ProgramError> {
+        let cap = (max_voter_weight as u128)
+                .checked_mul(self.max_voter_weight_share.into()).ok_or(VestingError::OverflowAmount)?
+                .checked_div(10000).ok_or(VestingError::OverflowAmount)?;
+        cap.try_into().map_err(|_| VestingError::OverflowAmount.into())
+    }
+
+    /// Returns the max voter weight for the mint supply and the amount held by the excluded token accounts
+    pub fn get_max_voter_weight_from_supply(&self, supply: u64, excluded_amount: u64) -> Result<u64, ProgramError> {
+        let supply_fraction = match &self.max_voter_weight_source {
+            MaxVoterWeightSource::MintSupply {supply_fraction, ..} => *supply_fraction,
+            MaxVoterWeightSource::Deposits => return Err(VestingError::InvalidMaxVoterWeightSource.into()),
+        };
+        let max_voter_weight = (supply.checked_sub(excluded_amount).ok_or(VestingError::UnderflowAmount)? as u128)
+                .checked_mul(supply_fraction.into()).ok_or(VestingError::OverflowAmount)?
+                .checked_div(SUPPLY_FRACTION_BASE.into()).ok_or(VestingError::OverflowAmount)?;
+        max_voter_weight.try_into().map_err(|_| VestingError::OverflowAmount.into())
+    }
+}
+
+impl IsInitialized for VestingConfig {
+    fn is_initialized(&self) -> bool {
+        self.account_type == VestingAccountType::VestingConfig
+    }
+}
+
+impl AccountMaxSize for VestingConfig {
+    fn get_max_size(&self) -> Option<usize> {
+        Some(388)   // for MAX_WEIGHT_CURVE_POINTS curve points and MAX_EXCLUDED_TOKEN_ACCOUNTS excluded accounts
+    }
+}
+
+/// Returns VestingConfig PDA seeds
+pub fn get_vesting_config_seeds<'a>(
+    realm: &'a Pubkey,
+    mint: &'a Pubkey,
+) -> [&'a [u8]; 3] {
+    [b"vesting-config", realm.as_ref(), mint.as_ref()]
+}
+
+/// Returns VestingConfig PDA address
+pub fn get_vesting_config_address(program_id: &Pubkey, realm: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&get_vesting_config_seeds(realm, mint), program_id).0
+}
+
+/// Deserializes VestingConfig account and checks the address, owner program and linkage
+/// Returns default settings (with the bump seed of the PDA) if the account is not created.
+/// The address of the created account is checked with the bump seed stored in the account
+pub fn get_vesting_config_data_or_default(
+    program_id: &Pubkey,
+    vesting_config_info: &AccountInfo,
+    realm: &Pubkey,
+    mint: &Pubkey,
+) -> Result<VestingConfig, ProgramError> {
+    let seeds = get_vesting_config_seeds(realm, mint);
+
+    if vesting_config_info.data_is_empty() {
+        let (vesting_config_address, bump_seed) = Pubkey::find_program_address(&seeds, program_id);
+        if vesting_config_address != *vesting_config_info.key {
+            return Err(VestingError::InvalidVestingConfigAccount.into());
+        }
+        return Ok(VestingConfig { bump_seed, ..VestingConfig::new(realm, mint) });
+    }
+
+    let vesting_config = get_account_data::<VestingConfig>(program_id, vesting_config_info)?;
+    if vesting_config.realm != *realm || vesting_config.mint != *mint {
+        return Err(VestingError::InvalidVestingConfigAccount.into());
+    }
+
+    let vesting_config_address = Pubkey::create_program_address(
+        &[seeds[0], seeds[1], seeds[2], &[vesting_config.bump_seed]],
+        program_id,
+    ).map_err(|_| VestingError::InvalidVestingConfigAccount)?;
+    if vesting_config_address != *vesting_config_info.key {
+        return Err(VestingError::InvalidVestingConfigAccount.into());
+    }
+    Ok(vesting_config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weight_curves() {
+        assert_eq!(WeightCurve::Linear.get_weight(1_000_000).unwrap(), 1_000_000);
+
+        assert_eq!(WeightCurve::SquareRoot.get_weight(0).unwrap(), 0);
+        assert_eq!(WeightCurve::SquareRoot.get_weight(1_000_000).unwrap(), 1_000);
+        assert_eq!(WeightCurve::SquareRoot.get_weight(1_000_001).unwrap(), 1_000);
+        assert_eq!(WeightCurve::SquareRoot.get_weight(u64::MAX).unwrap(), u32::MAX as u64);
+
+        let curve = WeightCurve::PiecewiseLinear {
+            points: vec![
+                WeightCurvePoint {amount: 100, weight: 100},
+                WeightCurvePoint {amount: 300, weight: 200},
+            ],
+        };
+        curve.validate().unwrap();
+        assert_eq!(curve.get_weight(50).unwrap(), 50);
+        assert_eq!(curve.get_weight(200).unwrap(), 150);
+        assert_eq!(curve.get_weight(300).unwrap(), 200);
+        assert_eq!(curve.get_weight(u64::MAX).unwrap(), 200);
+
+        // Positive amounts are not rounded down to zero weight
+        let curve = WeightCurve::PiecewiseLinear {
+            points: vec![WeightCurvePoint {amount: 1_000, weight: 1}],
+        };
+        curve.validate().unwrap();
+        assert_eq!(curve.get_weight(0).unwrap(), 0);
+        assert_eq!(curve.get_weight(1).unwrap(), 1);
+        assert!(WeightCurve::PiecewiseLinear {
+            points: vec![WeightCurvePoint {amount: 1_000, weight: 0}],
+        }.validate().is_err());
+
+        assert!(WeightCurve::PiecewiseLinear {points: vec![]}.validate().is_err());
+        assert!(WeightCurve::PiecewiseLinear {
+            points: vec![
+                WeightCurvePoint {amount: 100, weight: 100},
+                WeightCurvePoint {amount: 100, weight: 200},
+            ],
+        }.validate().is_err());
+    }
+
+    #[test]
+    fn test_voter_weight_cap() {
+        let mut vesting_config = VestingConfig::new(&Pubkey::new_unique(), &Pubkey::new_unique());
+        assert!(!vesting_config.has_voter_weight_cap());
+        assert_eq!(vesting_config.get_voter_weight_cap(u64::MAX).unwrap(), u64::MAX);
+
+        vesting_config.max_voter_weight_share = 2_500;
+        assert!(vesting_config.has_voter_weight_cap());
+        assert_eq!(vesting_config.get_voter_weight_cap(1_000).unwrap(), 250);
+        assert_eq!(vesting_config.get_voter_weight_cap(0).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_max_voter_weight_from_supply() {
+        let mut vesting_config = VestingConfig::new(&Pubkey::new_unique(), &Pubkey::new_unique());
+        assert!(vesting_config.get_max_voter_weight_from_supply(1_000, 0).is_err());
+
+        let treasury = Pubkey::new_unique();
+        vesting_config.max_voter_weight_source = MaxVoterWeightSource::MintSupply {
+            supply_fraction: SUPPLY_FRACTION_BASE / 2,
+            excluded_token_accounts: vec![treasury],
+        };
+        vesting_config.max_voter_weight_source.validate().unwrap();
+        assert_eq!(vesting_config.get_max_voter_weight_from_supply(1_000, 400).unwrap(), 300);
+        assert!(vesting_config.get_max_voter_weight_from_supply(1_000, 1_001).is_err());
+
+        assert!(MaxVoterWeightSource::MintSupply {
+            supply_fraction: SUPPLY_FRACTION_BASE + 1,
+            excluded_token_accounts: vec![],
+        }.validate().is_err());
+        assert!(MaxVoterWeightSource::MintSupply {
+            supply_fraction: SUPPLY_FRACTION_BASE,
+            excluded_token_accounts: vec![treasury, treasury],
+        }.validate().is_err());
+    }
+
+    #[test]
+    fn test_vesting_config_max_size() {
+        let vesting_config = VestingConfig {
+            weight_curve: WeightCurve::PiecewiseLinear {
+                points: vec![WeightCurvePoint::default(); MAX_WEIGHT_CURVE_POINTS],
+            },
+            max_voter_weight_source: MaxVoterWeightSource::MintSupply {
+                supply_fraction: SUPPLY_FRACTION_BASE,
+                excluded_token_accounts: vec![Pubkey::new_unique(); MAX_EXCLUDED_TOKEN_ACCOUNTS],
+            },
+            tge_timestamp: Some(0),
+            release_authority: Some(Pubkey::new_unique()),
+            ..VestingConfig::new(&Pubkey::new_unique(), &Pubkey::new_unique())
+        };
+        assert_eq!(Some(vesting_config.try_to_vec().unwrap().len()), vesting_config.get_max_size());
+    }
+}
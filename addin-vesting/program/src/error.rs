@@ -71,6 +71,93 @@ pub enum VestingError {
 
     #[error("Invalid schedule")]
     InvalidSchedule,
+
+    #[error("VestingIsAlreadyUnderRealm")]
+    VestingIsAlreadyUnderRealm,
+
+    #[error("Invalid RealmConfig account")]
+    InvalidRealmConfigAccount,
+
+    #[error("Vesting addin is not the voter weight addin of the Realm")]
+    InvalidRealmVoterWeightAddin,
+
+    #[error("Vesting addin is still the voter weight addin of the Realm")]
+    RealmUsesVestingAddin,
+
+    #[error("Invalid Realm authority")]
+    InvalidRealmAuthority,
+
+    #[error("Invalid Proposal account")]
+    InvalidProposalAccount,
+
+    #[error("Proposal has not entered Voting state")]
+    ProposalIsNotVoting,
+
+    #[error("Invalid VestingConfig account")]
+    InvalidVestingConfigAccount,
+
+    #[error("Invalid weight curve")]
+    InvalidWeightCurve,
+
+    #[error("Invalid max voter weight source")]
+    InvalidMaxVoterWeightSource,
+
+    #[error("Invalid excluded token account")]
+    InvalidExcludedTokenAccount,
+
+    #[error("Vote percentage expiry is in the past")]
+    InvalidVotePercentageExpiry,
+
+    #[error("Max Voter Weight Record not empty")]
+    MaxVoterWeightRecordNotEmpty,
+
+    #[error("Invalid rent payer account")]
+    InvalidRentPayerAccount,
+
+    #[error("Vesting label is too long")]
+    InvalidLabel,
+
+    #[error("Invalid grantor account")]
+    InvalidGrantorAccount,
+
+    #[error("Invalid vesting template")]
+    InvalidVestingTemplate,
+
+    #[error("Invalid VestingTemplate account")]
+    InvalidVestingTemplateAccount,
+
+    #[error("TGE timestamp is already set")]
+    TgeTimestampAlreadySet,
+
+    #[error("TGE-relative schedule is not resolved")]
+    UnresolvedTgeRelativeSchedule,
+
+    #[error("Invalid VestingIndex account")]
+    InvalidVestingIndexAccount,
+
+    #[error("Invalid spl-governance program")]
+    InvalidGovernanceProgram,
+
+    #[error("Voter weight history doesn't cover the slot")]
+    VoterWeightHistoryUnavailable,
+
+    #[error("Invalid voter weight action")]
+    InvalidVoterWeightAction,
+
+    #[error("Proposal account is required to report the voter weight for CastVote")]
+    ProposalRequiredForCastVote,
+
+    #[error("VestingIndex has reached the maximum number of vesting accounts")]
+    VestingIndexIsFull,
+
+    #[error("Vesting account has the legacy layout and must be migrated by MigrateVestingRecord")]
+    VestingRecordMigrationRequired,
+
+    #[error("VoterWeightRecord has the legacy layout and must be migrated by MigrateVoterWeightRecord")]
+    VoterWeightRecordMigrationRequired,
+
+    #[error("Max voter weight of the mint supply requires the linear weight curve")]
+    NonLinearWeightCurveWithMintSupply,
 }
 
 impl From<VestingError> for ProgramError {
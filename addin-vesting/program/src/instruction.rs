@@ -1,10 +1,14 @@
 use crate::{
-    state::VestingSchedule,
-    voter_weight::get_voter_weight_record_address,
+    state::{VestingSchedule, VestingTokenSeed, get_vesting_token_seeds},
+    config::{MaxVoterWeightSource, WeightCurve, get_vesting_config_address},
+    template::{VestingTemplateEntry, get_vesting_template_address},
+    vesting_index::get_vesting_index_address,
+    voter_weight::{VoterWeightAction, get_voter_weight_record_address},
     max_voter_weight::get_max_voter_weight_record_address,
 };
 
 use solana_program::{
+    clock::Slot,
     instruction::{AccountMeta, Instruction},
     program_error::ProgramError,
     pubkey::Pubkey,
@@ -12,7 +16,10 @@ use solana_program::{
 };
 
 use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
-use spl_governance::state::token_owner_record::get_token_owner_record_address;
+use spl_governance::state::{
+    realm_config::get_realm_config_address,
+    token_owner_record::get_token_owner_record_address,
+};
 
 #[repr(C)]
 #[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
@@ -26,20 +33,48 @@ pub enum VestingInstruction {
     ///   0. `[]` The system program account
     ///   1. `[]` The spl-token program account
     ///   2. `[writable]` The vesting account. PDA seeds: [vesting spl-token account]
-    ///   3. `[writable]` The vesting spl-token account
+    ///   3. `[writable]` The vesting spl-token account (PDA seeds: ['vesting-token', vesting_token_seed]
+    ///      if it's created by the instruction)
     ///   4. `[signer]` The source spl-token account owner
     ///   5. `[writable]` The source spl-token account
     ///   6. `[]` The Vesting Owner account
-    ///   7. `[signer]` Payer
+    ///   7. `[signer]` Payer (`[writable,signer]` with vesting_token_seed)
+    ///   *. `[]` The token mint account (with vesting_token_seed only)
     ///
     ///  Optional part (vesting for Realm)
-    ///   8. `[]` The Realm account
-    ///   9. `[writable]` The VoterWeightRecord. PDA seeds: ['voter_weight', realm, token_mint, token_owner]
-    ///  10. `[writable]` The MaxVoterWeightRecord. PDA seeds: ['max_voter_weight', realm, token_mint]
+    ///   *. `[]` The Governance program account
+    ///   *. `[]` The Realm account
+    ///   *. `[]` The RealmConfig account. PDA seeds (governance program): ['realm-config', realm]
+    ///   *. `[]` The VestingConfig account. PDA seeds: ['vesting-config', realm, token_mint]
+    ///   *. `[writable]` The VoterWeightRecord. PDA seeds: ['voter_weight', realm, token_mint, token_owner]
+    ///   *. `[writable]` The MaxVoterWeightRecord. PDA seeds: ['max_voter_weight', realm, token_mint]
+    ///
+    ///  Optional part (updating the indexes of the vestings)
+    ///   *. `[writable]` The VestingIndex accounts (created by UpdateVestingIndex) of the owners
+    ///      (the vesting is added only to the index of the owner signing the instruction)
     ///
     Deposit {
+        /// Milestone entries (with release_proposal) require the vesting for Realm: the governance
+        /// program is pinned in the vesting account and only its Proposals can release them
         #[allow(dead_code)]
         schedules: Vec<VestingSchedule>,
+
+        #[allow(dead_code)]
+        label: Option<String>,
+
+        /// The release times are offsets from the TGE timestamp of the Realm (vesting for Realm only)
+        #[allow(dead_code)]
+        tge_relative: bool,
+
+        /// The vesting spl-token account is created by the instruction (owned by the vesting account)
+        /// with the seed, so the caller doesn't need to create and sign it.
+        /// The seed is any unique key (e.g. the pubkey of a new random keypair)
+        #[allow(dead_code)]
+        vesting_token_seed: Option<VestingTokenSeed>,
+
+        /// Canonical bump seed of the vesting account PDA (seeds: [vesting spl-token account])
+        #[allow(dead_code)]
+        vesting_bump_seed: u8,
     },
 
 
@@ -57,13 +92,25 @@ pub enum VestingInstruction {
     ///   5. `[]` The Governance program account
     ///   6. `[]` The Realm account
     ///   7. `[]` Governing Owner Record. PDA seeds (governance program): ['governance', realm, token_mint, vesting_owner]
-    ///   8. `[writable]` The VoterWeightRecord. PDA seeds: ['voter_weight', realm, token_mint, vesting_owner]
-    ///   9. `[writable]` The MaxVoterWeightRecord. PDA seeds: ['max_voter_weight', realm, token_mint]
+    ///   8. `[]` The VestingConfig account. PDA seeds: ['vesting-config', realm, token_mint]
+    ///   9. `[writable]` The VoterWeightRecord. PDA seeds: ['voter_weight', realm, token_mint, vesting_owner]
+    ///  10. `[writable]` The MaxVoterWeightRecord. PDA seeds: ['max_voter_weight', realm, token_mint]
+    ///
+    ///  Optional part (milestone releases)
+    ///   *. `[]` The Proposal accounts referenced by the schedule (owned by the pinned governance program)
+    ///
+    ///  Optional part (closing the VoterWeightRecord left without the tokens of the vestings)
+    ///   *. `[writable]` Spill account (the last account, not owned by the governance program)
     ///
     Withdraw,
 
 
     /// Set Vote Percentage for calcalate voter_weight from total_amount of deposited tokens
+    /// The percentage set with valid_until reverts to the default vote percentage of the Realm after the slot,
+    /// the voter weight of the percentage expires after the slot (VoterWeightRecord.voter_weight_expiry).
+    /// The next instruction changing the VoterWeightRecord (e.g. UpdateVoterWeightRecord) applies the default
+    /// vote percentage and records the voter weight change as of the slot following valid_until
+    /// (as of the current slot for the Realm with the voter weight cap)
     ///
     /// Accounts expected by this instruction:
     /// 
@@ -75,9 +122,15 @@ pub enum VestingInstruction {
     ///   4. `[]` The Realm account
     ///   5. `[]` Governing Owner Record. PDA seeds (governance program): ['governance', realm, token_mint, vesting_owner]
     ///   6. `[writable]` The VoterWeight Record. PDA seeds: ['voter_weight', realm, token_mint, vesting_owner]
+    ///   7. `[]` The VestingConfig account. PDA seeds: ['vesting-config', realm, token_mint]
+    ///   8. `[]` The MaxVoterWeightRecord. PDA seeds: ['max_voter_weight', realm, token_mint]
     SetVotePercentage {
         #[allow(dead_code)]
         vote_percentage: u16,
+
+        /// The last slot when the vote percentage is valid (without expiration if None)
+        #[allow(dead_code)]
+        valid_until: Option<Slot>,
     },
 
 
@@ -95,8 +148,21 @@ pub enum VestingInstruction {
     ///   3. `[]` The Governance program account
     ///   4. `[]` The Realm account
     ///   5. `[]` Governing Owner Record. PDA seeds (governance program): ['governance', realm, token_mint, current_vesting_owner]
-    ///   6. `[writable]` The from VoterWeight Record. PDA seeds: ['voter_weight', realm, token_mint, current_vesting_owner]
-    ///   7. `[writable]` The to VoterWeight Record. PDA seeds: ['voter_weight', realm, token_mint, new_vesting_owner]
+    ///   6. `[]` The VestingConfig account. PDA seeds: ['vesting-config', realm, token_mint]
+    ///   7. `[writable]` The from VoterWeight Record. PDA seeds: ['voter_weight', realm, token_mint, current_vesting_owner]
+    ///   8. `[writable]` The to VoterWeight Record. PDA seeds: ['voter_weight', realm, token_mint, new_vesting_owner]
+    ///   9. `[writable]` The MaxVoterWeightRecord. PDA seeds: ['max_voter_weight', realm, token_mint]
+    ///
+    ///  Optional part (creating the to VoterWeight Record if it doesn't exist)
+    ///  10. `[]` The system program account
+    ///  11. `[signer]` Payer
+    ///
+    ///  Optional part (closing the from VoterWeightRecord left without the tokens of the vestings)
+    ///   *. `[writable]` Spill account
+    ///
+    ///  Optional part (updating the indexes of the vestings)
+    ///   *. `[writable]` The VestingIndex accounts (created by UpdateVestingIndex) of the owners
+    ///      (the vesting is added only to the index of the owner signing the instruction)
     ChangeOwner,
 
 
@@ -121,7 +187,10 @@ pub enum VestingInstruction {
     ///   1. `[writable]` The Vesting account. PDA seeds: [vesting spl-token account]
     ///   2. `[writable]` The vesting spl-token account
     ///   3. `[signer]` The vesting Owner account
-    ///   4. `[writable]` Spill account
+    ///   4. `[writable]` The rent payer of the Vesting account (receives the lamports of the closed accounts)
+    ///
+    ///  Optional part (updating the indexes of the vestings)
+    ///   *. `[writable]` The VestingIndex accounts (created by UpdateVestingIndex) of the owners
     Close,
 
 
@@ -148,22 +217,478 @@ pub enum VestingInstruction {
     ///   3. `[writable]` The vesting spl-token account
     ///   4. `[signer]` The vesting Owner account
     ///   5. `[writable]` The new vesting account. PDA seeds: [new vesting spl-token account]
-    ///   6. `[]` The new vesting spl-token account
+    ///   6. `[writable]` The new vesting spl-token account (PDA seeds: ['vesting-token', new_vesting_token_seed]
+    ///      if it's created by the instruction)
     ///   7. `[]` The new Vesting Owner account
-    ///   8. `[signer]` Payer
+    ///   8. `[signer]` Payer (`[writable,signer]` with new_vesting_token_seed)
+    ///   *. `[]` The token mint account (with new_vesting_token_seed only)
     ///
     ///  Optional part (vesting for Realm)
-    ///   9. `[]` The Governance program account
-    ///  10. `[]` The Realm account
-    ///  11. `[]` Governing Owner Record. PDA seed (governance program): ['governance', realm, token_mint, vesting_owner]
-    ///  12. `[writable]` The VoterWeightRecord. PDA seeds: ['voter_weight', realm, token_mint, token_owner]
-    ///  13. `[writable]` The new VoterWeightRecord. PDA seeds: ['voter_weight', realm, token_mint, new_token_owner]
+    ///   *. `[]` The Governance program account
+    ///   *. `[]` The Realm account
+    ///   *. `[]` Governing Owner Record. PDA seed (governance program): ['governance', realm, token_mint, vesting_owner]
+    ///   *. `[]` The VestingConfig account. PDA seeds: ['vesting-config', realm, token_mint]
+    ///   *. `[writable]` The VoterWeightRecord. PDA seeds: ['voter_weight', realm, token_mint, token_owner]
+    ///   *. `[writable]` The new VoterWeightRecord. PDA seeds: ['voter_weight', realm, token_mint, new_token_owner]
+    ///   *. `[writable]` The MaxVoterWeightRecord. PDA seeds: ['max_voter_weight', realm, token_mint]
+    ///   *. `[writable]` Spill account to close the VoterWeightRecord left without the tokens of the vestings
+    ///      (optional)
+    ///
+    ///  Optional part (updating the indexes of the vestings)
+    ///   *. `[writable]` The VestingIndex accounts (created by UpdateVestingIndex) of the owners
+    ///      (the vesting is added only to the index of the owner signing the instruction)
+    ///
+    /// The splitted vesting inherits the label of the source vesting unless the label is specified
     ///
     Split {
         #[allow(dead_code)]
         schedules: Vec<VestingSchedule>,
+
+        #[allow(dead_code)]
+        label: Option<String>,
+
+        /// The new vesting spl-token account is created by the instruction with the seed (see Deposit)
+        #[allow(dead_code)]
+        new_vesting_token_seed: Option<VestingTokenSeed>,
+
+        /// Canonical bump seed of the new vesting account PDA (seeds: [new vesting spl-token account])
+        #[allow(dead_code)]
+        new_vesting_bump_seed: u8,
+    },
+
+
+    /// Attach the vesting created without Realm to the Realm
+    /// The remaining amount of the vesting is added to the voter weight of the vesting owner.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   * Single owner
+    ///   0. `[]` The system program account
+    ///   1. `[writable]` The vesting account. PDA seeds: [vesting spl-token account]
+    ///   2. `[signer]` The vesting Owner account
+    ///   3. `[signer]` Payer
+    ///   4. `[]` The Governance program account
+    ///   5. `[]` The Realm account
+    ///   6. `[]` The RealmConfig account. PDA seeds (governance program): ['realm-config', realm]
+    ///   7. `[]` The VestingConfig account. PDA seeds: ['vesting-config', realm, token_mint]
+    ///   8. `[writable]` The VoterWeightRecord. PDA seeds: ['voter_weight', realm, token_mint, vesting_owner]
+    ///   9. `[writable]` The MaxVoterWeightRecord. PDA seeds: ['max_voter_weight', realm, token_mint]
+    ///
+    AttachRealm,
+
+
+    /// Detach the vesting from the Realm
+    /// The remaining amount of the vesting is removed from the voter weight of the vesting owner.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   * Single owner
+    ///   0. `[writable]` The vesting account. PDA seeds: [vesting spl-token account]
+    ///   1. `[signer]` The vesting Owner account
+    ///   2. `[writable]` The rent payer of the Vesting account (receives the rent released by the vesting account)
+    ///   3. `[]` The Governance program account
+    ///   4. `[]` The Realm account
+    ///   5. `[]` Governing Owner Record. PDA seeds (governance program): ['governance', realm, token_mint, vesting_owner]
+    ///   6. `[]` The VestingConfig account. PDA seeds: ['vesting-config', realm, token_mint]
+    ///   7. `[writable]` The VoterWeightRecord. PDA seeds: ['voter_weight', realm, token_mint, vesting_owner]
+    ///   8. `[writable]` The MaxVoterWeightRecord. PDA seeds: ['max_voter_weight', realm, token_mint]
+    ///
+    DetachRealm,
+
+
+    /// Unlocks a vesting for the Realm which doesn't use the vesting addin anymore as the community
+    /// or council voter weight addin (or can't be deserialized by the governance program)
+    /// and detaches the vesting from the Realm.
+    /// The vesting amount is removed from VoterWeightRecord and MaxVoterWeightRecord if they exist.
+    /// The tokens are released even if the records can't be updated.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   * Single owner
+    ///   0. `[]` The spl-token program account
+    ///   1. `[writable]` The vesting account. PDA seeds: [vesting spl-token account]
+    ///   2. `[writable]` The vesting spl-token account
+    ///   3. `[writable]` The destination spl-token account
+    ///   4. `[signer]` The Vesting Owner account
+    ///   5. `[writable]` The rent payer of the Vesting account (receives the rent released by the vesting account)
+    ///   6. `[]` The Governance program account
+    ///   7. `[]` The Realm account
+    ///   8. `[]` The RealmConfig account. PDA seeds (governance program): ['realm-config', realm]
+    ///   9. `[]` The VestingConfig account. PDA seeds: ['vesting-config', realm, token_mint]
+    ///  10. `[writable]` The VoterWeightRecord. PDA seeds: ['voter_weight', realm, token_mint, vesting_owner]
+    ///  11. `[writable]` The MaxVoterWeightRecord. PDA seeds: ['max_voter_weight', realm, token_mint]
+    ///
+    ///  Optional part (milestone releases)
+    ///   *. `[]` The Proposal accounts referenced by the schedule (owned by the pinned governance program)
+    ///
+    WithdrawOutOfRealm,
+
+
+    /// Pull the remaining schedules of the vestings under the Realm forward to the release time
+    /// (e.g. acquisition or termination of the contract).
+    /// Can be invoked by the Realm authority (the governance PDA when the Realm is governed by itself)
+    /// or by the Governance account named as the release authority in the VestingConfig.
+    /// The total amounts of the vestings and the voter weights are not changed.
+    /// The vesting accounts are shrunk to the new schedule, the released rent goes to the rent payers.
+    /// VestingOperationResult with zero amounts is returned: the vestings of several owners are changed
+    /// and no tokens are transferred.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` The Governance program account
+    ///   1. `[]` The Realm account
+    ///   2. `[signer]` The Realm authority or the release authority account
+    ///   3. `[]` The Mint account of the vestings
+    ///   4. `[]` The VestingConfig account. PDA seeds: ['vesting-config', realm, token_mint]
+    ///   * Pairs of the accounts for every vesting:
+    ///     `[writable]` The vesting account. PDA seeds: [vesting spl-token account]
+    ///     `[writable]` The rent payer of the vesting account
+    ///
+    AccelerateRelease {
+        #[allow(dead_code)]
+        release_time: u64,
+
+        /// Milestone entries are released unconditionally too. Otherwise they keep
+        /// their Proposals and release times
+        #[allow(dead_code)]
+        include_milestones: bool,
+    },
+
+
+    /// Update voter weight reported by the VoterWeightRecord
+    /// The current weight curve of the Realm is applied to the record and MaxVoterWeightRecord is updated.
+    /// With the action the voter weight is reported by the token owner or the delegate (valid in the
+    /// current slot only). With the Proposal account the voter weight before the slot when the Proposal
+    /// entered Voting state is reported for CastVote action over the Proposal. Without the Proposal account
+    /// the current voter weight is reported for the action. Without the action the current voter weight
+    /// is reported until the expiry of the temporary vote percentage. The Realm with snapshot voting doesn't accept CastVote without
+    /// the Proposal account.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` The VoterWeightRecord. PDA seeds: ['voter_weight', realm, token_mint, token_owner]
+    ///   1. `[]` The VestingConfig account. PDA seeds: ['vesting-config', realm, token_mint]
+    ///   2. `[writable]` The MaxVoterWeightRecord. PDA seeds: ['max_voter_weight', realm, token_mint]
+    ///
+    ///  Optional part (required with the action)
+    ///   3. `[]` The Governance program account
+    ///   4. `[]` The Realm account
+    ///   5. `[]` Governing Owner Record. PDA seeds (governance program): ['governance', realm, token_mint, token_owner]
+    ///   6. `[signer]` The token owner or the governance delegate
+    ///   7. `[]` The Proposal account (optional, snapshot voting)
+    ///
+    UpdateVoterWeightRecord {
+        /// The action to report the voter weight for (CastVote with the Proposal account)
+        #[allow(dead_code)]
+        weight_action: Option<VoterWeightAction>,
+    },
+
+
+    /// Set the vesting addin settings for the Realm and the governing token mint
+    /// Can be invoked by the Realm authority only.
+    /// VoterWeightRecords are converted by the new settings on the next change or UpdateVoterWeightRecord.
+    /// After the weight curve is changed the records and MaxVoterWeightRecord keep the weighted amounts
+    /// of the previous curve until every record is converted by UpdateVoterWeightRecord.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` The system program account
+    ///   1. `[]` The Governance program account
+    ///   2. `[]` The Realm account
+    ///   3. `[signer]` The Realm authority account
+    ///   4. `[signer]` Payer
+    ///   5. `[]` The Mint account
+    ///   6. `[writable]` The VestingConfig account. PDA seeds: ['vesting-config', realm, token_mint]
+    ///
+    SetVestingConfig {
+        #[allow(dead_code)]
+        weight_curve: WeightCurve,
+
+        /// Maximum voter weight of a single voter as a share of the max voter weight
+        /// (in hundredths of a percent, 10000 means no cap)
+        #[allow(dead_code)]
+        max_voter_weight_share: u16,
+
+        /// Source of the max voter weight. Can't be changed from MintSupply back to Deposits
+        #[allow(dead_code)]
+        max_voter_weight_source: MaxVoterWeightSource,
+
+        /// Count only the tokens not released by the schedules toward the voter weight
+        #[allow(dead_code)]
+        count_unvested_only: bool,
+
+        /// Governance account of the Realm which can accelerate the release of the vestings
+        #[allow(dead_code)]
+        release_authority: Option<Pubkey>,
+
+        /// CastVote uses only the voter weight as of the voting start of the Proposal
+        #[allow(dead_code)]
+        snapshot_voting: bool,
+
+        /// Vote percentage of the VoterWeightRecords after the expiration of the temporary vote percentage
+        #[allow(dead_code)]
+        default_vote_percentage: u16,
+    },
+
+
+    /// Recalculate MaxVoterWeightRecord from the mint supply for the Realm with MintSupply max voter weight source
+    /// The max voter weight is valid in the current slot only (max_voter_weight_expiry is set to the current slot)
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` The system program account
+    ///   1. `[signer]` Payer
+    ///   2. `[]` The Realm account
+    ///   3. `[]` The Mint account
+    ///   4. `[]` The VestingConfig account. PDA seeds: ['vesting-config', realm, token_mint]
+    ///   5. `[writable]` The MaxVoterWeightRecord. PDA seeds: ['max_voter_weight', realm, token_mint]
+    ///   *. `[]` The excluded token accounts in the order of VestingConfig
+    ///
+    RefreshMaxVoterWeight,
+
+
+    /// Recalculate the released amount of the vesting excluded from the voter weight of the owner.
+    /// The amount is excluded only if count_unvested_only is set in the VestingConfig of the Realm,
+    /// otherwise the previously excluded amount is counted again
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` The Vesting account. PDA seeds: [vesting spl-token account]
+    ///   1. `[]` The VestingConfig account. PDA seeds: ['vesting-config', realm, token_mint]
+    ///   2. `[writable]` The VoterWeightRecord. PDA seeds: ['voter_weight', realm, token_mint, vesting_owner]
+    ///   3. `[writable]` The MaxVoterWeightRecord. PDA seeds: ['max_voter_weight', realm, token_mint]
+    ///
+    RefreshReleasedAmount,
+
+
+    /// Set the exact number of deposited tokens to calculate voter_weight from (instead of vote percentage).
+    /// The amount is clamped to total_amount of deposited tokens and resets the vote percentage to 100%
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` The Vesting Mint
+    ///   1. `[]` The Vesting Owner account
+    ///   2. `[signer]` The Vesting Authority account
+    ///   3. `[]` The Governance program account
+    ///   4. `[]` The Realm account
+    ///   5. `[]` Governing Owner Record. PDA seeds (governance program): ['governance', realm, token_mint, vesting_owner]
+    ///   6. `[writable]` The VoterWeight Record. PDA seeds: ['voter_weight', realm, token_mint, vesting_owner]
+    ///   7. `[]` The VestingConfig account. PDA seeds: ['vesting-config', realm, token_mint]
+    ///   8. `[]` The MaxVoterWeightRecord. PDA seeds: ['max_voter_weight', realm, token_mint]
+    SetVoteAmount {
+        #[allow(dead_code)]
+        amount: u64,
+    },
+
+
+    /// Close MaxVoterWeightRecord account of the Realm with zero max voter weight
+    ///
+    /// Accounts expected by this instruction:
+    ///   0. `[]` The Governance program account
+    ///   1. `[]` The Realm account
+    ///   2. `[signer]` The Realm authority
+    ///   3. `[]` The Mint account
+    ///   4. `[writable]` The MaxVoterWeightRecord. PDA seeds: ['max_voter_weight', realm, token_mint]
+    ///   5. `[writable]` Spill account
+    CloseMaxVoterWeightRecord,
+
+
+    /// Split the share of Vesting into the new vesting
+    /// The share (in basis points) is taken from every entry of the schedule, so the new vesting keeps
+    /// the same release times. The rounding dust is taken from the last entry.
+    ///
+    /// Accounts expected by this instruction are the same as for Split instruction
+    ///
+    SplitPercent {
+        #[allow(dead_code)]
+        basis_points: u16,
+
+        /// The new vesting spl-token account is created by the instruction with the seed (see Deposit)
+        #[allow(dead_code)]
+        new_vesting_token_seed: Option<VestingTokenSeed>,
+
+        /// Canonical bump seed of the new vesting account PDA (seeds: [new vesting spl-token account])
+        #[allow(dead_code)]
+        new_vesting_bump_seed: u8,
+    },
+
+
+    /// Split Vesting into many parts at once (one new vesting for every item of schedules)
+    ///
+    /// Accounts expected by this instruction are the same as for Split instruction, except
+    /// the new vesting accounts (5-7) and the new VoterWeightRecord (14) are repeated for every item of schedules:
+    ///
+    ///   * Single owner
+    ///   0. `[]` The system program account
+    ///   1. `[]` The spl-token program account
+    ///   2. `[writable]` The vesting account. PDA seeds: [vesting spl-token account]
+    ///   3. `[writable]` The vesting spl-token account
+    ///   4. `[signer]` The vesting Owner account
+    ///   *. `[writable]` The new vesting account. PDA seeds: [new vesting spl-token account]
+    ///      `[writable]` The new vesting spl-token account
+    ///      `[]` The new Vesting Owner account
+    ///   *. `[signer]` Payer (`[writable,signer]` with new_vesting_token_seeds)
+    ///   *. `[]` The token mint account (with new_vesting_token_seeds only)
+    ///
+    ///  Optional part (vesting for Realm)
+    ///   *. `[]` The Governance program account
+    ///   *. `[]` The Realm account
+    ///   *. `[]` Governing Owner Record. PDA seed (governance program): ['governance', realm, token_mint, vesting_owner]
+    ///   *. `[]` The VestingConfig account. PDA seeds: ['vesting-config', realm, token_mint]
+    ///   *. `[writable]` The VoterWeightRecord. PDA seeds: ['voter_weight', realm, token_mint, token_owner]
+    ///   *. `[writable]` The new VoterWeightRecord for every new vesting. PDA seeds: ['voter_weight', realm, token_mint, new_token_owner]
+    ///   *. `[writable]` The MaxVoterWeightRecord. PDA seeds: ['max_voter_weight', realm, token_mint]
+    ///   *. `[writable]` Spill account to close the VoterWeightRecord left without the tokens of the vestings
+    ///      (optional)
+    ///
+    ///  Optional part (updating the indexes of the vestings)
+    ///   *. `[writable]` The VestingIndex accounts (created by UpdateVestingIndex) of the owners
+    ///      (the vesting is added only to the index of the owner signing the instruction)
+    ///
+    SplitMany {
+        #[allow(dead_code)]
+        schedules: Vec<Vec<VestingSchedule>>,
+
+        /// The new vesting spl-token accounts are created by the instruction with the seeds
+        /// (one seed for every item of schedules, see Deposit)
+        #[allow(dead_code)]
+        new_vesting_token_seeds: Option<Vec<VestingTokenSeed>>,
+
+        /// Canonical bump seeds of the new vesting account PDAs (one bump seed for every item of schedules)
+        #[allow(dead_code)]
+        new_vesting_bump_seeds: Vec<u8>,
+    },
+
+
+    /// Set the label of the vesting (can be invoked by the grantor only)
+    ///
+    /// Accounts expected by this instruction:
+    ///   0. `[]` The system program account
+    ///   1. `[writable]` The vesting account. PDA seeds: [vesting spl-token account]
+    ///   2. `[signer]` The grantor of the vesting
+    ///   3. `[writable,signer]` Payer (for the longer label)
+    ///
+    SetLabel {
+        #[allow(dead_code)]
+        label: Option<String>,
+    },
+
+
+    /// Create VestingTemplate account with the shape of the vesting schedule
+    ///
+    /// Accounts expected by this instruction:
+    ///   0. `[]` The system program account
+    ///   1. `[writable]` The VestingTemplate account. PDA seeds: ['vesting-template', authority, name]
+    ///   2. `[signer]` The template authority
+    ///   3. `[writable,signer]` Payer
+    ///
+    CreateVestingTemplate {
+        #[allow(dead_code)]
+        name: String,
+
+        #[allow(dead_code)]
+        entries: Vec<VestingTemplateEntry>,
     },
 
+
+    /// Creates a new vesting with the schedule expanded from the VestingTemplate:
+    /// the total amount is split by the fractions of the entries released at the start plus the offsets
+    ///
+    /// Accounts expected by this instruction:
+    ///   0. `[]` The VestingTemplate account
+    ///   1.. The accounts of Deposit instruction (including the optional part)
+    ///
+    DepositFromTemplate {
+        #[allow(dead_code)]
+        start: u64,
+
+        #[allow(dead_code)]
+        total: u64,
+
+        /// Canonical bump seed of the vesting account PDA (seeds: [vesting spl-token account])
+        #[allow(dead_code)]
+        vesting_bump_seed: u8,
+    },
+
+
+    /// Set the TGE timestamp for the TGE-relative vestings of the Realm and the governing token mint
+    /// Can be invoked by the Realm authority only and only once.
+    /// The TGE-relative release times are resolved on the next Withdraw, Split or RefreshReleasedAmount
+    ///
+    /// Accounts expected by this instruction are the same as for SetVestingConfig instruction
+    ///
+    SetTgeTimestamp {
+        #[allow(dead_code)]
+        tge_timestamp: u64,
+    },
+
+
+    /// Synchronizes the VestingIndex of the owner with the vesting records:
+    /// adds the vesting accounts owned by the owner and removes the vesting accounts
+    /// owned by somebody else or closed. Creates the VestingIndex if it doesn't exist
+    /// (with the room for MAX_VESTING_INDEX_ENTRIES vesting accounts).
+    /// The vesting accounts are added only by the owner, the stale ones can be removed by anybody
+    ///
+    /// Accounts expected by this instruction:
+    ///   0. `[]` The system program account
+    ///   1. `[writable]` The VestingIndex account. PDA seeds: ['vesting-index', owner]
+    ///   2. `[signer]` The vesting owner account (a signer only to add the vesting accounts)
+    ///   3. `[writable,signer]` Payer
+    ///   4.. `[]` The vesting accounts to add or remove
+    ///
+    UpdateVestingIndex,
+
+
+    /// Returns VestingStatus of the vesting via the return data without changing any account.
+    /// The TGE-relative schedule is resolved with the TGE timestamp of the VestingConfig (if it's set),
+    /// the voter weight of the vesting is its share of the VoterWeightRecord of the owner
+    ///
+    /// Accounts expected by this instruction:
+    ///   0. `[]` The vesting account. PDA seeds: [vesting spl-token account]
+    ///
+    ///  Optional part (vesting for Realm)
+    ///   1. `[]` The VestingConfig account. PDA seeds: ['vesting-config', realm, token_mint]
+    ///   2. `[]` The VoterWeightRecord. PDA seeds: ['voter_weight', realm, token_mint, vesting_owner]
+    ///
+    ///  Optional part (milestone releases)
+    ///   *. `[]` The Proposal accounts of the milestones to include the released ones into the claimable amount
+    ///
+    GetVestingStatus,
+
+
+    /// Converts the legacy vesting account (VestingRecordV1) to the current VestingRecord layout.
+    /// The other instructions reject the legacy vesting account until it's migrated.
+    /// The grantor of the migrated vesting is unknown (Pubkey::default()), the payer funds
+    /// the grown account and becomes the rent payer of the vesting.
+    /// The vesting under Realm is pinned to the spl-governance program which owns the Realm
+    ///
+    /// Accounts expected by this instruction:
+    ///   0. `[]` The system program account
+    ///   1. `[writable]` The vesting account. PDA seeds: [vesting spl-token account]
+    ///   2. `[signer]` The Vesting Owner account
+    ///   3. `[writable,signer]` Payer
+    ///
+    ///  Optional part (vesting for Realm)
+    ///   4. `[]` The Realm account
+    ///
+    MigrateVestingRecord {
+        #[allow(dead_code)]
+        bump_seed: u8,
+    },
+
+
+    /// Converts the legacy VoterWeightRecord (ExtendedVoterWeightRecordV1) to the current layout.
+    /// The other instructions reject the legacy record until it's migrated.
+    /// The weighted amount of the migrated record is its total amount until the record is updated
+    /// by the weight curve of the Realm, the payer funds the grown account
+    ///
+    /// Accounts expected by this instruction:
+    ///   0. `[]` The system program account
+    ///   1. `[writable]` The VoterWeightRecord. PDA seeds: ['voter_weight', realm, token_mint, token_owner]
+    ///   2. `[writable,signer]` Payer
+    ///
+    MigrateVoterWeightRecord,
+
 }
 
 /// Creates a `Deposit` instruction to create and initialize the vesting token account
@@ -177,8 +702,9 @@ pub fn deposit(
     vesting_owner: &Pubkey,
     payer: &Pubkey,
     schedules: Vec<VestingSchedule>,
+    label: Option<String>,
 ) -> Result<Instruction, ProgramError> {
-    let (vesting_account, _) = Pubkey::find_program_address(&[vesting_token_account.as_ref()], program_id);
+    let (vesting_account, vesting_bump_seed) = Pubkey::find_program_address(&[vesting_token_account.as_ref()], program_id);
     let accounts = vec![
         AccountMeta::new_readonly(system_program::id(), false),
         AccountMeta::new_readonly(*token_program_id, false),
@@ -190,7 +716,7 @@ pub fn deposit(
         AccountMeta::new_readonly(*payer, true),
     ];
 
-    let instruction = VestingInstruction::Deposit { schedules };
+    let instruction = VestingInstruction::Deposit { schedules, label, tge_relative: false, vesting_token_seed: None, vesting_bump_seed };
 
     Ok(Instruction {
         program_id: *program_id,
@@ -213,8 +739,13 @@ pub fn deposit_with_realm(
     schedules: Vec<VestingSchedule>,
     realm: &Pubkey,
     mint: &Pubkey,
+    governance_id: &Pubkey,
+    label: Option<String>,
+    tge_relative: bool,
 ) -> Result<Instruction, ProgramError> {
-    let (vesting_account, _) = Pubkey::find_program_address(&[vesting_token_account.as_ref()], program_id);
+    let (vesting_account, vesting_bump_seed) = Pubkey::find_program_address(&[vesting_token_account.as_ref()], program_id);
+    let realm_config_account = get_realm_config_address(governance_id, realm);
+    let vesting_config_account = get_vesting_config_address(program_id, realm, mint);
     let voting_weight_record_account = get_voter_weight_record_address(program_id, realm, mint, vesting_owner);
     let max_voting_weight_record_account = get_max_voter_weight_record_address(program_id, realm, mint);
     let accounts = vec![
@@ -227,12 +758,15 @@ pub fn deposit_with_realm(
         AccountMeta::new_readonly(*vesting_owner, false),
         AccountMeta::new(*payer, true),
 
+        AccountMeta::new_readonly(*governance_id, false),
         AccountMeta::new_readonly(*realm, false),
+        AccountMeta::new_readonly(realm_config_account, false),
+        AccountMeta::new_readonly(vesting_config_account, false),
         AccountMeta::new(voting_weight_record_account, false),
         AccountMeta::new(max_voting_weight_record_account, false),
     ];
 
-    let instruction = VestingInstruction::Deposit { schedules };
+    let instruction = VestingInstruction::Deposit { schedules, label, tge_relative, vesting_token_seed: None, vesting_bump_seed };
 
     Ok(Instruction {
         program_id: *program_id,
@@ -248,15 +782,17 @@ pub fn withdraw(
     vesting_token_account: &Pubkey,
     destination_token_account: &Pubkey,
     vesting_owner: &Pubkey,
+    release_proposals: &[Pubkey],
 ) -> Result<Instruction, ProgramError> {
     let (vesting_account, _) = Pubkey::find_program_address(&[vesting_token_account.as_ref()], program_id);
-    let accounts = vec![
+    let mut accounts = vec![
         AccountMeta::new_readonly(*token_program_id, false),
         AccountMeta::new(vesting_account, false),
         AccountMeta::new(*vesting_token_account, false),
         AccountMeta::new(*destination_token_account, false),
         AccountMeta::new_readonly(*vesting_owner, true),
     ];
+    accounts.extend(release_proposals.iter().map(|proposal| AccountMeta::new_readonly(*proposal, false)));
 
     let instruction = VestingInstruction::Withdraw;
 
@@ -278,12 +814,15 @@ pub fn withdraw_with_realm(
     governance_id: &Pubkey,
     realm: &Pubkey,
     mint: &Pubkey,
+    release_proposals: &[Pubkey],
+    spill: Option<&Pubkey>,
 ) -> Result<Instruction, ProgramError> {
     let (vesting_account, _) = Pubkey::find_program_address(&[vesting_token_account.as_ref()], program_id);
     let owner_record_account = get_token_owner_record_address(governance_id, realm, mint, vesting_owner);
+    let vesting_config_account = get_vesting_config_address(program_id, realm, mint);
     let voting_weight_record_account = get_voter_weight_record_address(program_id, realm, mint, vesting_owner);
     let max_voting_weight_record_account = get_max_voter_weight_record_address(program_id, realm, mint);
-    let accounts = vec![
+    let mut accounts = vec![
         AccountMeta::new_readonly(*token_program_id, false),
         AccountMeta::new(vesting_account, false),
         AccountMeta::new(*vesting_token_account, false),
@@ -293,9 +832,14 @@ pub fn withdraw_with_realm(
         AccountMeta::new_readonly(*governance_id, false),
         AccountMeta::new_readonly(*realm, false),
         AccountMeta::new_readonly(owner_record_account, false),
+        AccountMeta::new_readonly(vesting_config_account, false),
         AccountMeta::new(voting_weight_record_account, false),
         AccountMeta::new(max_voting_weight_record_account, false),
     ];
+    accounts.extend(release_proposals.iter().map(|proposal| AccountMeta::new_readonly(*proposal, false)));
+    if let Some(spill) = spill {
+        accounts.push(AccountMeta::new(*spill, false));
+    }
 
     let instruction = VestingInstruction::Withdraw;
 
@@ -306,14 +850,59 @@ pub fn withdraw_with_realm(
     })
 }
 
-/// Creates a `ChangeOwner` instruction
-pub fn change_owner(
+/// Creates a `WithdrawOutOfRealm` instruction
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_out_of_realm(
     program_id: &Pubkey,
+    token_program_id: &Pubkey,
     vesting_token_account: &Pubkey,
+    destination_token_account: &Pubkey,
     vesting_owner: &Pubkey,
-    new_vesting_owner: &Pubkey,
-) -> Result<Instruction, ProgramError> {
-    let (vesting_account, _) = Pubkey::find_program_address(&[vesting_token_account.as_ref()], program_id);
+    rent_payer: &Pubkey,
+    governance_id: &Pubkey,
+    realm: &Pubkey,
+    mint: &Pubkey,
+    release_proposals: &[Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let (vesting_account, _) = Pubkey::find_program_address(&[vesting_token_account.as_ref()], program_id);
+    let realm_config_account = get_realm_config_address(governance_id, realm);
+    let vesting_config_account = get_vesting_config_address(program_id, realm, mint);
+    let voting_weight_record_account = get_voter_weight_record_address(program_id, realm, mint, vesting_owner);
+    let max_voting_weight_record_account = get_max_voter_weight_record_address(program_id, realm, mint);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new(vesting_account, false),
+        AccountMeta::new(*vesting_token_account, false),
+        AccountMeta::new(*destination_token_account, false),
+        AccountMeta::new_readonly(*vesting_owner, true),
+        AccountMeta::new(*rent_payer, false),
+
+        AccountMeta::new_readonly(*governance_id, false),
+        AccountMeta::new_readonly(*realm, false),
+        AccountMeta::new_readonly(realm_config_account, false),
+        AccountMeta::new_readonly(vesting_config_account, false),
+        AccountMeta::new(voting_weight_record_account, false),
+        AccountMeta::new(max_voting_weight_record_account, false),
+    ];
+    accounts.extend(release_proposals.iter().map(|proposal| AccountMeta::new_readonly(*proposal, false)));
+
+    let instruction = VestingInstruction::WithdrawOutOfRealm;
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: instruction.try_to_vec().unwrap(),
+    })
+}
+
+/// Creates a `ChangeOwner` instruction
+pub fn change_owner(
+    program_id: &Pubkey,
+    vesting_token_account: &Pubkey,
+    vesting_owner: &Pubkey,
+    new_vesting_owner: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (vesting_account, _) = Pubkey::find_program_address(&[vesting_token_account.as_ref()], program_id);
     let accounts = vec![
         AccountMeta::new(vesting_account, false),
         AccountMeta::new_readonly(*vesting_owner, true),
@@ -330,6 +919,7 @@ pub fn change_owner(
 }
 
 /// Creates a `ChangeOwner` instruction with realm
+#[allow(clippy::too_many_arguments)]
 pub fn change_owner_with_realm(
     program_id: &Pubkey,
     vesting_token_account: &Pubkey,
@@ -338,12 +928,16 @@ pub fn change_owner_with_realm(
     governance_id: &Pubkey,
     realm: &Pubkey,
     mint: &Pubkey,
+    spill: Option<&Pubkey>,
+    payer: Option<&Pubkey>,
 ) -> Result<Instruction, ProgramError> {
     let (vesting_account, _) = Pubkey::find_program_address(&[vesting_token_account.as_ref()], program_id);
     let current_owner_record_account = get_token_owner_record_address(governance_id, realm, mint, vesting_owner);
+    let vesting_config_account = get_vesting_config_address(program_id, realm, mint);
     let current_voter_weight_record_account = get_voter_weight_record_address(program_id, realm, mint, vesting_owner);
     let new_voter_weight_record_account = get_voter_weight_record_address(program_id, realm, mint, new_vesting_owner);
-    let accounts = vec![
+    let max_voter_weight_record_account = get_max_voter_weight_record_address(program_id, realm, mint);
+    let mut accounts = vec![
         AccountMeta::new(vesting_account, false),
         AccountMeta::new_readonly(*vesting_owner, true),
         AccountMeta::new_readonly(*new_vesting_owner, false),
@@ -351,9 +945,18 @@ pub fn change_owner_with_realm(
         AccountMeta::new_readonly(*governance_id, false),
         AccountMeta::new_readonly(*realm, false),
         AccountMeta::new_readonly(current_owner_record_account, false),
+        AccountMeta::new_readonly(vesting_config_account, false),
         AccountMeta::new(current_voter_weight_record_account, false),
         AccountMeta::new(new_voter_weight_record_account, false),
+        AccountMeta::new(max_voter_weight_record_account, false),
     ];
+    if let Some(payer) = payer {
+        accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+        accounts.push(AccountMeta::new(*payer, true));
+    }
+    if let Some(spill) = spill {
+        accounts.push(AccountMeta::new(*spill, false));
+    }
 
     let instruction = VestingInstruction::ChangeOwner;
 
@@ -403,9 +1006,12 @@ pub fn set_vote_percentage_with_realm(
     realm: &Pubkey,
     mint: &Pubkey,
     vote_percentage: u16,
+    valid_until: Option<Slot>,
 ) -> Result<Instruction, ProgramError> {
     let token_owner_record_account = get_token_owner_record_address(governance_id, realm, mint, vesting_owner);
     let voter_weight_record_account = get_voter_weight_record_address(program_id, realm, mint, vesting_owner);
+    let vesting_config_account = get_vesting_config_address(program_id, realm, mint);
+    let max_voter_weight_record_account = get_max_voter_weight_record_address(program_id, realm, mint);
     let accounts = vec![
         AccountMeta::new_readonly(*mint, false),
         AccountMeta::new_readonly(*vesting_owner, false),
@@ -414,9 +1020,11 @@ pub fn set_vote_percentage_with_realm(
         AccountMeta::new_readonly(*realm, false),
         AccountMeta::new_readonly(token_owner_record_account, false),
         AccountMeta::new(voter_weight_record_account, false),
+        AccountMeta::new_readonly(vesting_config_account, false),
+        AccountMeta::new_readonly(max_voter_weight_record_account, false),
     ];
 
-    let instruction = VestingInstruction::SetVotePercentage { vote_percentage };
+    let instruction = VestingInstruction::SetVotePercentage { vote_percentage, valid_until };
 
     Ok(Instruction {
         program_id: *program_id,
@@ -432,7 +1040,7 @@ pub fn close(
     token_program_id: &Pubkey,
     vesting_token_account: &Pubkey,
     vesting_owner: &Pubkey,
-    spill: &Pubkey,
+    rent_payer: &Pubkey,
 ) -> Result<Instruction, ProgramError> {
     let (vesting_account, _) = Pubkey::find_program_address(&[vesting_token_account.as_ref()], program_id);
     let accounts = vec![
@@ -440,7 +1048,7 @@ pub fn close(
         AccountMeta::new(vesting_account, false),
         AccountMeta::new(*vesting_token_account, false),
         AccountMeta::new_readonly(*vesting_owner, true),
-        AccountMeta::new(*spill, false),
+        AccountMeta::new(*rent_payer, false),
     ];
 
     let instruction = VestingInstruction::Close;
@@ -491,9 +1099,10 @@ pub fn split(
     new_vesting_owner: &Pubkey,
     payer: &Pubkey,
     schedules: Vec<VestingSchedule>,
+    label: Option<String>,
 ) -> Result<Instruction, ProgramError> {
     let (vesting_account, _) = Pubkey::find_program_address(&[vesting_token_account.as_ref()], program_id);
-    let (new_vesting_account, _) = Pubkey::find_program_address(&[new_vesting_token_account.as_ref()], program_id);
+    let (new_vesting_account, new_vesting_bump_seed) = Pubkey::find_program_address(&[new_vesting_token_account.as_ref()], program_id);
     let accounts = vec![
         AccountMeta::new_readonly(system_program::id(), false),
         AccountMeta::new_readonly(*token_program_id, false),
@@ -506,7 +1115,7 @@ pub fn split(
         AccountMeta::new_readonly(*payer, true),
     ];
 
-    let instruction = VestingInstruction::Split { schedules };
+    let instruction = VestingInstruction::Split { schedules, label, new_vesting_token_seed: None, new_vesting_bump_seed };
 
     Ok(Instruction {
         program_id: *program_id,
@@ -527,17 +1136,21 @@ pub fn split_with_realm(
     new_vesting_owner: &Pubkey,
     payer: &Pubkey,
     schedules: Vec<VestingSchedule>,
+    label: Option<String>,
     governance_id: &Pubkey,
     realm: &Pubkey,
     mint: &Pubkey,
+    spill: Option<&Pubkey>,
 ) -> Result<Instruction, ProgramError> {
     let (vesting_account, _) = Pubkey::find_program_address(&[vesting_token_account.as_ref()], program_id);
     let owner_record_account = get_token_owner_record_address(governance_id, realm, mint, vesting_owner);
+    let vesting_config_account = get_vesting_config_address(program_id, realm, mint);
     let voting_weight_record_account = get_voter_weight_record_address(program_id, realm, mint, vesting_owner);
+    let max_voting_weight_record_account = get_max_voter_weight_record_address(program_id, realm, mint);
 
-    let (new_vesting_account, _) = Pubkey::find_program_address(&[new_vesting_token_account.as_ref()], program_id);
+    let (new_vesting_account, new_vesting_bump_seed) = Pubkey::find_program_address(&[new_vesting_token_account.as_ref()], program_id);
     let new_voting_weight_record_account = get_voter_weight_record_address(program_id, realm, mint, new_vesting_owner);
-    let accounts = vec![
+    let mut accounts = vec![
         AccountMeta::new_readonly(system_program::id(), false),
         AccountMeta::new_readonly(*token_program_id, false),
         AccountMeta::new(vesting_account, false),
@@ -551,11 +1164,16 @@ pub fn split_with_realm(
         AccountMeta::new_readonly(*governance_id, false),
         AccountMeta::new_readonly(*realm, false),
         AccountMeta::new_readonly(owner_record_account, false),
+        AccountMeta::new_readonly(vesting_config_account, false),
         AccountMeta::new(voting_weight_record_account, false),
         AccountMeta::new(new_voting_weight_record_account, false),
+        AccountMeta::new(max_voting_weight_record_account, false),
     ];
+    if let Some(spill) = spill {
+        accounts.push(AccountMeta::new(*spill, false));
+    }
 
-    let instruction = VestingInstruction::Split { schedules };
+    let instruction = VestingInstruction::Split { schedules, label, new_vesting_token_seed: None, new_vesting_bump_seed };
 
     Ok(Instruction {
         program_id: *program_id,
@@ -564,43 +1182,979 @@ pub fn split_with_realm(
     })
 }
 
+/// Creates a `SplitPercent` instruction to split the share of the vesting into the new vesting token account
+#[allow(clippy::too_many_arguments)]
+pub fn split_percent(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    vesting_token_account: &Pubkey,
+    vesting_owner: &Pubkey,
+    new_vesting_token_account: &Pubkey,
+    new_vesting_owner: &Pubkey,
+    payer: &Pubkey,
+    basis_points: u16,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = split(
+        program_id,
+        token_program_id,
+        vesting_token_account,
+        vesting_owner,
+        new_vesting_token_account,
+        new_vesting_owner,
+        payer,
+        Vec::new(),
+        None,
+    )?;
+    let (_, new_vesting_bump_seed) = Pubkey::find_program_address(&[new_vesting_token_account.as_ref()], program_id);
+    instruction.data = VestingInstruction::SplitPercent { basis_points, new_vesting_token_seed: None, new_vesting_bump_seed }.try_to_vec().unwrap();
+    Ok(instruction)
+}
+
+/// Creates a `SplitPercent` instruction to split the share of the vesting into the new vesting token account
+/// inside the Realm
+#[allow(clippy::too_many_arguments)]
+pub fn split_percent_with_realm(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    vesting_token_account: &Pubkey,
+    vesting_owner: &Pubkey,
+    new_vesting_token_account: &Pubkey,
+    new_vesting_owner: &Pubkey,
+    payer: &Pubkey,
+    basis_points: u16,
+    governance_id: &Pubkey,
+    realm: &Pubkey,
+    mint: &Pubkey,
+    spill: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = split_with_realm(
+        program_id,
+        token_program_id,
+        vesting_token_account,
+        vesting_owner,
+        new_vesting_token_account,
+        new_vesting_owner,
+        payer,
+        Vec::new(),
+        None,
+        governance_id,
+        realm,
+        mint,
+        spill,
+    )?;
+    let (_, new_vesting_bump_seed) = Pubkey::find_program_address(&[new_vesting_token_account.as_ref()], program_id);
+    instruction.data = VestingInstruction::SplitPercent { basis_points, new_vesting_token_seed: None, new_vesting_bump_seed }.try_to_vec().unwrap();
+    Ok(instruction)
+}
 
+/// Creates a `SplitMany` instruction to split the vesting into many new vesting token accounts.
+/// Every split is (new vesting token account, new vesting owner, schedules)
+pub fn split_many(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    vesting_token_account: &Pubkey,
+    vesting_owner: &Pubkey,
+    payer: &Pubkey,
+    splits: Vec<(Pubkey, Pubkey, Vec<VestingSchedule>)>,
+) -> Result<Instruction, ProgramError> {
+    let (vesting_account, _) = Pubkey::find_program_address(&[vesting_token_account.as_ref()], program_id);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new(vesting_account, false),
+        AccountMeta::new(*vesting_token_account, false),
+        AccountMeta::new_readonly(*vesting_owner, true),
+    ];
+    let mut schedules = Vec::with_capacity(splits.len());
+    let mut new_vesting_bump_seeds = Vec::with_capacity(splits.len());
+    for (new_vesting_token_account, new_vesting_owner, new_schedules) in splits {
+        let (new_vesting_account, new_vesting_bump_seed) = Pubkey::find_program_address(&[new_vesting_token_account.as_ref()], program_id);
+        accounts.push(AccountMeta::new(new_vesting_account, false));
+        accounts.push(AccountMeta::new(new_vesting_token_account, false));
+        accounts.push(AccountMeta::new_readonly(new_vesting_owner, false));
+        schedules.push(new_schedules);
+        new_vesting_bump_seeds.push(new_vesting_bump_seed);
+    }
+    accounts.push(AccountMeta::new_readonly(*payer, true));
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    let instruction = VestingInstruction::SplitMany { schedules, new_vesting_token_seeds: None, new_vesting_bump_seeds };
 
-    #[test]
-    fn test_instruction_packing() {
-        let original_deposit = VestingInstruction::Deposit {
-            schedules: vec![VestingSchedule {
-                amount: 42,
-                release_time: 250,
-            }],
-        };
-        assert_eq!(
-            original_deposit,
-            VestingInstruction::try_from_slice(&original_deposit.try_to_vec().unwrap()).unwrap()
-        );
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: instruction.try_to_vec().unwrap(),
+    })
+}
 
+/// Creates a `SplitMany` instruction to split the vesting into many new vesting token accounts
+/// inside the Realm. Every split is (new vesting token account, new vesting owner, schedules)
+#[allow(clippy::too_many_arguments)]
+pub fn split_many_with_realm(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    vesting_token_account: &Pubkey,
+    vesting_owner: &Pubkey,
+    payer: &Pubkey,
+    splits: Vec<(Pubkey, Pubkey, Vec<VestingSchedule>)>,
+    governance_id: &Pubkey,
+    realm: &Pubkey,
+    mint: &Pubkey,
+    spill: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let owner_record_account = get_token_owner_record_address(governance_id, realm, mint, vesting_owner);
+    let vesting_config_account = get_vesting_config_address(program_id, realm, mint);
+    let voting_weight_record_account = get_voter_weight_record_address(program_id, realm, mint, vesting_owner);
+    let max_voting_weight_record_account = get_max_voter_weight_record_address(program_id, realm, mint);
+    let new_voting_weight_record_accounts: Vec<Pubkey> = splits.iter()
+            .map(|(_, new_vesting_owner, _)| get_voter_weight_record_address(program_id, realm, mint, new_vesting_owner))
+            .collect();
 
-        let original_withdraw = VestingInstruction::Withdraw;
-        assert_eq!(
-            original_withdraw,
-            VestingInstruction::try_from_slice(&original_withdraw.try_to_vec().unwrap()).unwrap()
-        );
+    let mut instruction = split_many(program_id, token_program_id, vesting_token_account, vesting_owner, payer, splits)?;
+    instruction.accounts.extend([
+        AccountMeta::new_readonly(*governance_id, false),
+        AccountMeta::new_readonly(*realm, false),
+        AccountMeta::new_readonly(owner_record_account, false),
+        AccountMeta::new_readonly(vesting_config_account, false),
+        AccountMeta::new(voting_weight_record_account, false),
+    ]);
+    instruction.accounts.extend(new_voting_weight_record_accounts.into_iter().map(|record| AccountMeta::new(record, false)));
+    instruction.accounts.push(AccountMeta::new(max_voting_weight_record_account, false));
+    if let Some(spill) = spill {
+        instruction.accounts.push(AccountMeta::new(*spill, false));
+    }
+    Ok(instruction)
+}
 
+/// Creates a `CreateVestingTemplate` instruction
+pub fn create_vesting_template(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    name: String,
+    entries: Vec<VestingTemplateEntry>,
+) -> Result<Instruction, ProgramError> {
+    let vesting_template_account = get_vesting_template_address(program_id, authority, &name);
+    let accounts = vec![
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new(vesting_template_account, false),
+        AccountMeta::new_readonly(*authority, true),
+        AccountMeta::new(*payer, true),
+    ];
 
-        let original_change = VestingInstruction::ChangeOwner;
-        assert_eq!(
-            original_change,
-            VestingInstruction::try_from_slice(&original_change.try_to_vec().unwrap()).unwrap()
-        );
+    let instruction = VestingInstruction::CreateVestingTemplate { name, entries };
 
-        let original_set_vote_percentage = VestingInstruction::SetVotePercentage { vote_percentage: 2500 };
-        assert_eq!(
-            original_set_vote_percentage,
-            VestingInstruction::try_from_slice(&original_set_vote_percentage.try_to_vec().unwrap()).unwrap()
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: instruction.try_to_vec().unwrap(),
+    })
+}
+
+/// Creates a `DepositFromTemplate` instruction to create the vesting with the schedule of the template
+#[allow(clippy::too_many_arguments)]
+pub fn deposit_from_template(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    vesting_token_account: &Pubkey,
+    source_token_owner: &Pubkey,
+    source_token_account: &Pubkey,
+    vesting_owner: &Pubkey,
+    payer: &Pubkey,
+    vesting_template: &Pubkey,
+    start: u64,
+    total: u64,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = deposit(
+        program_id,
+        token_program_id,
+        vesting_token_account,
+        source_token_owner,
+        source_token_account,
+        vesting_owner,
+        payer,
+        Vec::new(),
+        None,
+    )?;
+    let (_, vesting_bump_seed) = Pubkey::find_program_address(&[vesting_token_account.as_ref()], program_id);
+    instruction.accounts.insert(0, AccountMeta::new_readonly(*vesting_template, false));
+    instruction.data = VestingInstruction::DepositFromTemplate { start, total, vesting_bump_seed }.try_to_vec().unwrap();
+    Ok(instruction)
+}
+
+/// Creates a `DepositFromTemplate` instruction to create the vesting with the schedule of the template
+/// inside the Realm
+#[allow(clippy::too_many_arguments)]
+pub fn deposit_from_template_with_realm(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    vesting_token_account: &Pubkey,
+    source_token_owner: &Pubkey,
+    source_token_account: &Pubkey,
+    vesting_owner: &Pubkey,
+    payer: &Pubkey,
+    vesting_template: &Pubkey,
+    start: u64,
+    total: u64,
+    governance_id: &Pubkey,
+    realm: &Pubkey,
+    mint: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = deposit_with_realm(
+        program_id,
+        token_program_id,
+        vesting_token_account,
+        source_token_owner,
+        source_token_account,
+        vesting_owner,
+        payer,
+        Vec::new(),
+        realm,
+        mint,
+        governance_id,
+        None,
+        false,
+    )?;
+    let (_, vesting_bump_seed) = Pubkey::find_program_address(&[vesting_token_account.as_ref()], program_id);
+    instruction.accounts.insert(0, AccountMeta::new_readonly(*vesting_template, false));
+    instruction.data = VestingInstruction::DepositFromTemplate { start, total, vesting_bump_seed }.try_to_vec().unwrap();
+    Ok(instruction)
+}
+
+/// Creates a `SetTgeTimestamp` instruction
+pub fn set_tge_timestamp(
+    program_id: &Pubkey,
+    governance_id: &Pubkey,
+    realm: &Pubkey,
+    realm_authority: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    tge_timestamp: u64,
+) -> Result<Instruction, ProgramError> {
+    let vesting_config_account = get_vesting_config_address(program_id, realm, mint);
+    let accounts = vec![
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(*governance_id, false),
+        AccountMeta::new_readonly(*realm, false),
+        AccountMeta::new_readonly(*realm_authority, true),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new(vesting_config_account, false),
+    ];
+
+    let instruction = VestingInstruction::SetTgeTimestamp { tge_timestamp };
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: instruction.try_to_vec().unwrap(),
+    })
+}
+
+/// Creates a `GetVestingStatus` instruction to get the status of the vesting via the return data
+pub fn get_vesting_status(
+    program_id: &Pubkey,
+    vesting_token_account: &Pubkey,
+    release_proposals: &[Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let (vesting_account, _) = Pubkey::find_program_address(&[vesting_token_account.as_ref()], program_id);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(vesting_account, false),
+    ];
+    for proposal in release_proposals {
+        accounts.push(AccountMeta::new_readonly(*proposal, false));
+    }
+
+    let instruction = VestingInstruction::GetVestingStatus;
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: instruction.try_to_vec().unwrap(),
+    })
+}
+
+/// Creates a `GetVestingStatus` instruction to get the status of the vesting under Realm via the return data
+pub fn get_vesting_status_with_realm(
+    program_id: &Pubkey,
+    vesting_token_account: &Pubkey,
+    vesting_owner: &Pubkey,
+    realm: &Pubkey,
+    mint: &Pubkey,
+    release_proposals: &[Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = get_vesting_status(program_id, vesting_token_account, &[])?;
+    let vesting_config_account = get_vesting_config_address(program_id, realm, mint);
+    let voter_weight_record_account = get_voter_weight_record_address(program_id, realm, mint, vesting_owner);
+    instruction.accounts.push(AccountMeta::new_readonly(vesting_config_account, false));
+    instruction.accounts.push(AccountMeta::new_readonly(voter_weight_record_account, false));
+    for proposal in release_proposals {
+        instruction.accounts.push(AccountMeta::new_readonly(*proposal, false));
+    }
+    Ok(instruction)
+}
+
+/// Creates a `MigrateVestingRecord` instruction to convert the legacy vesting account
+/// (the Realm is required for the vesting under Realm)
+pub fn migrate_vesting_record(
+    program_id: &Pubkey,
+    vesting_token_account: &Pubkey,
+    vesting_owner: &Pubkey,
+    payer: &Pubkey,
+    realm: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let (vesting_account, bump_seed) = Pubkey::find_program_address(&[vesting_token_account.as_ref()], program_id);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new(vesting_account, false),
+        AccountMeta::new_readonly(*vesting_owner, true),
+        AccountMeta::new(*payer, true),
+    ];
+    if let Some(realm) = realm {
+        accounts.push(AccountMeta::new_readonly(*realm, false));
+    }
+
+    let instruction = VestingInstruction::MigrateVestingRecord { bump_seed };
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: instruction.try_to_vec().unwrap(),
+    })
+}
+
+/// Creates a `MigrateVoterWeightRecord` instruction to convert the legacy VoterWeightRecord
+pub fn migrate_voter_weight_record(
+    program_id: &Pubkey,
+    record_owner: &Pubkey,
+    realm: &Pubkey,
+    mint: &Pubkey,
+    payer: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let voter_weight_record_account = get_voter_weight_record_address(program_id, realm, mint, record_owner);
+    let accounts = vec![
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new(voter_weight_record_account, false),
+        AccountMeta::new(*payer, true),
+    ];
+
+    let instruction = VestingInstruction::MigrateVoterWeightRecord;
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: instruction.try_to_vec().unwrap(),
+    })
+}
+
+/// Makes the `Deposit`, `Split`, `SplitPercent` or `SplitMany` instruction create the (new) vesting
+/// spl-token accounts with the seeds (one seed for every new vesting). The instruction must be created
+/// with the vesting spl-token accounts returned by `get_vesting_token_address(program_id, seed)`
+pub fn with_created_vesting_token_accounts(
+    mut instruction: Instruction,
+    mint: &Pubkey,
+    seeds: &[Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let mut vesting_token_seeds = seeds.iter()
+            .map(|seed| VestingTokenSeed {
+                seed: *seed,
+                bump_seed: Pubkey::find_program_address(&get_vesting_token_seeds(seed), &instruction.program_id).1,
+            })
+            .collect::<Vec<_>>();
+
+    let mut vesting_instruction = VestingInstruction::try_from_slice(&instruction.data)
+            .map_err(|_| ProgramError::InvalidInstructionData)?;
+    let payer_index = match &mut vesting_instruction {
+        VestingInstruction::Deposit { vesting_token_seed, .. } if seeds.len() == 1 => {
+            *vesting_token_seed = vesting_token_seeds.pop();
+            7
+        },
+        VestingInstruction::Split { new_vesting_token_seed, .. } |
+        VestingInstruction::SplitPercent { new_vesting_token_seed, .. } if seeds.len() == 1 => {
+            *new_vesting_token_seed = vesting_token_seeds.pop();
+            8
+        },
+        VestingInstruction::SplitMany { schedules, new_vesting_token_seeds, .. } if seeds.len() == schedules.len() => {
+            *new_vesting_token_seeds = Some(vesting_token_seeds);
+            5 + 3 * seeds.len()
+        },
+        _ => return Err(ProgramError::InvalidArgument),
+    };
+
+    instruction.data = vesting_instruction.try_to_vec().unwrap();
+    instruction.accounts[payer_index].is_writable = true;
+    instruction.accounts.insert(payer_index + 1, AccountMeta::new_readonly(*mint, false));
+    Ok(instruction)
+}
+
+/// Creates a `UpdateVestingIndex` instruction to synchronize the VestingIndex of the owner
+/// with the vestings of the vesting token accounts
+pub fn update_vesting_index(
+    program_id: &Pubkey,
+    vesting_owner: &Pubkey,
+    payer: &Pubkey,
+    vesting_token_accounts: &[Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let vesting_index_account = get_vesting_index_address(program_id, vesting_owner);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new(vesting_index_account, false),
+        AccountMeta::new_readonly(*vesting_owner, true),
+        AccountMeta::new(*payer, true),
+    ];
+    for vesting_token_account in vesting_token_accounts {
+        let (vesting_account, _) = Pubkey::find_program_address(&[vesting_token_account.as_ref()], program_id);
+        accounts.push(AccountMeta::new_readonly(vesting_account, false));
+    }
+
+    let instruction = VestingInstruction::UpdateVestingIndex;
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: instruction.try_to_vec().unwrap(),
+    })
+}
+
+/// Appends the VestingIndex accounts of the owners to Deposit, ChangeOwner, Split or Close instruction,
+/// so the instruction updates the indexes (created by UpdateVestingIndex) along with the vestings
+/// (the vestings are added only to the indexes of the signers)
+pub fn add_vesting_index_accounts(program_id: &Pubkey, instruction: &mut Instruction, vesting_owners: &[Pubkey]) {
+    for vesting_owner in vesting_owners {
+        let vesting_index_account = get_vesting_index_address(program_id, vesting_owner);
+        instruction.accounts.push(AccountMeta::new(vesting_index_account, false));
+    }
+}
+
+/// Creates a `SetLabel` instruction to set the label of the vesting
+pub fn set_label(
+    program_id: &Pubkey,
+    vesting_token_account: &Pubkey,
+    grantor: &Pubkey,
+    payer: &Pubkey,
+    label: Option<String>,
+) -> Result<Instruction, ProgramError> {
+    let (vesting_account, _) = Pubkey::find_program_address(&[vesting_token_account.as_ref()], program_id);
+    let accounts = vec![
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new(vesting_account, false),
+        AccountMeta::new_readonly(*grantor, true),
+        AccountMeta::new(*payer, true),
+    ];
+
+    let instruction = VestingInstruction::SetLabel { label };
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: instruction.try_to_vec().unwrap(),
+    })
+}
+
+/// Creates a `AttachRealm` instruction
+#[allow(clippy::too_many_arguments)]
+pub fn attach_realm(
+    program_id: &Pubkey,
+    vesting_token_account: &Pubkey,
+    vesting_owner: &Pubkey,
+    payer: &Pubkey,
+    governance_id: &Pubkey,
+    realm: &Pubkey,
+    mint: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (vesting_account, _) = Pubkey::find_program_address(&[vesting_token_account.as_ref()], program_id);
+    let realm_config_account = get_realm_config_address(governance_id, realm);
+    let vesting_config_account = get_vesting_config_address(program_id, realm, mint);
+    let voting_weight_record_account = get_voter_weight_record_address(program_id, realm, mint, vesting_owner);
+    let max_voting_weight_record_account = get_max_voter_weight_record_address(program_id, realm, mint);
+    let accounts = vec![
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new(vesting_account, false),
+        AccountMeta::new_readonly(*vesting_owner, true),
+        AccountMeta::new(*payer, true),
+
+        AccountMeta::new_readonly(*governance_id, false),
+        AccountMeta::new_readonly(*realm, false),
+        AccountMeta::new_readonly(realm_config_account, false),
+        AccountMeta::new_readonly(vesting_config_account, false),
+        AccountMeta::new(voting_weight_record_account, false),
+        AccountMeta::new(max_voting_weight_record_account, false),
+    ];
+
+    let instruction = VestingInstruction::AttachRealm;
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: instruction.try_to_vec().unwrap(),
+    })
+}
+
+/// Creates a `DetachRealm` instruction
+pub fn detach_realm(
+    program_id: &Pubkey,
+    vesting_token_account: &Pubkey,
+    vesting_owner: &Pubkey,
+    rent_payer: &Pubkey,
+    governance_id: &Pubkey,
+    realm: &Pubkey,
+    mint: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (vesting_account, _) = Pubkey::find_program_address(&[vesting_token_account.as_ref()], program_id);
+    let owner_record_account = get_token_owner_record_address(governance_id, realm, mint, vesting_owner);
+    let vesting_config_account = get_vesting_config_address(program_id, realm, mint);
+    let voting_weight_record_account = get_voter_weight_record_address(program_id, realm, mint, vesting_owner);
+    let max_voting_weight_record_account = get_max_voter_weight_record_address(program_id, realm, mint);
+    let accounts = vec![
+        AccountMeta::new(vesting_account, false),
+        AccountMeta::new_readonly(*vesting_owner, true),
+        AccountMeta::new(*rent_payer, false),
+
+        AccountMeta::new_readonly(*governance_id, false),
+        AccountMeta::new_readonly(*realm, false),
+        AccountMeta::new_readonly(owner_record_account, false),
+        AccountMeta::new_readonly(vesting_config_account, false),
+        AccountMeta::new(voting_weight_record_account, false),
+        AccountMeta::new(max_voting_weight_record_account, false),
+    ];
+
+    let instruction = VestingInstruction::DetachRealm;
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: instruction.try_to_vec().unwrap(),
+    })
+}
+
+/// Creates a `AccelerateRelease` instruction
+/// The vestings are the pairs of the vesting spl-token account and the rent payer of the vesting account
+#[allow(clippy::too_many_arguments)]
+pub fn accelerate_release(
+    program_id: &Pubkey,
+    governance_id: &Pubkey,
+    realm: &Pubkey,
+    release_authority: &Pubkey,
+    mint: &Pubkey,
+    vestings: &[(Pubkey, Pubkey)],
+    release_time: u64,
+    include_milestones: bool,
+) -> Result<Instruction, ProgramError> {
+    let vesting_config_account = get_vesting_config_address(program_id, realm, mint);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*governance_id, false),
+        AccountMeta::new_readonly(*realm, false),
+        AccountMeta::new_readonly(*release_authority, true),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new_readonly(vesting_config_account, false),
+    ];
+    for (vesting_token_account, rent_payer) in vestings {
+        let (vesting_account, _) = Pubkey::find_program_address(&[vesting_token_account.as_ref()], program_id);
+        accounts.push(AccountMeta::new(vesting_account, false));
+        accounts.push(AccountMeta::new(*rent_payer, false));
+    }
+
+    let instruction = VestingInstruction::AccelerateRelease { release_time, include_milestones };
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: instruction.try_to_vec().unwrap(),
+    })
+}
+
+
+/// Creates a `UpdateVoterWeightRecord` instruction.
+/// The record authority (the token owner or the delegate) signs the instruction with the action only
+#[allow(clippy::too_many_arguments)]
+pub fn update_voter_weight_record(
+    program_id: &Pubkey,
+    record_owner: &Pubkey,
+    record_authority: &Pubkey,
+    governance_id: &Pubkey,
+    realm: &Pubkey,
+    mint: &Pubkey,
+    weight_action: Option<VoterWeightAction>,
+) -> Result<Instruction, ProgramError> {
+    let voter_weight_record_account = get_voter_weight_record_address(program_id, realm, mint, record_owner);
+    let vesting_config_account = get_vesting_config_address(program_id, realm, mint);
+    let max_voter_weight_record_account = get_max_voter_weight_record_address(program_id, realm, mint);
+    let mut accounts = vec![
+        AccountMeta::new(voter_weight_record_account, false),
+        AccountMeta::new_readonly(vesting_config_account, false),
+        AccountMeta::new(max_voter_weight_record_account, false),
+    ];
+    if weight_action.is_some() {
+        let owner_record_account = get_token_owner_record_address(governance_id, realm, mint, record_owner);
+        accounts.extend([
+            AccountMeta::new_readonly(*governance_id, false),
+            AccountMeta::new_readonly(*realm, false),
+            AccountMeta::new_readonly(owner_record_account, false),
+            AccountMeta::new_readonly(*record_authority, true),
+        ]);
+    }
+
+    let instruction = VestingInstruction::UpdateVoterWeightRecord {weight_action};
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: instruction.try_to_vec().unwrap(),
+    })
+}
+
+/// Creates a `UpdateVoterWeightRecord` instruction to report the voter weight as of the Proposal voting start
+pub fn update_voter_weight_record_for_proposal(
+    program_id: &Pubkey,
+    record_owner: &Pubkey,
+    record_authority: &Pubkey,
+    governance_id: &Pubkey,
+    realm: &Pubkey,
+    mint: &Pubkey,
+    proposal: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = update_voter_weight_record(
+        program_id,
+        record_owner,
+        record_authority,
+        governance_id,
+        realm,
+        mint,
+        Some(VoterWeightAction::CastVote),
+    )?;
+    instruction.accounts.push(AccountMeta::new_readonly(*proposal, false));
+    Ok(instruction)
+}
+
+/// Creates a `SetVestingConfig` instruction
+#[allow(clippy::too_many_arguments)]
+pub fn set_vesting_config(
+    program_id: &Pubkey,
+    governance_id: &Pubkey,
+    realm: &Pubkey,
+    realm_authority: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    weight_curve: WeightCurve,
+    max_voter_weight_share: u16,
+    max_voter_weight_source: MaxVoterWeightSource,
+    count_unvested_only: bool,
+    release_authority: Option<Pubkey>,
+    snapshot_voting: bool,
+    default_vote_percentage: u16,
+) -> Result<Instruction, ProgramError> {
+    let vesting_config_account = get_vesting_config_address(program_id, realm, mint);
+    let accounts = vec![
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(*governance_id, false),
+        AccountMeta::new_readonly(*realm, false),
+        AccountMeta::new_readonly(*realm_authority, true),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new(vesting_config_account, false),
+    ];
+
+    let instruction = VestingInstruction::SetVestingConfig {
+        weight_curve,
+        max_voter_weight_share,
+        max_voter_weight_source,
+        count_unvested_only,
+        release_authority,
+        snapshot_voting,
+        default_vote_percentage,
+    };
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: instruction.try_to_vec().unwrap(),
+    })
+}
+
+/// Creates a `RefreshMaxVoterWeight` instruction
+pub fn refresh_max_voter_weight(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    realm: &Pubkey,
+    mint: &Pubkey,
+    excluded_token_accounts: &[Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let vesting_config_account = get_vesting_config_address(program_id, realm, mint);
+    let max_voter_weight_record_account = get_max_voter_weight_record_address(program_id, realm, mint);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(*realm, false),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new_readonly(vesting_config_account, false),
+        AccountMeta::new(max_voter_weight_record_account, false),
+    ];
+    accounts.extend(excluded_token_accounts.iter().map(|token_account| AccountMeta::new_readonly(*token_account, false)));
+
+    let instruction = VestingInstruction::RefreshMaxVoterWeight;
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: instruction.try_to_vec().unwrap(),
+    })
+}
+
+/// Creates a `RefreshReleasedAmount` instruction
+pub fn refresh_released_amount(
+    program_id: &Pubkey,
+    vesting_token_account: &Pubkey,
+    vesting_owner: &Pubkey,
+    realm: &Pubkey,
+    mint: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let (vesting_account, _) = Pubkey::find_program_address(&[vesting_token_account.as_ref()], program_id);
+    let vesting_config_account = get_vesting_config_address(program_id, realm, mint);
+    let voter_weight_record_account = get_voter_weight_record_address(program_id, realm, mint, vesting_owner);
+    let max_voter_weight_record_account = get_max_voter_weight_record_address(program_id, realm, mint);
+    let accounts = vec![
+        AccountMeta::new(vesting_account, false),
+        AccountMeta::new_readonly(vesting_config_account, false),
+        AccountMeta::new(voter_weight_record_account, false),
+        AccountMeta::new(max_voter_weight_record_account, false),
+    ];
+
+    let instruction = VestingInstruction::RefreshReleasedAmount;
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: instruction.try_to_vec().unwrap(),
+    })
+}
+
+/// Creates a `SetVoteAmount` instruction
+#[allow(clippy::too_many_arguments)]
+pub fn set_vote_amount_with_realm(
+    program_id: &Pubkey,
+    vesting_owner: &Pubkey,
+    vesting_authority: &Pubkey,
+    governance_id: &Pubkey,
+    realm: &Pubkey,
+    mint: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let token_owner_record_account = get_token_owner_record_address(governance_id, realm, mint, vesting_owner);
+    let voter_weight_record_account = get_voter_weight_record_address(program_id, realm, mint, vesting_owner);
+    let vesting_config_account = get_vesting_config_address(program_id, realm, mint);
+    let max_voter_weight_record_account = get_max_voter_weight_record_address(program_id, realm, mint);
+    let accounts = vec![
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new_readonly(*vesting_owner, false),
+        AccountMeta::new_readonly(*vesting_authority, true),
+        AccountMeta::new_readonly(*governance_id, false),
+        AccountMeta::new_readonly(*realm, false),
+        AccountMeta::new_readonly(token_owner_record_account, false),
+        AccountMeta::new(voter_weight_record_account, false),
+        AccountMeta::new_readonly(vesting_config_account, false),
+        AccountMeta::new_readonly(max_voter_weight_record_account, false),
+    ];
+
+    let instruction = VestingInstruction::SetVoteAmount { amount };
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: instruction.try_to_vec().unwrap(),
+    })
+}
+
+/// Creates a `CloseMaxVoterWeightRecord` instruction
+pub fn close_max_voter_weight_record(
+    program_id: &Pubkey,
+    governance_id: &Pubkey,
+    realm: &Pubkey,
+    realm_authority: &Pubkey,
+    mint: &Pubkey,
+    spill: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let max_voter_weight_record_account = get_max_voter_weight_record_address(program_id, realm, mint);
+    let accounts = vec![
+        AccountMeta::new_readonly(*governance_id, false),
+        AccountMeta::new_readonly(*realm, false),
+        AccountMeta::new_readonly(*realm_authority, true),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new(max_voter_weight_record_account, false),
+        AccountMeta::new(*spill, false),
+    ];
+
+    let instruction = VestingInstruction::CloseMaxVoterWeightRecord;
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: instruction.try_to_vec().unwrap(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_instruction_packing() {
+        let original_deposit = VestingInstruction::Deposit {
+            schedules: vec![VestingSchedule {
+                amount: 42,
+                release_time: 250,
+                release_proposal: None,
+            }],
+            label: Some("Seed round".to_string()),
+            tge_relative: true,
+            vesting_token_seed: Some(VestingTokenSeed { seed: Pubkey::new_unique(), bump_seed: 254 }),
+            vesting_bump_seed: 255,
+        };
+        assert_eq!(
+            original_deposit,
+            VestingInstruction::try_from_slice(&original_deposit.try_to_vec().unwrap()).unwrap()
+        );
+
+
+        let original_withdraw = VestingInstruction::Withdraw;
+        assert_eq!(
+            original_withdraw,
+            VestingInstruction::try_from_slice(&original_withdraw.try_to_vec().unwrap()).unwrap()
+        );
+
+
+        let original_change = VestingInstruction::ChangeOwner;
+        assert_eq!(
+            original_change,
+            VestingInstruction::try_from_slice(&original_change.try_to_vec().unwrap()).unwrap()
+        );
+
+        let original_set_vote_percentage = VestingInstruction::SetVotePercentage { vote_percentage: 2500, valid_until: Some(1000) };
+        assert_eq!(
+            original_set_vote_percentage,
+            VestingInstruction::try_from_slice(&original_set_vote_percentage.try_to_vec().unwrap()).unwrap()
+        );
+
+        let original_accelerate_release = VestingInstruction::AccelerateRelease { release_time: 1_700_000_000, include_milestones: true };
+        assert_eq!(
+            original_accelerate_release,
+            VestingInstruction::try_from_slice(&original_accelerate_release.try_to_vec().unwrap()).unwrap()
+        );
+
+        let original_set_vesting_config = VestingInstruction::SetVestingConfig {
+            weight_curve: WeightCurve::PiecewiseLinear {
+                points: vec![crate::config::WeightCurvePoint { amount: 100, weight: 10 }],
+            },
+            max_voter_weight_share: 2500,
+            max_voter_weight_source: MaxVoterWeightSource::MintSupply {
+                supply_fraction: crate::config::SUPPLY_FRACTION_BASE,
+                excluded_token_accounts: vec![Pubkey::new_unique()],
+            },
+            count_unvested_only: true,
+            release_authority: Some(Pubkey::new_unique()),
+            snapshot_voting: true,
+            default_vote_percentage: 5000,
+        };
+        assert_eq!(
+            original_set_vesting_config,
+            VestingInstruction::try_from_slice(&original_set_vesting_config.try_to_vec().unwrap()).unwrap()
+        );
+
+        let original_update_voter_weight_record = VestingInstruction::UpdateVoterWeightRecord {
+            weight_action: Some(VoterWeightAction::CreateProposal),
+        };
+        assert_eq!(
+            original_update_voter_weight_record,
+            VestingInstruction::try_from_slice(&original_update_voter_weight_record.try_to_vec().unwrap()).unwrap()
+        );
+
+        let original_set_vote_amount = VestingInstruction::SetVoteAmount { amount: 1_000_000 };
+        assert_eq!(
+            original_set_vote_amount,
+            VestingInstruction::try_from_slice(&original_set_vote_amount.try_to_vec().unwrap()).unwrap()
+        );
+
+        let original_split_percent = VestingInstruction::SplitPercent { basis_points: 3000, new_vesting_token_seed: None, new_vesting_bump_seed: 253 };
+        assert_eq!(
+            original_split_percent,
+            VestingInstruction::try_from_slice(&original_split_percent.try_to_vec().unwrap()).unwrap()
+        );
+
+        let original_split_many = VestingInstruction::SplitMany {
+            schedules: vec![
+                vec![VestingSchedule { amount: 42, release_time: 250, release_proposal: None }],
+                vec![
+                    VestingSchedule { amount: 10, release_time: 100, release_proposal: None },
+                    VestingSchedule { amount: 20, release_time: 200, release_proposal: Some(Pubkey::new_unique()) },
+                ],
+            ],
+            new_vesting_token_seeds: Some(vec![
+                VestingTokenSeed { seed: Pubkey::new_unique(), bump_seed: 255 },
+                VestingTokenSeed { seed: Pubkey::new_unique(), bump_seed: 254 },
+            ]),
+            new_vesting_bump_seeds: vec![255, 252],
+        };
+        assert_eq!(
+            original_split_many,
+            VestingInstruction::try_from_slice(&original_split_many.try_to_vec().unwrap()).unwrap()
+        );
+
+        let original_close_max_voter_weight_record = VestingInstruction::CloseMaxVoterWeightRecord;
+        assert_eq!(
+            original_close_max_voter_weight_record,
+            VestingInstruction::try_from_slice(&original_close_max_voter_weight_record.try_to_vec().unwrap()).unwrap()
+        );
+
+        let original_set_label = VestingInstruction::SetLabel { label: Some("Advisor grant #12".to_string()) };
+        assert_eq!(
+            original_set_label,
+            VestingInstruction::try_from_slice(&original_set_label.try_to_vec().unwrap()).unwrap()
+        );
+
+        let original_create_vesting_template = VestingInstruction::CreateVestingTemplate {
+            name: "Advisors".to_string(),
+            entries: vec![
+                VestingTemplateEntry { offset: 31_536_000, fraction: 12 },
+                VestingTemplateEntry { offset: 34_128_000, fraction: 1 },
+            ],
+        };
+        assert_eq!(
+            original_create_vesting_template,
+            VestingInstruction::try_from_slice(&original_create_vesting_template.try_to_vec().unwrap()).unwrap()
+        );
+
+        let original_deposit_from_template = VestingInstruction::DepositFromTemplate { start: 1_700_000_000, total: 1_000, vesting_bump_seed: 255 };
+        assert_eq!(
+            original_deposit_from_template,
+            VestingInstruction::try_from_slice(&original_deposit_from_template.try_to_vec().unwrap()).unwrap()
+        );
+
+        let original_set_tge_timestamp = VestingInstruction::SetTgeTimestamp { tge_timestamp: 1_700_000_000 };
+        assert_eq!(
+            original_set_tge_timestamp,
+            VestingInstruction::try_from_slice(&original_set_tge_timestamp.try_to_vec().unwrap()).unwrap()
+        );
+
+        let original_update_vesting_index = VestingInstruction::UpdateVestingIndex;
+        assert_eq!(
+            original_update_vesting_index,
+            VestingInstruction::try_from_slice(&original_update_vesting_index.try_to_vec().unwrap()).unwrap()
+        );
+
+        let original_get_vesting_status = VestingInstruction::GetVestingStatus;
+        assert_eq!(
+            original_get_vesting_status,
+            VestingInstruction::try_from_slice(&original_get_vesting_status.try_to_vec().unwrap()).unwrap()
+        );
+
+        let original_migrate_vesting_record = VestingInstruction::MigrateVestingRecord { bump_seed: 254 };
+        assert_eq!(
+            original_migrate_vesting_record,
+            VestingInstruction::try_from_slice(&original_migrate_vesting_record.try_to_vec().unwrap()).unwrap()
+        );
+
+        let original_migrate_voter_weight_record = VestingInstruction::MigrateVoterWeightRecord;
+        assert_eq!(
+            original_migrate_voter_weight_record,
+            VestingInstruction::try_from_slice(&original_migrate_voter_weight_record.try_to_vec().unwrap()).unwrap()
         );
     }
 }
@@ -4,8 +4,13 @@ pub mod entrypoint;
 pub mod error;
 pub mod instruction;
 pub mod state;
+pub mod config;
+pub mod template;
+pub mod vesting_index;
 pub mod voter_weight;
 pub mod max_voter_weight;
 pub mod token_owner_record;
+pub mod realm;
+pub mod proposal;
 
 pub mod processor;
@@ -24,20 +24,35 @@ pub fn get_max_voter_weight_record_address(program_id: &Pubkey, realm: &Pubkey,
     Pubkey::find_program_address(&get_max_voter_weight_record_seeds(realm, mint), program_id).0
 }
 
-/// Deserializes MaxVoterWeightRecord account and checks owner program
+/// The bump seed of the MaxVoterWeightRecord PDA is kept in the first byte of `reserved`:
+/// the record layout is defined by spl-governance-addin-api and has no field for the bump seed.
+/// The governance program doesn't read the reserved bytes, so the layout and the size of the account
+/// are not changed. The records created before the bump seed was stored have zero in the byte
+const BUMP_SEED_RESERVED_INDEX: usize = 0;
+
+/// Deserializes MaxVoterWeightRecord account and checks owner program and the address
+/// with the bump seed stored in the record (the records created before the bump seed
+/// was stored are checked with find_program_address)
 pub fn get_max_voter_weight_record_data_for_seeds(
     program_id: &Pubkey,
     max_voter_weight_record_info: &AccountInfo,
     max_voter_weight_record_seeds: &[&[u8]],
 ) -> Result<MaxVoterWeightRecord, ProgramError> {
-    let (max_voter_weight_record_address, _) =
-        Pubkey::find_program_address(max_voter_weight_record_seeds, program_id);
+    let record = get_max_voter_weight_record_data(program_id, max_voter_weight_record_info)?;
+
+    let bump_seed = [record.reserved[BUMP_SEED_RESERVED_INDEX]];
+    let mut seeds_with_bump = max_voter_weight_record_seeds.to_vec();
+    seeds_with_bump.push(&bump_seed);
+    let max_voter_weight_record_address = match Pubkey::create_program_address(&seeds_with_bump, program_id) {
+        Ok(address) if address == *max_voter_weight_record_info.key => address,
+        _ => Pubkey::find_program_address(max_voter_weight_record_seeds, program_id).0,
+    };
 
     if max_voter_weight_record_address != *max_voter_weight_record_info.key {
         return Err(VestingError::InvalidMaxVoterWeightRecordAccountAddress.into());
     }
 
-    get_max_voter_weight_record_data(program_id, max_voter_weight_record_info)
+    Ok(record)
 }
 
 /// Deserializes MaxVoterWeightRecord account and checks owner program and linkage
@@ -56,6 +71,18 @@ pub fn get_max_voter_weight_record_data_checked(
     Ok(record)
 }
 
+/// Replaces the contribution of the VoterWeightRecord to the max voter weight
+pub fn update_max_voter_weight(
+    record: &mut MaxVoterWeightRecord,
+    old_weighted_amount: u64,
+    new_weighted_amount: u64,
+) -> Result<(), ProgramError> {
+    record.max_voter_weight = record.max_voter_weight
+            .checked_sub(old_weighted_amount).ok_or(VestingError::UnderflowAmount)?
+            .checked_add(new_weighted_amount).ok_or(VestingError::OverflowAmount)?;
+    Ok(())
+}
+
 /// Create Voter Weight Record
 pub fn create_max_voter_weight_record<'a, I>(
     program_id: &Pubkey,
@@ -76,6 +103,8 @@ where I: FnOnce(&mut MaxVoterWeightRecord) -> Result<(), ProgramError>
         max_voter_weight_expiry: None,
         reserved: [0u8; 8],
     };
+    record_data.reserved[BUMP_SEED_RESERVED_INDEX] =
+        Pubkey::find_program_address(&get_max_voter_weight_record_seeds(realm, mint), program_id).1;
     initialize_func(&mut record_data)?;
     create_and_serialize_account_signed::<MaxVoterWeightRecord>(
         payer_account,
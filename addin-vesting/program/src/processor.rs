@@ -3,12 +3,13 @@ use solana_program::{
     borsh::try_from_slice_unchecked,
     entrypoint::ProgramResult,
     msg,
-    program::invoke_signed,
+    program::{invoke, invoke_signed, set_return_data},
     program_error::ProgramError,
     program_pack::Pack,
     pubkey::Pubkey,
     rent::Rent,
-    sysvar::{clock::Clock, Sysvar},
+    system_instruction,
+    sysvar::{clock::{Clock, Slot}, Sysvar},
 };
 
 use borsh::BorshSerialize;
@@ -18,9 +19,10 @@ use spl_token::{
         set_authority,
         AuthorityType,
     },
-    state::Account,
+    state::{Account, Mint},
 };
 use spl_governance_tools::account::{
+    AccountMaxSize,
     get_account_data,
     create_and_serialize_account_signed,
     dispose_account,
@@ -36,18 +38,66 @@ use spl_governance::state::{
 use crate::{
     error::VestingError,
     instruction::VestingInstruction,
-    state::{VestingAccountType, VestingRecord, VestingSchedule},
+    state::{
+        MAX_LABEL_LEN,
+        VestingAccountType,
+        VestingRecord,
+        VestingRecordV1,
+        VestingSchedule,
+        VestingOperationResult,
+        VestingStatus,
+        VestingTokenSeed,
+        get_vesting_record_data,
+        get_vesting_token_seeds,
+    },
     voter_weight::{
+        ExtendedVoterWeightRecord,
+        ExtendedVoterWeightRecordV1,
+        VoterWeightAction,
         create_voter_weight_record,
+        get_voter_weight_record_data,
         get_voter_weight_record_data_checked,
+        get_voter_weight_record_seeds,
+        is_legacy_voter_weight_record,
     },
     max_voter_weight::{
         create_max_voter_weight_record,
         get_max_voter_weight_record_data_checked,
+        update_max_voter_weight,
+    },
+    config::{
+        MaxVoterWeightSource,
+        VestingConfig,
+        WeightCurve,
+        get_vesting_config_data_or_default,
+        get_vesting_config_seeds,
+    },
+    template::{
+        VestingTemplate,
+        VestingTemplateEntry,
+        get_vesting_template_address,
+        get_vesting_template_data,
+        get_vesting_template_seeds,
+    },
+    vesting_index::{
+        VestingIndex,
+        get_vesting_index_data_or_default,
+        get_vesting_index_seeds,
+        is_vesting_index_account,
     },
     token_owner_record::{
         get_token_owner_record_data_if_exists,
     },
+    realm::{
+        assert_is_realm_authority,
+        assert_is_realm_release_authority,
+        assert_is_realm_voter_weight_addin,
+        get_realm_configured_voter_weight_addin,
+    },
+    proposal::{
+        get_proposal_voting_at_slot,
+        get_succeeded_proposals,
+    },
 };
 
 pub struct Processor {}
@@ -58,7 +108,12 @@ impl Processor {
         program_id: &Pubkey,
         accounts: &[AccountInfo],
         schedules: Vec<VestingSchedule>,
+        label: Option<String>,
+        tge_relative: bool,
+        vesting_token_seed: Option<VestingTokenSeed>,
+        vesting_bump_seed: u8,
     ) -> ProgramResult {
+        let (accounts, vesting_index_accounts) = split_vesting_index_accounts(program_id, accounts);
         let accounts_iter = &mut accounts.iter();
 
         let system_program_account = next_account_info(accounts_iter)?;
@@ -69,11 +124,19 @@ impl Processor {
         let source_token_account = next_account_info(accounts_iter)?;
         let vesting_owner_account = next_account_info(accounts_iter)?;
         let payer_account = next_account_info(accounts_iter)?;
+        let mint_account = if vesting_token_seed.is_some() {
+            Some(next_account_info(accounts_iter)?)
+        } else {
+            None
+        };
 
-        let realm_info = if let Some(realm) = accounts_iter.next() {
+        let realm_info = if let Some(governance) = accounts_iter.next() {
+            let realm = next_account_info(accounts_iter)?;
+            let realm_config = next_account_info(accounts_iter)?;
+            let vesting_config = next_account_info(accounts_iter)?;
             let voter_weight = next_account_info(accounts_iter)?;
             let max_voter_weight = next_account_info(accounts_iter)?;
-            Some((realm, voter_weight, max_voter_weight,))
+            Some((governance, realm, realm_config, vesting_config, voter_weight, max_voter_weight,))
         } else {
             None
         };
@@ -82,31 +145,72 @@ impl Processor {
             return Err(VestingError::MissingRequiredSigner.into());
         }
 
+        // The TGE timestamp is kept in the VestingConfig of the Realm
+        if tge_relative && realm_info.is_none() {
+            return Err(VestingError::MissingRealmAccounts.into());
+        }
+
+        // The milestone Proposals are verified through the governance program of the Realm
+        if schedules.iter().any(|s| s.release_proposal.is_some()) && realm_info.is_none() {
+            return Err(VestingError::MissingRealmAccounts.into());
+        }
+
         verify_schedule(&schedules)?;
+        verify_label(&label)?;
+
+        verify_new_vesting_account_address(program_id, vesting_account, vesting_token_account.key, vesting_bump_seed)?;
+
+        if let (Some(vesting_token_seed), Some(mint_account)) = (vesting_token_seed, mint_account) {
+            create_vesting_token_account(
+                program_id,
+                &vesting_token_seed,
+                vesting_token_account,
+                vesting_account.key,
+                mint_account,
+                payer_account,
+                system_program_account,
+                spl_token_account,
+            )?;
+        }
 
         let vesting_token_account_data = Account::unpack(&vesting_token_account.data.borrow())?;
         verify_token_account_owned_by_vesting(vesting_account, vesting_token_account_data)?;
 
+        if let Some((governance_account, realm_account, realm_config_account, _, _, _)) = realm_info {
+            assert_is_realm_voter_weight_addin(
+                program_id,
+                governance_account.key,
+                realm_account,
+                realm_config_account,
+                &vesting_token_account_data.mint,
+            )?;
+        }
+
         let total_amount = schedules.iter()
                 .try_fold(0u64, |acc, item| acc.checked_add(item.amount))
                 .ok_or(VestingError::OverflowAmount)?;
-        
+
         let vesting_record = VestingRecord {
-            account_type: VestingAccountType::VestingRecord,
+            account_type: VestingAccountType::VestingRecordV2,
             owner: *vesting_owner_account.key,
             mint: vesting_token_account_data.mint,
             token: *vesting_token_account.key,
-            realm: realm_info.map(|v| *v.0.key),
-            schedule: schedules
+            grantor: *source_token_account_owner.key,
+            rent_payer: *payer_account.key,
+            bump_seed: vesting_bump_seed,
+            realm: realm_info.map(|v| *v.1.key),
+            governance: realm_info.map(|v| *v.0.key),
+            excluded_amount: 0,
+            schedule: schedules,
+            tge_relative,
+            label,
         };
-        create_and_serialize_account_signed::<VestingRecord>(
-            payer_account,
+        create_vesting_account(
+            program_id,
             vesting_account,
             &vesting_record,
-            &[vesting_token_account.key.as_ref()],
-            program_id,
+            payer_account,
             system_program_account,
-            &Rent::get()?,
         )?;
 
         if Account::unpack(&source_token_account.data.borrow())?.amount < total_amount {
@@ -122,32 +226,111 @@ impl Processor {
             &[]
         )?;
 
-        if let Some((realm_account, voter_weight_record_account, max_voter_weight_record_account)) = realm_info {
-            create_or_increase_voter_weight_record(
+        if let Some((_, realm_account, _, vesting_config_account, voter_weight_record_account, max_voter_weight_record_account)) = realm_info {
+            let vesting_config = get_vesting_config_data_or_default(
+                program_id,
+                vesting_config_account,
+                realm_account.key,
+                &vesting_token_account_data.mint,
+            )?;
+
+            let weighted_amounts = create_or_increase_voter_weight_record(
                 &realm_account.key,
                 &vesting_token_account_data.mint,
                 vesting_owner_account.key,
                 voter_weight_record_account,
                 total_amount,
+                0,
+                &vesting_config,
                 program_id,
                 system_program_account,
                 payer_account
             )?;
             
-            create_or_increase_max_voter_weight_record(
+            let max_voter_weight = create_or_update_max_voter_weight_record(
                 &realm_account.key,
                 &vesting_token_account_data.mint,
                 max_voter_weight_record_account,
-                total_amount,
+                &[weighted_amounts],
+                &vesting_config,
                 program_id,
                 system_program_account,
                 payer_account
             )?;
+
+            apply_voter_weight_cap(
+                &[voter_weight_record_account],
+                &vesting_config,
+                max_voter_weight,
+                program_id,
+            )?;
+        }
+
+        update_vesting_indexes(program_id, accounts, vesting_index_accounts, &[(vesting_account.key, Some(vesting_owner_account.key))])?;
+
+        set_operation_return_data(program_id, total_amount, realm_info.map(|v| v.4))?;
+
+        Ok(())
+    }
+
+    pub fn process_create_vesting_template(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        name: String,
+        entries: Vec<VestingTemplateEntry>,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let system_program_account = next_account_info(accounts_iter)?;
+        let vesting_template_account = next_account_info(accounts_iter)?;
+        let authority_account = next_account_info(accounts_iter)?;
+        let payer_account = next_account_info(accounts_iter)?;
+
+        if !authority_account.is_signer {
+            return Err(VestingError::MissingRequiredSigner.into());
+        }
+
+        let vesting_template = VestingTemplate {
+            account_type: VestingAccountType::VestingTemplate,
+            authority: *authority_account.key,
+            name,
+            entries,
+        };
+        vesting_template.validate()?;
+
+        if get_vesting_template_address(program_id, authority_account.key, &vesting_template.name) != *vesting_template_account.key {
+            return Err(VestingError::InvalidVestingTemplateAccount.into());
         }
 
+        create_and_serialize_account_signed::<VestingTemplate>(
+            payer_account,
+            vesting_template_account,
+            &vesting_template,
+            &get_vesting_template_seeds(authority_account.key, &vesting_template.name),
+            program_id,
+            system_program_account,
+            &Rent::get()?,
+        )?;
+
         Ok(())
     }
 
+    pub fn process_deposit_from_template(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        start: u64,
+        total: u64,
+        vesting_bump_seed: u8,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let vesting_template_account = next_account_info(accounts_iter)?;
+        let vesting_template = get_vesting_template_data(program_id, vesting_template_account)?;
+
+        let schedules = vesting_template.expand(start, total)?;
+        Self::process_deposit(program_id, &accounts[1..], schedules, None, false, None, vesting_bump_seed)
+    }
+
     pub fn process_withdraw(
         program_id: &Pubkey,
         _accounts: &[AccountInfo],
@@ -160,39 +343,49 @@ impl Processor {
         let destination_token_account = next_account_info(accounts_iter)?;
         let vesting_owner_account = next_account_info(accounts_iter)?;
 
-        let realm_info = if let Some(governance) = accounts_iter.next() {
+        let mut vesting_record = get_vesting_record_data(program_id, vesting_account)?;
+        let vesting_account_key = verify_vesting_account(program_id, &vesting_record, vesting_account, vesting_token_account.key)?;
+        let vesting_account_seed = vesting_record.bump_seed;
+        let vesting_token_account_data = Account::unpack(&vesting_token_account.data.borrow())?;
+        verify_vesting_owner(&vesting_record, vesting_owner_account)?;
+        verify_vesting_token_account(&vesting_record, vesting_token_account, vesting_token_account_data, vesting_account_key)?;
+
+        // Realm accounts are expected only for the vesting under Realm, the rest are the Proposal accounts
+        let realm_info = if vesting_record.realm.is_some() {
+            let governance = accounts_iter.next().ok_or(VestingError::MissingRealmAccounts)?;
             let realm = next_account_info(accounts_iter)?;
             let owner_record = next_account_info(accounts_iter)?;
+            let vesting_config = next_account_info(accounts_iter)?;
             let voter_weight = next_account_info(accounts_iter)?;
             let max_voter_weight = next_account_info(accounts_iter)?;
-            Some((governance, realm, owner_record, voter_weight, max_voter_weight,))
+            Some((governance, realm, owner_record, vesting_config, voter_weight, max_voter_weight,))
         } else {
             None
         };
+        let mut proposal_accounts: Vec<&AccountInfo> = accounts_iter.collect();
+        // The last account is the spill account if it's not owned by the governance program (unlike the Proposals)
+        let spill_account = match realm_info {
+            Some((governance, ..)) if proposal_accounts.last().map_or(false, |last| last.owner != governance.key) => {
+                proposal_accounts.pop()
+            },
+            _ => None,
+        };
 
-        let (vesting_account_key,vesting_account_seed) = Pubkey::find_program_address(&[vesting_token_account.key.as_ref()], program_id);
-        if vesting_account_key != *vesting_account.key {
-            return Err(VestingError::InvalidVestingAccount.into());
+        if let Some((_, _, _, vesting_config_account, _, _)) = realm_info {
+            resolve_tge_relative_schedule(program_id, &mut vesting_record, vesting_config_account)?;
         }
 
-        let mut vesting_record = get_account_data::<VestingRecord>(program_id, vesting_account)?;
-        let vesting_token_account_data = Account::unpack(&vesting_token_account.data.borrow())?;
-        verify_vesting_owner(&vesting_record, vesting_owner_account)?;
-        verify_vesting_token_account(&vesting_record, vesting_token_account, vesting_token_account_data, vesting_account_key)?;
+        let succeeded_proposals = get_succeeded_proposals(
+            vesting_record.governance.as_ref(),
+            &proposal_accounts,
+        )?;
+        let total_amount_to_transfer = unlock_matured_schedules(&mut vesting_record, &succeeded_proposals)?;
 
-        // Unlock the schedules that have reached maturity
-        let clock = Clock::get()?;
-        let mut total_amount_to_transfer = 0u64;
-        for s in vesting_record.schedule.iter_mut() {
-            if clock.unix_timestamp as u64 >= s.release_time {
-                total_amount_to_transfer = total_amount_to_transfer.checked_add(s.amount)
-                        .ok_or(VestingError::OverflowAmount)?;
-                s.amount = 0;
-            }
-        }
-        if total_amount_to_transfer == 0 {
-            return Err(VestingError::NotReachedReleaseTime.into());
-        }
+        // The released amount excluded from the voter weight is withdrawn together with the rest
+        let excluded_amount_to_transfer = vesting_record.excluded_amount;
+        let voting_amount_to_transfer = total_amount_to_transfer.checked_sub(excluded_amount_to_transfer)
+                .ok_or(VestingError::UnderflowAmount)?;
+        vesting_record.excluded_amount = 0;
 
         invoke_transfer_signed(
             spl_token_account,
@@ -210,12 +403,14 @@ impl Processor {
             let (governance_account,
                  realm_account,
                  owner_record_account,
+                 vesting_config_account,
                  voter_weight_record_account,
                  max_voter_weight_record_account) = realm_info.ok_or(VestingError::MissingRealmAccounts)?;
 
             if *realm_account.key != expected_realm_account {
                 return Err(VestingError::InvalidRealmAccount.into())
             };
+            verify_governance_program(&vesting_record, governance_account)?;
 
             let realm_data = get_realm_data(governance_account.key, realm_account)?;
             realm_data.assert_is_valid_governing_token_mint(&vesting_record.mint)?;
@@ -233,27 +428,190 @@ impl Processor {
                 owner_record_data.assert_can_withdraw_governing_tokens()?;
             }
 
-            let mut voter_weight_record = get_voter_weight_record_data_checked(
-                    program_id,
+            let vesting_config = get_vesting_config_data_or_default(
+                program_id,
+                vesting_config_account,
+                realm_account.key,
+                &vesting_record.mint,
+            )?;
+
+            let weighted_amounts = decrease_voter_weight_record(
+                    realm_account.key,
+                    &vesting_record.mint,
+                    vesting_owner_account.key,
                     voter_weight_record_account,
+                    voting_amount_to_transfer,
+                    excluded_amount_to_transfer,
+                    &vesting_config,
+                    program_id)?;
+
+            let max_voter_weight = update_max_voter_weight_record(
                     realm_account.key,
                     &vesting_record.mint,
-                    vesting_owner_account.key)?;
+                    max_voter_weight_record_account,
+                    &[weighted_amounts],
+                    &vesting_config,
+                    program_id)?;
 
-            voter_weight_record.decrease_total_amount(total_amount_to_transfer)?;
-            voter_weight_record.serialize(&mut *voter_weight_record_account.data.borrow_mut())?;
+            apply_voter_weight_cap(
+                    &[voter_weight_record_account],
+                    &vesting_config,
+                    max_voter_weight,
+                    program_id)?;
 
-            let mut max_voter_weight_record = get_max_voter_weight_record_data_checked(
-                    program_id,
-                    max_voter_weight_record_account,
-                    realm_account.key,
-                    &vesting_record.mint)?;
+            dispose_voter_weight_record_if_empty(
+                    voter_weight_record_account,
+                    spill_account,
+                    program_id)?;
+        }
 
-            let max_voter_weight = &mut max_voter_weight_record.max_voter_weight;
-            *max_voter_weight = max_voter_weight.checked_sub(total_amount_to_transfer).ok_or(VestingError::UnderflowAmount)?;
-            max_voter_weight_record.serialize(&mut *max_voter_weight_record_account.data.borrow_mut())?;
+        set_operation_return_data(program_id, total_amount_to_transfer, realm_info.map(|v| v.4))?;
+
+        Ok(())
+    }
+
+    pub fn process_withdraw_out_of_realm(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let spl_token_account = next_account_info(accounts_iter)?;
+        let vesting_account = next_account_info(accounts_iter)?;
+        let vesting_token_account = next_account_info(accounts_iter)?;
+        let destination_token_account = next_account_info(accounts_iter)?;
+        let vesting_owner_account = next_account_info(accounts_iter)?;
+        let rent_payer_account = next_account_info(accounts_iter)?;
+        let governance_account = next_account_info(accounts_iter)?;
+        let realm_account = next_account_info(accounts_iter)?;
+        let realm_config_account = next_account_info(accounts_iter)?;
+        let vesting_config_account = next_account_info(accounts_iter)?;
+        let voter_weight_record_account = next_account_info(accounts_iter)?;
+        let max_voter_weight_record_account = next_account_info(accounts_iter)?;
+        let proposal_accounts: Vec<&AccountInfo> = accounts_iter.collect();
+
+        let mut vesting_record = get_vesting_record_data(program_id, vesting_account)?;
+        let vesting_account_key = verify_vesting_account(program_id, &vesting_record, vesting_account, vesting_token_account.key)?;
+        let vesting_account_seed = vesting_record.bump_seed;
+        let vesting_token_account_data = Account::unpack(&vesting_token_account.data.borrow())?;
+        verify_vesting_owner(&vesting_record, vesting_owner_account)?;
+        verify_vesting_token_account(&vesting_record, vesting_token_account, vesting_token_account_data, vesting_account_key)?;
+        if *rent_payer_account.key != vesting_record.rent_payer {
+            return Err(VestingError::InvalidRentPayerAccount.into());
+        }
+
+        let expected_realm_account = vesting_record.realm.ok_or(VestingError::VestingIsNotUnderRealm)?;
+        if *realm_account.key != expected_realm_account {
+            return Err(VestingError::InvalidRealmAccount.into())
+        };
+        verify_governance_program(&vesting_record, governance_account)?;
+
+        // The Realm which can't be deserialized anymore (the governance program was upgraded
+        // to an incompatible layout) can't take the voter weights from the addin either
+        if get_realm_data(governance_account.key, realm_account).is_ok() {
+            let voter_weight_addin = get_realm_configured_voter_weight_addin(
+                governance_account.key,
+                realm_account,
+                realm_config_account,
+                &vesting_record.mint,
+            )?;
+            if voter_weight_addin == Some(*program_id) {
+                return Err(VestingError::RealmUsesVestingAddin.into());
+            }
+        } else {
+            msg!("Realm {} is not compatible with the governance program", realm_account.key);
         }
 
+        // The TGE timestamp of the Realm is not available for the vesting out of the Realm
+        resolve_tge_relative_schedule(program_id, &mut vesting_record, vesting_config_account)?;
+        if vesting_record.tge_relative {
+            return Err(VestingError::UnresolvedTgeRelativeSchedule.into());
+        }
+
+        let total_amount = vesting_record.schedule.iter()
+                .try_fold(0u64, |acc, item| acc.checked_add(item.amount))
+                .ok_or(VestingError::OverflowAmount)?;
+        let excluded_amount = vesting_record.excluded_amount;
+        let voting_amount = total_amount.checked_sub(excluded_amount)
+                .ok_or(VestingError::UnderflowAmount)?;
+
+        let succeeded_proposals = get_succeeded_proposals(
+            vesting_record.governance.as_ref(),
+            &proposal_accounts,
+        )?;
+        let total_amount_to_transfer = unlock_matured_schedules(&mut vesting_record, &succeeded_proposals)?;
+
+        invoke_transfer_signed(
+            spl_token_account,
+            vesting_token_account,
+            destination_token_account,
+            vesting_account,
+            total_amount_to_transfer,
+            &[&[vesting_token_account.key.as_ref(), &[vesting_account_seed]]],
+        )?;
+
+        // The vesting leaves the Realm, so it can be attached again (to the Realm with the vesting addin)
+        // without counting its tokens twice. The stale realm bytes are dropped like in DetachRealm
+        vesting_record.realm = None;
+        vesting_record.excluded_amount = 0;
+        shrink_account(
+            vesting_account,
+            vesting_record.try_to_vec()?.len(),
+            rent_payer_account,
+        )?;
+        vesting_record.serialize(&mut *vesting_account.data.borrow_mut())?;
+
+        // The escape hatch releases the tokens even if the voter weight bookkeeping fails
+        // (e.g. the records can't be deserialized or the amounts are inconsistent)
+        let update_voter_weight = || -> ProgramResult {
+            let vesting_config = get_vesting_config_data_or_default(
+                program_id,
+                vesting_config_account,
+                realm_account.key,
+                &vesting_record.mint,
+            )?;
+
+            // The VoterWeightRecord (and MaxVoterWeightRecord) may be already closed
+            if voter_weight_record_account.data_is_empty() {
+                return Ok(());
+            }
+            let weighted_amounts = decrease_voter_weight_record(
+                    realm_account.key,
+                    &vesting_record.mint,
+                    vesting_owner_account.key,
+                    voter_weight_record_account,
+                    voting_amount,
+                    excluded_amount,
+                    &vesting_config,
+                    program_id)?;
+
+            if max_voter_weight_record_account.data_is_empty() {
+                return Ok(());
+            }
+            let max_voter_weight = update_max_voter_weight_record(
+                    realm_account.key,
+                    &vesting_record.mint,
+                    max_voter_weight_record_account,
+                    &[weighted_amounts],
+                    &vesting_config,
+                    program_id)?;
+
+            apply_voter_weight_cap(
+                    &[voter_weight_record_account],
+                    &vesting_config,
+                    max_voter_weight,
+                    program_id)
+        };
+        let voter_weight_record_account = match update_voter_weight() {
+            Ok(()) => Some(voter_weight_record_account),
+            Err(error) => {
+                msg!("Voter weight of {} is not updated: {:?}", vesting_owner_account.key, error);
+                None
+            },
+        };
+
+        set_operation_return_data(program_id, total_amount_to_transfer, voter_weight_record_account)?;
+
         Ok(())
     }
 
@@ -261,6 +619,7 @@ impl Processor {
         program_id: &Pubkey,
         accounts: &[AccountInfo],
     ) -> ProgramResult {
+        let (accounts, vesting_index_accounts) = split_vesting_index_accounts(program_id, accounts);
         let accounts_iter = &mut accounts.iter();
 
         let vesting_account = next_account_info(accounts_iter)?;
@@ -269,35 +628,53 @@ impl Processor {
         let realm_info = if let Some(governance) = accounts_iter.next() {
             let realm = next_account_info(accounts_iter)?;
             let current_owner_record = next_account_info(accounts_iter)?;
+            let vesting_config = next_account_info(accounts_iter)?;
             let current_voter_weight = next_account_info(accounts_iter)?;
             let new_voter_weight = next_account_info(accounts_iter)?;
-            Some((governance, realm, current_owner_record, current_voter_weight, new_voter_weight,))
+            let max_voter_weight = next_account_info(accounts_iter)?;
+            Some((governance, realm, current_owner_record, vesting_config, current_voter_weight, new_voter_weight, max_voter_weight,))
         } else {
             None
         };
+        // System program and payer are expected only to create the VoterWeightRecord of the new owner,
+        // the spill account only to close the VoterWeightRecord of the current owner left empty
+        let (payer_info, spill_account) = match accounts_iter.as_slice() {
+            [] => (None, None),
+            [spill] => (None, Some(spill)),
+            [system_program, payer] => (Some((system_program, payer,)), None),
+            [system_program, payer, spill, ..] => (Some((system_program, payer,)), Some(spill)),
+        };
 
         msg!("Change owner {} -> {}", vesting_owner_account.key, new_vesting_owner_account.key);
 
-        let mut vesting_record = get_account_data::<VestingRecord>(program_id, vesting_account)?;
+        let mut vesting_record = get_vesting_record_data(program_id, vesting_account)?;
         verify_vesting_owner(&vesting_record, vesting_owner_account)?;
 
         let total_amount = vesting_record.schedule.iter()
                 .try_fold(0u64, |acc, item| acc.checked_add(item.amount))
                 .ok_or(VestingError::OverflowAmount)?;
+        let excluded_amount = vesting_record.excluded_amount;
+        let voting_amount = total_amount.checked_sub(excluded_amount)
+                .ok_or(VestingError::UnderflowAmount)?;
 
         vesting_record.owner = *new_vesting_owner_account.key;
         vesting_record.serialize(&mut *vesting_account.data.borrow_mut())?;
 
-        if let Some(expected_realm_account) = vesting_record.realm {
+        update_vesting_indexes(program_id, accounts, vesting_index_accounts, &[(vesting_account.key, Some(new_vesting_owner_account.key))])?;
+
+        let new_voter_weight_record_account = if let Some(expected_realm_account) = vesting_record.realm {
             let (governance_account,
                  realm_account,
                  owner_record_account,
+                 vesting_config_account,
                  voter_weight_record_account,
-                 new_voter_weight_record_account) = realm_info.ok_or(VestingError::MissingRealmAccounts)?;
+                 new_voter_weight_record_account,
+                 max_voter_weight_record_account) = realm_info.ok_or(VestingError::MissingRealmAccounts)?;
 
             if *realm_account.key != expected_realm_account {
                 return Err(VestingError::InvalidRealmAccount.into())
             };
+            verify_governance_program(&vesting_record, governance_account)?;
 
             let realm_data = get_realm_data(governance_account.key, realm_account)?;
             realm_data.assert_is_valid_governing_token_mint(&vesting_record.mint)?;
@@ -315,27 +692,74 @@ impl Processor {
                 owner_record_data.assert_can_withdraw_governing_tokens()?;
             }
 
-            let mut voter_weight_record = get_voter_weight_record_data_checked(
-                    program_id,
-                    voter_weight_record_account,
+            let vesting_config = get_vesting_config_data_or_default(
+                program_id,
+                vesting_config_account,
+                realm_account.key,
+                &vesting_record.mint,
+            )?;
+
+            let weighted_amounts = decrease_voter_weight_record(
                     realm_account.key,
                     &vesting_record.mint,
-                    vesting_owner_account.key)?;
-
-            voter_weight_record.decrease_total_amount(total_amount)?;
-            voter_weight_record.serialize(&mut *voter_weight_record_account.data.borrow_mut())?;
+                    vesting_owner_account.key,
+                    voter_weight_record_account,
+                    voting_amount,
+                    excluded_amount,
+                    &vesting_config,
+                    program_id)?;
+
+            let new_weighted_amounts = if let Some((system_program_account, payer_account)) = payer_info {
+                create_or_increase_voter_weight_record(
+                        realm_account.key,
+                        &vesting_record.mint,
+                        new_vesting_owner_account.key,
+                        new_voter_weight_record_account,
+                        voting_amount,
+                        excluded_amount,
+                        &vesting_config,
+                        program_id,
+                        system_program_account,
+                        payer_account)?
+            } else {
+                increase_voter_weight_record(
+                        realm_account.key,
+                        &vesting_record.mint,
+                        new_vesting_owner_account.key,
+                        new_voter_weight_record_account,
+                        voting_amount,
+                        excluded_amount,
+                        &vesting_config,
+                        program_id)?
+            };
 
-            let mut new_voter_weight_record = get_voter_weight_record_data_checked(
-                    program_id,
-                    new_voter_weight_record_account,
+            // Max voter weight is changed for the non-linear weight curves only
+            let max_voter_weight = update_max_voter_weight_record(
                     realm_account.key,
                     &vesting_record.mint,
-                    new_vesting_owner_account.key)?;
+                    max_voter_weight_record_account,
+                    &[weighted_amounts, new_weighted_amounts],
+                    &vesting_config,
+                    program_id)?;
 
-            new_voter_weight_record.increase_total_amount(total_amount)?;
-            new_voter_weight_record.serialize(&mut *new_voter_weight_record_account.data.borrow_mut())?;
+            apply_voter_weight_cap(
+                    &[voter_weight_record_account, new_voter_weight_record_account],
+                    &vesting_config,
+                    max_voter_weight,
+                    program_id)?;
 
-        }
+            dispose_voter_weight_record_if_empty(
+                    voter_weight_record_account,
+                    spill_account,
+                    program_id)?;
+
+            Some(new_voter_weight_record_account)
+        } else {
+            None
+        };
+
+        // No tokens are transferred, the resulting amounts are the ones of the new owner
+        set_operation_return_data(program_id, 0, new_voter_weight_record_account)?;
 
         Ok(())
     }
@@ -371,66 +795,52 @@ impl Processor {
         program_id: &Pubkey,
         accounts: &[AccountInfo],
         vote_percentage: u16,
+        valid_until: Option<Slot>,
     ) -> ProgramResult {
-        let accounts_iter = &mut accounts.iter();
-
-        let vesting_mint_account = next_account_info(accounts_iter)?;
-        let vesting_owner_account = next_account_info(accounts_iter)?;
-        let vesting_authority_account = next_account_info(accounts_iter)?;
-        let governance_account = next_account_info(accounts_iter)?;
-        let realm_account = next_account_info(accounts_iter)?;
-        let owner_record_account = next_account_info(accounts_iter)?;
-        let voter_weight_record_account = next_account_info(accounts_iter)?;
-
-        let realm_data = get_realm_data(governance_account.key, realm_account)?;
-        realm_data.assert_is_valid_governing_token_mint(vesting_mint_account.key)?;
-
-        let owner_record_data = get_token_owner_record_data_for_seeds(
-            governance_account.key,
-            owner_record_account,
-            &get_token_owner_record_address_seeds(
-                realm_account.key,
-                vesting_mint_account.key,
-                vesting_owner_account.key,
-            ),
-        )?;
-        owner_record_data.assert_token_owner_or_delegate_is_signer(vesting_authority_account)?;
-
-        let mut voter_weight_record = get_voter_weight_record_data_checked(
-                program_id,
-                voter_weight_record_account,
-                realm_account.key,
-                vesting_mint_account.key,
-                vesting_owner_account.key)?;
-
-        voter_weight_record.set_vote_percentage(vote_percentage)?;
-        voter_weight_record.serialize(&mut *voter_weight_record_account.data.borrow_mut())?;
+        update_voter_weight_record_by_authority(
+            program_id,
+            accounts,
+            |record, vesting_config| record.set_vote_percentage(vote_percentage, valid_until, vesting_config),
+        )
+    }
 
-        Ok(())
+    pub fn process_set_vote_amount(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        update_voter_weight_record_by_authority(
+            program_id,
+            accounts,
+            |record, vesting_config| record.set_vote_amount(amount, vesting_config),
+        )
     }
 
     pub fn process_close(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
     ) -> ProgramResult {
+        let (accounts, vesting_index_accounts) = split_vesting_index_accounts(program_id, accounts);
         let accounts_iter = &mut accounts.iter();
 
         let spl_token_account = next_account_info(accounts_iter)?;
         let vesting_account = next_account_info(accounts_iter)?;
         let vesting_token_account = next_account_info(accounts_iter)?;
         let vesting_owner_account = next_account_info(accounts_iter)?;
-        let spill_account = next_account_info(accounts_iter)?;
-
-        let (vesting_account_key, vesting_account_seed) = Pubkey::find_program_address(&[vesting_token_account.key.as_ref()], program_id);
-        if vesting_account_key != *vesting_account.key {
-            return Err(VestingError::InvalidVestingAccount.into());
-        }
+        let rent_payer_account = next_account_info(accounts_iter)?;
 
-        let mut vesting_record = get_account_data::<VestingRecord>(program_id, vesting_account)?;
+        let mut vesting_record = get_vesting_record_data(program_id, vesting_account)?;
+        let vesting_account_key = verify_vesting_account(program_id, &vesting_record, vesting_account, vesting_token_account.key)?;
+        let vesting_account_seed = vesting_record.bump_seed;
         let vesting_token_account_data = Account::unpack(&vesting_token_account.data.borrow())?;
         verify_vesting_owner(&vesting_record, vesting_owner_account)?;
         verify_vesting_token_account(&vesting_record, vesting_token_account, vesting_token_account_data, vesting_account_key)?;
 
+        // The lamports are returned to the payer of the vesting account rent
+        if *rent_payer_account.key != vesting_record.rent_payer {
+            return Err(VestingError::InvalidRentPayerAccount.into());
+        }
+
         let mut total_amount = 0u64;
         for s in vesting_record.schedule.iter_mut() {
             total_amount = total_amount.checked_add(s.amount).ok_or(VestingError::OverflowAmount)?;
@@ -443,7 +853,7 @@ impl Processor {
             close_account(
                 spl_token_account.key,
                 vesting_token_account.key,
-                spill_account.key,
+                rent_payer_account.key,
                 vesting_account.key,
                 &[],
             )?
@@ -463,13 +873,15 @@ impl Processor {
             &[
                 spl_token_account.clone(),
                 vesting_token_account.clone(),
-                spill_account.clone(),
+                rent_payer_account.clone(),
                 vesting_account.clone(),
             ],
             &[&[vesting_token_account.key.as_ref(), &[vesting_account_seed]]],
         )?;
 
-        dispose_account(vesting_account, spill_account);
+        dispose_account(vesting_account, rent_payer_account);
+
+        update_vesting_indexes(program_id, accounts, vesting_index_accounts, &[(vesting_account.key, None)])?;
 
         Ok(())
     }
@@ -497,7 +909,7 @@ impl Processor {
                 mint_account.key,
                 owner_account.key)?;
 
-        if voter_weight_record.total_amount != 0 {
+        if !voter_weight_record.is_empty() {
             return Err(VestingError::VoterWeightRecordNotEmpty.into());
         }
 
@@ -506,86 +918,1256 @@ impl Processor {
         Ok(())
     }
 
-    pub fn process_split(
+    pub fn process_close_max_voter_weight_record(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        schedules: Vec<VestingSchedule>,
     ) -> ProgramResult {
         let accounts_iter = &mut accounts.iter();
 
-        let system_program_account = next_account_info(accounts_iter)?;
-        let spl_token_account = next_account_info(accounts_iter)?;
-        let vesting_account = next_account_info(accounts_iter)?;
-        let vesting_token_account = next_account_info(accounts_iter)?;
-        let vesting_owner_account = next_account_info(accounts_iter)?;
-        let new_vesting_account = next_account_info(accounts_iter)?;
-        let new_vesting_token_account = next_account_info(accounts_iter)?;
-        let new_vesting_owner_account = next_account_info(accounts_iter)?;
-        let payer_account = next_account_info(accounts_iter)?;
+        let governance_account = next_account_info(accounts_iter)?;
+        let realm_account = next_account_info(accounts_iter)?;
+        let realm_authority_account = next_account_info(accounts_iter)?;
+        let mint_account = next_account_info(accounts_iter)?;
+        let max_voter_weight_record_account = next_account_info(accounts_iter)?;
+        let spill_account = next_account_info(accounts_iter)?;
 
-        let realm_info = if let Some(governance) = accounts_iter.next() {
-            let realm = next_account_info(accounts_iter)?;
-            let owner_record = next_account_info(accounts_iter)?;
-            let voter_weight = next_account_info(accounts_iter)?;
-            let new_voter_weight = next_account_info(accounts_iter)?;
-            Some((governance, realm, owner_record, voter_weight, new_voter_weight,))
+        let realm_data = get_realm_data(governance_account.key, realm_account)?;
+        assert_is_realm_authority(&realm_data, realm_authority_account)?;
+        realm_data.assert_is_valid_governing_token_mint(mint_account.key)?;
+
+        let max_voter_weight_record = get_max_voter_weight_record_data_checked(
+                program_id,
+                max_voter_weight_record_account,
+                realm_account.key,
+                mint_account.key)?;
+
+        if max_voter_weight_record.max_voter_weight != 0 {
+            return Err(VestingError::MaxVoterWeightRecordNotEmpty.into());
+        }
+
+        dispose_account(max_voter_weight_record_account, spill_account);
+
+        Ok(())
+    }
+
+    pub fn process_split(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        schedules: Vec<VestingSchedule>,
+        label: Option<String>,
+        new_vesting_token_seed: Option<VestingTokenSeed>,
+        new_vesting_bump_seed: u8,
+    ) -> ProgramResult {
+        verify_schedule(&schedules)?;
+        verify_label(&label)?;
+
+        split_vesting(
+            program_id,
+            accounts,
+            1,
+            label,
+            new_vesting_token_seed.map(|seed| vec![seed]),
+            vec![new_vesting_bump_seed],
+            |vesting_record| {
+                vesting_record.take_schedules(&schedules)?;
+                Ok(vec![schedules])
+            },
+        )
+    }
+
+    pub fn process_split_percent(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        basis_points: u16,
+        new_vesting_token_seed: Option<VestingTokenSeed>,
+        new_vesting_bump_seed: u8,
+    ) -> ProgramResult {
+        split_vesting(
+            program_id,
+            accounts,
+            1,
+            None,
+            new_vesting_token_seed.map(|seed| vec![seed]),
+            vec![new_vesting_bump_seed],
+            |vesting_record| Ok(vec![vesting_record.take_schedule_share(basis_points)?]),
+        )
+    }
+
+    pub fn process_split_many(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        schedules: Vec<Vec<VestingSchedule>>,
+        new_vesting_token_seeds: Option<Vec<VestingTokenSeed>>,
+        new_vesting_bump_seeds: Vec<u8>,
+    ) -> ProgramResult {
+        if schedules.is_empty() {
+            return Err(VestingError::InvalidSchedule.into());
+        }
+        for item in &schedules {
+            verify_schedule(item)?;
+        }
+
+        split_vesting(
+            program_id,
+            accounts,
+            schedules.len(),
+            None,
+            new_vesting_token_seeds,
+            new_vesting_bump_seeds,
+            |vesting_record| {
+                for item in &schedules {
+                    vesting_record.take_schedules(item)?;
+                }
+                Ok(schedules)
+            },
+        )
+    }
+
+    pub fn process_set_label(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        label: Option<String>,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let system_program_account = next_account_info(accounts_iter)?;
+        let vesting_account = next_account_info(accounts_iter)?;
+        let grantor_account = next_account_info(accounts_iter)?;
+        let payer_account = next_account_info(accounts_iter)?;
+
+        let mut vesting_record = get_vesting_record_data(program_id, vesting_account)?;
+
+        if vesting_record.grantor != *grantor_account.key {
+            return Err(VestingError::InvalidGrantorAccount.into());
+        }
+        if !grantor_account.is_signer {
+            return Err(VestingError::MissingRequiredSigner.into());
+        }
+
+        verify_label(&label)?;
+
+        vesting_record.label = label;
+        resize_account(
+            vesting_account,
+            vesting_record.try_to_vec()?.len(),
+            payer_account,
+            system_program_account,
+        )?;
+        vesting_record.serialize(&mut *vesting_account.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_attach_realm(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let system_program_account = next_account_info(accounts_iter)?;
+        let vesting_account = next_account_info(accounts_iter)?;
+        let vesting_owner_account = next_account_info(accounts_iter)?;
+        let payer_account = next_account_info(accounts_iter)?;
+        let governance_account = next_account_info(accounts_iter)?;
+        let realm_account = next_account_info(accounts_iter)?;
+        let realm_config_account = next_account_info(accounts_iter)?;
+        let vesting_config_account = next_account_info(accounts_iter)?;
+        let voter_weight_record_account = next_account_info(accounts_iter)?;
+        let max_voter_weight_record_account = next_account_info(accounts_iter)?;
+
+        let mut vesting_record = get_vesting_record_data(program_id, vesting_account)?;
+        verify_vesting_account(program_id, &vesting_record, vesting_account, &vesting_record.token)?;
+        verify_vesting_owner(&vesting_record, vesting_owner_account)?;
+
+        if vesting_record.realm.is_some() {
+            return Err(VestingError::VestingIsAlreadyUnderRealm.into());
+        }
+        // The milestone Proposals of the detached vesting stay under the pinned governance program
+        verify_governance_program(&vesting_record, governance_account)?;
+
+        assert_is_realm_voter_weight_addin(
+            program_id,
+            governance_account.key,
+            realm_account,
+            realm_config_account,
+            &vesting_record.mint,
+        )?;
+
+        let total_amount = vesting_record.schedule.iter()
+                .try_fold(0u64, |acc, item| acc.checked_add(item.amount))
+                .ok_or(VestingError::OverflowAmount)?;
+
+        vesting_record.realm = Some(*realm_account.key);
+        vesting_record.governance = Some(*governance_account.key);
+        resize_account(
+            vesting_account,
+            vesting_record.try_to_vec()?.len(),
+            payer_account,
+            system_program_account,
+        )?;
+        vesting_record.serialize(&mut *vesting_account.data.borrow_mut())?;
+
+        let vesting_config = get_vesting_config_data_or_default(
+            program_id,
+            vesting_config_account,
+            realm_account.key,
+            &vesting_record.mint,
+        )?;
+
+        let weighted_amounts = create_or_increase_voter_weight_record(
+            realm_account.key,
+            &vesting_record.mint,
+            vesting_owner_account.key,
+            voter_weight_record_account,
+            total_amount,
+            0,
+            &vesting_config,
+            program_id,
+            system_program_account,
+            payer_account
+        )?;
+
+        let max_voter_weight = create_or_update_max_voter_weight_record(
+            realm_account.key,
+            &vesting_record.mint,
+            max_voter_weight_record_account,
+            &[weighted_amounts],
+            &vesting_config,
+            program_id,
+            system_program_account,
+            payer_account
+        )?;
+
+        apply_voter_weight_cap(
+            &[voter_weight_record_account],
+            &vesting_config,
+            max_voter_weight,
+            program_id,
+        )?;
+
+        set_operation_return_data(program_id, 0, Some(voter_weight_record_account))?;
+
+        Ok(())
+    }
+
+    pub fn process_detach_realm(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let vesting_account = next_account_info(accounts_iter)?;
+        let vesting_owner_account = next_account_info(accounts_iter)?;
+        let rent_payer_account = next_account_info(accounts_iter)?;
+        let governance_account = next_account_info(accounts_iter)?;
+        let realm_account = next_account_info(accounts_iter)?;
+        let owner_record_account = next_account_info(accounts_iter)?;
+        let vesting_config_account = next_account_info(accounts_iter)?;
+        let voter_weight_record_account = next_account_info(accounts_iter)?;
+        let max_voter_weight_record_account = next_account_info(accounts_iter)?;
+
+        let mut vesting_record = get_vesting_record_data(program_id, vesting_account)?;
+        verify_vesting_owner(&vesting_record, vesting_owner_account)?;
+        if *rent_payer_account.key != vesting_record.rent_payer {
+            return Err(VestingError::InvalidRentPayerAccount.into());
+        }
+
+        let expected_realm_account = vesting_record.realm.ok_or(VestingError::VestingIsNotUnderRealm)?;
+        if *realm_account.key != expected_realm_account {
+            return Err(VestingError::InvalidRealmAccount.into())
+        };
+        verify_governance_program(&vesting_record, governance_account)?;
+
+        let realm_data = get_realm_data(governance_account.key, realm_account)?;
+        realm_data.assert_is_valid_governing_token_mint(&vesting_record.mint)?;
+
+        let owner_record_optional_data = get_token_owner_record_data_if_exists(
+            governance_account.key,
+            owner_record_account,
+            &get_token_owner_record_address_seeds(
+                realm_account.key,
+                &vesting_record.mint,
+                vesting_owner_account.key,
+            ),
+        )?;
+        if let Some(owner_record_data) = owner_record_optional_data {
+            owner_record_data.assert_can_withdraw_governing_tokens()?;
+        }
+
+        // The TGE timestamp of the Realm is not available for the detached vesting
+        resolve_tge_relative_schedule(program_id, &mut vesting_record, vesting_config_account)?;
+        if vesting_record.tge_relative {
+            return Err(VestingError::UnresolvedTgeRelativeSchedule.into());
+        }
+
+        let total_amount = vesting_record.schedule.iter()
+                .try_fold(0u64, |acc, item| acc.checked_add(item.amount))
+                .ok_or(VestingError::OverflowAmount)?;
+        let excluded_amount = vesting_record.excluded_amount;
+        let voting_amount = total_amount.checked_sub(excluded_amount)
+                .ok_or(VestingError::UnderflowAmount)?;
+
+        // The stale realm bytes are dropped, so the record stays strictly deserializable
+        vesting_record.realm = None;
+        vesting_record.excluded_amount = 0;
+        shrink_account(
+            vesting_account,
+            vesting_record.try_to_vec()?.len(),
+            rent_payer_account,
+        )?;
+        vesting_record.serialize(&mut *vesting_account.data.borrow_mut())?;
+
+        let vesting_config = get_vesting_config_data_or_default(
+            program_id,
+            vesting_config_account,
+            realm_account.key,
+            &vesting_record.mint,
+        )?;
+
+        let weighted_amounts = decrease_voter_weight_record(
+                realm_account.key,
+                &vesting_record.mint,
+                vesting_owner_account.key,
+                voter_weight_record_account,
+                voting_amount,
+                excluded_amount,
+                &vesting_config,
+                program_id)?;
+
+        let max_voter_weight = update_max_voter_weight_record(
+                realm_account.key,
+                &vesting_record.mint,
+                max_voter_weight_record_account,
+                &[weighted_amounts],
+                &vesting_config,
+                program_id)?;
+
+        apply_voter_weight_cap(
+                &[voter_weight_record_account],
+                &vesting_config,
+                max_voter_weight,
+                program_id)?;
+
+        set_operation_return_data(program_id, 0, Some(voter_weight_record_account))?;
+
+        Ok(())
+    }
+
+    pub fn process_accelerate_release(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        release_time: u64,
+        include_milestones: bool,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let governance_account = next_account_info(accounts_iter)?;
+        let realm_account = next_account_info(accounts_iter)?;
+        let release_authority_account = next_account_info(accounts_iter)?;
+        let mint_account = next_account_info(accounts_iter)?;
+        let vesting_config_account = next_account_info(accounts_iter)?;
+
+        let realm_data = get_realm_data(governance_account.key, realm_account)?;
+        let vesting_config = get_vesting_config_data_or_default(
+            program_id,
+            vesting_config_account,
+            realm_account.key,
+            mint_account.key,
+        )?;
+        assert_is_realm_release_authority(
+            governance_account.key,
+            realm_account,
+            &realm_data,
+            &vesting_config,
+            release_authority_account,
+        )?;
+
+        while let Some(vesting_account) = accounts_iter.next() {
+            let rent_payer_account = next_account_info(accounts_iter)?;
+
+            let mut vesting_record = get_vesting_record_data(program_id, vesting_account)?;
+            if vesting_record.realm != Some(*realm_account.key) {
+                return Err(VestingError::InvalidRealmAccount.into());
+            }
+            if vesting_record.mint != *mint_account.key {
+                return Err(VestingError::InvalidVestingConfigAccount.into());
+            }
+            if vesting_record.rent_payer != *rent_payer_account.key {
+                return Err(VestingError::InvalidRentPayerAccount.into());
+            }
+            // The TGE-relative schedule can be accelerated only after the TGE timestamp is set
+            vesting_record.apply_tge_timestamp(vesting_config.tge_timestamp)?;
+            if vesting_record.tge_relative {
+                return Err(VestingError::UnresolvedTgeRelativeSchedule.into());
+            }
+
+            // All entries released at or after the release_time are merged into the single entry
+            // placed before the kept milestone entries of the same release time.
+            // The total amount (and voter weight) is not changed.
+            // Milestone entries are merged (and released unconditionally) only on request
+            let is_accelerated = |item: &VestingSchedule| {
+                item.release_time >= release_time && (include_milestones || item.release_proposal.is_none())
+            };
+            let accelerated_amount = vesting_record.schedule.iter()
+                    .filter(|item| is_accelerated(item))
+                    .try_fold(0u64, |acc, item| acc.checked_add(item.amount))
+                    .ok_or(VestingError::OverflowAmount)?;
+            vesting_record.schedule.retain(|item| !is_accelerated(item));
+            if accelerated_amount != 0 {
+                let position = vesting_record.schedule.iter()
+                        .take_while(|item| item.release_time < release_time)
+                        .count();
+                vesting_record.schedule.insert(position, VestingSchedule {
+                    release_time,
+                    amount: accelerated_amount,
+                    release_proposal: None,
+                });
+            }
+            verify_schedule(&vesting_record.schedule)?;
+
+            shrink_account(
+                vesting_account,
+                vesting_record.try_to_vec()?.len(),
+                rent_payer_account,
+            )?;
+            vesting_record.serialize(&mut *vesting_account.data.borrow_mut())?;
+        }
+
+        set_operation_return_data(program_id, 0, None)?;
+        Ok(())
+    }
+
+    pub fn process_update_voter_weight_record(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        weight_action: Option<VoterWeightAction>,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let voter_weight_record_account = next_account_info(accounts_iter)?;
+        let vesting_config_account = next_account_info(accounts_iter)?;
+        let max_voter_weight_record_account = next_account_info(accounts_iter)?;
+
+        let mut voter_weight_record = get_voter_weight_record_data(program_id, voter_weight_record_account)?;
+        let realm = voter_weight_record.base.realm;
+        let mint = voter_weight_record.base.governing_token_mint;
+
+        // Apply the current weight curve and voter weight cap of the Realm to the record
+        let vesting_config = get_vesting_config_data_or_default(
+            program_id,
+            vesting_config_account,
+            &realm,
+            &mint,
+        )?;
+        let old_weighted_amount = voter_weight_record.weighted_amount;
+        voter_weight_record.recalculate_weighted_amount(&vesting_config)?;
+
+        let max_voter_weight = update_max_voter_weight_record(
+            &realm,
+            &mint,
+            max_voter_weight_record_account,
+            &[(old_weighted_amount, voter_weight_record.weighted_amount)],
+            &vesting_config,
+            program_id,
+        )?;
+        if vesting_config.has_voter_weight_cap() {
+            voter_weight_record.apply_voter_weight_cap(&vesting_config, max_voter_weight)?;
+        }
+
+        if let Some(weight_action) = weight_action {
+            let governance_account = next_account_info(accounts_iter)?;
+            let realm_account = next_account_info(accounts_iter)?;
+            let owner_record_account = next_account_info(accounts_iter)?;
+            let record_authority_account = next_account_info(accounts_iter)?;
+
+            // The voter weight is reported for the action by the token owner or the delegate only
+            if *realm_account.key != realm {
+                return Err(VestingError::InvalidRealmAccount.into())
+            }
+            get_realm_data(governance_account.key, realm_account)?;
+            let owner_record_data = get_token_owner_record_data_for_seeds(
+                governance_account.key,
+                owner_record_account,
+                &get_token_owner_record_address_seeds(
+                    &realm,
+                    &mint,
+                    &voter_weight_record.base.governing_token_owner,
+                ),
+            )?;
+            owner_record_data.assert_token_owner_or_delegate_is_signer(record_authority_account)?;
+
+            if let Some(proposal_account) = accounts_iter.next() {
+                if weight_action != VoterWeightAction::CastVote {
+                    return Err(VestingError::InvalidVoterWeightAction.into());
+                }
+                let voting_at_slot = get_proposal_voting_at_slot(
+                    governance_account.key,
+                    proposal_account,
+                    &mint,
+                )?;
+                voter_weight_record.set_voter_weight_snapshot(
+                    voting_at_slot,
+                    VoterWeightAction::CastVote,
+                    *proposal_account.key,
+                )?;
+            } else {
+                // The current voter weight can be changed during the voting
+                if vesting_config.snapshot_voting && weight_action == VoterWeightAction::CastVote {
+                    return Err(VestingError::ProposalRequiredForCastVote.into());
+                }
+                voter_weight_record.set_voter_weight_action(weight_action)?;
+            }
+        }
+        voter_weight_record.serialize(&mut *voter_weight_record_account.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn process_set_vesting_config(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        weight_curve: WeightCurve,
+        max_voter_weight_share: u16,
+        max_voter_weight_source: MaxVoterWeightSource,
+        count_unvested_only: bool,
+        release_authority: Option<Pubkey>,
+        snapshot_voting: bool,
+        default_vote_percentage: u16,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let system_program_account = next_account_info(accounts_iter)?;
+        let governance_account = next_account_info(accounts_iter)?;
+        let realm_account = next_account_info(accounts_iter)?;
+        let realm_authority_account = next_account_info(accounts_iter)?;
+        let payer_account = next_account_info(accounts_iter)?;
+        let mint_account = next_account_info(accounts_iter)?;
+        let vesting_config_account = next_account_info(accounts_iter)?;
+
+        let realm_data = get_realm_data(governance_account.key, realm_account)?;
+        assert_is_realm_authority(&realm_data, realm_authority_account)?;
+        realm_data.assert_is_valid_governing_token_mint(mint_account.key)?;
+
+        weight_curve.validate()?;
+        if max_voter_weight_share > 10000 || default_vote_percentage > 10000 {
+            return Err(VestingError::InvalidPercentage.into());
+        }
+        max_voter_weight_source.validate()?;
+        // The max voter weight of the mint supply is not converted by the weight curve
+        if weight_curve != WeightCurve::Linear && !max_voter_weight_source.is_deposits() {
+            return Err(VestingError::NonLinearWeightCurveWithMintSupply.into());
+        }
+
+        let mut vesting_config = get_vesting_config_data_or_default(
+            program_id,
+            vesting_config_account,
+            realm_account.key,
+            mint_account.key,
+        )?;
+
+        // The records keep the weighted amounts of the previous curve (and MaxVoterWeightRecord
+        // keeps their sum) until they are converted by UpdateVoterWeightRecord
+        vesting_config.weight_curve = weight_curve;
+        vesting_config.max_voter_weight_share = max_voter_weight_share;
+
+        // The sum of deposits is not tracked while the max voter weight comes from the mint supply
+        if !vesting_config.max_voter_weight_source.is_deposits() && max_voter_weight_source.is_deposits() {
+            return Err(VestingError::InvalidMaxVoterWeightSource.into());
+        }
+        vesting_config.max_voter_weight_source = max_voter_weight_source;
+        vesting_config.count_unvested_only = count_unvested_only;
+        vesting_config.release_authority = release_authority;
+        vesting_config.snapshot_voting = snapshot_voting;
+        vesting_config.default_vote_percentage = default_vote_percentage;
+
+        // Existing VoterWeightRecords and MaxVoterWeightRecord are converted by UpdateVoterWeightRecord instruction
+        if vesting_config_account.data_is_empty() {
+            create_and_serialize_account_signed::<VestingConfig>(
+                payer_account,
+                vesting_config_account,
+                &vesting_config,
+                &get_vesting_config_seeds(realm_account.key, mint_account.key),
+                program_id,
+                system_program_account,
+                &Rent::get()?,
+            )?;
+        } else {
+            vesting_config.serialize(&mut *vesting_config_account.data.borrow_mut())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn process_set_tge_timestamp(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        tge_timestamp: u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let system_program_account = next_account_info(accounts_iter)?;
+        let governance_account = next_account_info(accounts_iter)?;
+        let realm_account = next_account_info(accounts_iter)?;
+        let realm_authority_account = next_account_info(accounts_iter)?;
+        let payer_account = next_account_info(accounts_iter)?;
+        let mint_account = next_account_info(accounts_iter)?;
+        let vesting_config_account = next_account_info(accounts_iter)?;
+
+        let realm_data = get_realm_data(governance_account.key, realm_account)?;
+        assert_is_realm_authority(&realm_data, realm_authority_account)?;
+        realm_data.assert_is_valid_governing_token_mint(mint_account.key)?;
+
+        let mut vesting_config = get_vesting_config_data_or_default(
+            program_id,
+            vesting_config_account,
+            realm_account.key,
+            mint_account.key,
+        )?;
+        if vesting_config.tge_timestamp.is_some() {
+            return Err(VestingError::TgeTimestampAlreadySet.into());
+        }
+        vesting_config.tge_timestamp = Some(tge_timestamp);
+
+        if vesting_config_account.data_is_empty() {
+            create_and_serialize_account_signed::<VestingConfig>(
+                payer_account,
+                vesting_config_account,
+                &vesting_config,
+                &get_vesting_config_seeds(realm_account.key, mint_account.key),
+                program_id,
+                system_program_account,
+                &Rent::get()?,
+            )?;
+        } else {
+            vesting_config.serialize(&mut *vesting_config_account.data.borrow_mut())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn process_get_vesting_status(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let vesting_account = next_account_info(accounts_iter)?;
+        let mut vesting_record = get_vesting_record_data(program_id, vesting_account)?;
+        let clock = Clock::get()?;
+
+        // Realm accounts are expected only for the vesting under Realm, the rest are the Proposal accounts
+        let realm_info = match vesting_record.realm {
+            Some(realm) if !accounts_iter.as_slice().is_empty() => {
+                let vesting_config = next_account_info(accounts_iter)?;
+                let voter_weight = next_account_info(accounts_iter)?;
+                Some((realm, vesting_config, voter_weight))
+            },
+            _ => None,
+        };
+        let proposal_accounts: Vec<&AccountInfo> = accounts_iter.collect();
+
+        let mut voter_weight = 0;
+        if let Some((realm, vesting_config_account, voter_weight_record_account)) = realm_info {
+            let vesting_config = get_vesting_config_data_or_default(
+                program_id,
+                vesting_config_account,
+                &realm,
+                &vesting_record.mint,
+            )?;
+            // The resolved schedule is not saved, the account is read-only
+            vesting_record.apply_tge_timestamp(vesting_config.tge_timestamp)?;
+
+            let voter_weight_record = get_voter_weight_record_data_checked(
+                program_id,
+                voter_weight_record_account,
+                &realm,
+                &vesting_record.mint,
+                &vesting_record.owner,
+            )?;
+            voter_weight = voter_weight_record.get_voter_weight_share(
+                vesting_record.get_voting_amount()?,
+                clock.slot,
+                &vesting_config,
+            )?;
+        }
+
+        let succeeded_proposals = get_succeeded_proposals(
+            vesting_record.governance.as_ref(),
+            &proposal_accounts,
+        )?;
+        let vesting_status = VestingStatus {
+            voter_weight,
+            ..vesting_record.get_status(clock.unix_timestamp as u64, &succeeded_proposals)?
+        };
+        set_return_data(&vesting_status.try_to_vec()?);
+
+        Ok(())
+    }
+
+    pub fn process_update_vesting_index(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let system_program_account = next_account_info(accounts_iter)?;
+        let vesting_index_account = next_account_info(accounts_iter)?;
+        let vesting_owner_account = next_account_info(accounts_iter)?;
+        let payer_account = next_account_info(accounts_iter)?;
+
+        let mut vesting_index = get_vesting_index_data_or_default(
+            program_id,
+            vesting_index_account,
+            vesting_owner_account.key,
+        )?;
+
+        for vesting_account in accounts_iter {
+            // The closed vesting account is returned to the system program
+            let vesting_owner = if vesting_account.owner == program_id && !vesting_account.data_is_empty() {
+                Some(get_vesting_record_data(program_id, vesting_account)?.owner)
+            } else {
+                None
+            };
+            // Only the owner adds the vestings to the capped list, the stale vestings are removed by anybody
+            if vesting_owner.as_ref() == Some(vesting_owner_account.key) &&
+               !vesting_owner_account.is_signer &&
+               !vesting_index.vestings.contains(vesting_account.key) {
+                return Err(VestingError::MissingRequiredSigner.into());
+            }
+            vesting_index.update(vesting_account.key, vesting_owner.as_ref())?;
+        }
+
+        if vesting_index_account.data_is_empty() {
+            create_and_serialize_account_signed::<VestingIndex>(
+                payer_account,
+                vesting_index_account,
+                &vesting_index,
+                &get_vesting_index_seeds(vesting_owner_account.key),
+                program_id,
+                system_program_account,
+                &Rent::get()?,
+            )?;
+        } else {
+            resize_account(
+                vesting_index_account,
+                vesting_index.try_to_vec()?.len(),
+                payer_account,
+                system_program_account,
+            )?;
+            vesting_index.serialize(&mut *vesting_index_account.data.borrow_mut())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn process_refresh_max_voter_weight(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let system_program_account = next_account_info(accounts_iter)?;
+        let payer_account = next_account_info(accounts_iter)?;
+        let realm_account = next_account_info(accounts_iter)?;
+        let mint_account = next_account_info(accounts_iter)?;
+        let vesting_config_account = next_account_info(accounts_iter)?;
+        let max_voter_weight_record_account = next_account_info(accounts_iter)?;
+
+        let vesting_config = get_vesting_config_data_or_default(
+            program_id,
+            vesting_config_account,
+            realm_account.key,
+            mint_account.key,
+        )?;
+        let excluded_token_accounts = match &vesting_config.max_voter_weight_source {
+            MaxVoterWeightSource::MintSupply {excluded_token_accounts, ..} => excluded_token_accounts,
+            MaxVoterWeightSource::Deposits => return Err(VestingError::InvalidMaxVoterWeightSource.into()),
+        };
+
+        // The mint is linked to the VestingConfig by the PDA seeds
+        let mint_data = Mint::unpack(&mint_account.data.borrow())?;
+
+        let mut excluded_amount = 0u64;
+        for excluded_token_account in excluded_token_accounts {
+            let token_account = next_account_info(accounts_iter)?;
+            if token_account.key != excluded_token_account {
+                return Err(VestingError::InvalidExcludedTokenAccount.into());
+            }
+            let token_account_data = Account::unpack(&token_account.data.borrow())?;
+            if token_account_data.mint != *mint_account.key {
+                return Err(VestingError::InvalidExcludedTokenAccount.into());
+            }
+            excluded_amount = excluded_amount.checked_add(token_account_data.amount)
+                    .ok_or(VestingError::OverflowAmount)?;
+        }
+
+        let max_voter_weight = vesting_config.get_max_voter_weight_from_supply(mint_data.supply, excluded_amount)?;
+        let max_voter_weight_expiry = Some(Clock::get()?.slot);
+
+        if max_voter_weight_record_account.data_is_empty() {
+            create_max_voter_weight_record(
+                program_id,
+                realm_account.key,
+                mint_account.key,
+                payer_account,
+                max_voter_weight_record_account,
+                system_program_account,
+                |record| {
+                    record.max_voter_weight = max_voter_weight;
+                    record.max_voter_weight_expiry = max_voter_weight_expiry;
+                    Ok(())
+                },
+            )?;
         } else {
-            None
+            let mut max_voter_weight_record = get_max_voter_weight_record_data_checked(
+                program_id,
+                max_voter_weight_record_account,
+                realm_account.key,
+                mint_account.key,
+            )?;
+            max_voter_weight_record.max_voter_weight = max_voter_weight;
+            max_voter_weight_record.max_voter_weight_expiry = max_voter_weight_expiry;
+            max_voter_weight_record.serialize(&mut *max_voter_weight_record_account.data.borrow_mut())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn process_refresh_released_amount(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let vesting_account = next_account_info(accounts_iter)?;
+        let vesting_config_account = next_account_info(accounts_iter)?;
+        let voter_weight_record_account = next_account_info(accounts_iter)?;
+        let max_voter_weight_record_account = next_account_info(accounts_iter)?;
+
+        let mut vesting_record = get_vesting_record_data(program_id, vesting_account)?;
+        let realm = vesting_record.realm.ok_or(VestingError::VestingIsNotUnderRealm)?;
+
+        let vesting_config = get_vesting_config_data_or_default(
+            program_id,
+            vesting_config_account,
+            &realm,
+            &vesting_record.mint,
+        )?;
+
+        vesting_record.apply_tge_timestamp(vesting_config.tge_timestamp)?;
+
+        let excluded_amount = if vesting_config.count_unvested_only {
+            vesting_record.get_released_amount(Clock::get()?.unix_timestamp as u64)?
+        } else {
+            0
         };
 
-        verify_schedule(&schedules)?;
+        let weighted_amounts = change_voter_weight_record(
+            &realm,
+            &vesting_record.mint,
+            &vesting_record.owner,
+            voter_weight_record_account,
+            program_id,
+            |record| if excluded_amount > vesting_record.excluded_amount {
+                record.exclude_amount(excluded_amount - vesting_record.excluded_amount, &vesting_config)
+            } else {
+                record.include_amount(vesting_record.excluded_amount - excluded_amount, &vesting_config)
+            },
+        )?;
+
+        vesting_record.excluded_amount = excluded_amount;
+        vesting_record.serialize(&mut *vesting_account.data.borrow_mut())?;
 
-        let (vesting_account_key, vesting_account_seed) = Pubkey::find_program_address(&[vesting_token_account.key.as_ref()], program_id);
-        if vesting_account_key != *vesting_account.key {
+        let max_voter_weight = update_max_voter_weight_record(
+            &realm,
+            &vesting_record.mint,
+            max_voter_weight_record_account,
+            &[weighted_amounts],
+            &vesting_config,
+            program_id,
+        )?;
+
+        apply_voter_weight_cap(
+            &[voter_weight_record_account],
+            &vesting_config,
+            max_voter_weight,
+            program_id,
+        )?;
+
+        set_operation_return_data(program_id, 0, Some(voter_weight_record_account))?;
+
+        Ok(())
+    }
+
+    pub fn process_migrate_vesting_record(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        bump_seed: u8,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let system_program_account = next_account_info(accounts_iter)?;
+        let vesting_account = next_account_info(accounts_iter)?;
+        let vesting_owner_account = next_account_info(accounts_iter)?;
+        let payer_account = next_account_info(accounts_iter)?;
+        let realm_account = accounts_iter.next();
+
+        if vesting_account.data.borrow().first() != Some(&(VestingAccountType::VestingRecordV1 as u8)) {
             return Err(VestingError::InvalidVestingAccount.into());
         }
+        let vesting_record_v1 = get_account_data::<VestingRecordV1>(program_id, vesting_account)?;
 
-        // ================== Verify accounts related to the existing vesting =====================
-        let mut vesting_record = get_account_data::<VestingRecord>(program_id, vesting_account)?;
-        let vesting_token_account_data = Account::unpack(&vesting_token_account.data.borrow())?;
+        // The legacy vesting under Realm is pinned to the spl-governance program which owns the Realm
+        let governance = if let Some(expected_realm_account) = vesting_record_v1.realm {
+            let realm_account = realm_account.ok_or(VestingError::MissingRealmAccounts)?;
+            if *realm_account.key != expected_realm_account {
+                return Err(VestingError::InvalidRealmAccount.into());
+            }
+            let realm_data = get_realm_data(realm_account.owner, realm_account)?;
+            realm_data.assert_is_valid_governing_token_mint(&vesting_record_v1.mint)?;
+            Some(*realm_account.owner)
+        } else {
+            None
+        };
+
+        let vesting_record = vesting_record_v1.migrate(*payer_account.key, bump_seed, governance);
+        verify_vesting_account(program_id, &vesting_record, vesting_account, &vesting_record.token)?;
         verify_vesting_owner(&vesting_record, vesting_owner_account)?;
-        verify_vesting_token_account(&vesting_record, vesting_token_account, vesting_token_account_data, vesting_account_key)?;
 
-        // ================== Verify accounts related to new vesting record =======================
-        let new_vesting_token_account_data = Account::unpack(&new_vesting_token_account.data.borrow())?;
-        verify_token_account_owned_by_vesting(new_vesting_account, new_vesting_token_account_data)?;
+        // The payer funds the grown account, so it gets the rent back on Close
+        resize_account(
+            vesting_account,
+            vesting_record.try_to_vec()?.len(),
+            payer_account,
+            system_program_account,
+        )?;
+        vesting_record.serialize(&mut *vesting_account.data.borrow_mut())?;
 
-        let mut total_amount_to_transfer = 0u64;
-        let mut source_schedule_iterator = vesting_record.schedule.iter_mut().rev();
-        let mut source_schedule = source_schedule_iterator.next().ok_or(VestingError::InsufficientFunds)?;
-        for item in (&schedules).into_iter().rev() {
-            let mut rest_amount = item.amount;
-            total_amount_to_transfer = total_amount_to_transfer.checked_add(item.amount)
-                    .ok_or(VestingError::OverflowAmount)?;
-            while rest_amount != 0 {
-                while item.release_time < source_schedule.release_time || source_schedule.amount == 0 {
-                    source_schedule = source_schedule_iterator.next()
-                        .ok_or(VestingError::InsufficientFunds)?;
-                }
-                let available_amount = rest_amount.min(source_schedule.amount);
-                source_schedule.amount -= available_amount;
-                rest_amount -= available_amount;
+        Ok(())
+    }
+
+    pub fn process_migrate_voter_weight_record(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let system_program_account = next_account_info(accounts_iter)?;
+        let voter_weight_record_account = next_account_info(accounts_iter)?;
+        let payer_account = next_account_info(accounts_iter)?;
+
+        if !is_legacy_voter_weight_record(program_id, voter_weight_record_account) {
+            return Err(VestingError::InvalidVoterWeightRecordAccountAddress.into());
+        }
+        let voter_weight_record_v1 = get_account_data::<ExtendedVoterWeightRecordV1>(program_id, voter_weight_record_account)?;
+
+        // The legacy record doesn't store the bump seed, so the canonical one is stored
+        let (voter_weight_record_address, bump_seed) = Pubkey::find_program_address(
+            &get_voter_weight_record_seeds(
+                &voter_weight_record_v1.base.realm,
+                &voter_weight_record_v1.base.governing_token_mint,
+                &voter_weight_record_v1.base.governing_token_owner,
+            ),
+            program_id,
+        );
+        if voter_weight_record_address != *voter_weight_record_account.key {
+            return Err(VestingError::InvalidVoterWeightRecordAccountAddress.into());
+        }
+
+        let voter_weight_record = voter_weight_record_v1.migrate(bump_seed);
+        resize_account(
+            voter_weight_record_account,
+            voter_weight_record.get_max_size().unwrap_or_default(),
+            payer_account,
+            system_program_account,
+        )?;
+        voter_weight_record.serialize(&mut *voter_weight_record_account.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_instruction(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        instruction_data: &[u8],
+    ) -> ProgramResult {
+        msg!("VERSION:{:?}", env!("CARGO_PKG_VERSION"));
+        let instruction: VestingInstruction =
+                try_from_slice_unchecked(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+        msg!("VESTING-INSTRUCTION: {:?}", instruction);
+
+        match instruction {
+            VestingInstruction::Deposit {schedules, label, tge_relative, vesting_token_seed, vesting_bump_seed} => {
+                Self::process_deposit(program_id, accounts, schedules, label, tge_relative, vesting_token_seed, vesting_bump_seed)
+            }
+            VestingInstruction::Withdraw => {
+                Self::process_withdraw(program_id, accounts)
+            }
+            VestingInstruction::ChangeOwner => {
+                Self::process_change_owner(program_id, accounts)
+            }
+            VestingInstruction::CreateVoterWeightRecord => {
+                Self::process_create_voter_weight_record(program_id, accounts)
+            }
+            VestingInstruction::SetVotePercentage {vote_percentage, valid_until} => {
+                Self::process_set_vote_percentage(program_id, accounts, vote_percentage, valid_until)
+            }
+            VestingInstruction::SetVoteAmount {amount} => {
+                Self::process_set_vote_amount(program_id, accounts, amount)
+            }
+            VestingInstruction::Close => {
+                Self::process_close(program_id, accounts)
+            }
+            VestingInstruction::CloseVoterWeightRecord => {
+                Self::process_close_voter_weight_record(program_id, accounts)
+            }
+            VestingInstruction::Split {schedules, label, new_vesting_token_seed, new_vesting_bump_seed} => {
+                Self::process_split(program_id, accounts, schedules, label, new_vesting_token_seed, new_vesting_bump_seed)
+            }
+            VestingInstruction::AttachRealm => {
+                Self::process_attach_realm(program_id, accounts)
+            }
+            VestingInstruction::DetachRealm => {
+                Self::process_detach_realm(program_id, accounts)
+            }
+            VestingInstruction::WithdrawOutOfRealm => {
+                Self::process_withdraw_out_of_realm(program_id, accounts)
+            }
+            VestingInstruction::AccelerateRelease {release_time, include_milestones} => {
+                Self::process_accelerate_release(program_id, accounts, release_time, include_milestones)
+            }
+            VestingInstruction::UpdateVoterWeightRecord {weight_action} => {
+                Self::process_update_voter_weight_record(program_id, accounts, weight_action)
+            }
+            VestingInstruction::SetVestingConfig {weight_curve, max_voter_weight_share, max_voter_weight_source, count_unvested_only, release_authority, snapshot_voting, default_vote_percentage} => {
+                Self::process_set_vesting_config(program_id, accounts, weight_curve, max_voter_weight_share, max_voter_weight_source, count_unvested_only, release_authority, snapshot_voting, default_vote_percentage)
+            }
+            VestingInstruction::RefreshMaxVoterWeight => {
+                Self::process_refresh_max_voter_weight(program_id, accounts)
+            }
+            VestingInstruction::RefreshReleasedAmount => {
+                Self::process_refresh_released_amount(program_id, accounts)
+            }
+            VestingInstruction::CloseMaxVoterWeightRecord => {
+                Self::process_close_max_voter_weight_record(program_id, accounts)
+            }
+            VestingInstruction::SplitPercent {basis_points, new_vesting_token_seed, new_vesting_bump_seed} => {
+                Self::process_split_percent(program_id, accounts, basis_points, new_vesting_token_seed, new_vesting_bump_seed)
+            }
+            VestingInstruction::SplitMany {schedules, new_vesting_token_seeds, new_vesting_bump_seeds} => {
+                Self::process_split_many(program_id, accounts, schedules, new_vesting_token_seeds, new_vesting_bump_seeds)
+            }
+            VestingInstruction::SetLabel {label} => {
+                Self::process_set_label(program_id, accounts, label)
+            }
+            VestingInstruction::CreateVestingTemplate {name, entries} => {
+                Self::process_create_vesting_template(program_id, accounts, name, entries)
+            }
+            VestingInstruction::DepositFromTemplate {start, total, vesting_bump_seed} => {
+                Self::process_deposit_from_template(program_id, accounts, start, total, vesting_bump_seed)
+            }
+            VestingInstruction::SetTgeTimestamp {tge_timestamp} => {
+                Self::process_set_tge_timestamp(program_id, accounts, tge_timestamp)
+            }
+            VestingInstruction::UpdateVestingIndex => {
+                Self::process_update_vesting_index(program_id, accounts)
+            }
+            VestingInstruction::GetVestingStatus => {
+                Self::process_get_vesting_status(program_id, accounts)
+            }
+            VestingInstruction::MigrateVestingRecord {bump_seed} => {
+                Self::process_migrate_vesting_record(program_id, accounts, bump_seed)
+            }
+            VestingInstruction::MigrateVoterWeightRecord => {
+                Self::process_migrate_voter_weight_record(program_id, accounts)
             }
         }
+    }
+}
 
-        vesting_record.serialize(&mut *vesting_account.data.borrow_mut())?;
+fn invoke_transfer_signed<'a>(
+        spl_token_account: &AccountInfo<'a>,
+        source_account: &AccountInfo<'a>,
+        destination_account: &AccountInfo<'a>,
+        authority_account: &AccountInfo<'a>,
+        amount_to_transfer: u64,
+        signers_seeds: &[&[&[u8]]],
+) -> Result<(), ProgramError> {
+    let instruction = spl_token::instruction::transfer(
+        spl_token_account.key,
+        source_account.key,
+        destination_account.key,
+        authority_account.key,
+        &[],
+        amount_to_transfer,
+    )?;
+    invoke_signed(
+        &instruction,
+        &[
+            spl_token_account.clone(),
+            source_account.clone(),
+            destination_account.clone(),
+            authority_account.clone(),
+        ],
+        signers_seeds,
+    )?;
+    Ok(())
+}
+
+/// Moves the schedules taken from the vesting to the new vestings of the new owners
+/// together with the tokens and the voter weight.
+/// The new vestings get the label (or inherit the label of the source vesting).
+/// The new vesting spl-token accounts are created with the seeds (if they are specified)
+fn split_vesting<F>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    splits_count: usize,
+    label: Option<String>,
+    new_vesting_token_seeds: Option<Vec<VestingTokenSeed>>,
+    new_vesting_bump_seeds: Vec<u8>,
+    take_schedules: F,
+) -> ProgramResult
+where F: FnOnce(&mut VestingRecord) -> Result<Vec<Vec<VestingSchedule>>, ProgramError>
+{
+    let (accounts, vesting_index_accounts) = split_vesting_index_accounts(program_id, accounts);
+    let accounts_iter = &mut accounts.iter();
+
+    let system_program_account = next_account_info(accounts_iter)?;
+    let spl_token_account = next_account_info(accounts_iter)?;
+    let vesting_account = next_account_info(accounts_iter)?;
+    let vesting_token_account = next_account_info(accounts_iter)?;
+    let vesting_owner_account = next_account_info(accounts_iter)?;
+    let mut new_vesting_infos = Vec::with_capacity(splits_count);
+    for _ in 0..splits_count {
+        let new_vesting = next_account_info(accounts_iter)?;
+        let new_vesting_token = next_account_info(accounts_iter)?;
+        let new_vesting_owner = next_account_info(accounts_iter)?;
+        new_vesting_infos.push((new_vesting, new_vesting_token, new_vesting_owner,));
+    }
+    let payer_account = next_account_info(accounts_iter)?;
+    let mint_account = if new_vesting_token_seeds.is_some() {
+        Some(next_account_info(accounts_iter)?)
+    } else {
+        None
+    };
+
+    let realm_info = if let Some(governance) = accounts_iter.next() {
+        let realm = next_account_info(accounts_iter)?;
+        let owner_record = next_account_info(accounts_iter)?;
+        let vesting_config = next_account_info(accounts_iter)?;
+        let voter_weight = next_account_info(accounts_iter)?;
+        let mut new_voter_weights = Vec::with_capacity(splits_count);
+        for _ in 0..splits_count {
+            new_voter_weights.push(next_account_info(accounts_iter)?);
+        }
+        let max_voter_weight = next_account_info(accounts_iter)?;
+        let spill = accounts_iter.next();
+        Some((governance, realm, owner_record, vesting_config, voter_weight, new_voter_weights, max_voter_weight, spill,))
+    } else {
+        None
+    };
+
+    // ================== Verify accounts related to the existing vesting =====================
+    let mut vesting_record = get_vesting_record_data(program_id, vesting_account)?;
+    let vesting_account_key = verify_vesting_account(program_id, &vesting_record, vesting_account, vesting_token_account.key)?;
+    let vesting_account_seed = vesting_record.bump_seed;
+    let vesting_token_account_data = Account::unpack(&vesting_token_account.data.borrow())?;
+    verify_vesting_owner(&vesting_record, vesting_owner_account)?;
+    verify_vesting_token_account(&vesting_record, vesting_token_account, vesting_token_account_data, vesting_account_key)?;
+
+    // The new vestings inherit the Realm of the vesting, the realm accounts are validated below
+    if vesting_record.realm.is_none() && realm_info.is_some() {
+        return Err(VestingError::VestingIsNotUnderRealm.into());
+    }
+
+    // ================== Verify accounts related to new vesting records ======================
+    if new_vesting_bump_seeds.len() != splits_count {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    for ((new_vesting_account, new_vesting_token_account, _), new_vesting_bump_seed)
+            in new_vesting_infos.iter().zip(new_vesting_bump_seeds.iter()) {
+        verify_new_vesting_account_address(program_id, new_vesting_account, new_vesting_token_account.key, *new_vesting_bump_seed)?;
+    }
+
+    if let (Some(seeds), Some(mint_account)) = (new_vesting_token_seeds, mint_account) {
+        if seeds.len() != splits_count {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        for ((new_vesting_account, new_vesting_token_account, _), seed) in new_vesting_infos.iter().zip(seeds.iter()) {
+            create_vesting_token_account(
+                program_id,
+                seed,
+                new_vesting_token_account,
+                new_vesting_account.key,
+                mint_account,
+                payer_account,
+                system_program_account,
+                spl_token_account,
+            )?;
+        }
+    }
 
+    let mut new_vesting_mints = Vec::with_capacity(splits_count);
+    for (new_vesting_account, new_vesting_token_account, _) in &new_vesting_infos {
+        let new_vesting_token_account_data = Account::unpack(&new_vesting_token_account.data.borrow())?;
+        verify_token_account_owned_by_vesting(new_vesting_account, new_vesting_token_account_data)?;
+        new_vesting_mints.push(new_vesting_token_account_data.mint);
+    }
+
+    // The new vestings keep the schedule TGE-relative if the TGE timestamp is not set yet
+    if let Some((governance_account, _, _, vesting_config_account, _, _, _, _)) = realm_info.as_ref() {
+        verify_governance_program(&vesting_record, governance_account)?;
+        resolve_tge_relative_schedule(program_id, &mut vesting_record, vesting_config_account)?;
+    }
+
+    let new_schedules = take_schedules(&mut vesting_record)?;
+    let mut new_amounts = Vec::with_capacity(splits_count);
+    for schedules in &new_schedules {
+        new_amounts.push(schedules.iter()
+                .try_fold(0u64, |acc, item| acc.checked_add(item.amount))
+                .ok_or(VestingError::OverflowAmount)?);
+    }
+    let total_amount_to_transfer = new_amounts.iter()
+            .try_fold(0u64, |acc, amount| acc.checked_add(*amount))
+            .ok_or(VestingError::OverflowAmount)?;
+
+    // Released tokens moved to the new vesting are counted toward the voter weight of the new owner
+    let excluded_amount = vesting_record.excluded_amount
+            .min(vesting_record.get_released_amount(Clock::get()?.unix_timestamp as u64)?);
+    let excluded_amount_to_transfer = vesting_record.excluded_amount - excluded_amount;
+    let voting_amount_to_transfer = total_amount_to_transfer
+            .checked_sub(excluded_amount_to_transfer)
+            .ok_or(VestingError::UnderflowAmount)?;
+    vesting_record.excluded_amount = excluded_amount;
+
+    vesting_record.serialize(&mut *vesting_account.data.borrow_mut())?;
+
+    let voter_weight_record_account = realm_info.as_ref().map(|v| v.4);
+
+    for ((((new_vesting_account, new_vesting_token_account, new_vesting_owner_account), schedules), (mint, amount)), new_vesting_bump_seed)
+            in new_vesting_infos.iter().zip(new_schedules).zip(new_vesting_mints.into_iter().zip(new_amounts.iter())).zip(new_vesting_bump_seeds) {
         let new_vesting_record = VestingRecord {
-            account_type: VestingAccountType::VestingRecord,
+            account_type: VestingAccountType::VestingRecordV2,
             owner: *new_vesting_owner_account.key,
-            mint: new_vesting_token_account_data.mint,
+            mint,
             token: *new_vesting_token_account.key,
-            realm: realm_info.map(|v| *v.1.key),
-            schedule: schedules
+            grantor: vesting_record.grantor,
+            rent_payer: *payer_account.key,
+            bump_seed: new_vesting_bump_seed,
+            realm: vesting_record.realm,
+            governance: vesting_record.governance.or_else(|| realm_info.as_ref().map(|v| *v.0.key)),
+            excluded_amount: 0,
+            schedule: schedules,
+            tge_relative: vesting_record.tge_relative,
+            label: label.clone().or_else(|| vesting_record.label.clone()),
         };
-        create_and_serialize_account_signed::<VestingRecord>(
-            payer_account,
+        create_vesting_account(
+            program_id,
             new_vesting_account,
             &new_vesting_record,
-            &[new_vesting_token_account.key.as_ref()],
-            program_id,
+            payer_account,
             system_program_account,
-            &Rent::get()?,
         )?;
 
         invoke_transfer_signed(
@@ -593,137 +2175,385 @@ impl Processor {
             vesting_token_account,
             new_vesting_token_account,
             vesting_account,
-            total_amount_to_transfer,
+            *amount,
             &[&[vesting_token_account.key.as_ref(), &[vesting_account_seed]]]
         )?;
+    }
 
-        if let Some(expected_realm_account) = vesting_record.realm {
-            let (governance_account, 
-                 realm_account,
-                 owner_record_account,
-                 voter_weight_record_account,
-                 new_voter_weight_record_account) = realm_info.ok_or(VestingError::MissingRealmAccounts)?;
-
-            if *realm_account.key != expected_realm_account {
-                return Err(VestingError::InvalidRealmAccount.into())
-            };
-
-            let realm_data = get_realm_data(governance_account.key, realm_account)?;
-            realm_data.assert_is_valid_governing_token_mint(&vesting_record.mint)?;
+    if let Some(expected_realm_account) = vesting_record.realm {
+        let (governance_account, 
+             realm_account,
+             owner_record_account,
+             vesting_config_account,
+             voter_weight_record_account,
+             new_voter_weight_record_accounts,
+             max_voter_weight_record_account,
+             spill_account) = realm_info.ok_or(VestingError::MissingRealmAccounts)?;
+
+        if *realm_account.key != expected_realm_account {
+            return Err(VestingError::InvalidRealmAccount.into())
+        };
 
-            let owner_record_optional_data = get_token_owner_record_data_if_exists(
-                governance_account.key,
-                owner_record_account,
-                &get_token_owner_record_address_seeds(
-                    realm_account.key,
-                    &vesting_record.mint,
-                    vesting_owner_account.key,
-                ),
-            )?;
-            if let Some(owner_record_data) = owner_record_optional_data {
-                owner_record_data.assert_can_withdraw_governing_tokens()?;
-            }
+        let realm_data = get_realm_data(governance_account.key, realm_account)?;
+        realm_data.assert_is_valid_governing_token_mint(&vesting_record.mint)?;
 
-            let mut voter_weight_record = get_voter_weight_record_data_checked(
-                program_id,
-                voter_weight_record_account,
+        let owner_record_optional_data = get_token_owner_record_data_if_exists(
+            governance_account.key,
+            owner_record_account,
+            &get_token_owner_record_address_seeds(
                 realm_account.key,
                 &vesting_record.mint,
-                vesting_owner_account.key)?;
-            voter_weight_record.decrease_total_amount(total_amount_to_transfer)?;
-            voter_weight_record.serialize(&mut *voter_weight_record_account.data.borrow_mut())?;
+                vesting_owner_account.key,
+            ),
+        )?;
+        if let Some(owner_record_data) = owner_record_optional_data {
+            owner_record_data.assert_can_withdraw_governing_tokens()?;
+        }
 
-            create_or_increase_voter_weight_record(
-                &realm_account.key,
+        let vesting_config = get_vesting_config_data_or_default(
+            program_id,
+            vesting_config_account,
+            realm_account.key,
+            &vesting_record.mint,
+        )?;
+
+        let mut weighted_amounts = Vec::with_capacity(splits_count + 1);
+        weighted_amounts.push(decrease_voter_weight_record(
+            realm_account.key,
+            &vesting_record.mint,
+            vesting_owner_account.key,
+            voter_weight_record_account,
+            voting_amount_to_transfer,
+            excluded_amount_to_transfer,
+            &vesting_config,
+            program_id)?);
+
+        for ((_, _, new_vesting_owner_account), (new_voter_weight_record_account, amount))
+                in new_vesting_infos.iter().zip(new_voter_weight_record_accounts.iter().zip(new_amounts.iter())) {
+            weighted_amounts.push(create_or_increase_voter_weight_record(
+                realm_account.key,
                 &vesting_record.mint,
                 new_vesting_owner_account.key,
                 new_voter_weight_record_account,
-                total_amount_to_transfer,
+                *amount,
+                0,
+                &vesting_config,
                 program_id,
                 system_program_account,
-                payer_account)?;
+                payer_account)?);
         }
 
-        Ok(())
+        // Max voter weight is changed for the non-linear weight curves only
+        let max_voter_weight = update_max_voter_weight_record(
+            realm_account.key,
+            &vesting_record.mint,
+            max_voter_weight_record_account,
+            &weighted_amounts,
+            &vesting_config,
+            program_id)?;
+
+        let mut voter_weight_record_accounts = vec![voter_weight_record_account];
+        voter_weight_record_accounts.extend(new_voter_weight_record_accounts.iter());
+        apply_voter_weight_cap(
+            &voter_weight_record_accounts,
+            &vesting_config,
+            max_voter_weight,
+            program_id)?;
+
+        dispose_voter_weight_record_if_empty(
+            voter_weight_record_account,
+            spill_account,
+            program_id)?;
     }
 
-    pub fn process_instruction(
-        program_id: &Pubkey,
-        accounts: &[AccountInfo],
-        instruction_data: &[u8],
-    ) -> ProgramResult {
-        msg!("VERSION:{:?}", env!("CARGO_PKG_VERSION"));
-        let instruction: VestingInstruction =
-                try_from_slice_unchecked(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
-        msg!("VESTING-INSTRUCTION: {:?}", instruction);
+    let new_vestings = new_vesting_infos.iter()
+            .map(|(new_vesting_account, _, new_vesting_owner_account)| (new_vesting_account.key, Some(new_vesting_owner_account.key)))
+            .collect::<Vec<_>>();
+    update_vesting_indexes(program_id, accounts, vesting_index_accounts, &new_vestings)?;
 
-        match instruction {
-            VestingInstruction::Deposit {schedules} => {
-                Self::process_deposit(program_id, accounts, schedules)
-            }
-            VestingInstruction::Withdraw => {
-                Self::process_withdraw(program_id, accounts)
-            }
-            VestingInstruction::ChangeOwner => {
-                Self::process_change_owner(program_id, accounts)
-            }
-            VestingInstruction::CreateVoterWeightRecord => {
-                Self::process_create_voter_weight_record(program_id, accounts)
-            }
-            VestingInstruction::SetVotePercentage {vote_percentage} => {
-                Self::process_set_vote_percentage(program_id, accounts, vote_percentage)
-            }
-            VestingInstruction::Close => {
-                Self::process_close(program_id, accounts)
-            }
-            VestingInstruction::CloseVoterWeightRecord => {
-                Self::process_close_voter_weight_record(program_id, accounts)
-            }
-            VestingInstruction::Split {schedules} => {
-                Self::process_split(program_id, accounts, schedules)
-            }
-        }
+    set_operation_return_data(program_id, total_amount_to_transfer, voter_weight_record_account)?;
+
+    Ok(())
+}
+
+/// Changes the VoterWeightRecord on behalf of the token owner or the governance delegate
+/// and recalculates the voter weight limited by the cap of the Realm
+fn update_voter_weight_record_by_authority<F>(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    update_func: F,
+) -> ProgramResult
+where F: FnOnce(&mut ExtendedVoterWeightRecord, &VestingConfig) -> Result<(), ProgramError>
+{
+    let accounts_iter = &mut accounts.iter();
+
+    let vesting_mint_account = next_account_info(accounts_iter)?;
+    let vesting_owner_account = next_account_info(accounts_iter)?;
+    let vesting_authority_account = next_account_info(accounts_iter)?;
+    let governance_account = next_account_info(accounts_iter)?;
+    let realm_account = next_account_info(accounts_iter)?;
+    let owner_record_account = next_account_info(accounts_iter)?;
+    let voter_weight_record_account = next_account_info(accounts_iter)?;
+    let vesting_config_account = next_account_info(accounts_iter)?;
+    let max_voter_weight_record_account = next_account_info(accounts_iter)?;
+
+    let realm_data = get_realm_data(governance_account.key, realm_account)?;
+    realm_data.assert_is_valid_governing_token_mint(vesting_mint_account.key)?;
+
+    let owner_record_data = get_token_owner_record_data_for_seeds(
+        governance_account.key,
+        owner_record_account,
+        &get_token_owner_record_address_seeds(
+            realm_account.key,
+            vesting_mint_account.key,
+            vesting_owner_account.key,
+        ),
+    )?;
+    owner_record_data.assert_token_owner_or_delegate_is_signer(vesting_authority_account)?;
+
+    let mut voter_weight_record = get_voter_weight_record_data_checked(
+            program_id,
+            voter_weight_record_account,
+            realm_account.key,
+            vesting_mint_account.key,
+            vesting_owner_account.key)?;
+
+    let vesting_config = get_vesting_config_data_or_default(
+        program_id,
+        vesting_config_account,
+        realm_account.key,
+        vesting_mint_account.key,
+    )?;
+    update_func(&mut voter_weight_record, &vesting_config)?;
+
+    if vesting_config.has_voter_weight_cap() {
+        let max_voter_weight_record = get_max_voter_weight_record_data_checked(
+            program_id,
+            max_voter_weight_record_account,
+            realm_account.key,
+            vesting_mint_account.key,
+        )?;
+        voter_weight_record.apply_voter_weight_cap(&vesting_config, max_voter_weight_record.max_voter_weight)?;
     }
+    voter_weight_record.serialize(&mut *voter_weight_record_account.data.borrow_mut())?;
+
+    set_operation_return_data(program_id, 0, Some(voter_weight_record_account))?;
+    Ok(())
 }
 
-fn invoke_transfer_signed<'a>(
-        spl_token_account: &AccountInfo<'a>,
-        source_account: &AccountInfo<'a>,
-        destination_account: &AccountInfo<'a>,
-        authority_account: &AccountInfo<'a>,
-        amount_to_transfer: u64,
-        signers_seeds: &[&[&[u8]]],
+/// Applies the TGE timestamp of the Realm to the TGE-relative schedule of the vesting (if the timestamp is set)
+fn resolve_tge_relative_schedule(
+    program_id: &Pubkey,
+    vesting_record: &mut VestingRecord,
+    vesting_config_info: &AccountInfo,
 ) -> Result<(), ProgramError> {
-    let instruction = spl_token::instruction::transfer(
-        spl_token_account.key,
-        source_account.key,
-        destination_account.key,
-        authority_account.key,
-        &[],
-        amount_to_transfer,
+    if !vesting_record.tge_relative {
+        return Ok(());
+    }
+    let realm = vesting_record.realm.ok_or(VestingError::VestingIsNotUnderRealm)?;
+    let vesting_config = get_vesting_config_data_or_default(
+        program_id,
+        vesting_config_info,
+        &realm,
+        &vesting_record.mint,
     )?;
+    vesting_record.apply_tge_timestamp(vesting_config.tge_timestamp)
+}
+
+/// Sets VestingOperationResult as the return data of the instruction.
+/// The resulting amounts are taken from the VoterWeightRecord of the vesting owner (if any)
+fn set_operation_return_data(
+    program_id: &Pubkey,
+    transferred_amount: u64,
+    voter_weight_record_account: Option<&AccountInfo>,
+) -> Result<(), ProgramError> {
+    let (total_amount, voter_weight) = match voter_weight_record_account {
+        // The record disposed by the instruction had zero total_amount
+        Some(account) if account.lamports() != 0 => {
+            let voter_weight_record = get_voter_weight_record_data(program_id, account)?;
+            (voter_weight_record.total_amount, voter_weight_record.base.voter_weight)
+        },
+        _ => (0, 0),
+    };
+
+    let operation_result = VestingOperationResult {
+        transferred_amount,
+        total_amount,
+        voter_weight,
+    };
+    set_return_data(&operation_result.try_to_vec()?);
+    Ok(())
+}
+
+/// Unlocks the schedules that have reached maturity and returns the released amount
+/// Milestone schedules are unlocked only when their proposal is in the list of succeeded proposals
+fn unlock_matured_schedules(vesting_record: &mut VestingRecord, succeeded_proposals: &[Pubkey]) -> Result<u64, ProgramError> {
+    // Every entry is locked until the TGE timestamp is applied to the schedule
+    if vesting_record.tge_relative {
+        return Ok(0);
+    }
+    let clock = Clock::get()?;
+    let mut total_amount_to_transfer = 0u64;
+    for s in vesting_record.schedule.iter_mut() {
+        if s.is_released(clock.unix_timestamp as u64, succeeded_proposals) {
+            total_amount_to_transfer = total_amount_to_transfer.checked_add(s.amount)
+                    .ok_or(VestingError::OverflowAmount)?;
+            s.amount = 0;
+        }
+    }
+    if total_amount_to_transfer == 0 {
+        return Err(VestingError::NotReachedReleaseTime.into());
+    }
+    Ok(total_amount_to_transfer)
+}
+
+/// Creates the vesting spl-token account (PDA seeds: ['vesting-token', seed]) owned by
+/// the vesting account (PDA seeds: [vesting spl-token account])
+#[allow(clippy::too_many_arguments)]
+fn create_vesting_token_account<'a>(
+    program_id: &Pubkey,
+    vesting_token_seed: &VestingTokenSeed,
+    vesting_token_account: &AccountInfo<'a>,
+    vesting_account_key: &Pubkey,
+    mint_account: &AccountInfo<'a>,
+    payer_account: &AccountInfo<'a>,
+    system_program_account: &AccountInfo<'a>,
+    spl_token_account: &AccountInfo<'a>,
+) -> ProgramResult {
+    let seeds = get_vesting_token_seeds(&vesting_token_seed.seed);
+    let seeds_with_bump: &[&[u8]] = &[seeds[0], seeds[1], &[vesting_token_seed.bump_seed]];
+    // Only the canonical bump seed is accepted: the account is looked up with find_program_address
+    let (vesting_token_account_key, canonical_bump_seed) = Pubkey::find_program_address(&seeds, program_id);
+    if vesting_token_account_key != *vesting_token_account.key || vesting_token_seed.bump_seed != canonical_bump_seed {
+        return Err(VestingError::InvalidVestingTokenAccount.into());
+    }
+
     invoke_signed(
-        &instruction,
+        &system_instruction::create_account(
+            payer_account.key,
+            vesting_token_account.key,
+            Rent::get()?.minimum_balance(Account::LEN),
+            Account::LEN as u64,
+            spl_token_account.key,
+        ),
         &[
+            payer_account.clone(),
+            vesting_token_account.clone(),
+            system_program_account.clone(),
+        ],
+        &[seeds_with_bump],
+    )?;
+
+    invoke(
+        &spl_token::instruction::initialize_account3(
+            spl_token_account.key,
+            vesting_token_account.key,
+            mint_account.key,
+            vesting_account_key,
+        )?,
+        &[
+            vesting_token_account.clone(),
+            mint_account.clone(),
             spl_token_account.clone(),
-            source_account.clone(),
-            destination_account.clone(),
-            authority_account.clone(),
         ],
-        signers_seeds,
     )?;
+
+    Ok(())
+}
+
+/// Creates the vesting account with the bump seed of the VestingRecord
+/// (create_and_serialize_account_signed looks for the bump seed with find_program_address)
+fn create_vesting_account<'a>(
+    program_id: &Pubkey,
+    vesting_account: &AccountInfo<'a>,
+    vesting_record: &VestingRecord,
+    payer_account: &AccountInfo<'a>,
+    system_program_account: &AccountInfo<'a>,
+) -> ProgramResult {
+    let vesting_data = vesting_record.try_to_vec()?;
+    invoke_signed(
+        &system_instruction::create_account(
+            payer_account.key,
+            vesting_account.key,
+            Rent::get()?.minimum_balance(vesting_data.len()),
+            vesting_data.len() as u64,
+            program_id,
+        ),
+        &[
+            payer_account.clone(),
+            vesting_account.clone(),
+            system_program_account.clone(),
+        ],
+        &[&[vesting_record.token.as_ref(), &[vesting_record.bump_seed]]],
+    )?;
+
+    vesting_account.data.borrow_mut().copy_from_slice(&vesting_data);
+    Ok(())
+}
+
+fn resize_account<'a>(
+        account: &AccountInfo<'a>,
+        new_size: usize,
+        payer_account: &AccountInfo<'a>,
+        system_program_account: &AccountInfo<'a>,
+) -> Result<(), ProgramError> {
+    if account.data_len() >= new_size {
+        return Ok(());
+    }
+
+    let required_lamports = Rent::get()?.minimum_balance(new_size).saturating_sub(account.lamports());
+    if required_lamports > 0 {
+        invoke(
+            &system_instruction::transfer(payer_account.key, account.key, required_lamports),
+            &[
+                payer_account.clone(),
+                account.clone(),
+                system_program_account.clone(),
+            ],
+        )?;
+    }
+
+    account.realloc(new_size, false)
+}
+
+/// Shrinks the account to the new size and moves the rent released by the shrink to the refund account
+fn shrink_account(
+        account: &AccountInfo,
+        new_size: usize,
+        refund_account: &AccountInfo,
+) -> Result<(), ProgramError> {
+    if account.data_len() <= new_size {
+        return Ok(());
+    }
+
+    account.realloc(new_size, false)?;
+
+    let excess_lamports = account.lamports().saturating_sub(Rent::get()?.minimum_balance(new_size));
+    if excess_lamports > 0 {
+        let refund_lamports = refund_account.lamports()
+                .checked_add(excess_lamports)
+                .ok_or(VestingError::OverflowAmount)?;
+        **account.lamports.borrow_mut() -= excess_lamports;
+        **refund_account.lamports.borrow_mut() = refund_lamports;
+    }
+
     Ok(())
 }
 
+/// Returns the weighted amount of the VoterWeightRecord before and after the increase
+#[allow(clippy::too_many_arguments)]
 fn create_or_increase_voter_weight_record<'a>(
         realm: &Pubkey, mint: &Pubkey, vesting_owner: &Pubkey,
         voter_weight_record_account: &AccountInfo<'a>,
         total_amount: u64,
+        excluded_amount: u64,
+        vesting_config: &VestingConfig,
         program_id: &Pubkey,
         system_program_account: &AccountInfo<'a>,
         payer_account: &AccountInfo<'a>,
-) -> Result<(), ProgramError> {
+) -> Result<(u64, u64), ProgramError> {
     if voter_weight_record_account.data_is_empty() {
+        let mut new_weighted_amount = 0;
         create_voter_weight_record(
             program_id,
             realm,
@@ -732,31 +2562,97 @@ fn create_or_increase_voter_weight_record<'a>(
             payer_account,
             voter_weight_record_account,
             system_program_account,
-            |record| {record.increase_total_amount(total_amount)},
+            |record| {
+                record.increase_total_amount(total_amount, vesting_config)?;
+                record.increase_excluded_amount(excluded_amount)?;
+                new_weighted_amount = record.weighted_amount;
+                Ok(())
+            },
         )?;
+        Ok((0, new_weighted_amount))
     } else {
-        let mut voter_weight_record = get_voter_weight_record_data_checked(
-                program_id,
-                voter_weight_record_account,
+        increase_voter_weight_record(
                 realm,
                 mint,
-                vesting_owner)?;
-
-        voter_weight_record.increase_total_amount(total_amount)?;
-        voter_weight_record.serialize(&mut *voter_weight_record_account.data.borrow_mut())?;
+                vesting_owner,
+                voter_weight_record_account,
+                total_amount,
+                excluded_amount,
+                vesting_config,
+                program_id)
     }
-    Ok(())
 }
 
-fn create_or_increase_max_voter_weight_record<'a>(
+/// Returns the weighted amount of the VoterWeightRecord before and after the increase
+/// (the excluded amount is the released tokens not counted toward the voter weight)
+#[allow(clippy::too_many_arguments)]
+fn increase_voter_weight_record(
+        realm: &Pubkey, mint: &Pubkey, vesting_owner: &Pubkey,
+        voter_weight_record_account: &AccountInfo,
+        total_amount: u64,
+        excluded_amount: u64,
+        vesting_config: &VestingConfig,
+        program_id: &Pubkey,
+) -> Result<(u64, u64), ProgramError> {
+    change_voter_weight_record(realm, mint, vesting_owner, voter_weight_record_account, program_id, |record| {
+        record.increase_total_amount(total_amount, vesting_config)?;
+        record.increase_excluded_amount(excluded_amount)
+    })
+}
+
+/// Returns the weighted amount of the VoterWeightRecord before and after the decrease
+/// (the excluded amount is the released tokens not counted toward the voter weight)
+#[allow(clippy::too_many_arguments)]
+fn decrease_voter_weight_record(
+        realm: &Pubkey, mint: &Pubkey, vesting_owner: &Pubkey,
+        voter_weight_record_account: &AccountInfo,
+        total_amount: u64,
+        excluded_amount: u64,
+        vesting_config: &VestingConfig,
+        program_id: &Pubkey,
+) -> Result<(u64, u64), ProgramError> {
+    change_voter_weight_record(realm, mint, vesting_owner, voter_weight_record_account, program_id, |record| {
+        record.decrease_total_amount(total_amount, vesting_config)?;
+        record.decrease_excluded_amount(excluded_amount)
+    })
+}
+
+/// Returns the weighted amount of the VoterWeightRecord before and after the change
+fn change_voter_weight_record<F>(
+        realm: &Pubkey, mint: &Pubkey, vesting_owner: &Pubkey,
+        voter_weight_record_account: &AccountInfo,
+        program_id: &Pubkey,
+        change: F,
+) -> Result<(u64, u64), ProgramError>
+where F: FnOnce(&mut ExtendedVoterWeightRecord) -> Result<(), ProgramError>
+{
+    let mut voter_weight_record = get_voter_weight_record_data_checked(
+            program_id,
+            voter_weight_record_account,
+            realm,
+            mint,
+            vesting_owner)?;
+
+    let old_weighted_amount = voter_weight_record.weighted_amount;
+    change(&mut voter_weight_record)?;
+    voter_weight_record.serialize(&mut *voter_weight_record_account.data.borrow_mut())?;
+    Ok((old_weighted_amount, voter_weight_record.weighted_amount))
+}
+
+/// Replaces the contributions of the VoterWeightRecords (old and new weighted amounts) to the max voter weight
+/// Returns the new max voter weight
+#[allow(clippy::too_many_arguments)]
+fn create_or_update_max_voter_weight_record<'a>(
     realm: &Pubkey, mint: &Pubkey,
     max_voter_weight_record_account: &AccountInfo<'a>,
-    total_amount: u64,
+    weighted_amounts: &[(u64, u64)],
+    vesting_config: &VestingConfig,
     program_id: &Pubkey,
     system_program_account: &AccountInfo<'a>,
     payer_account: &AccountInfo<'a>,
-) -> Result<(), ProgramError> {
+) -> Result<u64, ProgramError> {
     if max_voter_weight_record_account.data_is_empty() {
+        let mut max_voter_weight = 0;
         create_max_voter_weight_record(
             program_id,
             realm,
@@ -764,18 +2660,123 @@ fn create_or_increase_max_voter_weight_record<'a>(
             payer_account,
             max_voter_weight_record_account,
             system_program_account,
-            |record| {record.max_voter_weight = total_amount; Ok(())},
+            |record| {
+                for (old_weighted_amount, new_weighted_amount) in weighted_amounts {
+                    update_max_voter_weight(record, *old_weighted_amount, *new_weighted_amount)?;
+                }
+                max_voter_weight = record.max_voter_weight;
+                // The mint supply based max voter weight is valid after RefreshMaxVoterWeight only
+                if !vesting_config.max_voter_weight_source.is_deposits() {
+                    record.max_voter_weight_expiry = Some(Clock::get()?.slot);
+                }
+                Ok(())
+            },
         )?;
+        Ok(max_voter_weight)
     } else {
-        let mut max_voter_weight_record = get_max_voter_weight_record_data_checked(
-                program_id,
-                max_voter_weight_record_account,
-                realm,
-                mint)?;
+        update_max_voter_weight_record(realm, mint, max_voter_weight_record_account, weighted_amounts, vesting_config, program_id)
+    }
+}
+
+/// Replaces the contributions of the VoterWeightRecords (old and new weighted amounts) to the max voter weight
+/// Returns the new max voter weight
+fn update_max_voter_weight_record(
+    realm: &Pubkey, mint: &Pubkey,
+    max_voter_weight_record_account: &AccountInfo,
+    weighted_amounts: &[(u64, u64)],
+    vesting_config: &VestingConfig,
+    program_id: &Pubkey,
+) -> Result<u64, ProgramError> {
+    let mut max_voter_weight_record = get_max_voter_weight_record_data_checked(
+            program_id,
+            max_voter_weight_record_account,
+            realm,
+            mint)?;
+
+    // The mint supply based max voter weight doesn't depend on deposits
+    if !vesting_config.max_voter_weight_source.is_deposits() {
+        return Ok(max_voter_weight_record.max_voter_weight);
+    }
+
+    for (old_weighted_amount, new_weighted_amount) in weighted_amounts {
+        update_max_voter_weight(&mut max_voter_weight_record, *old_weighted_amount, *new_weighted_amount)?;
+    }
+    max_voter_weight_record.serialize(&mut *max_voter_weight_record_account.data.borrow_mut())?;
+    Ok(max_voter_weight_record.max_voter_weight)
+}
+
+/// Splits the trailing VestingIndex accounts off the instruction accounts
+fn split_vesting_index_accounts<'a, 'b>(
+    program_id: &Pubkey,
+    accounts: &'a [AccountInfo<'b>],
+) -> (&'a [AccountInfo<'b>], &'a [AccountInfo<'b>]) {
+    let count = accounts.iter().rev()
+            .take_while(|account| is_vesting_index_account(program_id, account))
+            .count();
+    accounts.split_at(accounts.len() - count)
+}
+
+/// Updates the VestingIndexes with the new owners of the vestings (None for the closed vesting).
+/// The VestingIndex is created by UpdateVestingIndex instruction with the room for MAX_VESTING_INDEX_ENTRIES
+fn update_vesting_indexes(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vesting_index_accounts: &[AccountInfo],
+    vestings: &[(&Pubkey, Option<&Pubkey>)],
+) -> Result<(), ProgramError> {
+    for vesting_index_account in vesting_index_accounts {
+        // The program creates the VestingIndex at the PDA of its owner only
+        let mut vesting_index = get_account_data::<VestingIndex>(program_id, vesting_index_account)?;
+        // The vestings are added to the capped list only if the owner of the index signs the instruction,
+        // the vestings of other owners are removed from any index
+        let is_owner_signer = accounts.iter().any(|account| account.is_signer && *account.key == vesting_index.owner);
+        let mut changed = false;
+        for (vesting, vesting_owner) in vestings {
+            if *vesting_owner == Some(&vesting_index.owner) && !is_owner_signer {
+                continue;
+            }
+            changed |= vesting_index.update(vesting, *vesting_owner)?;
+        }
+        if changed {
+            vesting_index.serialize(&mut *vesting_index_account.data.borrow_mut())?;
+        }
+    }
+    Ok(())
+}
+
+/// Limits the voter weight of the VoterWeightRecords (already checked by the caller) by the cap of the Realm.
+/// Other VoterWeightRecords of the Realm are refreshed by UpdateVoterWeightRecord instruction
+fn apply_voter_weight_cap(
+    voter_weight_record_accounts: &[&AccountInfo],
+    vesting_config: &VestingConfig,
+    max_voter_weight: u64,
+    program_id: &Pubkey,
+) -> Result<(), ProgramError> {
+    if !vesting_config.has_voter_weight_cap() {
+        return Ok(());
+    }
+    for voter_weight_record_account in voter_weight_record_accounts {
+        let mut voter_weight_record = get_voter_weight_record_data(program_id, voter_weight_record_account)?;
+        voter_weight_record.apply_voter_weight_cap(vesting_config, max_voter_weight)?;
+        voter_weight_record.serialize(&mut *voter_weight_record_account.data.borrow_mut())?;
+    }
+    Ok(())
+}
 
-        let max_voter_weight = &mut max_voter_weight_record.max_voter_weight;
-        *max_voter_weight = max_voter_weight.checked_add(total_amount).ok_or(VestingError::OverflowAmount)?;
-        max_voter_weight_record.serialize(&mut *max_voter_weight_record_account.data.borrow_mut())?;
+/// Disposes the VoterWeightRecord (already checked by the caller) left without the tokens of the vestings
+/// (including the released tokens excluded from the voter weight) if the spill account is passed
+fn dispose_voter_weight_record_if_empty(
+    voter_weight_record_account: &AccountInfo,
+    spill_account: Option<&AccountInfo>,
+    program_id: &Pubkey,
+) -> Result<(), ProgramError> {
+    let spill_account = match spill_account {
+        Some(spill_account) => spill_account,
+        None => return Ok(()),
+    };
+    let voter_weight_record = get_voter_weight_record_data(program_id, voter_weight_record_account)?;
+    if voter_weight_record.is_empty() {
+        dispose_account(voter_weight_record_account, spill_account);
     }
     Ok(())
 }
@@ -792,6 +2793,53 @@ fn verify_token_account_owned_by_vesting(vesting_account: &AccountInfo, vesting_
     Ok(())
 }
 
+/// Checks the vesting account address with the bump seed stored in the VestingRecord
+/// (cheaper than find_program_address) and returns the address
+fn verify_vesting_account(
+    program_id: &Pubkey,
+    vesting_record: &VestingRecord,
+    vesting_account: &AccountInfo,
+    vesting_token_account: &Pubkey,
+) -> Result<Pubkey, ProgramError> {
+    verify_vesting_account_address(program_id, vesting_account, vesting_token_account, vesting_record.bump_seed)?;
+    Ok(*vesting_account.key)
+}
+
+/// Checks the address of the vesting account created by the instruction and the passed bump seed.
+/// Only the canonical bump seed is accepted: the vesting is looked up with find_program_address
+fn verify_new_vesting_account_address(
+    program_id: &Pubkey,
+    vesting_account: &AccountInfo,
+    vesting_token_account: &Pubkey,
+    bump_seed: u8,
+) -> Result<(), ProgramError> {
+    let (vesting_account_key, canonical_bump_seed) = Pubkey::find_program_address(
+        &[vesting_token_account.as_ref()],
+        program_id,
+    );
+    if vesting_account_key != *vesting_account.key || bump_seed != canonical_bump_seed {
+        return Err(VestingError::InvalidVestingAccount.into());
+    }
+    Ok(())
+}
+
+/// Checks the vesting account address with the bump seed stored in the VestingRecord
+fn verify_vesting_account_address(
+    program_id: &Pubkey,
+    vesting_account: &AccountInfo,
+    vesting_token_account: &Pubkey,
+    bump_seed: u8,
+) -> Result<(), ProgramError> {
+    let vesting_account_key = Pubkey::create_program_address(
+        &[vesting_token_account.as_ref(), &[bump_seed]],
+        program_id,
+    ).map_err(|_| VestingError::InvalidVestingAccount)?;
+    if vesting_account_key != *vesting_account.key {
+        return Err(VestingError::InvalidVestingAccount.into());
+    }
+    Ok(())
+}
+
 fn verify_vesting_token_account(vesting_record: &VestingRecord, vesting_token_account: &AccountInfo, vesting_token_account_data: Account, vesting_account_key: Pubkey) -> Result<(), ProgramError> {
     if vesting_record.token != *vesting_token_account.key {
         return Err(VestingError::InvalidVestingTokenAccount.into());
@@ -813,15 +2861,34 @@ fn verify_vesting_owner(vesting_record: &VestingRecord, vesting_owner_account: &
     Ok(())
 }
 
+/// Checks the governance account is the spl-governance program pinned in the VestingRecord (if any)
+fn verify_governance_program(vesting_record: &VestingRecord, governance_account: &AccountInfo) -> Result<(), ProgramError> {
+    if vesting_record.governance.map_or(false, |governance| governance != *governance_account.key) {
+        return Err(VestingError::InvalidGovernanceProgram.into());
+    }
+    Ok(())
+}
+
+fn verify_label(label: &Option<String>) -> Result<(), ProgramError> {
+    if label.as_ref().map_or(false, |label| label.len() > MAX_LABEL_LEN) {
+        return Err(VestingError::InvalidLabel.into());
+    }
+    Ok(())
+}
+
+/// Checks the schedule is strictly ordered by the release time.
+/// The milestone entry may follow the entry which is not a milestone with the same release time
 fn verify_schedule(schedule: &[VestingSchedule]) -> Result<(), ProgramError> {
+    let order_key = |item: &VestingSchedule| (item.release_time, item.release_proposal.is_some());
     let mut iterator = schedule.iter();
     if let Some(item) = iterator.next() {
-        let mut release_time = item.release_time;
-        while let Some(item) = iterator.next() {
-            if item.release_time <= release_time {
+        let mut previous_key = order_key(item);
+        for item in iterator {
+            let key = order_key(item);
+            if key <= previous_key {
                 return Err(VestingError::InvalidSchedule.into());
             }
-            release_time = item.release_time;
+            previous_key = key;
         }
     }
     Ok(())
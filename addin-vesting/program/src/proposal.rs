@@ -0,0 +1,57 @@
+use crate::error::VestingError;
+use solana_program::{
+    account_info::AccountInfo,
+    clock::Slot,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use spl_governance::state::{
+    enums::ProposalState,
+    proposal::get_proposal_data,
+};
+
+/// Checks the Proposal account is in Succeeded or Completed state
+pub fn is_proposal_succeeded(
+    governance_program_id: &Pubkey,
+    proposal_info: &AccountInfo,
+) -> Result<bool, ProgramError> {
+    let proposal_data = get_proposal_data(governance_program_id, proposal_info)?;
+
+    Ok(matches!(proposal_data.state, ProposalState::Succeeded | ProposalState::Completed))
+}
+
+/// Returns the keys of the Proposal accounts which are in Succeeded or Completed state
+/// The Proposals must be owned by the governance program pinned in the VestingRecord,
+/// so they are rejected if no governance program is pinned
+pub fn get_succeeded_proposals(
+    governance_program_id: Option<&Pubkey>,
+    proposal_infos: &[&AccountInfo],
+) -> Result<Vec<Pubkey>, ProgramError> {
+    let mut proposals = Vec::new();
+    for proposal_info in proposal_infos {
+        let governance_program_id = governance_program_id.ok_or(VestingError::InvalidGovernanceProgram)?;
+        if proposal_info.owner != governance_program_id {
+            return Err(VestingError::InvalidProposalAccount.into());
+        }
+        if is_proposal_succeeded(governance_program_id, proposal_info)? {
+            proposals.push(*proposal_info.key);
+        }
+    }
+    Ok(proposals)
+}
+
+/// Returns the slot when the Proposal entered Voting state
+/// Checks the Proposal is for the governing token mint
+pub fn get_proposal_voting_at_slot(
+    governance_program_id: &Pubkey,
+    proposal_info: &AccountInfo,
+    mint: &Pubkey,
+) -> Result<Slot, ProgramError> {
+    let proposal_data = get_proposal_data(governance_program_id, proposal_info)?;
+    if proposal_data.governing_token_mint != *mint {
+        return Err(VestingError::InvalidProposalAccount.into());
+    }
+
+    proposal_data.voting_at_slot.ok_or_else(|| VestingError::ProposalIsNotVoting.into())
+}
@@ -0,0 +1,139 @@
+use crate::{
+    config::VestingConfig,
+    error::VestingError,
+};
+use solana_program::{
+    account_info::AccountInfo,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use spl_governance::state::{
+    governance::get_governance_data_for_realm,
+    realm::{RealmV2, get_realm_data},
+    realm_config::{
+        get_realm_config_address,
+        get_realm_config_data_for_realm,
+    },
+};
+
+/// Returns the voter weight addin configured in the Realm for the governing token mint
+/// Checks the Realm account, the RealmConfig account address and the governing token mint
+pub fn get_realm_voter_weight_addin(
+    governance_program_id: &Pubkey,
+    realm_info: &AccountInfo,
+    realm_config_info: &AccountInfo,
+    mint: &Pubkey,
+) -> Result<Option<Pubkey>, ProgramError> {
+    get_voter_weight_addin(governance_program_id, realm_info, realm_config_info, mint, false)
+}
+
+/// Returns the voter weight addin named in the RealmConfig for the governing token mint
+/// including the council voter weight addin which is not in effect yet
+pub fn get_realm_configured_voter_weight_addin(
+    governance_program_id: &Pubkey,
+    realm_info: &AccountInfo,
+    realm_config_info: &AccountInfo,
+    mint: &Pubkey,
+) -> Result<Option<Pubkey>, ProgramError> {
+    get_voter_weight_addin(governance_program_id, realm_info, realm_config_info, mint, true)
+}
+
+fn get_voter_weight_addin(
+    governance_program_id: &Pubkey,
+    realm_info: &AccountInfo,
+    realm_config_info: &AccountInfo,
+    mint: &Pubkey,
+    include_council: bool,
+) -> Result<Option<Pubkey>, ProgramError> {
+    let realm_data = get_realm_data(governance_program_id, realm_info)?;
+    realm_data.assert_is_valid_governing_token_mint(mint)?;
+
+    if get_realm_config_address(governance_program_id, realm_info.key) != *realm_config_info.key {
+        return Err(VestingError::InvalidRealmConfigAccount.into());
+    }
+
+    // RealmConfig account is created by the governance program only when the Realm uses addins
+    if realm_config_info.data_is_empty() {
+        return Ok(None);
+    }
+
+    let realm_config_data = get_realm_config_data_for_realm(
+        governance_program_id,
+        realm_config_info,
+        realm_info.key,
+    )?;
+
+    if realm_data.community_mint == *mint {
+        if realm_data.config.use_community_voter_weight_addin {
+            Ok(realm_config_data.community_voter_weight_addin)
+        } else {
+            Ok(None)
+        }
+    } else if include_council {
+        Ok(realm_config_data.council_voter_weight_addin)
+    } else {
+        // RealmConfig has no use_council_voter_weight_addin flag and the governance program
+        // never takes the council voter weight from the addin, so the configured one is not in effect
+        Ok(None)
+    }
+}
+
+/// Checks the vesting addin is the voter weight addin of the Realm for the governing token mint
+pub fn assert_is_realm_voter_weight_addin(
+    program_id: &Pubkey,
+    governance_program_id: &Pubkey,
+    realm_info: &AccountInfo,
+    realm_config_info: &AccountInfo,
+    mint: &Pubkey,
+) -> Result<(), ProgramError> {
+    let voter_weight_addin = get_realm_voter_weight_addin(
+        governance_program_id,
+        realm_info,
+        realm_config_info,
+        mint,
+    )?;
+
+    if voter_weight_addin != Some(*program_id) {
+        return Err(VestingError::InvalidRealmVoterWeightAddin.into());
+    }
+
+    Ok(())
+}
+
+/// Checks the Realm authority is the signer of the transaction
+pub fn assert_is_realm_authority(
+    realm_data: &RealmV2,
+    realm_authority_info: &AccountInfo,
+) -> Result<(), ProgramError> {
+    if !realm_authority_info.is_signer {
+        return Err(VestingError::MissingRequiredSigner.into());
+    }
+    if realm_data.authority != Some(*realm_authority_info.key) {
+        return Err(VestingError::InvalidRealmAuthority.into());
+    }
+
+    Ok(())
+}
+
+/// Checks the signer is the Realm authority or the release authority named in the VestingConfig.
+/// The release authority must be a Governance account of the Realm (signed by the governance program
+/// when the Proposal transaction is executed)
+pub fn assert_is_realm_release_authority(
+    governance_program_id: &Pubkey,
+    realm_info: &AccountInfo,
+    realm_data: &RealmV2,
+    vesting_config: &VestingConfig,
+    release_authority_info: &AccountInfo,
+) -> Result<(), ProgramError> {
+    if realm_data.authority == Some(*release_authority_info.key) ||
+       vesting_config.release_authority != Some(*release_authority_info.key) {
+        return assert_is_realm_authority(realm_data, release_authority_info);
+    }
+    if !release_authority_info.is_signer {
+        return Err(VestingError::MissingRequiredSigner.into());
+    }
+    get_governance_data_for_realm(governance_program_id, release_authority_info, realm_info.key)?;
+
+    Ok(())
+}
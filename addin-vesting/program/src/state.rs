@@ -1,9 +1,15 @@
 use solana_program::{
+    account_info::AccountInfo,
+    program_error::ProgramError,
     program_pack::IsInitialized,
     pubkey::Pubkey,
 };
+use crate::error::VestingError;
 use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
-use spl_governance_tools::account::AccountMaxSize;
+use spl_governance_tools::account::{AccountMaxSize, get_account_data};
+
+/// Max length (in bytes) of the vesting label
+pub const MAX_LABEL_LEN: usize = 64;
 
 #[repr(C)]
 #[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
@@ -11,14 +17,38 @@ pub enum VestingAccountType {
     /// Default uninitialized state
     Unitialized,
 
-    /// Vesting info account
-    VestingRecord,
+    /// Vesting info account of the legacy layout (VestingRecordV1) to be migrated by MigrateVestingRecord
+    VestingRecordV1,
+
+    /// Vesting addin settings for the Realm
+    VestingConfig,
+
+    /// Shape of the vesting schedule
+    VestingTemplate,
+
+    /// List of the vesting accounts of the owner
+    VestingIndex,
+
+    /// Vesting info account (VestingRecord)
+    VestingRecordV2,
 }
 
 #[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
 pub struct VestingSchedule {
     pub release_time: u64,
     pub amount: u64,
+
+    /// The spl-governance Proposal which must be in Succeeded or Completed state to release the amount
+    /// (milestone release). The release_time is the earliest time of the release in this case.
+    pub release_proposal: Option<Pubkey>,
+}
+
+impl VestingSchedule {
+    /// Returns true if the entry is released by the time (and by the succeeded Proposal for the milestone)
+    pub fn is_released(&self, unix_timestamp: u64, succeeded_proposals: &[Pubkey]) -> bool {
+        self.release_time <= unix_timestamp &&
+            self.release_proposal.map_or(true, |proposal| succeeded_proposals.contains(&proposal))
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
@@ -27,38 +57,376 @@ pub struct VestingRecord {
     pub owner: Pubkey,
     pub mint: Pubkey,
     pub token: Pubkey,
+
+    /// The source token account owner who funded the vesting (inherited by the splitted vestings).
+    /// Stored at the fixed offset 97 to filter the vestings by grantor
+    pub grantor: Pubkey,
+
+    /// The payer of the vesting account rent which receives the lamports on Close
+    pub rent_payer: Pubkey,
+
+    /// Bump seed of the vesting account PDA (seeds: [vesting spl-token account])
+    pub bump_seed: u8,
+
     pub realm: Option<Pubkey>,
+
+    /// The spl-governance program pinned at Deposit (or AttachRealm) which owns the Realm
+    /// and the milestone Proposals. It is kept when the vesting is detached from the Realm
+    pub governance: Option<Pubkey>,
+
+    /// Amount of the released (but not withdrawn) tokens which is not counted toward the voter weight
+    pub excluded_amount: u64,
+
     pub schedule: Vec<VestingSchedule>,
+
+    /// The release times of the schedule are offsets from the TGE timestamp of the VestingConfig.
+    /// The schedule is locked until the TGE timestamp is set and applied to the release times
+    pub tge_relative: bool,
+
+    /// Optional label of the vesting (e.g. "Seed round" or URI) editable by the grantor
+    pub label: Option<String>,
+}
+
+/// Schedule entry of the legacy vesting account
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct VestingScheduleV1 {
+    pub release_time: u64,
+    pub amount: u64,
+}
+
+/// Vesting account created before the grantor, rent payer, bump seed, governance program, excluded amount,
+/// milestone releases, TGE-relative schedule and label were added to VestingRecord.
+/// The instructions reject it until it's migrated by MigrateVestingRecord
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct VestingRecordV1 {
+    pub account_type: VestingAccountType,
+    pub owner: Pubkey,
+    pub mint: Pubkey,
+    pub token: Pubkey,
+    pub realm: Option<Pubkey>,
+    pub schedule: Vec<VestingScheduleV1>,
+}
+
+impl VestingRecordV1 {
+    /// Converts the legacy vesting to VestingRecord. The grantor of the legacy vesting is unknown
+    /// (Pubkey::default()), nothing is excluded from the voter weight and the schedule has no milestones
+    pub fn migrate(self, rent_payer: Pubkey, bump_seed: u8, governance: Option<Pubkey>) -> VestingRecord {
+        VestingRecord {
+            account_type: VestingAccountType::VestingRecordV2,
+            owner: self.owner,
+            mint: self.mint,
+            token: self.token,
+            grantor: Pubkey::default(),
+            rent_payer,
+            bump_seed,
+            realm: self.realm,
+            governance,
+            excluded_amount: 0,
+            schedule: self.schedule.into_iter()
+                    .map(|item| VestingSchedule {
+                        release_time: item.release_time,
+                        amount: item.amount,
+                        release_proposal: None,
+                    })
+                    .collect(),
+            tge_relative: false,
+            label: None,
+        }
+    }
+}
+
+/// Status of the vesting returned by GetVestingStatus instruction (via the return data)
+#[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct VestingStatus {
+    /// Amount of tokens which are not released yet (by the time or by the Proposal)
+    pub locked_amount: u64,
+
+    /// Amount of tokens released by the time (and by the passed succeeded Proposals for the milestones)
+    /// which can be withdrawn now
+    pub claimable_amount: u64,
+
+    /// The earliest release time of the locked schedule entries which are not milestones
+    /// (or the milestones of the passed succeeded Proposals)
+    pub next_release_time: Option<u64>,
+
+    /// Voter weight contributed by the vesting to the VoterWeightRecord of the owner after the weight curve
+    /// and the vote percentage are applied (zero for the vesting without Realm or without the passed record)
+    pub voter_weight: u64,
+}
+
+/// Result of the instructions changing the vestings or the voter weight, returned via the return data:
+/// Deposit, Withdraw, WithdrawOutOfRealm, Split, ChangeOwner, AttachRealm, DetachRealm, RefreshReleasedAmount,
+/// SetVotePercentage, SetVoteAmount and AccelerateRelease (zero amounts: the vestings of several owners are changed)
+#[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct VestingOperationResult {
+    /// Amount of tokens transferred by the instruction (zero for the instructions without a transfer)
+    pub transferred_amount: u64,
+
+    /// total_amount of the ExtendedVoterWeightRecord of the vesting owner after the instruction
+    /// (zero for the vesting without Realm)
+    pub total_amount: u64,
+
+    /// Voter weight of the vesting owner after the instruction (zero for the vesting without Realm)
+    pub voter_weight: u64,
+}
+
+impl VestingRecord {
+    /// Returns the amount released by the time. Milestone schedules are released by the Proposals only
+    pub fn get_released_amount(&self, unix_timestamp: u64) -> Result<u64, ProgramError> {
+        if self.tge_relative {
+            return Ok(0);
+        }
+        self.get_claimable_amount(unix_timestamp, &[])
+    }
+
+    /// Returns the amount released by the time and by the succeeded Proposals of the milestones
+    pub fn get_claimable_amount(&self, unix_timestamp: u64, succeeded_proposals: &[Pubkey]) -> Result<u64, ProgramError> {
+        if self.tge_relative {
+            return Ok(0);
+        }
+        self.schedule.iter()
+            .filter(|s| s.is_released(unix_timestamp, succeeded_proposals))
+            .try_fold(0u64, |acc, s| acc.checked_add(s.amount))
+            .ok_or_else(|| VestingError::OverflowAmount.into())
+    }
+
+    /// Returns the amount counted toward the VoterWeightRecord of the owner (zero for the vesting without Realm)
+    pub fn get_voting_amount(&self) -> Result<u64, ProgramError> {
+        if self.realm.is_none() {
+            return Ok(0);
+        }
+        self.schedule.iter()
+            .try_fold(0u64, |acc, item| acc.checked_add(item.amount))
+            .ok_or(VestingError::OverflowAmount)?
+            .checked_sub(self.excluded_amount)
+            .ok_or_else(|| VestingError::UnderflowAmount.into())
+    }
+
+    /// Returns the status of the vesting by the time and the succeeded Proposals of the milestones.
+    /// The voter weight of the vesting is taken from the VoterWeightRecord by the caller
+    pub fn get_status(&self, unix_timestamp: u64, succeeded_proposals: &[Pubkey]) -> Result<VestingStatus, ProgramError> {
+        let total_amount = self.schedule.iter()
+                .try_fold(0u64, |acc, item| acc.checked_add(item.amount))
+                .ok_or(VestingError::OverflowAmount)?;
+        let claimable_amount = self.get_claimable_amount(unix_timestamp, succeeded_proposals)?;
+        let next_release_time = if self.tge_relative {
+            None
+        } else {
+            self.schedule.iter()
+                .filter(|s| s.amount != 0 && s.release_time > unix_timestamp && s.is_released(u64::MAX, succeeded_proposals))
+                .map(|s| s.release_time)
+                .min()
+        };
+
+        Ok(VestingStatus {
+            locked_amount: total_amount - claimable_amount,
+            claimable_amount,
+            next_release_time,
+            voter_weight: 0,
+        })
+    }
+
+    /// Converts the TGE-relative release times to the absolute ones when the TGE timestamp is set
+    pub fn apply_tge_timestamp(&mut self, tge_timestamp: Option<u64>) -> Result<(), ProgramError> {
+        if let (true, Some(tge_timestamp)) = (self.tge_relative, tge_timestamp) {
+            for item in self.schedule.iter_mut() {
+                item.release_time = item.release_time.checked_add(tge_timestamp).ok_or(VestingError::OverflowAmount)?;
+            }
+            self.tge_relative = false;
+        }
+        Ok(())
+    }
+
+    /// Takes the amounts of the schedules from the latest entries which are not stronger than the schedule items.
+    /// Milestone entries can be taken only to the schedule items with the same proposal
+    pub fn take_schedules(&mut self, schedules: &[VestingSchedule]) -> Result<(), ProgramError> {
+        for item in schedules.iter().rev() {
+            let mut rest_amount = item.amount;
+            for source_schedule in self.schedule.iter_mut().rev() {
+                if rest_amount == 0 {
+                    break;
+                }
+                if item.release_time < source_schedule.release_time ||
+                   (source_schedule.release_proposal.is_some() && source_schedule.release_proposal != item.release_proposal) {
+                    continue;
+                }
+                let available_amount = rest_amount.min(source_schedule.amount);
+                source_schedule.amount -= available_amount;
+                rest_amount -= available_amount;
+            }
+            if rest_amount != 0 {
+                return Err(VestingError::InsufficientFunds.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Takes the share (in basis points) of every entry keeping the release times and returns the taken schedule.
+    /// The rounding dust is taken from the last entry (up to its rest amount)
+    pub fn take_schedule_share(&mut self, basis_points: u16) -> Result<Vec<VestingSchedule>, ProgramError> {
+        if basis_points == 0 || basis_points > 10_000 {
+            return Err(VestingError::InvalidPercentage.into());
+        }
+
+        let total_amount = self.schedule.iter()
+                .try_fold(0u64, |acc, item| acc.checked_add(item.amount))
+                .ok_or(VestingError::OverflowAmount)?;
+        let share_amount = (total_amount as u128 * basis_points as u128 / 10_000) as u64;
+
+        let mut schedules: Vec<VestingSchedule> = Vec::new();
+        let mut taken_amount = 0u64;
+        for item in self.schedule.iter_mut() {
+            let amount = (item.amount as u128 * basis_points as u128 / 10_000) as u64;
+            if amount != 0 {
+                item.amount -= amount;
+                taken_amount += amount;
+                schedules.push(VestingSchedule {amount, ..item.clone()});
+            }
+        }
+
+        // Every entry with a non-zero amount keeps some rest unless the whole amount is taken
+        let dust_amount = share_amount - taken_amount;
+        if let Some(last) = self.schedule.iter_mut().rev().find(|item| item.amount != 0) {
+            let dust_amount = dust_amount.min(last.amount);
+            if dust_amount != 0 {
+                last.amount -= dust_amount;
+                match schedules.last_mut() {
+                    Some(item) if item.release_time == last.release_time && item.release_proposal == last.release_proposal => {
+                        item.amount += dust_amount;
+                    },
+                    _ => schedules.push(VestingSchedule {amount: dust_amount, ..last.clone()}),
+                }
+            }
+        }
+
+        if schedules.is_empty() {
+            return Err(VestingError::InsufficientFunds.into());
+        }
+        Ok(schedules)
+    }
+}
+
+/// The seed of the vesting spl-token account created by Deposit or Split
+/// with the canonical bump seed of the PDA (seeds: ['vesting-token', seed])
+#[derive(Clone, Copy, Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct VestingTokenSeed {
+    pub seed: Pubkey,
+    pub bump_seed: u8,
+}
+
+/// Returns the PDA seeds of the vesting spl-token account created by Deposit or Split
+pub fn get_vesting_token_seeds(seed: &Pubkey) -> [&[u8]; 2] {
+    [b"vesting-token", seed.as_ref()]
+}
+
+/// Returns the address of the vesting spl-token account created by Deposit or Split
+pub fn get_vesting_token_address(program_id: &Pubkey, seed: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&get_vesting_token_seeds(seed), program_id).0
 }
 
 impl IsInitialized for VestingRecord {
     fn is_initialized(&self) -> bool {
-        self.account_type == VestingAccountType::VestingRecord
+        self.account_type == VestingAccountType::VestingRecordV2
     }
 }
 
 impl AccountMaxSize for VestingRecord {}
 
+impl IsInitialized for VestingRecordV1 {
+    fn is_initialized(&self) -> bool {
+        self.account_type == VestingAccountType::VestingRecordV1
+    }
+}
+
+impl AccountMaxSize for VestingRecordV1 {}
+
+/// Deserializes the vesting account. The legacy vesting account (VestingRecordV1)
+/// is rejected with VestingRecordMigrationRequired
+pub fn get_vesting_record_data(program_id: &Pubkey, vesting_account: &AccountInfo) -> Result<VestingRecord, ProgramError> {
+    if vesting_account.owner == program_id &&
+            vesting_account.data.borrow().first() == Some(&(VestingAccountType::VestingRecordV1 as u8)) {
+        return Err(VestingError::VestingRecordMigrationRequired.into());
+    }
+    get_account_data::<VestingRecord>(program_id, vesting_account)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use solana_program::pubkey::Pubkey;
     use solana_program::account_info::AccountInfo;
-    use spl_governance_tools::account::get_account_data;
     use solana_program::clock::Epoch;
 
+    #[test]
+    fn test_take_schedule_share() {
+        let proposal = Pubkey::new_unique();
+        let mut vesting_record = VestingRecord {
+            account_type: VestingAccountType::VestingRecordV2,
+            owner: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            token: Pubkey::new_unique(),
+            grantor: Pubkey::new_unique(),
+            rent_payer: Pubkey::new_unique(),
+            bump_seed: 255,
+            realm: None,
+            governance: None,
+            excluded_amount: 0,
+            schedule: vec!(
+                VestingSchedule {release_time: 10, amount: 0, release_proposal: None},
+                VestingSchedule {release_time: 20, amount: 100, release_proposal: None},
+                VestingSchedule {release_time: 30, amount: 33, release_proposal: Some(proposal)},
+                VestingSchedule {release_time: 40, amount: 1, release_proposal: None},
+            ),
+            tge_relative: false,
+            label: None,
+        };
+
+        assert_eq!(vesting_record.take_schedule_share(0), Err(VestingError::InvalidPercentage.into()));
+        assert_eq!(vesting_record.take_schedule_share(10_001), Err(VestingError::InvalidPercentage.into()));
+
+        // 30% of 134 is 40 with the dust taken from the last entry
+        assert_eq!(
+            vesting_record.take_schedule_share(3_000).unwrap(),
+            vec!(
+                VestingSchedule {release_time: 20, amount: 30, release_proposal: None},
+                VestingSchedule {release_time: 30, amount: 9, release_proposal: Some(proposal)},
+                VestingSchedule {release_time: 40, amount: 1, release_proposal: None},
+            ),
+        );
+        assert_eq!(
+            vesting_record.schedule.iter().map(|item| item.amount).collect::<Vec<_>>(),
+            vec!(0, 70, 24, 0),
+        );
+
+        assert_eq!(
+            vesting_record.take_schedule_share(10_000).unwrap(),
+            vec!(
+                VestingSchedule {release_time: 20, amount: 70, release_proposal: None},
+                VestingSchedule {release_time: 30, amount: 24, release_proposal: Some(proposal)},
+            ),
+        );
+        assert_eq!(vesting_record.take_schedule_share(5_000), Err(VestingError::InsufficientFunds.into()));
+    }
+
     #[test]
     fn test_vesting_record_packing() {
         let vesting_record_source = VestingRecord {
-            account_type: VestingAccountType::VestingRecord,
+            account_type: VestingAccountType::VestingRecordV2,
             owner: Pubkey::new_unique(),
             mint: Pubkey::new_unique(),
             token: Pubkey::new_unique(),
+            grantor: Pubkey::new_unique(),
+            rent_payer: Pubkey::new_unique(),
+            bump_seed: 255,
             realm: Some(Pubkey::new_unique()),
+            governance: Some(Pubkey::new_unique()),
+            excluded_amount: 0,
             schedule: vec!(
-                VestingSchedule {release_time: 30767976, amount: 969},
-                VestingSchedule {release_time: 32767076, amount: 420},
+                VestingSchedule {release_time: 30767976, amount: 969, release_proposal: None},
+                VestingSchedule {release_time: 32767076, amount: 420, release_proposal: Some(Pubkey::new_unique())},
             ),
+            tge_relative: false,
+            label: Some("Seed round".to_string()),
         };
 
         let mut vesting_data = vesting_record_source.try_to_vec().unwrap();
@@ -82,5 +450,168 @@ mod tests {
         );
         let vesting_record_target = get_account_data::<VestingRecord>(&program_id, &account_info).unwrap();
         assert_eq!(vesting_record_source, vesting_record_target);
+
+        assert_eq!(vesting_record_target.get_released_amount(30767975).unwrap(), 0);
+        assert_eq!(vesting_record_target.get_released_amount(u64::MAX).unwrap(), 969);
+    }
+
+    #[test]
+    fn test_vesting_record_v1_migration() {
+        let vesting_record_v1 = VestingRecordV1 {
+            account_type: VestingAccountType::VestingRecordV1,
+            owner: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            token: Pubkey::new_unique(),
+            realm: Some(Pubkey::new_unique()),
+            schedule: vec!(
+                VestingScheduleV1 {release_time: 30767976, amount: 969},
+                VestingScheduleV1 {release_time: 32767076, amount: 420},
+            ),
+        };
+
+        let mut vesting_data = vesting_record_v1.try_to_vec().unwrap();
+
+        let program_id = Pubkey::new_unique();
+
+        let info_key = Pubkey::new_unique();
+        let mut lamports = 10u64;
+
+        let account_info = AccountInfo::new(
+            &info_key,
+            false,
+            false,
+            &mut lamports,
+            &mut vesting_data[..],
+            &program_id,
+            false,
+            Epoch::default(),
+        );
+        assert_eq!(
+            get_vesting_record_data(&program_id, &account_info),
+            Err(VestingError::VestingRecordMigrationRequired.into())
+        );
+        let vesting_record_target = get_account_data::<VestingRecordV1>(&program_id, &account_info).unwrap();
+        assert_eq!(vesting_record_v1, vesting_record_target);
+
+        let rent_payer = Pubkey::new_unique();
+        let governance = Pubkey::new_unique();
+        let vesting_record = vesting_record_target.migrate(rent_payer, 254, Some(governance));
+        assert_eq!(
+            vesting_record,
+            VestingRecord {
+                account_type: VestingAccountType::VestingRecordV2,
+                owner: vesting_record_v1.owner,
+                mint: vesting_record_v1.mint,
+                token: vesting_record_v1.token,
+                grantor: Pubkey::default(),
+                rent_payer,
+                bump_seed: 254,
+                realm: vesting_record_v1.realm,
+                governance: Some(governance),
+                excluded_amount: 0,
+                schedule: vec!(
+                    VestingSchedule {release_time: 30767976, amount: 969, release_proposal: None},
+                    VestingSchedule {release_time: 32767076, amount: 420, release_proposal: None},
+                ),
+                tge_relative: false,
+                label: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_apply_tge_timestamp() {
+        let mut vesting_record = VestingRecord {
+            account_type: VestingAccountType::VestingRecordV2,
+            owner: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            token: Pubkey::new_unique(),
+            grantor: Pubkey::new_unique(),
+            rent_payer: Pubkey::new_unique(),
+            bump_seed: 255,
+            realm: Some(Pubkey::new_unique()),
+            governance: Some(Pubkey::new_unique()),
+            excluded_amount: 0,
+            schedule: vec!(
+                VestingSchedule {release_time: 0, amount: 10, release_proposal: None},
+                VestingSchedule {release_time: 100, amount: 20, release_proposal: None},
+            ),
+            tge_relative: true,
+            label: None,
+        };
+
+        // The schedule is locked until the TGE timestamp is set
+        assert_eq!(vesting_record.get_released_amount(u64::MAX).unwrap(), 0);
+        vesting_record.apply_tge_timestamp(None).unwrap();
+        assert!(vesting_record.tge_relative);
+
+        vesting_record.apply_tge_timestamp(Some(1_000)).unwrap();
+        assert!(!vesting_record.tge_relative);
+        assert_eq!(
+            vesting_record.schedule.iter().map(|item| item.release_time).collect::<Vec<_>>(),
+            vec!(1_000, 1_100),
+        );
+        assert_eq!(vesting_record.get_released_amount(1_000).unwrap(), 10);
+
+        // The absolute release times are not changed anymore
+        vesting_record.apply_tge_timestamp(Some(2_000)).unwrap();
+        assert_eq!(vesting_record.schedule[0].release_time, 1_000);
+    }
+
+    #[test]
+    fn test_get_status() {
+        let proposal = Pubkey::new_unique();
+        let mut vesting_record = VestingRecord {
+            account_type: VestingAccountType::VestingRecordV2,
+            owner: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            token: Pubkey::new_unique(),
+            grantor: Pubkey::new_unique(),
+            rent_payer: Pubkey::new_unique(),
+            bump_seed: 255,
+            realm: Some(Pubkey::new_unique()),
+            governance: Some(Pubkey::new_unique()),
+            excluded_amount: 5,
+            schedule: vec!(
+                VestingSchedule {release_time: 10, amount: 0, release_proposal: None},
+                VestingSchedule {release_time: 20, amount: 10, release_proposal: None},
+                VestingSchedule {release_time: 30, amount: 20, release_proposal: Some(proposal)},
+                VestingSchedule {release_time: 50, amount: 40, release_proposal: None},
+            ),
+            tge_relative: false,
+            label: None,
+        };
+
+        assert_eq!(
+            vesting_record.get_status(5, &[]).unwrap(),
+            VestingStatus {locked_amount: 70, claimable_amount: 0, next_release_time: Some(20), voter_weight: 0},
+        );
+        assert_eq!(vesting_record.get_voting_amount().unwrap(), 65);
+        // Milestone entries are not claimable by the time
+        assert_eq!(
+            vesting_record.get_status(30, &[]).unwrap(),
+            VestingStatus {locked_amount: 60, claimable_amount: 10, next_release_time: Some(50), voter_weight: 0},
+        );
+        // Milestone entries of the succeeded Proposals are claimable since the release time
+        assert_eq!(
+            vesting_record.get_status(25, &[proposal]).unwrap(),
+            VestingStatus {locked_amount: 60, claimable_amount: 10, next_release_time: Some(30), voter_weight: 0},
+        );
+        assert_eq!(
+            vesting_record.get_status(30, &[proposal]).unwrap(),
+            VestingStatus {locked_amount: 40, claimable_amount: 30, next_release_time: Some(50), voter_weight: 0},
+        );
+        assert_eq!(
+            vesting_record.get_status(50, &[proposal]).unwrap(),
+            VestingStatus {locked_amount: 0, claimable_amount: 70, next_release_time: None, voter_weight: 0},
+        );
+
+        vesting_record.realm = None;
+        vesting_record.tge_relative = true;
+        assert_eq!(
+            vesting_record.get_status(50, &[proposal]).unwrap(),
+            VestingStatus {locked_amount: 70, claimable_amount: 0, next_release_time: None, voter_weight: 0},
+        );
+        assert_eq!(vesting_record.get_voting_amount().unwrap(), 0);
     }
 }
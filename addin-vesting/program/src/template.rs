@@ -0,0 +1,178 @@
+use crate::{
+    error::VestingError,
+    state::{VestingAccountType, VestingSchedule},
+};
+use std::convert::TryInto;
+use solana_program::{
+    pubkey::{Pubkey, MAX_SEED_LEN},
+    program_error::ProgramError,
+    program_pack::IsInitialized,
+    account_info::AccountInfo,
+};
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use spl_governance_tools::account::{
+    AccountMaxSize,
+    get_account_data,
+};
+
+/// Maximum number of entries in the vesting template
+pub const MAX_TEMPLATE_ENTRIES: usize = 64;
+
+/// Entry of the vesting template
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct VestingTemplateEntry {
+    /// Offset of the release time from the start of the vesting (in seconds)
+    pub offset: u64,
+
+    /// Share of the total amount released at the offset.
+    /// The denominator is the sum of fractions of all entries
+    pub fraction: u64,
+}
+
+/// VestingTemplate account
+/// Shape of the vesting schedule which is expanded into the concrete schedule by DepositFromTemplate
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct VestingTemplate {
+    pub account_type: VestingAccountType,
+    pub authority: Pubkey,
+    pub name: String,
+    pub entries: Vec<VestingTemplateEntry>,
+}
+
+impl VestingTemplate {
+    /// Checks the name and the entries: offsets are strictly increasing and the sum of fractions is not zero
+    pub fn validate(&self) -> Result<(), ProgramError> {
+        if self.name.is_empty() || self.name.len() > MAX_SEED_LEN {
+            return Err(VestingError::InvalidVestingTemplate.into());
+        }
+        if self.entries.is_empty() || self.entries.len() > MAX_TEMPLATE_ENTRIES {
+            return Err(VestingError::InvalidVestingTemplate.into());
+        }
+        for pair in self.entries.windows(2) {
+            if pair[1].offset <= pair[0].offset {
+                return Err(VestingError::InvalidVestingTemplate.into());
+            }
+        }
+        if self.get_fraction_base()? == 0 {
+            return Err(VestingError::InvalidVestingTemplate.into());
+        }
+        Ok(())
+    }
+
+    /// Returns the sum of fractions of all entries
+    pub fn get_fraction_base(&self) -> Result<u64, ProgramError> {
+        self.entries.iter()
+            .try_fold(0u64, |acc, entry| acc.checked_add(entry.fraction))
+            .ok_or_else(|| VestingError::OverflowAmount.into())
+    }
+
+    /// Expands the template into the schedule of the total amount starting at the start time.
+    /// The amounts are rounded down on the cumulative fractions, so the whole total amount is scheduled
+    pub fn expand(&self, start: u64, total: u64) -> Result<Vec<VestingSchedule>, ProgramError> {
+        let fraction_base = self.get_fraction_base()?;
+        if fraction_base == 0 {
+            return Err(VestingError::InvalidVestingTemplate.into());
+        }
+
+        let mut cumulative_fraction = 0u64;
+        let mut scheduled_amount = 0u64;
+        self.entries.iter()
+            .map(|entry| -> Result<VestingSchedule, ProgramError> {
+                cumulative_fraction += entry.fraction;
+                let cumulative_amount: u64 = (total as u128 * cumulative_fraction as u128 / fraction_base as u128)
+                        .try_into().map_err(|_| VestingError::OverflowAmount)?;
+                let amount = cumulative_amount - scheduled_amount;
+                scheduled_amount = cumulative_amount;
+                Ok(VestingSchedule {
+                    release_time: start.checked_add(entry.offset).ok_or(VestingError::OverflowAmount)?,
+                    amount,
+                    release_proposal: None,
+                })
+            })
+            .collect()
+    }
+}
+
+impl IsInitialized for VestingTemplate {
+    fn is_initialized(&self) -> bool {
+        self.account_type == VestingAccountType::VestingTemplate
+    }
+}
+
+impl AccountMaxSize for VestingTemplate {}
+
+/// Returns VestingTemplate PDA seeds
+pub fn get_vesting_template_seeds<'a>(
+    authority: &'a Pubkey,
+    name: &'a str,
+) -> [&'a [u8]; 3] {
+    [b"vesting-template", authority.as_ref(), name.as_bytes()]
+}
+
+/// Returns VestingTemplate PDA address
+pub fn get_vesting_template_address(program_id: &Pubkey, authority: &Pubkey, name: &str) -> Pubkey {
+    Pubkey::find_program_address(&get_vesting_template_seeds(authority, name), program_id).0
+}
+
+/// Deserializes VestingTemplate account and checks the owner program
+pub fn get_vesting_template_data(
+    program_id: &Pubkey,
+    vesting_template_info: &AccountInfo,
+) -> Result<VestingTemplate, ProgramError> {
+    get_account_data::<VestingTemplate>(program_id, vesting_template_info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_template() {
+        // 25% after 12 months, then monthly for 36 months
+        const MONTH: u64 = 30 * 24 * 3600;
+        let mut entries = vec![VestingTemplateEntry {offset: 12 * MONTH, fraction: 12}];
+        entries.extend((13..=48).map(|month| VestingTemplateEntry {offset: month * MONTH, fraction: 1}));
+        let template = VestingTemplate {
+            account_type: VestingAccountType::VestingTemplate,
+            authority: Pubkey::new_unique(),
+            name: "Advisors 4y/1y cliff".to_string(),
+            entries,
+        };
+        template.validate().unwrap();
+
+        let schedule = template.expand(1_000, 1_000).unwrap();
+        assert_eq!(schedule.len(), 37);
+        assert_eq!(schedule[0], VestingSchedule {release_time: 1_000 + 12 * MONTH, amount: 250, release_proposal: None});
+        assert_eq!(schedule[1].amount, 20);
+        assert_eq!(schedule[2].amount, 21);
+        assert_eq!(schedule[36].release_time, 1_000 + 48 * MONTH);
+        assert_eq!(schedule.iter().map(|item| item.amount).sum::<u64>(), 1_000);
+
+        assert!(template.expand(u64::MAX, 1_000).is_err());
+    }
+
+    #[test]
+    fn test_validate_template() {
+        let template = |entries: Vec<VestingTemplateEntry>| VestingTemplate {
+            account_type: VestingAccountType::VestingTemplate,
+            authority: Pubkey::new_unique(),
+            name: "Seed".to_string(),
+            entries,
+        };
+
+        assert!(template(vec![]).validate().is_err());
+        assert!(template(vec![VestingTemplateEntry {offset: 0, fraction: 0}]).validate().is_err());
+        assert!(template(vec![
+            VestingTemplateEntry {offset: 10, fraction: 1},
+            VestingTemplateEntry {offset: 10, fraction: 1},
+        ]).validate().is_err());
+        assert!(template(vec![
+            VestingTemplateEntry {offset: 10, fraction: u64::MAX},
+            VestingTemplateEntry {offset: 20, fraction: 1},
+        ]).validate().is_err());
+        template(vec![
+            VestingTemplateEntry {offset: 0, fraction: 1},
+            VestingTemplateEntry {offset: 10, fraction: 0},
+        ]).validate().unwrap();
+    }
+}
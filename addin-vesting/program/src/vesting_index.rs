@@ -0,0 +1,154 @@
+use crate::{
+    error::VestingError,
+    state::VestingAccountType,
+};
+use solana_program::{
+    pubkey::Pubkey,
+    program_error::ProgramError,
+    program_pack::IsInitialized,
+    account_info::AccountInfo,
+};
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use spl_governance_tools::account::{
+    AccountMaxSize,
+    get_account_data,
+};
+
+/// Maximum number of the vesting accounts in the VestingIndex
+pub const MAX_VESTING_INDEX_ENTRIES: usize = 64;
+
+/// VestingIndex account
+/// List of the vesting accounts of the owner, so the vestings can be enumerated without scanning
+/// the program accounts. The list is synchronized with the vesting records by UpdateVestingIndex instruction
+/// and by Deposit, ChangeOwner, Split and Close instructions when the VestingIndex is passed to them.
+/// Only the owner adds the vestings, so the third parties can't fill the list
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct VestingIndex {
+    pub account_type: VestingAccountType,
+    pub owner: Pubkey,
+
+    /// The vesting accounts (PDA seeds: [vesting spl-token account]) of the owner
+    /// (up to MAX_VESTING_INDEX_ENTRIES)
+    pub vestings: Vec<Pubkey>,
+}
+
+impl VestingIndex {
+    /// Returns the empty index of the owner
+    pub fn new(owner: &Pubkey) -> Self {
+        Self {
+            account_type: VestingAccountType::VestingIndex,
+            owner: *owner,
+            vestings: Vec::new(),
+        }
+    }
+
+    /// Adds the vesting account owned by the index owner or removes the vesting account
+    /// owned by somebody else (or closed). Returns true if the list is changed
+    pub fn update(&mut self, vesting: &Pubkey, vesting_owner: Option<&Pubkey>) -> Result<bool, ProgramError> {
+        let position = self.vestings.iter().position(|item| item == vesting);
+        match (position, vesting_owner == Some(&self.owner)) {
+            (None, true) => {
+                if self.vestings.len() >= MAX_VESTING_INDEX_ENTRIES {
+                    return Err(VestingError::VestingIndexIsFull.into());
+                }
+                self.vestings.push(*vesting);
+                Ok(true)
+            },
+            (Some(position), false) => {
+                self.vestings.remove(position);
+                Ok(true)
+            },
+            _ => Ok(false),
+        }
+    }
+}
+
+impl IsInitialized for VestingIndex {
+    fn is_initialized(&self) -> bool {
+        self.account_type == VestingAccountType::VestingIndex
+    }
+}
+
+impl AccountMaxSize for VestingIndex {
+    fn get_max_size(&self) -> Option<usize> {
+        Some(1 + 32 + 4 + 32 * MAX_VESTING_INDEX_ENTRIES)
+    }
+}
+
+/// Returns true if the account is the VestingIndex created by the program
+pub fn is_vesting_index_account(program_id: &Pubkey, account_info: &AccountInfo) -> bool {
+    account_info.owner == program_id &&
+        account_info.data.borrow().first() == Some(&(VestingAccountType::VestingIndex as u8))
+}
+
+/// Returns VestingIndex PDA seeds
+pub fn get_vesting_index_seeds(owner: &Pubkey) -> [&[u8]; 2] {
+    [b"vesting-index", owner.as_ref()]
+}
+
+/// Returns VestingIndex PDA address
+pub fn get_vesting_index_address(program_id: &Pubkey, owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&get_vesting_index_seeds(owner), program_id).0
+}
+
+/// Deserializes VestingIndex account and checks the address and owner program
+/// Returns the empty index if the account is not created
+pub fn get_vesting_index_data_or_default(
+    program_id: &Pubkey,
+    vesting_index_info: &AccountInfo,
+    owner: &Pubkey,
+) -> Result<VestingIndex, ProgramError> {
+    if get_vesting_index_address(program_id, owner) != *vesting_index_info.key {
+        return Err(VestingError::InvalidVestingIndexAccount.into());
+    }
+
+    if vesting_index_info.data_is_empty() {
+        return Ok(VestingIndex::new(owner));
+    }
+
+    get_account_data::<VestingIndex>(program_id, vesting_index_info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_vesting_index() {
+        let owner = Pubkey::new_unique();
+        let other_owner = Pubkey::new_unique();
+        let vesting1 = Pubkey::new_unique();
+        let vesting2 = Pubkey::new_unique();
+        let mut vesting_index = VestingIndex::new(&owner);
+
+        assert!(vesting_index.update(&vesting1, Some(&owner)).unwrap());
+        assert!(!vesting_index.update(&vesting1, Some(&owner)).unwrap());
+        assert!(!vesting_index.update(&vesting2, Some(&other_owner)).unwrap());
+        assert!(vesting_index.update(&vesting2, Some(&owner)).unwrap());
+        assert_eq!(vesting_index.vestings, vec![vesting1, vesting2]);
+
+        // The vesting is moved to another owner or closed
+        assert!(vesting_index.update(&vesting1, Some(&other_owner)).unwrap());
+        assert!(vesting_index.update(&vesting2, None).unwrap());
+        assert!(vesting_index.vestings.is_empty());
+    }
+
+    #[test]
+    fn test_vesting_index_is_full() {
+        let owner = Pubkey::new_unique();
+        let mut vesting_index = VestingIndex::new(&owner);
+        for _ in 0..MAX_VESTING_INDEX_ENTRIES {
+            assert!(vesting_index.update(&Pubkey::new_unique(), Some(&owner)).unwrap());
+        }
+        assert_eq!(
+            vesting_index.update(&Pubkey::new_unique(), Some(&owner)),
+            Err(VestingError::VestingIndexIsFull.into())
+        );
+
+        assert_eq!(Some(vesting_index.try_to_vec().unwrap().len()), vesting_index.get_max_size());
+
+        // The vestings can still be removed from the full index
+        let vesting = vesting_index.vestings[0];
+        assert!(vesting_index.update(&vesting, None).unwrap());
+    }
+}
@@ -1,6 +1,10 @@
-use crate::error::VestingError;
+use crate::{
+    error::VestingError,
+    config::VestingConfig,
+};
 use std::convert::TryInto;
 use solana_program::{
+    clock::{Clock, Slot},
     pubkey::Pubkey,
     program_error::ProgramError,
     program_pack::IsInitialized,
@@ -15,7 +19,20 @@ use spl_governance_tools::account::{
     get_account_data,
 };
 
-pub use spl_governance_addin_api::voter_weight::VoterWeightRecord;
+pub use spl_governance_addin_api::voter_weight::{VoterWeightAction, VoterWeightRecord};
+
+/// Number of the voter weight checkpoints stored in ExtendedVoterWeightRecord
+pub const VOTER_WEIGHT_CHECKPOINTS: usize = 8;
+
+/// Vote percentage of the new ExtendedVoterWeightRecord and the default vote percentage of the Realm
+pub const DEFAULT_VOTE_PERCENTAGE: u16 = 10_000;
+
+/// Voter weight of the token owner starting from the slot
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct VoterWeightCheckpoint {
+    pub slot: Slot,
+    pub voter_weight: u64,
+}
 
 /// ExtendedVoterWeightRecord account
 /// The account is used as an api interface to provide voting power to the governance program
@@ -24,7 +41,7 @@ pub use spl_governance_addin_api::voter_weight::VoterWeightRecord;
 pub struct ExtendedVoterWeightRecord {
     pub base: VoterWeightRecord,
 
-    /// ExtendedVoterWeightRecord discriminator sha256("account:ExtendedVoterWeightRecord")[..8]
+    /// ExtendedVoterWeightRecord discriminator sha256("account:ExtendedVoterWeightRecordV2")[..8]
     /// Note: The discriminator size must match the addin implementing program discriminator size
     /// to ensure it's stored in the private space of the account data and it's unique
     pub account_discriminator: [u8; 8],
@@ -32,56 +49,334 @@ pub struct ExtendedVoterWeightRecord {
     /// Total number of tokens owned by the account
     pub total_amount: u64,
 
+    /// Number of the released tokens excluded from total_amount (VestingConfig.count_unvested_only)
+    /// until they are withdrawn. The record is in use by the vestings until both amounts are zero
+    pub excluded_amount: u64,
+
+    /// Total number of tokens converted by the weight curve of the Realm.
+    /// The record contributes this value to MaxVoterWeightRecord
+    pub weighted_amount: u64,
+
     /// Percentage of the total number of tokens for calculating the voting weight
     /// (in hundredths of a percent)
     pub vote_percentage: u16,
+
+    /// The last slot when vote_percentage is valid.
+    /// The default vote percentage of the Realm (VestingConfig) is used after the slot
+    pub vote_percentage_expiry: Option<Slot>,
+
+    /// Number of tokens for calculating the voting weight instead of vote_percentage
+    /// (clamped to total_amount)
+    pub vote_amount: Option<u64>,
+
+    /// The latest changes of the voter weight ordered by slot (the oldest first).
+    /// Unused entries are zeroed, which means zero voter weight before the first known change
+    pub checkpoints: [VoterWeightCheckpoint; VOTER_WEIGHT_CHECKPOINTS],
+
+    /// Bump seed of the record PDA (seeds: ['voter-weight-record', realm, token_mint, token_owner])
+    pub bump_seed: u8,
 }
 
 impl ExtendedVoterWeightRecord {
-    /// sha256("account:ExtendedVoterWeightRecord")[..8]
-    pub const ACCOUNT_DISCRIMINATOR: [u8; 8] = [0x49, 0x6b, 0x79, 0x9a, 0xfd, 0x90, 0x5d, 0xe7];
+    /// sha256("account:ExtendedVoterWeightRecordV2")[..8]
+    pub const ACCOUNT_DISCRIMINATOR: [u8; 8] = [0x87, 0x4e, 0x42, 0x5b, 0x79, 0x19, 0x7f, 0x3d];
 
-    fn recalculate_voter_weight(&mut self) -> Result<(), ProgramError> {
-        let voter_weight = (self.total_amount as u128)
+    fn get_uncapped_voter_weight(&self) -> Result<u64, ProgramError> {
+        let voter_weight = if let Some(vote_amount) = self.vote_amount {
+            // The share of the weighted amount which is exactly vote_amount for the linear weight curve
+            if self.total_amount == 0 {
+                0
+            } else {
+                (self.weighted_amount as u128)
+                    .checked_mul(vote_amount.into()).ok_or(VestingError::OverflowAmount)?
+                    .checked_div(self.total_amount.into()).ok_or(VestingError::OverflowAmount)?
+            }
+        } else {
+            (self.weighted_amount as u128)
                 .checked_mul(self.vote_percentage.into()).ok_or(VestingError::OverflowAmount)?
-                .checked_div(10000).ok_or(VestingError::OverflowAmount)?;
-        self.base.voter_weight = voter_weight.try_into().map_err(|_| VestingError::OverflowAmount)?;
+                .checked_div(10000).ok_or(VestingError::OverflowAmount)?
+        };
+        voter_weight.try_into().map_err(|_| VestingError::OverflowAmount.into())
+    }
+
+    fn recalculate_voter_weight(&mut self, vesting_config: &VestingConfig) -> Result<(), ProgramError> {
+        let slot = Clock::get()?.slot;
+        // The uncapped voter weight of the default vote percentage is valid since the expiry,
+        // the capped one depends on the max voter weight and is recorded in the current slot
+        let since = self.reset_expired_vote_percentage(slot, vesting_config)
+                .filter(|_| !vesting_config.has_voter_weight_cap())
+                .unwrap_or(slot);
+        let voter_weight = self.get_uncapped_voter_weight()?;
+        self.set_voter_weight(voter_weight, since, vesting_config)
+    }
+
+    /// Reverts the expired vote_percentage to the default vote percentage of the Realm.
+    /// Returns the first slot after the expiry
+    fn reset_expired_vote_percentage(&mut self, slot: Slot, vesting_config: &VestingConfig) -> Option<Slot> {
+        let expiry = self.vote_percentage_expiry.filter(|expiry| *expiry < slot)?;
+        self.vote_percentage = vesting_config.default_vote_percentage;
+        self.vote_percentage_expiry = None;
+        Some(expiry + 1)
+    }
+
+    fn set_voter_weight(&mut self, voter_weight: u64, since: Slot, vesting_config: &VestingConfig) -> Result<(), ProgramError> {
+        self.base.voter_weight = voter_weight;
+        // The voter weight of the temporary vote percentage is not valid after the vote percentage
+        self.base.voter_weight_expiry = self.vote_percentage_expiry;
+        if vesting_config.snapshot_voting {
+            // The voter weight is usable only after UpdateVoterWeightRecord reports it for the action:
+            // CastVote over the default target doesn't match any Proposal
+            self.base.weight_action = Some(VoterWeightAction::CastVote);
+            self.base.weight_action_target = Some(Pubkey::default());
+        } else {
+            self.base.weight_action = None;
+            self.base.weight_action_target = None;
+        }
+        self.add_checkpoint(since, self.base.voter_weight);
+        Ok(())
+    }
+
+    /// Add checkpoint for the voter weight. When the history is full the adjacent checkpoints with
+    /// the closest voter weights are merged (with the lower voter weight), so the small changes
+    /// (e.g. dust deposits of third parties) can't evict the significant changes from the history
+    fn add_checkpoint(&mut self, slot: Slot, voter_weight: u64) {
+        let last = &mut self.checkpoints[VOTER_WEIGHT_CHECKPOINTS - 1];
+        if last.voter_weight == voter_weight {
+            return;
+        }
+        if last.slot == slot {
+            last.voter_weight = voter_weight;
+            return;
+        }
+        let mut history = self.checkpoints.to_vec();
+        history.push(VoterWeightCheckpoint {slot, voter_weight});
+        let weight_change = |index: usize| {
+            let (prev, next) = (history[index].voter_weight, history[index + 1].voter_weight);
+            prev.max(next) - prev.min(next)
+        };
+        let merged = (0..VOTER_WEIGHT_CHECKPOINTS).min_by_key(|index| weight_change(*index)).unwrap_or(0);
+        history[merged].voter_weight = history[merged].voter_weight.min(history[merged + 1].voter_weight);
+        history.remove(merged + 1);
+        self.checkpoints.copy_from_slice(&history);
+    }
+
+    /// Returns voter weight before the slot (the changes made in the slot are not included).
+    /// Fails if the slot is older than the stored history
+    pub fn get_voter_weight_at(&self, slot: Slot) -> Result<u64, ProgramError> {
+        self.checkpoints.iter().rev()
+            .find(|checkpoint| checkpoint.slot < slot)
+            .map(|checkpoint| checkpoint.voter_weight)
+            .ok_or_else(|| VestingError::VoterWeightHistoryUnavailable.into())
+    }
+
+    /// Report voter weight before the slot for the action over the target (valid in the current slot only)
+    pub fn set_voter_weight_snapshot(
+        &mut self,
+        slot: Slot,
+        weight_action: VoterWeightAction,
+        weight_action_target: Pubkey,
+    ) -> Result<(), ProgramError> {
+        self.base.voter_weight = self.get_voter_weight_at(slot)?;
+        self.base.voter_weight_expiry = Some(Clock::get()?.slot);
+        self.base.weight_action = Some(weight_action);
+        self.base.weight_action_target = Some(weight_action_target);
+        Ok(())
+    }
+
+    /// Report current voter weight for the action (valid in the current slot only)
+    pub fn set_voter_weight_action(&mut self, weight_action: VoterWeightAction) -> Result<(), ProgramError> {
+        self.base.voter_weight_expiry = Some(Clock::get()?.slot);
+        self.base.weight_action = Some(weight_action);
+        self.base.weight_action_target = None;
         Ok(())
     }
 
+    /// Convert total_amount by the weight curve and recalculate current voter_weight
+    /// (valid until the expiry of the temporary vote percentage)
+    pub fn recalculate_weighted_amount(&mut self, vesting_config: &VestingConfig) -> Result<(), ProgramError> {
+        self.weighted_amount = vesting_config.weight_curve.get_weight(self.total_amount)?;
+        self.vote_amount = self.vote_amount.map(|vote_amount| vote_amount.min(self.total_amount));
+        self.recalculate_voter_weight(vesting_config)
+    }
+
     /// Increase total_amount to specified value and recalculate current voter_weight
-    pub fn increase_total_amount(&mut self, value: u64) -> Result<(), ProgramError> {
+    pub fn increase_total_amount(&mut self, value: u64, vesting_config: &VestingConfig) -> Result<(), ProgramError> {
         self.total_amount = self.total_amount.checked_add(value).ok_or(VestingError::OverflowAmount)?;
-        self.recalculate_voter_weight()?;
+        self.recalculate_weighted_amount(vesting_config)?;
         Ok(())
     }
 
     /// Decrease total_amount to specified value and recalculate current voter_weight
-    pub fn decrease_total_amount(&mut self, value: u64) -> Result<(), ProgramError> {
+    pub fn decrease_total_amount(&mut self, value: u64, vesting_config: &VestingConfig) -> Result<(), ProgramError> {
         self.total_amount = self.total_amount.checked_sub(value).ok_or(VestingError::UnderflowAmount)?;
-        self.recalculate_voter_weight()?;
+        self.recalculate_weighted_amount(vesting_config)?;
+        Ok(())
+    }
+
+    /// Move the released tokens from total_amount to excluded_amount and recalculate current voter_weight
+    pub fn exclude_amount(&mut self, value: u64, vesting_config: &VestingConfig) -> Result<(), ProgramError> {
+        self.excluded_amount = self.excluded_amount.checked_add(value).ok_or(VestingError::OverflowAmount)?;
+        self.decrease_total_amount(value, vesting_config)
+    }
+
+    /// Move the tokens from excluded_amount back to total_amount and recalculate current voter_weight
+    pub fn include_amount(&mut self, value: u64, vesting_config: &VestingConfig) -> Result<(), ProgramError> {
+        self.excluded_amount = self.excluded_amount.checked_sub(value).ok_or(VestingError::UnderflowAmount)?;
+        self.increase_total_amount(value, vesting_config)
+    }
+
+    /// Increase excluded_amount by the released tokens moved together with the vesting
+    pub fn increase_excluded_amount(&mut self, value: u64) -> Result<(), ProgramError> {
+        self.excluded_amount = self.excluded_amount.checked_add(value).ok_or(VestingError::OverflowAmount)?;
+        Ok(())
+    }
+
+    /// Decrease excluded_amount by the released tokens withdrawn or moved together with the vesting
+    pub fn decrease_excluded_amount(&mut self, value: u64) -> Result<(), ProgramError> {
+        self.excluded_amount = self.excluded_amount.checked_sub(value).ok_or(VestingError::UnderflowAmount)?;
         Ok(())
     }
 
-    /// Set new value for vote_percentage and recalculate current voter_weight
-    pub fn set_vote_percentage(&mut self, value: u16) -> Result<(), ProgramError> {
+    /// Returns true if none of the tokens of the vestings is counted by the record
+    pub fn is_empty(&self) -> bool {
+        self.total_amount == 0 && self.excluded_amount == 0
+    }
+
+    /// Returns the part of the current voter weight (after the weight curve and the vote percentage,
+    /// without the cap) contributed by the amount of the vesting counted in total_amount
+    pub fn get_voter_weight_share(&self, amount: u64, slot: Slot, vesting_config: &VestingConfig) -> Result<u64, ProgramError> {
+        if self.total_amount == 0 {
+            return Ok(0);
+        }
+        // The record is not converted by the current weight curve until it's changed
+        let mut record = self.clone();
+        record.weighted_amount = vesting_config.weight_curve.get_weight(record.total_amount)?;
+        record.reset_expired_vote_percentage(slot, vesting_config);
+        let voter_weight = (record.get_uncapped_voter_weight()? as u128)
+                .checked_mul(amount.into()).ok_or(VestingError::OverflowAmount)?
+                .checked_div(record.total_amount.into()).ok_or(VestingError::OverflowAmount)?;
+        voter_weight.try_into().map_err(|_| VestingError::OverflowAmount.into())
+    }
+
+    /// Recalculate current voter_weight limited by the cap of the Realm for the max voter weight
+    pub fn apply_voter_weight_cap(&mut self, vesting_config: &VestingConfig, max_voter_weight: u64) -> Result<(), ProgramError> {
+        let slot = Clock::get()?.slot;
+        self.reset_expired_vote_percentage(slot, vesting_config);
+        let voter_weight = self.get_uncapped_voter_weight()?
+                .min(vesting_config.get_voter_weight_cap(max_voter_weight)?);
+        self.set_voter_weight(voter_weight, slot, vesting_config)
+    }
+
+    /// Set new value for vote_percentage valid until the slot (inclusive) and recalculate current voter_weight
+    pub fn set_vote_percentage(
+        &mut self,
+        value: u16,
+        valid_until: Option<Slot>,
+        vesting_config: &VestingConfig,
+    ) -> Result<(), ProgramError> {
         if value > 10000 {
             return Err(VestingError::InvalidPercentage.into());
         }
+        let current_slot = Clock::get()?.slot;
+        if valid_until.map_or(false, |slot| slot < current_slot) {
+            return Err(VestingError::InvalidVotePercentageExpiry.into());
+        }
+        // Record the expiration of the previous vote percentage
+        self.recalculate_voter_weight(vesting_config)?;
         self.vote_percentage = value;
-        self.recalculate_voter_weight()?;
+        self.vote_percentage_expiry = valid_until;
+        self.vote_amount = None;
+        self.recalculate_voter_weight(vesting_config)?;
+        Ok(())
+    }
+
+    /// Set the number of tokens to vote with (clamped to total_amount) and recalculate current voter_weight.
+    /// The vote_percentage is reset to the default value of the Realm
+    pub fn set_vote_amount(&mut self, value: u64, vesting_config: &VestingConfig) -> Result<(), ProgramError> {
+        // Record the expiration of the previous vote percentage
+        self.recalculate_voter_weight(vesting_config)?;
+        self.vote_amount = Some(value.min(self.total_amount));
+        self.vote_percentage = vesting_config.default_vote_percentage;
+        self.vote_percentage_expiry = None;
+        self.recalculate_voter_weight(vesting_config)?;
         Ok(())
     }
 }
 
-impl AccountMaxSize for ExtendedVoterWeightRecord {}
+impl AccountMaxSize for ExtendedVoterWeightRecord {
+    fn get_max_size(&self) -> Option<usize> {
+        Some(345)   // with all optional fields set
+    }
+}
 
 impl IsInitialized for ExtendedVoterWeightRecord {
     fn is_initialized(&self) -> bool {
         self.account_discriminator == ExtendedVoterWeightRecord::ACCOUNT_DISCRIMINATOR
+    }
+}
+
+/// ExtendedVoterWeightRecord account created before the weight curve, temporary vote percentage,
+/// vote amount, voter weight checkpoints and bump seed were added.
+/// The instructions reject it until it's migrated by MigrateVoterWeightRecord
+#[derive(Clone, Debug, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct ExtendedVoterWeightRecordV1 {
+    pub base: VoterWeightRecord,
+    pub account_discriminator: [u8; 8],
+    pub total_amount: u64,
+    pub vote_percentage: u16,
+}
+
+impl ExtendedVoterWeightRecordV1 {
+    /// sha256("account:ExtendedVoterWeightRecord")[..8]
+    pub const ACCOUNT_DISCRIMINATOR: [u8; 8] = [0x49, 0x6b, 0x79, 0x9a, 0xfd, 0x90, 0x5d, 0xe7];
+
+    /// Returns true for the discriminator of the legacy record
+    pub fn is_account_discriminator(account_discriminator: &[u8; 8]) -> bool {
+        *account_discriminator == ExtendedVoterWeightRecordV1::ACCOUNT_DISCRIMINATOR
             // Check for legacy discriminator which is not compatible with Anchor but is used by older plugins
-            || self.account_discriminator == *b"496b799a"
+            || *account_discriminator == *b"496b799a"
     }
+
+    /// Converts the legacy record to ExtendedVoterWeightRecord. The legacy record counts the total amount
+    /// linearly, so it's the weighted amount until the record is updated by the weight curve of the Realm.
+    /// The current voter weight starts the history of the voter weight
+    pub fn migrate(self, bump_seed: u8) -> ExtendedVoterWeightRecord {
+        let voter_weight = self.base.voter_weight;
+        ExtendedVoterWeightRecord {
+            base: self.base,
+            account_discriminator: ExtendedVoterWeightRecord::ACCOUNT_DISCRIMINATOR,
+            total_amount: self.total_amount,
+            excluded_amount: 0,
+            weighted_amount: self.total_amount,
+            vote_percentage: self.vote_percentage,
+            vote_percentage_expiry: None,
+            vote_amount: None,
+            checkpoints: [VoterWeightCheckpoint {slot: 0, voter_weight}; VOTER_WEIGHT_CHECKPOINTS],
+            bump_seed,
+        }
+    }
+}
+
+impl AccountMaxSize for ExtendedVoterWeightRecordV1 {}
+
+impl IsInitialized for ExtendedVoterWeightRecordV1 {
+    fn is_initialized(&self) -> bool {
+        ExtendedVoterWeightRecordV1::is_account_discriminator(&self.account_discriminator)
+    }
+}
+
+/// Returns the discriminator of ExtendedVoterWeightRecord stored after the (variable-size) VoterWeightRecord
+fn get_extended_account_discriminator(data: &[u8]) -> Option<[u8; 8]> {
+    let mut data = data;
+    VoterWeightRecord::deserialize(&mut data).ok()?;
+    <[u8; 8]>::deserialize(&mut data).ok()
+}
+
+/// Returns true if the account is the legacy ExtendedVoterWeightRecord (ExtendedVoterWeightRecordV1)
+pub fn is_legacy_voter_weight_record(program_id: &Pubkey, voter_weight_record_info: &AccountInfo) -> bool {
+    voter_weight_record_info.owner == program_id &&
+        get_extended_account_discriminator(&voter_weight_record_info.data.borrow())
+            .map_or(false, |discriminator| ExtendedVoterWeightRecordV1::is_account_discriminator(&discriminator))
 }
 
 /// Returns ExtendedVoterWeightRecord PDA seeds
@@ -98,28 +393,38 @@ pub fn get_voter_weight_record_address(program_id: &Pubkey, realm: &Pubkey, mint
     Pubkey::find_program_address(&get_voter_weight_record_seeds(realm, mint, owner), program_id).0
 }
 
-/// Deserializes ExtendedVoterWeightRecord account and checks owner program
+/// Deserializes ExtendedVoterWeightRecord account and checks owner program.
+/// The legacy record (ExtendedVoterWeightRecordV1) is rejected with VoterWeightRecordMigrationRequired
 pub fn get_voter_weight_record_data(
     program_id: &Pubkey,
     voter_weight_record_info: &AccountInfo,
 ) -> Result<ExtendedVoterWeightRecord, ProgramError> {
+    if is_legacy_voter_weight_record(program_id, voter_weight_record_info) {
+        return Err(VestingError::VoterWeightRecordMigrationRequired.into());
+    }
     get_account_data::<ExtendedVoterWeightRecord>(program_id, voter_weight_record_info)
 }
 
-/// Deserializes ExtendedVoterWeightRecord account and checks owner program
+/// Deserializes ExtendedVoterWeightRecord account and checks owner program and the address
+/// with the bump seed stored in the record
 pub fn get_voter_weight_record_data_for_seeds(
     program_id: &Pubkey,
     voter_weight_record_info: &AccountInfo,
     voter_weight_record_seeds: &[&[u8]],
 ) -> Result<ExtendedVoterWeightRecord, ProgramError> {
-    let (voter_weight_record_address, _) =
-        Pubkey::find_program_address(voter_weight_record_seeds, program_id);
+    let record = get_voter_weight_record_data(program_id, voter_weight_record_info)?;
+
+    let bump_seed = [record.bump_seed];
+    let mut seeds_with_bump = voter_weight_record_seeds.to_vec();
+    seeds_with_bump.push(&bump_seed);
+    let voter_weight_record_address = Pubkey::create_program_address(&seeds_with_bump, program_id)
+        .map_err(|_| VestingError::InvalidVoterWeightRecordAccountAddress)?;
 
     if voter_weight_record_address != *voter_weight_record_info.key {
         return Err(VestingError::InvalidVoterWeightRecordAccountAddress.into());
     }
 
-    get_voter_weight_record_data(program_id, voter_weight_record_info)
+    Ok(record)
 }
 
 /// Deserialize ExtendedVoterWeightRecord account and checks owner program and linkage
@@ -168,7 +473,13 @@ where I: FnOnce(&mut ExtendedVoterWeightRecord) -> Result<(), ProgramError>
         },
         account_discriminator: ExtendedVoterWeightRecord::ACCOUNT_DISCRIMINATOR,
         total_amount: 0,
-        vote_percentage: 10_000,
+        excluded_amount: 0,
+        weighted_amount: 0,
+        vote_percentage: DEFAULT_VOTE_PERCENTAGE,
+        vote_percentage_expiry: None,
+        vote_amount: None,
+        checkpoints: [VoterWeightCheckpoint::default(); VOTER_WEIGHT_CHECKPOINTS],
+        bump_seed: Pubkey::find_program_address(&get_voter_weight_record_seeds(realm, mint, owner), program_id).1,
     };
     initialize_func(&mut record_data)?;
     create_and_serialize_account_signed::<ExtendedVoterWeightRecord>(
@@ -182,3 +493,246 @@ where I: FnOnce(&mut ExtendedVoterWeightRecord) -> Result<(), ProgramError>
     )?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WeightCurve;
+
+    #[test]
+    fn test_voter_weight_checkpoints() {
+        let mut record = ExtendedVoterWeightRecord {
+            base: VoterWeightRecord {
+                account_discriminator: VoterWeightRecord::ACCOUNT_DISCRIMINATOR,
+                realm: Pubkey::new_unique(),
+                governing_token_mint: Pubkey::new_unique(),
+                governing_token_owner: Pubkey::new_unique(),
+                voter_weight: 0,
+                voter_weight_expiry: None,
+                weight_action: None,
+                weight_action_target: None,
+                reserved: [0u8; 8],
+            },
+            account_discriminator: ExtendedVoterWeightRecord::ACCOUNT_DISCRIMINATOR,
+            total_amount: 0,
+            excluded_amount: 0,
+            weighted_amount: 0,
+            vote_percentage: DEFAULT_VOTE_PERCENTAGE,
+            vote_percentage_expiry: None,
+            vote_amount: None,
+            checkpoints: [VoterWeightCheckpoint::default(); VOTER_WEIGHT_CHECKPOINTS],
+            bump_seed: 255,
+        };
+
+        record.add_checkpoint(10, 100);
+        record.add_checkpoint(20, 300);
+        record.add_checkpoint(20, 200);
+        record.add_checkpoint(30, 200);
+        assert_eq!(
+            record.get_voter_weight_at(0),
+            Err(VestingError::VoterWeightHistoryUnavailable.into())
+        );
+        assert_eq!(record.get_voter_weight_at(10).unwrap(), 0);
+        assert_eq!(record.get_voter_weight_at(11).unwrap(), 100);
+        assert_eq!(record.get_voter_weight_at(21).unwrap(), 200);
+        assert_eq!(record.get_voter_weight_at(31).unwrap(), 200);
+
+        // The dust changes don't evict the significant changes when the history is full
+        record.add_checkpoint(40, 5_000);
+        for i in 0..2 * VOTER_WEIGHT_CHECKPOINTS as u64 {
+            record.add_checkpoint(100 + i, 5_001 + i);
+        }
+        assert_eq!(record.get_voter_weight_at(11).unwrap(), 100);
+        assert_eq!(record.get_voter_weight_at(21).unwrap(), 200);
+        assert_eq!(record.get_voter_weight_at(41).unwrap(), 5_000);
+        assert_eq!(record.get_voter_weight_at(u64::MAX).unwrap(), 5_016);
+        assert_eq!(record.checkpoints[..4], [
+            VoterWeightCheckpoint {slot: 0, voter_weight: 0},
+            VoterWeightCheckpoint {slot: 10, voter_weight: 100},
+            VoterWeightCheckpoint {slot: 20, voter_weight: 200},
+            VoterWeightCheckpoint {slot: 40, voter_weight: 5_000},
+        ]);
+    }
+
+    #[test]
+    fn test_vote_percentage_expiry() {
+        let realm = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let mut record = ExtendedVoterWeightRecord {
+            base: VoterWeightRecord {
+                account_discriminator: VoterWeightRecord::ACCOUNT_DISCRIMINATOR,
+                realm,
+                governing_token_mint: mint,
+                governing_token_owner: Pubkey::new_unique(),
+                voter_weight: 0,
+                voter_weight_expiry: None,
+                weight_action: None,
+                weight_action_target: None,
+                reserved: [0u8; 8],
+            },
+            account_discriminator: ExtendedVoterWeightRecord::ACCOUNT_DISCRIMINATOR,
+            total_amount: 1_000,
+            excluded_amount: 0,
+            weighted_amount: 1_000,
+            vote_percentage: 3_000,
+            vote_percentage_expiry: Some(200),
+            vote_amount: None,
+            checkpoints: [VoterWeightCheckpoint::default(); VOTER_WEIGHT_CHECKPOINTS],
+            bump_seed: 255,
+        };
+        let vesting_config = VestingConfig {
+            default_vote_percentage: 5_000,
+            ..VestingConfig::new(&realm, &mint)
+        };
+        assert_eq!(record.get_uncapped_voter_weight().unwrap(), 300);
+
+        // Temporary vote percentage is valid until the expiry slot inclusive
+        assert_eq!(record.reset_expired_vote_percentage(200, &vesting_config), None);
+        assert_eq!(record.vote_percentage, 3_000);
+
+        // After the expiry the default vote percentage of the Realm is used since the next slot
+        assert_eq!(record.reset_expired_vote_percentage(300, &vesting_config), Some(201));
+        assert_eq!(record.vote_percentage, 5_000);
+        assert_eq!(record.vote_percentage_expiry, None);
+        assert_eq!(record.get_uncapped_voter_weight().unwrap(), 500);
+        assert_eq!(record.reset_expired_vote_percentage(400, &vesting_config), None);
+    }
+
+    #[test]
+    fn test_vote_amount() {
+        let mut record = ExtendedVoterWeightRecord {
+            base: VoterWeightRecord {
+                account_discriminator: VoterWeightRecord::ACCOUNT_DISCRIMINATOR,
+                realm: Pubkey::new_unique(),
+                governing_token_mint: Pubkey::new_unique(),
+                governing_token_owner: Pubkey::new_unique(),
+                voter_weight: 0,
+                voter_weight_expiry: None,
+                weight_action: None,
+                weight_action_target: None,
+                reserved: [0u8; 8],
+            },
+            account_discriminator: ExtendedVoterWeightRecord::ACCOUNT_DISCRIMINATOR,
+            total_amount: 3_000_001,
+            excluded_amount: 0,
+            weighted_amount: 3_000_001,
+            vote_percentage: DEFAULT_VOTE_PERCENTAGE,
+            vote_percentage_expiry: None,
+            vote_amount: Some(1_000_000),
+            checkpoints: [VoterWeightCheckpoint::default(); VOTER_WEIGHT_CHECKPOINTS],
+            bump_seed: 255,
+        };
+        assert_eq!(record.get_uncapped_voter_weight().unwrap(), 1_000_000);
+
+        // Square root curve
+        record.weighted_amount = 1_732;
+        assert_eq!(record.get_uncapped_voter_weight().unwrap(), 577);
+
+        record.total_amount = 0;
+        record.weighted_amount = 0;
+        assert_eq!(record.get_uncapped_voter_weight().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_voter_weight_share() {
+        let realm = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let record = ExtendedVoterWeightRecord {
+            base: VoterWeightRecord {
+                account_discriminator: VoterWeightRecord::ACCOUNT_DISCRIMINATOR,
+                realm,
+                governing_token_mint: mint,
+                governing_token_owner: Pubkey::new_unique(),
+                voter_weight: 0,
+                voter_weight_expiry: None,
+                weight_action: None,
+                weight_action_target: None,
+                reserved: [0u8; 8],
+            },
+            account_discriminator: ExtendedVoterWeightRecord::ACCOUNT_DISCRIMINATOR,
+            total_amount: 10_000,
+            excluded_amount: 0,
+            weighted_amount: 10_000,
+            vote_percentage: 3_000,
+            vote_percentage_expiry: Some(200),
+            vote_amount: None,
+            checkpoints: [VoterWeightCheckpoint::default(); VOTER_WEIGHT_CHECKPOINTS],
+            bump_seed: 255,
+        };
+        let mut vesting_config = VestingConfig {
+            default_vote_percentage: 5_000,
+            ..VestingConfig::new(&realm, &mint)
+        };
+        assert_eq!(record.get_voter_weight_share(2_500, 200, &vesting_config).unwrap(), 750);
+
+        // The default vote percentage is used after the expiry
+        assert_eq!(record.get_voter_weight_share(2_500, 300, &vesting_config).unwrap(), 1_250);
+
+        // The current weight curve is applied to the record which is not converted yet
+        vesting_config.weight_curve = WeightCurve::SquareRoot;
+        assert_eq!(record.get_voter_weight_share(2_500, 200, &vesting_config).unwrap(), 7);
+
+        let empty_record = ExtendedVoterWeightRecord {total_amount: 0, weighted_amount: 0, ..record};
+        assert_eq!(empty_record.get_voter_weight_share(0, 200, &vesting_config).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_voter_weight_record_max_size() {
+        let record = ExtendedVoterWeightRecord {
+            base: VoterWeightRecord {
+                account_discriminator: VoterWeightRecord::ACCOUNT_DISCRIMINATOR,
+                realm: Pubkey::new_unique(),
+                governing_token_mint: Pubkey::new_unique(),
+                governing_token_owner: Pubkey::new_unique(),
+                voter_weight: 0,
+                voter_weight_expiry: Some(0),
+                weight_action: Some(VoterWeightAction::CastVote),
+                weight_action_target: Some(Pubkey::new_unique()),
+                reserved: [0u8; 8],
+            },
+            account_discriminator: ExtendedVoterWeightRecord::ACCOUNT_DISCRIMINATOR,
+            total_amount: 0,
+            excluded_amount: 0,
+            weighted_amount: 0,
+            vote_percentage: DEFAULT_VOTE_PERCENTAGE,
+            vote_percentage_expiry: Some(0),
+            vote_amount: Some(0),
+            checkpoints: [VoterWeightCheckpoint::default(); VOTER_WEIGHT_CHECKPOINTS],
+            bump_seed: 255,
+        };
+        assert_eq!(Some(record.try_to_vec().unwrap().len()), record.get_max_size());
+    }
+
+    #[test]
+    fn test_legacy_voter_weight_record() {
+        let legacy_record = ExtendedVoterWeightRecordV1 {
+            base: VoterWeightRecord {
+                account_discriminator: VoterWeightRecord::ACCOUNT_DISCRIMINATOR,
+                realm: Pubkey::new_unique(),
+                governing_token_mint: Pubkey::new_unique(),
+                governing_token_owner: Pubkey::new_unique(),
+                voter_weight: 50,
+                voter_weight_expiry: None,
+                weight_action: None,
+                weight_action_target: None,
+                reserved: [0u8; 8],
+            },
+            account_discriminator: *b"496b799a",
+            total_amount: 100,
+            vote_percentage: 5_000,
+        };
+        let legacy_data = legacy_record.try_to_vec().unwrap();
+        assert_eq!(get_extended_account_discriminator(&legacy_data), Some(*b"496b799a"));
+        assert!(ExtendedVoterWeightRecordV1::is_account_discriminator(&legacy_record.account_discriminator));
+
+        let record = legacy_record.clone().migrate(254);
+        assert_eq!(record.base, legacy_record.base);
+        assert_eq!(record.weighted_amount, 100);
+        assert_eq!(record.get_uncapped_voter_weight().unwrap(), 50);
+        assert_eq!(record.get_voter_weight_at(1).unwrap(), 50);
+
+        let data = record.try_to_vec().unwrap();
+        assert_eq!(get_extended_account_discriminator(&data), Some(ExtendedVoterWeightRecord::ACCOUNT_DISCRIMINATOR));
+        assert!(!ExtendedVoterWeightRecordV1::is_account_discriminator(&record.account_discriminator));
+    }
+}
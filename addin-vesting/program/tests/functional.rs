@@ -1,13 +1,16 @@
 #![cfg(feature = "test-bpf")]
 use std::str::FromStr;
 
+use borsh::BorshSerialize;
 use solana_program::{
     borsh::try_from_slice_unchecked,
+    clock::Clock,
     hash::Hash,
+    instruction::Instruction,
     pubkey::Pubkey,
     rent::Rent,
 };
-use solana_program_test::{processor, ProgramTest, ProgramTestBanksClientExt};
+use solana_program_test::{processor, BanksClient, BanksClientError, ProgramTest, ProgramTestBanksClientExt};
 use solana_sdk::{
     account::Account,
     signature::{Keypair, Signer},
@@ -18,17 +21,31 @@ use solana_sdk::{
 use spl_governance_addin_vesting::{
     entrypoint::process_instruction,
     error::VestingError,
-    state::{VestingSchedule, VestingRecord},
-    voter_weight::{ExtendedVoterWeightRecord, get_voter_weight_record_address},
-    max_voter_weight::{MaxVoterWeightRecord, get_max_voter_weight_record_address},
+    state::{
+        VestingAccountType, VestingSchedule, VestingScheduleV1, VestingRecord, VestingRecordV1, VestingStatus,
+        VestingOperationResult, get_vesting_token_address,
+    },
+    config::{MaxVoterWeightSource, WeightCurve, SUPPLY_FRACTION_BASE},
+    template::{VestingTemplate, VestingTemplateEntry, get_vesting_template_address},
+    vesting_index::{VestingIndex, get_vesting_index_address},
+    voter_weight::{
+        ExtendedVoterWeightRecord, ExtendedVoterWeightRecordV1, VoterWeightAction, VoterWeightCheckpoint, VoterWeightRecord,
+        VOTER_WEIGHT_CHECKPOINTS, get_voter_weight_record_address, get_voter_weight_record_seeds,
+    },
+    max_voter_weight::{MaxVoterWeightRecord, get_max_voter_weight_record_address, get_max_voter_weight_record_seeds},
     instruction as vesting_instruction,
 };
 use spl_token::{self, instruction as token_instruction, state::Account as TokenAccount};
 use spl_governance::{
+    error::GovernanceError,
     instruction as governance_instruction,
     state::{
-        enums::MintMaxVoteWeightSource,
+        enums::{MintMaxVoteWeightSource, ProposalState, VoteThresholdPercentage, VoteTipping},
+        governance::{GovernanceConfig, get_governance_address},
+        proposal::{ProposalV2, VoteType, get_proposal_address},
         realm::get_realm_address,
+        token_owner_record::get_token_owner_record_address,
+        vote_record::{Vote, VoteChoice},
     },
 };
 
@@ -126,11 +143,11 @@ async fn test_token_vesting_without_realm() {
     banks_client.process_transaction(setup_transaction).await.unwrap();
 
     let schedules = vec![
-        VestingSchedule {amount: 20, release_time:  0},
-        VestingSchedule {amount: 20, release_time: 10},
-        VestingSchedule {amount: 20, release_time: 20},
-        VestingSchedule {amount: 20, release_time: 30},
-        VestingSchedule {amount: 20, release_time: 40},
+        VestingSchedule {amount: 20, release_time: 0, release_proposal: None},
+        VestingSchedule {amount: 20, release_time: 10, release_proposal: None},
+        VestingSchedule {amount: 20, release_time: 20, release_proposal: None},
+        VestingSchedule {amount: 20, release_time: 30, release_proposal: None},
+        VestingSchedule {amount: 20, release_time: 40, release_proposal: None},
     ];
 
     let deposit_instructions = [
@@ -143,6 +160,7 @@ async fn test_token_vesting_without_realm() {
             &destination_account.pubkey(),
             &payer.pubkey(),
             schedules,
+            None,
         ).unwrap(),
     ];
     // Process transaction on test network
@@ -159,6 +177,7 @@ async fn test_token_vesting_without_realm() {
             &vesting_token_account.pubkey(),
             &destination_account.pubkey(),
             &new_destination_account.pubkey(),
+            &[],
         ).unwrap(),
     ];
     let mut change_owner_transaction = Transaction::new_with_payer(
@@ -176,7 +195,7 @@ async fn test_token_vesting_without_realm() {
                 &spl_token::id(),
                 &vesting_token_account.pubkey(),
                 &destination_account.pubkey(),
-                &spill.pubkey(),
+                &payer.pubkey(),
             ).unwrap(),
         ],
         Some(&payer.pubkey()),
@@ -195,7 +214,7 @@ async fn test_token_vesting_without_realm() {
                 &spl_token::id(),
                 &vesting_token_account.pubkey(),
                 &new_destination_account.pubkey(),
-                &spill.pubkey(),
+                &payer.pubkey(),
             ).unwrap(),
         ],
         Some(&payer.pubkey()),
@@ -222,12 +241,13 @@ async fn test_token_vesting_without_realm() {
         (Ok(vec![18,  0,  0,  0,  0]),         vec![(16, 30)]),
     ] {
         println!("Splitted schedule: {:?}", splitted_schedule);
-        let splitted_schedule : Vec<VestingSchedule> = splitted_schedule.into_iter().map(|(amount,release_time)| VestingSchedule {amount, release_time}).collect();
+        let splitted_schedule : Vec<VestingSchedule> = splitted_schedule.into_iter().map(|(amount,release_time)| VestingSchedule {amount, release_time, release_proposal: None}).collect();
         fn make_schedule_from_amount(amounts:&Vec<u64>) -> Vec<VestingSchedule> {
             amounts.iter().enumerate().map(
                 |(index, amount)| VestingSchedule {
                     amount: *amount,
-                    release_time: index as u64 * 10
+                    release_time: index as u64 * 10,
+                    release_proposal: None,
                 }
             ).collect()
         }
@@ -251,6 +271,7 @@ async fn test_token_vesting_without_realm() {
                     &splitted_vesting_owner.pubkey(),
                     &payer.pubkey(),
                     splitted_schedule.clone(),
+                    None,
                 ).unwrap(),
             ],
             Some(&payer.pubkey()),
@@ -295,6 +316,7 @@ async fn test_token_vesting_without_realm() {
             &vesting_token_account.pubkey(),
             &destination_token_account.pubkey(),
             &new_destination_account.pubkey(),
+            &[],
         ).unwrap(),
     ];
 
@@ -316,6 +338,7 @@ async fn test_token_vesting_without_realm() {
     println!("    {:?}", try_from_slice_unchecked::<VestingRecord>(&acc.as_ref().unwrap().data).unwrap());
 
 
+    // Lamports can only be returned to the rent payer of the vesting
     let mut close_transaction = Transaction::new_with_payer(
         &[
             vesting_instruction::close(
@@ -329,6 +352,24 @@ async fn test_token_vesting_without_realm() {
         Some(&payer.pubkey()),
     );
     close_transaction.partial_sign(&[&payer, &new_destination_account], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(close_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::InvalidRentPayerAccount)
+    );
+
+    let mut close_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::close(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &new_destination_account.pubkey(),
+                &payer.pubkey(),
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    close_transaction.partial_sign(&[&payer, &new_destination_account], recent_blockhash);
     banks_client.process_transaction(close_transaction).await.unwrap();
 
     assert_eq!(banks_client.get_account(vesting_account_key).await.unwrap(), None);
@@ -436,7 +477,7 @@ async fn test_token_vesting_with_realm() {
             &mint_authority.pubkey(),
             &mint.pubkey(),
             &payer.pubkey(),
-            None, None, None,
+            None, Some(program_id), Some(program_id),
             realm_name,
             1,
             MintMaxVoteWeightSource::SupplyFraction(10_000_000_000)
@@ -458,9 +499,9 @@ async fn test_token_vesting_with_realm() {
 
     // Create vesting account
     let schedules = vec![
-        VestingSchedule {amount: 20, release_time: 0},
-        VestingSchedule {amount: 20, release_time: 2},
-        VestingSchedule {amount: 20, release_time: 5}
+        VestingSchedule {amount: 20, release_time: 0, release_proposal: None},
+        VestingSchedule {amount: 20, release_time: 2, release_proposal: None},
+        VestingSchedule {amount: 20, release_time: 5, release_proposal: None}
     ];
 
     let deposit_instructions = [
@@ -475,6 +516,9 @@ async fn test_token_vesting_with_realm() {
             schedules.clone(),
             &realm_address,
             &mint.pubkey(),
+            &governance_id,
+            None,
+            false,
         ).unwrap(),
     ];
     let mut deposit_transaction = Transaction::new_with_payer(
@@ -512,6 +556,7 @@ async fn test_token_vesting_with_realm() {
             &realm_address,
             &mint.pubkey(),
             30*100,
+            None,
         ).unwrap(),
     ];
     let mut set_vote_percentage_transaction = Transaction::new_with_payer(
@@ -548,6 +593,8 @@ async fn test_token_vesting_with_realm() {
             &governance_id,
             &realm_address,
             &mint.pubkey(),
+            None,
+            None,
         ).unwrap(),
     ];
     let mut change_owner_transaction = Transaction::new_with_payer(
@@ -575,9 +622,9 @@ async fn test_token_vesting_with_realm() {
         &mint.pubkey(),
         &new_destination_account.pubkey()
     );
-    let voter_weight_record1 = banks_client.get_account_data_with_borsh::<ExtendedVoterWeightRecord>(voter_weight_record_address1).await.unwrap();
+    let voter_weight_record1 = get_voter_weight_record(&mut banks_client, voter_weight_record_address1).await;
     println!("VoterWeightRecord1 before withdraw: {:?}", voter_weight_record1);
-    let voter_weight_record2 = banks_client.get_account_data_with_borsh::<ExtendedVoterWeightRecord>(voter_weight_record_address2).await.unwrap();
+    let voter_weight_record2 = get_voter_weight_record(&mut banks_client, voter_weight_record_address2).await;
     println!("VoterWeightRecord2 before withdraw: {:?}", voter_weight_record2);
 
 
@@ -617,7 +664,7 @@ async fn test_token_vesting_with_realm() {
 
 
     {
-        let splitted_schedule = vec![VestingSchedule {amount: 28, release_time: 3}];
+        let splitted_schedule = vec![VestingSchedule {amount: 28, release_time: 3, release_proposal: None}];
         let splitted_vesting_owner = Keypair::new();
         let splitted_vesting_token_account = Keypair::new();
         let (splitted_vesting_account_key,_) = Pubkey::find_program_address(&[&splitted_vesting_token_account.pubkey().as_ref()], &program_id);
@@ -644,9 +691,11 @@ async fn test_token_vesting_with_realm() {
                     &splitted_vesting_owner.pubkey(),
                     &payer.pubkey(),
                     splitted_schedule.clone(),
+                    None,
                     &governance_id,
                     &realm_address,
                     &mint.pubkey(),
+                    None,
                 ).unwrap(),
             ],
             Some(&payer.pubkey()),
@@ -657,9 +706,9 @@ async fn test_token_vesting_with_realm() {
         assert_eq!(
             try_from_slice_unchecked::<VestingRecord>(&acc.as_ref().unwrap().data).unwrap().schedule,
             vec![
-                VestingSchedule {amount: 12, release_time: 0},
-                VestingSchedule {amount:  0, release_time: 2},
-                VestingSchedule {amount: 20, release_time: 5}
+                VestingSchedule {amount: 12, release_time: 0, release_proposal: None},
+                VestingSchedule {amount: 0, release_time: 2, release_proposal: None},
+                VestingSchedule {amount: 20, release_time: 5, release_proposal: None}
             ]
         );
         let splitted_acc = banks_client.get_account(splitted_vesting_account_key).await.unwrap();
@@ -674,7 +723,7 @@ async fn test_token_vesting_with_realm() {
         assert_eq!(splitted_token.owner, splitted_vesting_account_key);
         assert_eq!(splitted_token.mint, mint.pubkey());
 
-        let splitted_voter_weight_record = banks_client.get_account_data_with_borsh::<ExtendedVoterWeightRecord>(splitted_voter_weight_record_address).await.unwrap();
+        let splitted_voter_weight_record = get_voter_weight_record(&mut banks_client, splitted_voter_weight_record_address).await;
         assert_eq!(splitted_voter_weight_record.total_amount, 28);
     }
 
@@ -689,6 +738,8 @@ async fn test_token_vesting_with_realm() {
             &governance_id,
             &realm_address,
             &mint.pubkey(),
+            &[],
+            None,
         ).unwrap(),
     ];
 
@@ -711,7 +762,7 @@ async fn test_token_vesting_with_realm() {
     println!("VestingRecord: {:?}", vesting_record);
     assert_eq!(vesting_record.schedule.iter().map(|v| v.amount).sum::<u64>(), 0u64);
 
-    let voter_weight_record2 = banks_client.get_account_data_with_borsh::<ExtendedVoterWeightRecord>(voter_weight_record_address2).await.unwrap();
+    let voter_weight_record2 = get_voter_weight_record(&mut banks_client, voter_weight_record_address2).await;
     println!("VoterWeightRecord: {:?}", voter_weight_record2);
     assert_eq!(voter_weight_record2.total_amount, 0);
 
@@ -719,6 +770,16 @@ async fn test_token_vesting_with_realm() {
     println!("MaxVoterWeightRecord: {:?}", max_voter_weight_record);
     assert_eq!(max_voter_weight_record.max_voter_weight, 28);
 
+    // The bump seed of the record is stored in the first reserved byte
+    let mint_pubkey = mint.pubkey();
+    let mut max_voter_weight_record_seeds = get_max_voter_weight_record_seeds(&realm_address, &mint_pubkey).to_vec();
+    let max_voter_weight_bump_seed = [max_voter_weight_record.reserved[0]];
+    max_voter_weight_record_seeds.push(&max_voter_weight_bump_seed);
+    assert_eq!(
+        Pubkey::create_program_address(&max_voter_weight_record_seeds, &program_id).unwrap(),
+        max_voter_weight_record_address
+    );
+
 
     let recent_blockhash = banks_client.get_new_latest_blockhash(&recent_blockhash).await.unwrap();
 
@@ -730,7 +791,7 @@ async fn test_token_vesting_with_realm() {
                 &new_destination_account.pubkey(),
                 &realm_address,
                 &mint.pubkey(),
-                &spill.pubkey(),
+                &payer.pubkey(),
             ).unwrap(),
         ],
         Some(&payer.pubkey()),
@@ -740,6 +801,9 @@ async fn test_token_vesting_with_realm() {
     assert_eq!(banks_client.get_account(voter_weight_record_address2).await.unwrap(), None);
 
 
+    // The lamports of the closed accounts are returned to the rent payer
+    let payer_lamports = banks_client.get_balance(payer.pubkey()).await.unwrap();
+    let vesting_lamports = banks_client.get_balance(vesting_account_key).await.unwrap();
     let mut close_transaction = Transaction::new_with_payer(
         &[
             vesting_instruction::close(
@@ -747,7 +811,7 @@ async fn test_token_vesting_with_realm() {
                 &spl_token::id(),
                 &vesting_token_account.pubkey(),
                 &new_destination_account.pubkey(),
-                &spill.pubkey(),
+                &payer.pubkey(),
             ).unwrap(),
         ],
         Some(&payer.pubkey()),
@@ -755,6 +819,3999 @@ async fn test_token_vesting_with_realm() {
     close_transaction.partial_sign(&[&payer, &new_destination_account], recent_blockhash);
     banks_client.process_transaction(close_transaction).await.unwrap();
     assert_eq!(banks_client.get_account(vesting_token_account.pubkey()).await.unwrap(), None);
+    assert!(banks_client.get_balance(payer.pubkey()).await.unwrap() > payer_lamports + vesting_lamports / 2);
+}
+
+#[tokio::test]
+async fn test_token_vesting_attach_and_detach_realm() {
+
+    let mut context = VestingTestContext::new(60).await;
+
+    // Create realms with and without vesting addin
+    let realm_address = context.setup_realm("testing realm", true).await;
+    let other_realm_address = context.setup_realm("realm without addin", false).await;
+
+    let destination_account = Keypair::new();
+    let destination_token_account = context.create_token_account(&destination_account.pubkey()).await;
+    let (vesting_token_account, vesting_account_key) = context.create_vesting_token_account().await;
+
+    let VestingTestContext {
+        program_id, governance_id, mut banks_client, payer, recent_blockhash, mint_authority, mint,
+        source_account, source_token_account,
+    } = context;
+
+    // Create vesting account without realm (the rent payer gets the rent released by the vesting account)
+    let rent_payer = Keypair::new();
+    let schedules = vec![
+        VestingSchedule {amount: 20, release_time: 0, release_proposal: None},
+        VestingSchedule {amount: 40, release_time: 2, release_proposal: None},
+    ];
+    let mut deposit_transaction = Transaction::new_with_payer(
+        &[
+            system_instruction::transfer(
+                &payer.pubkey(),
+                &rent_payer.pubkey(),
+                1_000_000_000,
+            ),
+            vesting_instruction::deposit(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &source_account.pubkey(),
+                &source_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &rent_payer.pubkey(),
+                schedules.clone(),
+                None,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    deposit_transaction.partial_sign(&[&payer, &source_account, &rent_payer], recent_blockhash);
+    banks_client.process_transaction(deposit_transaction).await.unwrap();
+
+    let voter_weight_record_address = get_voter_weight_record_address(
+        &program_id,
+        &realm_address,
+        &mint.pubkey(),
+        &destination_account.pubkey()
+    );
+    let max_voter_weight_record_address = get_max_voter_weight_record_address(
+        &program_id,
+        &realm_address,
+        &mint.pubkey(),
+    );
+
+    // The vesting without realm can't be splitted into the vestings under realm
+    let mut split_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::split_with_realm(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &Keypair::new().pubkey(),
+                &destination_account.pubkey(),
+                &payer.pubkey(),
+                vec![VestingSchedule {amount: 20, release_time: 0, release_proposal: None}],
+                None,
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+                None,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    split_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(split_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::VestingIsNotUnderRealm)
+    );
+
+    // Realm without vesting addin can't be attached
+    let mut attach_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::attach_realm(
+                &program_id,
+                &vesting_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &payer.pubkey(),
+                &governance_id,
+                &other_realm_address,
+                &mint.pubkey(),
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    attach_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(attach_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::InvalidRealmVoterWeightAddin)
+    );
+
+    // Attach vesting to the realm
+    let mut attach_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::attach_realm(
+                &program_id,
+                &vesting_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &payer.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    attach_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    banks_client.process_transaction(attach_transaction).await.unwrap();
+
+    let vesting_record = banks_client.get_account_data_with_borsh::<VestingRecord>(vesting_account_key).await.unwrap();
+    assert_eq!(vesting_record.realm, Some(realm_address));
+    assert_eq!(vesting_record.schedule, schedules);
+
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.total_amount, 60);
+    assert_eq!(voter_weight_record.base.voter_weight, 60);
+
+    let max_voter_weight_record = banks_client.get_account_data_with_borsh::<MaxVoterWeightRecord>(max_voter_weight_record_address).await.unwrap();
+    assert_eq!(max_voter_weight_record.max_voter_weight, 60);
+
+    // Attaching twice is not allowed
+    let recent_blockhash = banks_client.get_new_latest_blockhash(&recent_blockhash).await.unwrap();
+    let mut attach_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::attach_realm(
+                &program_id,
+                &vesting_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &payer.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    attach_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(attach_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::VestingIsAlreadyUnderRealm)
+    );
+
+    // Withdraw without voter weights is not allowed while the realm uses vesting addin
+    let mut withdraw_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::withdraw_out_of_realm(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &destination_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &rent_payer.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+                &[],
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    withdraw_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(withdraw_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::RealmUsesVestingAddin)
+    );
+
+    // Detach vesting from the realm (the rent payer gets the rent of the dropped realm bytes)
+    let vesting_lamports = banks_client.get_balance(vesting_account_key).await.unwrap();
+    let rent_payer_lamports = banks_client.get_balance(rent_payer.pubkey()).await.unwrap();
+    let mut detach_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::detach_realm(
+                &program_id,
+                &vesting_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &rent_payer.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    detach_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    banks_client.process_transaction(detach_transaction).await.unwrap();
+
+    let vesting_record = banks_client.get_account_data_with_borsh::<VestingRecord>(vesting_account_key).await.unwrap();
+    assert_eq!(vesting_record.realm, None);
+    assert_eq!(vesting_record.schedule, schedules);
+
+    let refund = vesting_lamports - banks_client.get_balance(vesting_account_key).await.unwrap();
+    assert!(refund > 0);
+    assert_eq!(banks_client.get_balance(rent_payer.pubkey()).await.unwrap(), rent_payer_lamports + refund);
+
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.total_amount, 0);
+    assert_eq!(voter_weight_record.base.voter_weight, 0);
+
+    let max_voter_weight_record = banks_client.get_account_data_with_borsh::<MaxVoterWeightRecord>(max_voter_weight_record_address).await.unwrap();
+    assert_eq!(max_voter_weight_record.max_voter_weight, 0);
+
+    // Attach vesting again and switch the realm off the vesting addin
+    let recent_blockhash = banks_client.get_new_latest_blockhash(&recent_blockhash).await.unwrap();
+    let mut reconfigure_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::attach_realm(
+                &program_id,
+                &vesting_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &payer.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+            ).unwrap(),
+            governance_instruction::set_realm_config(
+                &governance_id,
+                &realm_address,
+                &mint_authority.pubkey(),
+                None,
+                &payer.pubkey(),
+                None,
+                None,
+                1,
+                MintMaxVoteWeightSource::SupplyFraction(10_000_000_000),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    reconfigure_transaction.partial_sign(&[&payer, &destination_account, &mint_authority], recent_blockhash);
+    banks_client.process_transaction(reconfigure_transaction).await.unwrap();
+
+    let mut withdraw_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::withdraw_out_of_realm(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &destination_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &rent_payer.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+                &[],
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    withdraw_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    banks_client.process_transaction(withdraw_transaction).await.unwrap();
+
+    let destination_token_data = banks_client.get_packed_account_data::<TokenAccount>(destination_token_account.pubkey()).await.unwrap();
+    assert_eq!(destination_token_data.amount, 60);
+
+    // The vesting leaves the realm together with its voter weight
+    let vesting_record = banks_client.get_account_data_with_borsh::<VestingRecord>(vesting_account_key).await.unwrap();
+    assert_eq!(vesting_record.realm, None);
+
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.total_amount, 0);
+
+    let max_voter_weight_record = banks_client.get_account_data_with_borsh::<MaxVoterWeightRecord>(max_voter_weight_record_address).await.unwrap();
+    assert_eq!(max_voter_weight_record.max_voter_weight, 0);
+}
+
+#[tokio::test]
+async fn test_token_vesting_accelerate_release() {
+
+    let mut context = VestingTestContext::new(60).await;
+
+    // Create realm governed by the mint authority
+    let realm_address = context.setup_realm("testing realm", true).await;
+
+    let destination_account = Keypair::new();
+    let destination_token_account = context.create_token_account(&destination_account.pubkey()).await;
+    let (vesting_token_account, vesting_account_key) = context.create_vesting_token_account().await;
+
+    let VestingTestContext {
+        program_id, governance_id, mut banks_client, payer, recent_blockhash, mint_authority, mint,
+        source_account, source_token_account,
+    } = context;
+
+    // Create vesting account with the schedule in the far future
+    let mut deposit_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::deposit_with_realm(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &source_account.pubkey(),
+                &source_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &payer.pubkey(),
+                vec![
+                    VestingSchedule {amount: 20, release_time: 0, release_proposal: None},
+                    VestingSchedule {amount: 20, release_time: 1_000_000_000_000, release_proposal: None},
+                    VestingSchedule {amount: 20, release_time: 2_000_000_000_000, release_proposal: Some(Pubkey::new_unique())},
+                ],
+                &realm_address,
+                &mint.pubkey(),
+                &governance_id,
+                None,
+                false,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    deposit_transaction.partial_sign(&[&payer, &source_account], recent_blockhash);
+    banks_client.process_transaction(deposit_transaction).await.unwrap();
+
+    let accelerate_release_transaction = |authority: &Keypair, include_milestones: bool| {
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                vesting_instruction::accelerate_release(
+                    &program_id,
+                    &governance_id,
+                    &realm_address,
+                    &authority.pubkey(),
+                    &mint.pubkey(),
+                    &[(vesting_token_account.pubkey(), payer.pubkey())],
+                    10,
+                    include_milestones,
+                ).unwrap(),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.partial_sign(&[&payer, authority], recent_blockhash);
+        transaction
+    };
+
+    // Only the realm authority can accelerate the release
+    assert_eq!(
+        banks_client.process_transaction(accelerate_release_transaction(&destination_account, false)).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::InvalidRealmAuthority)
+    );
+
+    // The release authority named in the VestingConfig must be a Governance account of the realm
+    let mut set_release_authority_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::set_vesting_config(
+                &program_id,
+                &governance_id,
+                &realm_address,
+                &mint_authority.pubkey(),
+                &payer.pubkey(),
+                &mint.pubkey(),
+                WeightCurve::Linear,
+                10_000,
+                MaxVoterWeightSource::Deposits,
+                false,
+                Some(destination_account.pubkey()),
+                false,
+                10_000,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    set_release_authority_transaction.partial_sign(&[&payer, &mint_authority], recent_blockhash);
+    banks_client.process_transaction(set_release_authority_transaction).await.unwrap();
+
+    let recent_blockhash = banks_client.get_new_latest_blockhash(&recent_blockhash).await.unwrap();
+    let mut accelerate_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::accelerate_release(
+                &program_id,
+                &governance_id,
+                &realm_address,
+                &destination_account.pubkey(),
+                &mint.pubkey(),
+                &[(vesting_token_account.pubkey(), payer.pubkey())],
+                10,
+                false,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    accelerate_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    assert!(banks_client.process_transaction(accelerate_transaction).await.is_err());
+
+    // Milestone entries keep their Proposals unless they are included explicitly
+    let simulation = banks_client.simulate_transaction(accelerate_release_transaction(&mint_authority, false)).await.unwrap();
+    let return_data = simulation.simulation_details.unwrap().return_data.unwrap();
+    assert_eq!(
+        try_from_slice_unchecked::<VestingOperationResult>(&return_data.data).unwrap(),
+        VestingOperationResult {transferred_amount: 0, total_amount: 0, voter_weight: 0}
+    );
+    banks_client.process_transaction(accelerate_release_transaction(&mint_authority, false)).await.unwrap();
+
+    let vesting_record = banks_client.get_account_data_with_borsh::<VestingRecord>(vesting_account_key).await.unwrap();
+    assert_eq!(vesting_record.schedule[..2], [
+        VestingSchedule {amount: 20, release_time: 0, release_proposal: None},
+        VestingSchedule {amount: 20, release_time: 10, release_proposal: None},
+    ]);
+    assert!(vesting_record.schedule[2].release_proposal.is_some());
+
+    // The remaining entries are merged, the rent of the dropped entries goes to the rent payer
+    let vesting_lamports = banks_client.get_balance(vesting_account_key).await.unwrap();
+    let recent_blockhash = banks_client.get_new_latest_blockhash(&recent_blockhash).await.unwrap();
+    let mut accelerate_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::accelerate_release(
+                &program_id,
+                &governance_id,
+                &realm_address,
+                &mint_authority.pubkey(),
+                &mint.pubkey(),
+                &[(vesting_token_account.pubkey(), payer.pubkey())],
+                10,
+                true,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    accelerate_transaction.partial_sign(&[&payer, &mint_authority], recent_blockhash);
+    banks_client.process_transaction(accelerate_transaction).await.unwrap();
+
+    let vesting_record = banks_client.get_account_data_with_borsh::<VestingRecord>(vesting_account_key).await.unwrap();
+    assert_eq!(
+        vesting_record.schedule,
+        vec![
+            VestingSchedule {amount: 20, release_time: 0, release_proposal: None},
+            VestingSchedule {amount: 40, release_time: 10, release_proposal: None},
+        ]
+    );
+    assert!(banks_client.get_balance(vesting_account_key).await.unwrap() < vesting_lamports);
+
+    let voter_weight_record_address = get_voter_weight_record_address(
+        &program_id,
+        &realm_address,
+        &mint.pubkey(),
+        &destination_account.pubkey()
+    );
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.total_amount, 60);
+
+    // All tokens can be withdrawn now
+    let mut withdraw_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::withdraw_with_realm(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &destination_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+                &[],
+                None,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    withdraw_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    banks_client.process_transaction(withdraw_transaction).await.unwrap();
+
+    let destination_token_data = banks_client.get_packed_account_data::<TokenAccount>(destination_token_account.pubkey()).await.unwrap();
+    assert_eq!(destination_token_data.amount, 60);
+}
+
+#[tokio::test]
+async fn test_token_vesting_accelerate_release_tge_relative() {
+
+    let mut context = VestingTestContext::new(60).await;
+    let realm_address = context.setup_realm("testing realm", true).await;
+
+    let destination_account = Keypair::new();
+    let (vesting_token_account, vesting_account_key) = context.create_vesting_token_account().await;
+
+    let VestingTestContext {
+        program_id, governance_id, mut banks_client, payer, recent_blockhash, mint_authority, mint,
+        source_account, source_token_account,
+    } = context;
+    let tge_timestamp = 1_000_000_000_000;
+
+    let mut deposit_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::deposit_with_realm(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &source_account.pubkey(),
+                &source_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &payer.pubkey(),
+                vec![
+                    VestingSchedule {amount: 10, release_time: 0, release_proposal: None},
+                    VestingSchedule {amount: 30, release_time: 1_000, release_proposal: Some(Pubkey::new_unique())},
+                    VestingSchedule {amount: 20, release_time: 2_000, release_proposal: None},
+                ],
+                &realm_address,
+                &mint.pubkey(),
+                &governance_id,
+                None,
+                true,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    deposit_transaction.partial_sign(&[&payer, &source_account], recent_blockhash);
+    banks_client.process_transaction(deposit_transaction).await.unwrap();
+
+    let accelerate_release_transaction = |recent_blockhash| {
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                vesting_instruction::accelerate_release(
+                    &program_id,
+                    &governance_id,
+                    &realm_address,
+                    &mint_authority.pubkey(),
+                    &mint.pubkey(),
+                    &[(vesting_token_account.pubkey(), payer.pubkey())],
+                    tge_timestamp + 1_000,
+                    false,
+                ).unwrap(),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.partial_sign(&[&payer, &mint_authority], recent_blockhash);
+        transaction
+    };
+
+    // The schedule relative to the unknown TGE can't be accelerated
+    assert_eq!(
+        banks_client.process_transaction(accelerate_release_transaction(recent_blockhash)).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::UnresolvedTgeRelativeSchedule)
+    );
+
+    let mut set_tge_timestamp_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::set_tge_timestamp(
+                &program_id,
+                &governance_id,
+                &realm_address,
+                &mint_authority.pubkey(),
+                &payer.pubkey(),
+                &mint.pubkey(),
+                tge_timestamp,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    set_tge_timestamp_transaction.partial_sign(&[&payer, &mint_authority], recent_blockhash);
+    banks_client.process_transaction(set_tge_timestamp_transaction).await.unwrap();
+
+    // The schedule is resolved by the TGE timestamp and the merged entry is placed
+    // before the kept milestone entry of the same release time
+    let recent_blockhash = banks_client.get_new_latest_blockhash(&recent_blockhash).await.unwrap();
+    banks_client.process_transaction(accelerate_release_transaction(recent_blockhash)).await.unwrap();
+
+    let vesting_record = banks_client.get_account_data_with_borsh::<VestingRecord>(vesting_account_key).await.unwrap();
+    assert!(!vesting_record.tge_relative);
+    assert_eq!(vesting_record.schedule[..2], [
+        VestingSchedule {amount: 10, release_time: tge_timestamp, release_proposal: None},
+        VestingSchedule {amount: 20, release_time: tge_timestamp + 1_000, release_proposal: None},
+    ]);
+    assert_eq!(vesting_record.schedule[2].release_time, tge_timestamp + 1_000);
+    assert!(vesting_record.schedule[2].release_proposal.is_some());
+}
+
+#[tokio::test]
+async fn test_token_vesting_weight_curve() {
+
+    let mut context = VestingTestContext::new(10_000).await;
+
+    // Create realm governed by the mint authority and set the quadratic weight curve
+    let realm_address = context.setup_realm("testing realm", true).await;
+    context.set_vesting_config(&realm_address, WeightCurve::SquareRoot, 10_000, MaxVoterWeightSource::Deposits, false).await;
+
+    let destination_account = Keypair::new();
+    let (vesting_token_account, _) = context.create_vesting_token_account().await;
+
+    let VestingTestContext {
+        program_id, governance_id, mut banks_client, payer, recent_blockhash, mint_authority, mint,
+        source_account, source_token_account,
+    } = context;
+
+    let mut deposit_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::deposit_with_realm(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &source_account.pubkey(),
+                &source_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &payer.pubkey(),
+                vec![
+                    VestingSchedule {amount: 10_000, release_time: 1_000_000_000_000, release_proposal: None},
+                ],
+                &realm_address,
+                &mint.pubkey(),
+                &governance_id,
+                None,
+                false,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    deposit_transaction.partial_sign(&[&payer, &source_account], recent_blockhash);
+    banks_client.process_transaction(deposit_transaction).await.unwrap();
+
+    let voter_weight_record_address = get_voter_weight_record_address(
+        &program_id,
+        &realm_address,
+        &mint.pubkey(),
+        &destination_account.pubkey()
+    );
+    let max_voter_weight_record_address = get_max_voter_weight_record_address(
+        &program_id,
+        &realm_address,
+        &mint.pubkey()
+    );
+
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.total_amount, 10_000);
+    assert_eq!(voter_weight_record.weighted_amount, 100);
+    assert_eq!(voter_weight_record.base.voter_weight, 100);
+    let max_voter_weight_record = banks_client.get_account_data_with_borsh::<MaxVoterWeightRecord>(max_voter_weight_record_address).await.unwrap();
+    assert_eq!(max_voter_weight_record.max_voter_weight, 100);
+
+    // The curve can be changed while tokens are deposited, the existing record and
+    // its share of the max voter weight are converted by UpdateVoterWeightRecord
+    let mut config_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::set_vesting_config(
+                &program_id,
+                &governance_id,
+                &realm_address,
+                &mint_authority.pubkey(),
+                &payer.pubkey(),
+                &mint.pubkey(),
+                WeightCurve::Linear,
+                10_000,
+                MaxVoterWeightSource::Deposits,
+                false,
+                None,
+                false,
+                10_000,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    config_transaction.partial_sign(&[&payer, &mint_authority], recent_blockhash);
+    banks_client.process_transaction(config_transaction).await.unwrap();
+
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.weighted_amount, 100);
+    let max_voter_weight_record = banks_client.get_account_data_with_borsh::<MaxVoterWeightRecord>(max_voter_weight_record_address).await.unwrap();
+    assert_eq!(max_voter_weight_record.max_voter_weight, 100);
+
+    let mut update_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::update_voter_weight_record(
+                &program_id,
+                &destination_account.pubkey(),
+                &destination_account.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+                None,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    update_transaction.partial_sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(update_transaction).await.unwrap();
+
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.weighted_amount, 10_000);
+    assert_eq!(voter_weight_record.base.voter_weight, 10_000);
+    let max_voter_weight_record = banks_client.get_account_data_with_borsh::<MaxVoterWeightRecord>(max_voter_weight_record_address).await.unwrap();
+    assert_eq!(max_voter_weight_record.max_voter_weight, 10_000);
+
+    // The curve is changed back with the voter weight cap
+    let recent_blockhash = banks_client.get_new_latest_blockhash(&recent_blockhash).await.unwrap();
+    let mut update_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::set_vesting_config(
+                &program_id,
+                &governance_id,
+                &realm_address,
+                &mint_authority.pubkey(),
+                &payer.pubkey(),
+                &mint.pubkey(),
+                WeightCurve::SquareRoot,
+                5_000,
+                MaxVoterWeightSource::Deposits,
+                false,
+                None,
+                false,
+                10_000,
+            ).unwrap(),
+            vesting_instruction::update_voter_weight_record(
+                &program_id,
+                &destination_account.pubkey(),
+                &destination_account.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+                None,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    update_transaction.partial_sign(&[&payer, &mint_authority], recent_blockhash);
+    banks_client.process_transaction(update_transaction).await.unwrap();
+
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.weighted_amount, 100);
+    assert_eq!(voter_weight_record.base.voter_weight, 50);
+    let max_voter_weight_record = banks_client.get_account_data_with_borsh::<MaxVoterWeightRecord>(max_voter_weight_record_address).await.unwrap();
+    assert_eq!(max_voter_weight_record.max_voter_weight, 100);
+}
+
+#[tokio::test]
+async fn test_token_vesting_voter_weight_cap() {
+
+    let mut context = VestingTestContext::new(1_000).await;
+
+    // Create realm governed by the mint authority and limit the voter weight by 50% of the max voter weight
+    let realm_address = context.setup_realm("testing realm", true).await;
+    context.set_vesting_config(&realm_address, WeightCurve::Linear, 5_000, MaxVoterWeightSource::Deposits, false).await;
+
+    let whale_account = Keypair::new();
+    let (whale_vesting_token_account, _) = context.create_vesting_token_account().await;
+
+    let holder_account = Keypair::new();
+    let (holder_vesting_token_account, _) = context.create_vesting_token_account().await;
+
+    let VestingTestContext {
+        program_id, governance_id, mut banks_client, payer, recent_blockhash, mint, source_account,
+        source_token_account, ..
+    } = context;
+
+    let whale_voter_weight_record_address = get_voter_weight_record_address(
+        &program_id,
+        &realm_address,
+        &mint.pubkey(),
+        &whale_account.pubkey()
+    );
+    let holder_voter_weight_record_address = get_voter_weight_record_address(
+        &program_id,
+        &realm_address,
+        &mint.pubkey(),
+        &holder_account.pubkey()
+    );
+
+    for (vesting_token_account, vesting_owner, amount) in [
+        (&whale_vesting_token_account, &whale_account, 900),
+        (&holder_vesting_token_account, &holder_account, 100),
+    ] {
+        let mut deposit_transaction = Transaction::new_with_payer(
+            &[
+                vesting_instruction::deposit_with_realm(
+                    &program_id,
+                    &spl_token::id(),
+                    &vesting_token_account.pubkey(),
+                    &source_account.pubkey(),
+                    &source_token_account.pubkey(),
+                    &vesting_owner.pubkey(),
+                    &payer.pubkey(),
+                    vec![
+                        VestingSchedule {amount, release_time: 1_000_000_000_000, release_proposal: None},
+                    ],
+                    &realm_address,
+                    &mint.pubkey(),
+                    &governance_id,
+                    None,
+                    false,
+                ).unwrap(),
+            ],
+            Some(&payer.pubkey()),
+        );
+        deposit_transaction.partial_sign(&[&payer, &source_account], recent_blockhash);
+        banks_client.process_transaction(deposit_transaction).await.unwrap();
+
+        if amount == 900 {
+            let voter_weight_record = get_voter_weight_record(&mut banks_client, whale_voter_weight_record_address).await;
+            assert_eq!(voter_weight_record.total_amount, 900);
+            assert_eq!(voter_weight_record.base.voter_weight, 450);
+        }
+    }
+
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, holder_voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.base.voter_weight, 100);
+
+    // The voter weight of the whale follows the max voter weight after the update
+    let mut update_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::update_voter_weight_record(
+                &program_id,
+                &whale_account.pubkey(),
+                &whale_account.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+                None,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    update_transaction.partial_sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(update_transaction).await.unwrap();
+
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, whale_voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.total_amount, 900);
+    assert_eq!(voter_weight_record.weighted_amount, 900);
+    assert_eq!(voter_weight_record.base.voter_weight, 500);
+}
+
+#[tokio::test]
+async fn test_token_vesting_max_voter_weight_from_supply() {
+
+    let mut context = VestingTestContext::new(800).await;
+
+    let treasury_owner = context.mint_authority.pubkey();
+    let treasury_token_account = context.create_token_account(&treasury_owner).await;
+    context.mint_to(&treasury_token_account.pubkey(), 200).await;
+
+    // Create realm governed by the mint authority, the max voter weight is a half of the supply without the treasury
+    let realm_address = context.setup_realm("testing realm", true).await;
+
+    // The weight curve is not applied to the mint supply
+    let set_vesting_config_instruction = vesting_instruction::set_vesting_config(
+        &context.program_id,
+        &context.governance_id,
+        &realm_address,
+        &context.mint_authority.pubkey(),
+        &context.payer.pubkey(),
+        &context.mint.pubkey(),
+        WeightCurve::SquareRoot,
+        10_000,
+        MaxVoterWeightSource::MintSupply {
+            supply_fraction: SUPPLY_FRACTION_BASE / 2,
+            excluded_token_accounts: vec![treasury_token_account.pubkey()],
+        },
+        false,
+        None,
+        false,
+        10_000,
+    ).unwrap();
+    let set_vesting_config_transaction = context.transaction(&[set_vesting_config_instruction], &[&context.mint_authority]);
+    assert_eq!(
+        context.banks_client.process_transaction(set_vesting_config_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::NonLinearWeightCurveWithMintSupply)
+    );
+
+    context.set_vesting_config(
+        &realm_address,
+        WeightCurve::Linear,
+        10_000,
+        MaxVoterWeightSource::MintSupply {
+            supply_fraction: SUPPLY_FRACTION_BASE / 2,
+            excluded_token_accounts: vec![treasury_token_account.pubkey()],
+        },
+        false,
+    ).await;
+
+    let destination_account = Keypair::new();
+    let (vesting_token_account, _) = context.create_vesting_token_account().await;
+
+    let VestingTestContext {
+        program_id, governance_id, mut banks_client, payer, recent_blockhash, mint_authority, mint,
+        source_account, source_token_account,
+    } = context;
+
+    let mut deposit_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::deposit_with_realm(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &source_account.pubkey(),
+                &source_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &payer.pubkey(),
+                vec![
+                    VestingSchedule {amount: 100, release_time: 1_000_000_000_000, release_proposal: None},
+                ],
+                &realm_address,
+                &mint.pubkey(),
+                &governance_id,
+                None,
+                false,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    deposit_transaction.partial_sign(&[&payer, &source_account], recent_blockhash);
+    banks_client.process_transaction(deposit_transaction).await.unwrap();
+
+    // The excluded token accounts must be passed in the configured order
+    let mut refresh_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::refresh_max_voter_weight(
+                &program_id,
+                &payer.pubkey(),
+                &realm_address,
+                &mint.pubkey(),
+                &[source_token_account.pubkey()],
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    refresh_transaction.partial_sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(refresh_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::InvalidExcludedTokenAccount)
+    );
+
+    let mut refresh_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::refresh_max_voter_weight(
+                &program_id,
+                &payer.pubkey(),
+                &realm_address,
+                &mint.pubkey(),
+                &[treasury_token_account.pubkey()],
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    refresh_transaction.partial_sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(refresh_transaction).await.unwrap();
+
+    let max_voter_weight_record_address = get_max_voter_weight_record_address(
+        &program_id,
+        &realm_address,
+        &mint.pubkey()
+    );
+    let max_voter_weight_record = banks_client.get_account_data_with_borsh::<MaxVoterWeightRecord>(max_voter_weight_record_address).await.unwrap();
+    assert_eq!(max_voter_weight_record.max_voter_weight, 400);
+    assert!(max_voter_weight_record.max_voter_weight_expiry.is_some());
+
+    // The source of the max voter weight can't be changed back to the deposits
+    let mut config_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::set_vesting_config(
+                &program_id,
+                &governance_id,
+                &realm_address,
+                &mint_authority.pubkey(),
+                &payer.pubkey(),
+                &mint.pubkey(),
+                WeightCurve::Linear,
+                10_000,
+                MaxVoterWeightSource::Deposits,
+                false,
+                None,
+                false,
+                10_000,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    config_transaction.partial_sign(&[&payer, &mint_authority], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(config_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::InvalidMaxVoterWeightSource)
+    );
+}
+
+#[tokio::test]
+async fn test_token_vesting_count_unvested_only() {
+
+    let mut context = VestingTestContext::new(200).await;
+
+    // Create realm governed by the mint authority where only unvested tokens have voting power
+    let realm_address = context.setup_realm("testing realm", true).await;
+    context.set_vesting_config(&realm_address, WeightCurve::Linear, 10_000, MaxVoterWeightSource::Deposits, true).await;
+
+    let destination_account = Keypair::new();
+    let destination_token_account = context.create_token_account(&destination_account.pubkey()).await;
+    let (vesting_token_account, vesting_account_key) = context.create_vesting_token_account().await;
+
+    let VestingTestContext {
+        program_id, governance_id, mut banks_client, payer, recent_blockhash, mint, source_account,
+        source_token_account, ..
+    } = context;
+
+    let mut deposit_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::deposit_with_realm(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &source_account.pubkey(),
+                &source_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &payer.pubkey(),
+                vec![
+                    VestingSchedule {amount: 100, release_time: 0, release_proposal: None},
+                    VestingSchedule {amount: 100, release_time: 1_000_000_000_000, release_proposal: None},
+                ],
+                &realm_address,
+                &mint.pubkey(),
+                &governance_id,
+                None,
+                false,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    deposit_transaction.partial_sign(&[&payer, &source_account], recent_blockhash);
+    banks_client.process_transaction(deposit_transaction).await.unwrap();
+
+    let voter_weight_record_address = get_voter_weight_record_address(
+        &program_id,
+        &realm_address,
+        &mint.pubkey(),
+        &destination_account.pubkey()
+    );
+    let max_voter_weight_record_address = get_max_voter_weight_record_address(
+        &program_id,
+        &realm_address,
+        &mint.pubkey()
+    );
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.total_amount, 200);
+
+    // The released amount is excluded from the voter weight
+    let mut refresh_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::refresh_released_amount(
+                &program_id,
+                &vesting_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &realm_address,
+                &mint.pubkey(),
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    refresh_transaction.partial_sign(&[&payer], recent_blockhash);
+
+    let simulation = banks_client.simulate_transaction(refresh_transaction.clone()).await.unwrap();
+    let return_data = simulation.simulation_details.unwrap().return_data.unwrap();
+    assert_eq!(
+        try_from_slice_unchecked::<VestingOperationResult>(&return_data.data).unwrap(),
+        VestingOperationResult {transferred_amount: 0, total_amount: 100, voter_weight: 100}
+    );
+
+    banks_client.process_transaction(refresh_transaction).await.unwrap();
+
+    let vesting_record = banks_client.get_account_data_with_borsh::<VestingRecord>(vesting_account_key).await.unwrap();
+    assert_eq!(vesting_record.excluded_amount, 100);
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.total_amount, 100);
+    assert_eq!(voter_weight_record.excluded_amount, 100);
+    assert_eq!(voter_weight_record.base.voter_weight, 100);
+    let max_voter_weight_record = banks_client.get_account_data_with_borsh::<MaxVoterWeightRecord>(max_voter_weight_record_address).await.unwrap();
+    assert_eq!(max_voter_weight_record.max_voter_weight, 100);
+
+    // Withdrawal of the excluded amount doesn't change the voter weight
+    let mut withdraw_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::withdraw_with_realm(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &destination_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+                &[],
+                None,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    withdraw_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+
+    // The withdrawn amount and the resulting voter weight are returned to the calling programs
+    let simulation = banks_client.simulate_transaction(withdraw_transaction.clone()).await.unwrap();
+    let return_data = simulation.simulation_details.unwrap().return_data.unwrap();
+    assert_eq!(
+        try_from_slice_unchecked::<VestingOperationResult>(&return_data.data).unwrap(),
+        VestingOperationResult {transferred_amount: 100, total_amount: 100, voter_weight: 100}
+    );
+
+    banks_client.process_transaction(withdraw_transaction).await.unwrap();
+
+    let destination_token_data = banks_client.get_packed_account_data::<TokenAccount>(destination_token_account.pubkey()).await.unwrap();
+    assert_eq!(destination_token_data.amount, 100);
+    let vesting_record = banks_client.get_account_data_with_borsh::<VestingRecord>(vesting_account_key).await.unwrap();
+    assert_eq!(vesting_record.excluded_amount, 0);
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.total_amount, 100);
+    assert_eq!(voter_weight_record.excluded_amount, 0);
+    let max_voter_weight_record = banks_client.get_account_data_with_borsh::<MaxVoterWeightRecord>(max_voter_weight_record_address).await.unwrap();
+    assert_eq!(max_voter_weight_record.max_voter_weight, 100);
+}
+
+#[tokio::test]
+async fn test_token_vesting_set_vote_amount() {
+
+    let mut context = VestingTestContext::new(3_000_001).await;
+    let realm_address = context.setup_realm("testing realm", true).await;
+
+    let destination_account = Keypair::new();
+    let destination_token_account = context.create_token_account(&destination_account.pubkey()).await;
+    let (vesting_token_account, _) = context.create_vesting_token_account().await;
+
+    let VestingTestContext {
+        program_id, governance_id, mut banks_client, payer, recent_blockhash, mint, source_account,
+        source_token_account, ..
+    } = context;
+
+    let mut setup_transaction = Transaction::new_with_payer(
+        &[
+            governance_instruction::create_token_owner_record(
+                &governance_id,
+                &realm_address,
+                &destination_account.pubkey(),
+                &mint.pubkey(),
+                &payer.pubkey(),
+            ),
+            vesting_instruction::deposit_with_realm(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &source_account.pubkey(),
+                &source_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &payer.pubkey(),
+                vec![
+                    VestingSchedule {amount: 2_500_001, release_time: 0, release_proposal: None},
+                    VestingSchedule {amount: 500_000, release_time: 1_000_000_000_000, release_proposal: None},
+                ],
+                &realm_address,
+                &mint.pubkey(),
+                &governance_id,
+                None,
+                false,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    setup_transaction.partial_sign(&[&payer, &source_account], recent_blockhash);
+    banks_client.process_transaction(setup_transaction).await.unwrap();
+
+    let voter_weight_record_address = get_voter_weight_record_address(
+        &program_id,
+        &realm_address,
+        &mint.pubkey(),
+        &destination_account.pubkey()
+    );
+
+    // Vote with exactly 1_000_000 tokens out of 3_000_001
+    let mut set_vote_amount_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::set_vote_amount_with_realm(
+                &program_id,
+                &destination_account.pubkey(),
+                &destination_account.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+                1_000_000,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    set_vote_amount_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+
+    let simulation = banks_client.simulate_transaction(set_vote_amount_transaction.clone()).await.unwrap();
+    let return_data = simulation.simulation_details.unwrap().return_data.unwrap();
+    assert_eq!(
+        try_from_slice_unchecked::<VestingOperationResult>(&return_data.data).unwrap(),
+        VestingOperationResult {transferred_amount: 0, total_amount: 3_000_001, voter_weight: 1_000_000}
+    );
+
+    banks_client.process_transaction(set_vote_amount_transaction).await.unwrap();
+
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.total_amount, 3_000_001);
+    assert_eq!(voter_weight_record.vote_amount, Some(1_000_000));
+    assert_eq!(voter_weight_record.base.voter_weight, 1_000_000);
+
+    // The vote amount is clamped to the rest of deposited tokens after withdraw
+    let mut withdraw_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::withdraw_with_realm(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &destination_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+                &[],
+                None,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    withdraw_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    banks_client.process_transaction(withdraw_transaction).await.unwrap();
+
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.total_amount, 500_000);
+    assert_eq!(voter_weight_record.vote_amount, Some(500_000));
+    assert_eq!(voter_weight_record.base.voter_weight, 500_000);
+
+    // Setting the vote percentage drops the vote amount
+    let mut set_vote_percentage_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::set_vote_percentage_with_realm(
+                &program_id,
+                &destination_account.pubkey(),
+                &destination_account.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+                50*100,
+                None,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    set_vote_percentage_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+
+    let simulation = banks_client.simulate_transaction(set_vote_percentage_transaction.clone()).await.unwrap();
+    let return_data = simulation.simulation_details.unwrap().return_data.unwrap();
+    assert_eq!(
+        try_from_slice_unchecked::<VestingOperationResult>(&return_data.data).unwrap(),
+        VestingOperationResult {transferred_amount: 0, total_amount: 500_000, voter_weight: 250_000}
+    );
+
+    banks_client.process_transaction(set_vote_percentage_transaction).await.unwrap();
+
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.vote_amount, None);
+    assert_eq!(voter_weight_record.base.voter_weight, 250_000);
+}
+
+#[tokio::test]
+async fn test_token_vesting_close_empty_records() {
+
+    let mut context = VestingTestContext::new(100).await;
+    let realm_address = context.setup_realm("testing realm", true).await;
+
+    let destination_account = Keypair::new();
+    let destination_token_account = context.create_token_account(&destination_account.pubkey()).await;
+    let spill_account = Keypair::new();
+    let (vesting_token_account, _) = context.create_vesting_token_account().await;
+
+    let VestingTestContext {
+        program_id, governance_id, mut banks_client, payer, recent_blockhash, mint_authority, mint,
+        source_account, source_token_account,
+    } = context;
+
+    let mut setup_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::deposit_with_realm(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &source_account.pubkey(),
+                &source_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &payer.pubkey(),
+                vec![VestingSchedule {amount: 100, release_time: 0, release_proposal: None}],
+                &realm_address,
+                &mint.pubkey(),
+                &governance_id,
+                None,
+                false,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    setup_transaction.partial_sign(&[&payer, &source_account], recent_blockhash);
+    banks_client.process_transaction(setup_transaction).await.unwrap();
+
+    let voter_weight_record_address = get_voter_weight_record_address(
+        &program_id,
+        &realm_address,
+        &mint.pubkey(),
+        &destination_account.pubkey()
+    );
+    let max_voter_weight_record_address = get_max_voter_weight_record_address(
+        &program_id,
+        &realm_address,
+        &mint.pubkey()
+    );
+
+    // MaxVoterWeightRecord can't be closed while the tokens are deposited
+    let mut close_max_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::close_max_voter_weight_record(
+                &program_id,
+                &governance_id,
+                &realm_address,
+                &mint_authority.pubkey(),
+                &mint.pubkey(),
+                &spill_account.pubkey(),
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    close_max_transaction.partial_sign(&[&payer, &mint_authority], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(close_max_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::MaxVoterWeightRecordNotEmpty)
+    );
+
+    // Withdrawal of all tokens closes the empty VoterWeightRecord
+    let voter_weight_record_lamports = banks_client.get_account(voter_weight_record_address).await.unwrap().unwrap().lamports;
+    let mut withdraw_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::withdraw_with_realm(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &destination_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+                &[],
+                Some(&spill_account.pubkey()),
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    withdraw_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    banks_client.process_transaction(withdraw_transaction).await.unwrap();
+
+    assert!(banks_client.get_account(voter_weight_record_address).await.unwrap().is_none());
+    assert_eq!(banks_client.get_balance(spill_account.pubkey()).await.unwrap(), voter_weight_record_lamports);
+    let max_voter_weight_record = banks_client.get_account_data_with_borsh::<MaxVoterWeightRecord>(max_voter_weight_record_address).await.unwrap();
+    assert_eq!(max_voter_weight_record.max_voter_weight, 0);
+
+    // The realm authority closes MaxVoterWeightRecord with zero max voter weight
+    let recent_blockhash = banks_client.get_new_latest_blockhash(&recent_blockhash).await.unwrap();
+    let mut close_max_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::close_max_voter_weight_record(
+                &program_id,
+                &governance_id,
+                &realm_address,
+                &mint_authority.pubkey(),
+                &mint.pubkey(),
+                &spill_account.pubkey(),
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    close_max_transaction.partial_sign(&[&payer, &mint_authority], recent_blockhash);
+    banks_client.process_transaction(close_max_transaction).await.unwrap();
+
+    assert!(banks_client.get_account(max_voter_weight_record_address).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_token_vesting_keep_voter_weight_record_with_excluded_amount() {
+
+    let mut context = VestingTestContext::new(200).await;
+    let realm_address = context.setup_realm("testing realm", true).await;
+    context.set_vesting_config(&realm_address, WeightCurve::Linear, 10_000, MaxVoterWeightSource::Deposits, true).await;
+
+    let destination_account = Keypair::new();
+    let destination_token_account = context.create_token_account(&destination_account.pubkey()).await;
+    let spill_account = Keypair::new();
+    let (vesting_token_account, _) = context.create_vesting_token_account().await;
+    let (other_vesting_token_account, _) = context.create_vesting_token_account().await;
+
+    let VestingTestContext {
+        program_id, governance_id, mut banks_client, payer, recent_blockhash, mint, source_account,
+        source_token_account, ..
+    } = context;
+
+    // Both vestings are released, so their tokens are excluded from the voter weight
+    let mut instructions = Vec::new();
+    for vesting_token_account in [&vesting_token_account, &other_vesting_token_account] {
+        instructions.push(vesting_instruction::deposit_with_realm(
+            &program_id,
+            &spl_token::id(),
+            &vesting_token_account.pubkey(),
+            &source_account.pubkey(),
+            &source_token_account.pubkey(),
+            &destination_account.pubkey(),
+            &payer.pubkey(),
+            vec![VestingSchedule {amount: 100, release_time: 0, release_proposal: None}],
+            &realm_address,
+            &mint.pubkey(),
+            &governance_id,
+            None,
+            false,
+        ).unwrap());
+        instructions.push(vesting_instruction::refresh_released_amount(
+            &program_id,
+            &vesting_token_account.pubkey(),
+            &destination_account.pubkey(),
+            &realm_address,
+            &mint.pubkey(),
+        ).unwrap());
+    }
+    let mut setup_transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    setup_transaction.partial_sign(&[&payer, &source_account], recent_blockhash);
+    banks_client.process_transaction(setup_transaction).await.unwrap();
+
+    let voter_weight_record_address = get_voter_weight_record_address(
+        &program_id,
+        &realm_address,
+        &mint.pubkey(),
+        &destination_account.pubkey()
+    );
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.total_amount, 0);
+    assert_eq!(voter_weight_record.excluded_amount, 200);
+
+    let withdraw = |vesting_token_account: &Keypair| Transaction::new_with_payer(
+        &[
+            vesting_instruction::withdraw_with_realm(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &destination_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+                &[],
+                Some(&spill_account.pubkey()),
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    // The record is kept open for the other vesting with the excluded tokens
+    let mut withdraw_transaction = withdraw(&vesting_token_account);
+    withdraw_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    banks_client.process_transaction(withdraw_transaction).await.unwrap();
+
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.total_amount, 0);
+    assert_eq!(voter_weight_record.excluded_amount, 100);
+
+    let mut withdraw_transaction = withdraw(&other_vesting_token_account);
+    withdraw_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    banks_client.process_transaction(withdraw_transaction).await.unwrap();
+
+    assert!(banks_client.get_account(voter_weight_record_address).await.unwrap().is_none());
+    let destination_token_data = banks_client.get_packed_account_data::<TokenAccount>(destination_token_account.pubkey()).await.unwrap();
+    assert_eq!(destination_token_data.amount, 200);
+}
+
+#[tokio::test]
+async fn test_token_vesting_change_owner_creates_voter_weight_record() {
+
+    let mut context = VestingTestContext::new(100).await;
+    let realm_address = context.setup_realm("testing realm", true).await;
+
+    let destination_account = Keypair::new();
+    let new_destination_account = Keypair::new();
+    let (vesting_token_account, vesting_account_key) = context.create_vesting_token_account().await;
+
+    let VestingTestContext {
+        program_id, governance_id, mut banks_client, payer, recent_blockhash, mint, source_account,
+        source_token_account, ..
+    } = context;
+
+    let mut setup_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::deposit_with_realm(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &source_account.pubkey(),
+                &source_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &payer.pubkey(),
+                vec![VestingSchedule {amount: 100, release_time: 1_000_000_000_000, release_proposal: None}],
+                &realm_address,
+                &mint.pubkey(),
+                &governance_id,
+                None,
+                false,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    setup_transaction.partial_sign(&[&payer, &source_account], recent_blockhash);
+    banks_client.process_transaction(setup_transaction).await.unwrap();
+
+    let voter_weight_record_address = get_voter_weight_record_address(
+        &program_id,
+        &realm_address,
+        &mint.pubkey(),
+        &destination_account.pubkey()
+    );
+    let new_voter_weight_record_address = get_voter_weight_record_address(
+        &program_id,
+        &realm_address,
+        &mint.pubkey(),
+        &new_destination_account.pubkey()
+    );
+    assert!(banks_client.get_account(new_voter_weight_record_address).await.unwrap().is_none());
+
+    // ChangeOwner creates VoterWeightRecord of the new owner and closes the empty one
+    let mut change_owner_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::change_owner_with_realm(
+                &program_id,
+                &vesting_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &new_destination_account.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+                Some(&payer.pubkey()),
+                Some(&payer.pubkey()),
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    change_owner_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+
+    // No tokens are transferred, the resulting amounts are the ones of the new owner
+    let simulation = banks_client.simulate_transaction(change_owner_transaction.clone()).await.unwrap();
+    let return_data = simulation.simulation_details.unwrap().return_data.unwrap();
+    assert_eq!(
+        try_from_slice_unchecked::<VestingOperationResult>(&return_data.data).unwrap(),
+        VestingOperationResult {transferred_amount: 0, total_amount: 100, voter_weight: 100}
+    );
+
+    banks_client.process_transaction(change_owner_transaction).await.unwrap();
+
+    assert!(banks_client.get_account(voter_weight_record_address).await.unwrap().is_none());
+    let new_voter_weight_record = get_voter_weight_record(&mut banks_client, new_voter_weight_record_address).await;
+    assert_eq!(new_voter_weight_record.total_amount, 100);
+    assert_eq!(new_voter_weight_record.base.voter_weight, 100);
+    let vesting_record = banks_client.get_account_data_with_borsh::<VestingRecord>(vesting_account_key).await.unwrap();
+    assert_eq!(vesting_record.owner, new_destination_account.pubkey());
+}
+
+#[tokio::test]
+async fn test_token_vesting_split_percent() {
+
+    // Create program and test environment
+    let program_id = Pubkey::from_str("VestingbGKPFXCWuBvfkegQfZyiNwAJb9Ss623VQ5DA").unwrap();
+    let mint_authority = Keypair::new();
+    let mint = Keypair::new();
+
+    let source_account = Keypair::new();
+    let source_token_account = Keypair::new();
+
+    let destination_account = Keypair::new();
+    let splitted_vesting_owner = Keypair::new();
+
+    let vesting_token_account = Keypair::new();
+    let (vesting_account_key,_) = Pubkey::find_program_address(&[&vesting_token_account.pubkey().as_ref()], &program_id);
+    let splitted_vesting_token_account = Keypair::new();
+    let (splitted_vesting_account_key,_) = Pubkey::find_program_address(&[&splitted_vesting_token_account.pubkey().as_ref()], &program_id);
+
+    let program_test = ProgramTest::new(
+        "spl_governance_addin_vesting",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    // Start and process transactions on the test network
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    banks_client.process_transaction(mint_init_transaction(
+        &payer,
+        &mint,
+        &mint_authority,
+        recent_blockhash
+    )).await.unwrap();
+
+    for (account, owner) in [
+        (&source_token_account, &source_account.pubkey()),
+        (&vesting_token_account, &vesting_account_key),
+        (&splitted_vesting_token_account, &splitted_vesting_account_key),
+    ] {
+        banks_client.process_transaction(
+            create_token_account(&payer, &mint, recent_blockhash, account, owner)
+        ).await.unwrap()
+    }
+
+    let mut deposit_transaction = Transaction::new_with_payer(
+        &[
+            token_instruction::mint_to(
+                &spl_token::id(),
+                &mint.pubkey(),
+                &source_token_account.pubkey(),
+                &mint_authority.pubkey(),
+                &[],
+                134
+            ).unwrap(),
+            vesting_instruction::deposit(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &source_account.pubkey(),
+                &source_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &payer.pubkey(),
+                vec![
+                    VestingSchedule {amount: 100, release_time: 1_000_000_000_000, release_proposal: None},
+                    VestingSchedule {amount: 33, release_time: 2_000_000_000_000, release_proposal: None},
+                    VestingSchedule {amount: 1, release_time: 3_000_000_000_000, release_proposal: None},
+                ],
+                None,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    deposit_transaction.partial_sign(&[&payer, &mint_authority, &source_account], recent_blockhash);
+    banks_client.process_transaction(deposit_transaction).await.unwrap();
+
+    // 30% of every entry keeping the release times, the rounding dust is taken from the last entry
+    let mut split_percent_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::split_percent(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &splitted_vesting_token_account.pubkey(),
+                &splitted_vesting_owner.pubkey(),
+                &payer.pubkey(),
+                30*100,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    split_percent_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+
+    let simulation = banks_client.simulate_transaction(split_percent_transaction.clone()).await.unwrap();
+    let return_data = simulation.simulation_details.unwrap().return_data.unwrap();
+    assert_eq!(
+        try_from_slice_unchecked::<VestingOperationResult>(&return_data.data).unwrap(),
+        VestingOperationResult {transferred_amount: 40, total_amount: 0, voter_weight: 0}
+    );
+
+    banks_client.process_transaction(split_percent_transaction).await.unwrap();
+
+    let vesting_record = banks_client.get_account_data_with_borsh::<VestingRecord>(vesting_account_key).await.unwrap();
+    assert_eq!(
+        vesting_record.schedule,
+        vec![
+            VestingSchedule {amount: 70, release_time: 1_000_000_000_000, release_proposal: None},
+            VestingSchedule {amount: 24, release_time: 2_000_000_000_000, release_proposal: None},
+            VestingSchedule {amount: 0, release_time: 3_000_000_000_000, release_proposal: None},
+        ]
+    );
+    let splitted_record = banks_client.get_account_data_with_borsh::<VestingRecord>(splitted_vesting_account_key).await.unwrap();
+    assert_eq!(splitted_record.owner, splitted_vesting_owner.pubkey());
+    assert_eq!(splitted_record.grantor, source_account.pubkey());
+    assert_eq!(splitted_record.rent_payer, payer.pubkey());
+    assert_eq!(
+        splitted_record.schedule,
+        vec![
+            VestingSchedule {amount: 30, release_time: 1_000_000_000_000, release_proposal: None},
+            VestingSchedule {amount: 9, release_time: 2_000_000_000_000, release_proposal: None},
+            VestingSchedule {amount: 1, release_time: 3_000_000_000_000, release_proposal: None},
+        ]
+    );
+
+    let splitted_token = banks_client.get_packed_account_data::<TokenAccount>(splitted_vesting_token_account.pubkey()).await.unwrap();
+    assert_eq!(splitted_token.amount, 40);
+    let vesting_token = banks_client.get_packed_account_data::<TokenAccount>(vesting_token_account.pubkey()).await.unwrap();
+    assert_eq!(vesting_token.amount, 94);
+}
+
+#[tokio::test]
+async fn test_token_vesting_split_many() {
+
+    let mut context = VestingTestContext::new(100).await;
+    let realm_address = context.setup_realm("testing realm", true).await;
+
+    let destination_account = Keypair::new();
+    let (vesting_token_account, vesting_account_key) = context.create_vesting_token_account().await;
+
+    let splitted_vesting_owners = [Keypair::new(), Keypair::new()];
+    let mut splitted_vesting_token_accounts = Vec::new();
+    let mut splitted_vesting_account_keys = Vec::new();
+    for _ in &splitted_vesting_owners {
+        let (token_account, vesting_account_key) = context.create_vesting_token_account().await;
+        splitted_vesting_token_accounts.push(token_account);
+        splitted_vesting_account_keys.push(vesting_account_key);
+    }
+
+    let VestingTestContext {
+        program_id, governance_id, mut banks_client, payer, recent_blockhash, mint, source_account,
+        source_token_account, ..
+    } = context;
+
+    let mut setup_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::deposit_with_realm(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &source_account.pubkey(),
+                &source_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &payer.pubkey(),
+                vec![
+                    VestingSchedule {amount: 50, release_time: 1_000_000_000_000, release_proposal: None},
+                    VestingSchedule {amount: 50, release_time: 2_000_000_000_000, release_proposal: None},
+                ],
+                &realm_address,
+                &mint.pubkey(),
+                &governance_id,
+                None,
+                false,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    setup_transaction.partial_sign(&[&payer, &source_account], recent_blockhash);
+    banks_client.process_transaction(setup_transaction).await.unwrap();
+
+    // Distribute the vesting to two owners at once
+    let splitted_schedules = [
+        vec![VestingSchedule {amount: 30, release_time: 2_000_000_000_000, release_proposal: None}],
+        vec![
+            VestingSchedule {amount: 10, release_time: 1_000_000_000_000, release_proposal: None},
+            VestingSchedule {amount: 10, release_time: 3_000_000_000_000, release_proposal: None},
+        ],
+    ];
+    let mut split_many_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::split_many_with_realm(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &payer.pubkey(),
+                vec![
+                    (splitted_vesting_token_accounts[0].pubkey(), splitted_vesting_owners[0].pubkey(), splitted_schedules[0].clone()),
+                    (splitted_vesting_token_accounts[1].pubkey(), splitted_vesting_owners[1].pubkey(), splitted_schedules[1].clone()),
+                ],
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+                None,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    split_many_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    banks_client.process_transaction(split_many_transaction).await.unwrap();
+
+    let vesting_record = banks_client.get_account_data_with_borsh::<VestingRecord>(vesting_account_key).await.unwrap();
+    assert_eq!(
+        vesting_record.schedule,
+        vec![
+            VestingSchedule {amount: 40, release_time: 1_000_000_000_000, release_proposal: None},
+            VestingSchedule {amount: 10, release_time: 2_000_000_000_000, release_proposal: None},
+        ]
+    );
+    let voter_weight_record = get_voter_weight_record(
+        &mut banks_client,
+        get_voter_weight_record_address(&program_id, &realm_address, &mint.pubkey(), &destination_account.pubkey()),
+    ).await;
+    assert_eq!(voter_weight_record.total_amount, 50);
+
+    for ((owner, token_account), (vesting_account_key, schedules)) in splitted_vesting_owners.iter()
+        .zip(splitted_vesting_token_accounts.iter())
+        .zip(splitted_vesting_account_keys.iter().zip(splitted_schedules.iter()))
+    {
+        let splitted_record = banks_client.get_account_data_with_borsh::<VestingRecord>(*vesting_account_key).await.unwrap();
+        assert_eq!(splitted_record.owner, owner.pubkey());
+        assert_eq!(splitted_record.realm, Some(realm_address));
+        assert_eq!(&splitted_record.schedule, schedules);
+
+        let amount = schedules.iter().map(|v| v.amount).sum::<u64>();
+        let splitted_token = banks_client.get_packed_account_data::<TokenAccount>(token_account.pubkey()).await.unwrap();
+        assert_eq!(splitted_token.amount, amount);
+
+        let splitted_voter_weight_record = get_voter_weight_record(
+            &mut banks_client,
+            get_voter_weight_record_address(&program_id, &realm_address, &mint.pubkey(), &owner.pubkey()),
+        ).await;
+        assert_eq!(splitted_voter_weight_record.total_amount, amount);
+    }
+
+    let max_voter_weight_record = banks_client.get_account_data_with_borsh::<MaxVoterWeightRecord>(
+        get_max_voter_weight_record_address(&program_id, &realm_address, &mint.pubkey())
+    ).await.unwrap();
+    assert_eq!(max_voter_weight_record.max_voter_weight, 100);
+}
+
+#[tokio::test]
+async fn test_token_vesting_label() {
+
+    // Create program and test environment
+    let program_id = Pubkey::from_str("VestingbGKPFXCWuBvfkegQfZyiNwAJb9Ss623VQ5DA").unwrap();
+    let mint_authority = Keypair::new();
+    let mint = Keypair::new();
+
+    let source_account = Keypair::new();
+    let source_token_account = Keypair::new();
+
+    let destination_account = Keypair::new();
+    let splitted_vesting_owner = Keypair::new();
+
+    let vesting_token_account = Keypair::new();
+    let (vesting_account_key,_) = Pubkey::find_program_address(&[&vesting_token_account.pubkey().as_ref()], &program_id);
+    let splitted_vesting_token_account = Keypair::new();
+    let (splitted_vesting_account_key,_) = Pubkey::find_program_address(&[&splitted_vesting_token_account.pubkey().as_ref()], &program_id);
+
+    let program_test = ProgramTest::new(
+        "spl_governance_addin_vesting",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    // Start and process transactions on the test network
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    banks_client.process_transaction(mint_init_transaction(
+        &payer,
+        &mint,
+        &mint_authority,
+        recent_blockhash
+    )).await.unwrap();
+
+    for (account, owner) in [
+        (&source_token_account, &source_account.pubkey()),
+        (&vesting_token_account, &vesting_account_key),
+        (&splitted_vesting_token_account, &splitted_vesting_account_key),
+    ] {
+        banks_client.process_transaction(
+            create_token_account(&payer, &mint, recent_blockhash, account, owner)
+        ).await.unwrap()
+    }
+
+    let deposit_instructions = |label: &str| [
+        token_instruction::mint_to(
+            &spl_token::id(),
+            &mint.pubkey(),
+            &source_token_account.pubkey(),
+            &mint_authority.pubkey(),
+            &[],
+            100
+        ).unwrap(),
+        vesting_instruction::deposit(
+            &program_id,
+            &spl_token::id(),
+            &vesting_token_account.pubkey(),
+            &source_account.pubkey(),
+            &source_token_account.pubkey(),
+            &destination_account.pubkey(),
+            &payer.pubkey(),
+            vec![
+                VestingSchedule {amount: 100, release_time: 1_000_000_000_000, release_proposal: None},
+            ],
+            Some(label.to_string()),
+        ).unwrap(),
+    ];
+
+    let mut deposit_transaction = Transaction::new_with_payer(&deposit_instructions(&"x".repeat(65)), Some(&payer.pubkey()));
+    deposit_transaction.partial_sign(&[&payer, &mint_authority, &source_account], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(deposit_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(1, VestingError::InvalidLabel)
+    );
+
+    let mut deposit_transaction = Transaction::new_with_payer(&deposit_instructions("Seed round"), Some(&payer.pubkey()));
+    deposit_transaction.partial_sign(&[&payer, &mint_authority, &source_account], recent_blockhash);
+    banks_client.process_transaction(deposit_transaction).await.unwrap();
+
+    let vesting_record = banks_client.get_account_data_with_borsh::<VestingRecord>(vesting_account_key).await.unwrap();
+    assert_eq!(vesting_record.label, Some("Seed round".to_string()));
+
+    // The splitted vesting inherits the label
+    let mut split_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::split(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &splitted_vesting_token_account.pubkey(),
+                &splitted_vesting_owner.pubkey(),
+                &payer.pubkey(),
+                vec![
+                    VestingSchedule {amount: 40, release_time: 1_000_000_000_000, release_proposal: None},
+                ],
+                None,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    split_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    banks_client.process_transaction(split_transaction).await.unwrap();
+
+    let splitted_record = banks_client.get_account_data_with_borsh::<VestingRecord>(splitted_vesting_account_key).await.unwrap();
+    assert_eq!(splitted_record.label, Some("Seed round".to_string()));
+
+    // Only the grantor can change the label
+    let mut set_label_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::set_label(
+                &program_id,
+                &vesting_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &payer.pubkey(),
+                Some("Owner label".to_string()),
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    set_label_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(set_label_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::InvalidGrantorAccount)
+    );
+
+    // The longer label grows the vesting account
+    let mut set_label_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::set_label(
+                &program_id,
+                &vesting_token_account.pubkey(),
+                &source_account.pubkey(),
+                &payer.pubkey(),
+                Some("https://example.com/grants/advisor-grant-12".to_string()),
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    set_label_transaction.partial_sign(&[&payer, &source_account], recent_blockhash);
+    banks_client.process_transaction(set_label_transaction).await.unwrap();
+
+    let vesting_record = banks_client.get_account_data_with_borsh::<VestingRecord>(vesting_account_key).await.unwrap();
+    assert_eq!(vesting_record.label, Some("https://example.com/grants/advisor-grant-12".to_string()));
+    assert_eq!(
+        vesting_record.schedule,
+        vec![
+            VestingSchedule {amount: 60, release_time: 1_000_000_000_000, release_proposal: None},
+        ]
+    );
+
+    let splitted_record = banks_client.get_account_data_with_borsh::<VestingRecord>(splitted_vesting_account_key).await.unwrap();
+    assert_eq!(splitted_record.label, Some("Seed round".to_string()));
+}
+
+#[tokio::test]
+async fn test_token_vesting_deposit_from_template() {
+
+    // Create program and test environment
+    let program_id = Pubkey::from_str("VestingbGKPFXCWuBvfkegQfZyiNwAJb9Ss623VQ5DA").unwrap();
+    let mint_authority = Keypair::new();
+    let mint = Keypair::new();
+    let template_authority = Keypair::new();
+
+    let source_account = Keypair::new();
+    let source_token_account = Keypair::new();
+
+    let destination_account = Keypair::new();
+
+    let vesting_token_account = Keypair::new();
+    let (vesting_account_key,_) = Pubkey::find_program_address(&[&vesting_token_account.pubkey().as_ref()], &program_id);
+
+    let program_test = ProgramTest::new(
+        "spl_governance_addin_vesting",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    // Start and process transactions on the test network
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    banks_client.process_transaction(mint_init_transaction(
+        &payer,
+        &mint,
+        &mint_authority,
+        recent_blockhash
+    )).await.unwrap();
+
+    for (account, owner) in [
+        (&source_token_account, &source_account.pubkey()),
+        (&vesting_token_account, &vesting_account_key),
+    ] {
+        banks_client.process_transaction(
+            create_token_account(&payer, &mint, recent_blockhash, account, owner)
+        ).await.unwrap()
+    }
+
+    // 25% after the cliff, then 3 equal releases
+    let entries = vec![
+        VestingTemplateEntry {offset: 1_000, fraction: 3},
+        VestingTemplateEntry {offset: 2_000, fraction: 3},
+        VestingTemplateEntry {offset: 3_000, fraction: 3},
+        VestingTemplateEntry {offset: 4_000, fraction: 3},
+    ];
+    let vesting_template_key = get_vesting_template_address(&program_id, &template_authority.pubkey(), "Advisors");
+
+    let mut create_template_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::create_vesting_template(
+                &program_id,
+                &template_authority.pubkey(),
+                &payer.pubkey(),
+                "Advisors".to_string(),
+                vec![VestingTemplateEntry {offset: 1_000, fraction: 0}],
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    create_template_transaction.partial_sign(&[&payer, &template_authority], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(create_template_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::InvalidVestingTemplate)
+    );
+
+    let mut create_template_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::create_vesting_template(
+                &program_id,
+                &template_authority.pubkey(),
+                &payer.pubkey(),
+                "Advisors".to_string(),
+                entries.clone(),
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    create_template_transaction.partial_sign(&[&payer, &template_authority], recent_blockhash);
+    banks_client.process_transaction(create_template_transaction).await.unwrap();
+
+    let vesting_template = banks_client.get_account_data_with_borsh::<VestingTemplate>(vesting_template_key).await.unwrap();
+    assert_eq!(vesting_template.authority, template_authority.pubkey());
+    assert_eq!(vesting_template.entries, entries);
+
+    let mut deposit_transaction = Transaction::new_with_payer(
+        &[
+            token_instruction::mint_to(
+                &spl_token::id(),
+                &mint.pubkey(),
+                &source_token_account.pubkey(),
+                &mint_authority.pubkey(),
+                &[],
+                1_000
+            ).unwrap(),
+            vesting_instruction::deposit_from_template(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &source_account.pubkey(),
+                &source_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &payer.pubkey(),
+                &vesting_template_key,
+                1_000_000_000_000,
+                1_000,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    deposit_transaction.partial_sign(&[&payer, &mint_authority, &source_account], recent_blockhash);
+    banks_client.process_transaction(deposit_transaction).await.unwrap();
+
+    let vesting_record = banks_client.get_account_data_with_borsh::<VestingRecord>(vesting_account_key).await.unwrap();
+    assert_eq!(vesting_record.owner, destination_account.pubkey());
+    assert_eq!(
+        vesting_record.schedule,
+        vec![
+            VestingSchedule {amount: 250, release_time: 1_000_000_001_000, release_proposal: None},
+            VestingSchedule {amount: 250, release_time: 1_000_000_002_000, release_proposal: None},
+            VestingSchedule {amount: 250, release_time: 1_000_000_003_000, release_proposal: None},
+            VestingSchedule {amount: 250, release_time: 1_000_000_004_000, release_proposal: None},
+        ]
+    );
+
+    let vesting_token = banks_client.get_packed_account_data::<TokenAccount>(vesting_token_account.pubkey()).await.unwrap();
+    assert_eq!(vesting_token.amount, 1_000);
+}
+
+#[tokio::test]
+async fn test_token_vesting_tge_relative() {
+
+    let mut context = VestingTestContext::new(40).await;
+
+    // Create realm governed by the mint authority
+    let realm_address = context.setup_realm("testing realm", true).await;
+
+    let destination_account = Keypair::new();
+    let destination_token_account = context.create_token_account(&destination_account.pubkey()).await;
+    let (vesting_token_account, vesting_account_key) = context.create_vesting_token_account().await;
+
+    let VestingTestContext {
+        program_id, governance_id, mut banks_client, payer, recent_blockhash, mint_authority, mint,
+        source_account, source_token_account,
+    } = context;
+
+    // The release times are offsets from the TGE timestamp which is not known yet
+    let mut deposit_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::deposit_with_realm(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &source_account.pubkey(),
+                &source_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &payer.pubkey(),
+                vec![
+                    VestingSchedule {amount: 20, release_time: 0, release_proposal: None},
+                    VestingSchedule {amount: 20, release_time: 1_000_000_000_000, release_proposal: None},
+                ],
+                &realm_address,
+                &mint.pubkey(),
+                &governance_id,
+                None,
+                true,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    deposit_transaction.partial_sign(&[&payer, &source_account], recent_blockhash);
+    banks_client.process_transaction(deposit_transaction).await.unwrap();
+
+    let voter_weight_record_address = get_voter_weight_record_address(
+        &program_id,
+        &realm_address,
+        &mint.pubkey(),
+        &destination_account.pubkey()
+    );
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.total_amount, 40);
+
+    let withdraw_instruction = vesting_instruction::withdraw_with_realm(
+        &program_id,
+        &spl_token::id(),
+        &vesting_token_account.pubkey(),
+        &destination_token_account.pubkey(),
+        &destination_account.pubkey(),
+        &governance_id,
+        &realm_address,
+        &mint.pubkey(),
+        &[],
+        None,
+    ).unwrap();
+
+    // Every entry is locked until the TGE timestamp is set
+    let mut withdraw_transaction = Transaction::new_with_payer(&[withdraw_instruction.clone()], Some(&payer.pubkey()));
+    withdraw_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    banks_client.process_transaction(withdraw_transaction).await.unwrap();
+
+    let destination_token_data = banks_client.get_packed_account_data::<TokenAccount>(destination_token_account.pubkey()).await.unwrap();
+    assert_eq!(destination_token_data.amount, 0);
+
+    let mut accelerate_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::accelerate_release(
+                &program_id,
+                &governance_id,
+                &realm_address,
+                &mint_authority.pubkey(),
+                &mint.pubkey(),
+                &[(vesting_token_account.pubkey(), payer.pubkey())],
+                10,
+                false,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    accelerate_transaction.partial_sign(&[&payer, &mint_authority], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(accelerate_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::UnresolvedTgeRelativeSchedule)
+    );
+
+    // Only the realm authority can set the TGE timestamp
+    let set_tge_timestamp_transaction = |authority: &Keypair, tge_timestamp: u64| {
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                vesting_instruction::set_tge_timestamp(
+                    &program_id,
+                    &governance_id,
+                    &realm_address,
+                    &authority.pubkey(),
+                    &payer.pubkey(),
+                    &mint.pubkey(),
+                    tge_timestamp,
+                ).unwrap(),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.partial_sign(&[&payer, authority], recent_blockhash);
+        transaction
+    };
+
+    assert_eq!(
+        banks_client.process_transaction(set_tge_timestamp_transaction(&destination_account, 10)).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::InvalidRealmAuthority)
+    );
+    banks_client.process_transaction(set_tge_timestamp_transaction(&mint_authority, 10)).await.unwrap();
+    assert_eq!(
+        banks_client.process_transaction(set_tge_timestamp_transaction(&mint_authority, 20)).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::TgeTimestampAlreadySet)
+    );
+
+    // Withdraw resolves the release times by the TGE timestamp
+    let recent_blockhash = banks_client.get_new_latest_blockhash(&recent_blockhash).await.unwrap();
+    let mut withdraw_transaction = Transaction::new_with_payer(&[withdraw_instruction], Some(&payer.pubkey()));
+    withdraw_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    banks_client.process_transaction(withdraw_transaction).await.unwrap();
+
+    let destination_token_data = banks_client.get_packed_account_data::<TokenAccount>(destination_token_account.pubkey()).await.unwrap();
+    assert_eq!(destination_token_data.amount, 20);
+
+    let vesting_record = banks_client.get_account_data_with_borsh::<VestingRecord>(vesting_account_key).await.unwrap();
+    assert!(!vesting_record.tge_relative);
+    assert_eq!(
+        vesting_record.schedule,
+        vec![
+            VestingSchedule {amount: 0, release_time: 10, release_proposal: None},
+            VestingSchedule {amount: 20, release_time: 1_000_000_000_010, release_proposal: None},
+        ]
+    );
+
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.total_amount, 20);
+}
+
+#[tokio::test]
+async fn test_token_vesting_index() {
+
+    // Create program and test environment
+    let program_id = Pubkey::from_str("VestingbGKPFXCWuBvfkegQfZyiNwAJb9Ss623VQ5DA").unwrap();
+    let mint_authority = Keypair::new();
+    let mint = Keypair::new();
+
+    let source_account = Keypair::new();
+    let source_token_account = Keypair::new();
+
+    let destination_account = Keypair::new();
+    let splitted_vesting_owner = Keypair::new();
+
+    let vesting_token_account = Keypair::new();
+    let (vesting_account_key,_) = Pubkey::find_program_address(&[&vesting_token_account.pubkey().as_ref()], &program_id);
+    let splitted_vesting_token_account = Keypair::new();
+    let (splitted_vesting_account_key,_) = Pubkey::find_program_address(&[&splitted_vesting_token_account.pubkey().as_ref()], &program_id);
+
+    let destination_index_key = get_vesting_index_address(&program_id, &destination_account.pubkey());
+    let splitted_owner_index_key = get_vesting_index_address(&program_id, &splitted_vesting_owner.pubkey());
+
+    let program_test = ProgramTest::new(
+        "spl_governance_addin_vesting",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    // Start and process transactions on the test network
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    banks_client.process_transaction(mint_init_transaction(
+        &payer,
+        &mint,
+        &mint_authority,
+        recent_blockhash
+    )).await.unwrap();
+
+    for (account, owner) in [
+        (&source_token_account, &source_account.pubkey()),
+        (&vesting_token_account, &vesting_account_key),
+        (&splitted_vesting_token_account, &splitted_vesting_account_key),
+    ] {
+        banks_client.process_transaction(
+            create_token_account(&payer, &mint, recent_blockhash, account, owner)
+        ).await.unwrap()
+    }
+
+    // Deposit and create the indexes of the owners in the same transaction
+    let mut deposit_transaction = Transaction::new_with_payer(
+        &[
+            token_instruction::mint_to(
+                &spl_token::id(),
+                &mint.pubkey(),
+                &source_token_account.pubkey(),
+                &mint_authority.pubkey(),
+                &[],
+                100
+            ).unwrap(),
+            vesting_instruction::deposit(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &source_account.pubkey(),
+                &source_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &payer.pubkey(),
+                vec![
+                    VestingSchedule {amount: 100, release_time: 1_000_000_000_000, release_proposal: None},
+                ],
+                None,
+            ).unwrap(),
+            vesting_instruction::update_vesting_index(
+                &program_id,
+                &destination_account.pubkey(),
+                &payer.pubkey(),
+                &[vesting_token_account.pubkey()],
+            ).unwrap(),
+            vesting_instruction::update_vesting_index(
+                &program_id,
+                &splitted_vesting_owner.pubkey(),
+                &payer.pubkey(),
+                &[],
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    deposit_transaction.partial_sign(
+        &[&payer, &mint_authority, &source_account, &destination_account, &splitted_vesting_owner],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(deposit_transaction).await.unwrap();
+
+    let get_vesting_index = |vesting_index_key: Pubkey| {
+        let mut banks_client = banks_client.clone();
+        async move {
+            let account = banks_client.get_account(vesting_index_key).await.unwrap().unwrap();
+            try_from_slice_unchecked::<VestingIndex>(&account.data).unwrap()
+        }
+    };
+
+    let destination_index = get_vesting_index(destination_index_key).await;
+    assert_eq!(destination_index.owner, destination_account.pubkey());
+    assert_eq!(destination_index.vestings, vec![vesting_account_key]);
+    let splitted_owner_index = get_vesting_index(splitted_owner_index_key).await;
+    assert!(splitted_owner_index.vestings.is_empty());
+
+    // Split to another owner, the vesting is not added to the index of the owner who doesn't sign
+    let mut split_instruction = vesting_instruction::split(
+        &program_id,
+        &spl_token::id(),
+        &vesting_token_account.pubkey(),
+        &destination_account.pubkey(),
+        &splitted_vesting_token_account.pubkey(),
+        &splitted_vesting_owner.pubkey(),
+        &payer.pubkey(),
+        vec![
+            VestingSchedule {amount: 40, release_time: 1_000_000_000_000, release_proposal: None},
+        ],
+        None,
+    ).unwrap();
+    vesting_instruction::add_vesting_index_accounts(
+        &program_id,
+        &mut split_instruction,
+        &[destination_account.pubkey(), splitted_vesting_owner.pubkey()],
+    );
+    let mut split_transaction = Transaction::new_with_payer(&[split_instruction], Some(&payer.pubkey()));
+    split_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    banks_client.process_transaction(split_transaction).await.unwrap();
+
+    let splitted_owner_index = get_vesting_index(splitted_owner_index_key).await;
+    assert!(splitted_owner_index.vestings.is_empty());
+    let destination_index = get_vesting_index(destination_index_key).await;
+    assert_eq!(destination_index.vestings, vec![vesting_account_key]);
+
+    // Change the owner of the original vesting, the vesting is removed from the index of the previous owner
+    let mut change_owner_instruction = vesting_instruction::change_owner(
+        &program_id,
+        &vesting_token_account.pubkey(),
+        &destination_account.pubkey(),
+        &splitted_vesting_owner.pubkey(),
+    ).unwrap();
+    vesting_instruction::add_vesting_index_accounts(
+        &program_id,
+        &mut change_owner_instruction,
+        &[destination_account.pubkey(), splitted_vesting_owner.pubkey()],
+    );
+    let mut change_owner_transaction = Transaction::new_with_payer(&[change_owner_instruction], Some(&payer.pubkey()));
+    change_owner_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    banks_client.process_transaction(change_owner_transaction).await.unwrap();
+
+    let destination_index = get_vesting_index(destination_index_key).await;
+    assert!(destination_index.vestings.is_empty());
+
+    let splitted_owner_index = get_vesting_index(splitted_owner_index_key).await;
+    assert!(splitted_owner_index.vestings.is_empty());
+
+    // Third parties can't add the vestings to the index of the owner
+    let mut update_index_instruction = vesting_instruction::update_vesting_index(
+        &program_id,
+        &splitted_vesting_owner.pubkey(),
+        &payer.pubkey(),
+        &[splitted_vesting_token_account.pubkey(), vesting_token_account.pubkey()],
+    ).unwrap();
+    update_index_instruction.accounts[2].is_signer = false;
+    let mut update_index_transaction = Transaction::new_with_payer(&[update_index_instruction], Some(&payer.pubkey()));
+    update_index_transaction.partial_sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(update_index_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::MissingRequiredSigner)
+    );
+
+    // The owner adds the vestings to the index
+    let mut update_index_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::update_vesting_index(
+                &program_id,
+                &splitted_vesting_owner.pubkey(),
+                &payer.pubkey(),
+                &[splitted_vesting_token_account.pubkey(), vesting_token_account.pubkey()],
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    update_index_transaction.partial_sign(&[&payer, &splitted_vesting_owner], recent_blockhash);
+    banks_client.process_transaction(update_index_transaction).await.unwrap();
+
+    let splitted_owner_index = get_vesting_index(splitted_owner_index_key).await;
+    assert_eq!(splitted_owner_index.vestings, vec![splitted_vesting_account_key, vesting_account_key]);
+
+    // The index address must be derived from the owner
+    let mut update_index_instruction = vesting_instruction::update_vesting_index(
+        &program_id,
+        &destination_account.pubkey(),
+        &payer.pubkey(),
+        &[vesting_token_account.pubkey()],
+    ).unwrap();
+    update_index_instruction.accounts[1].pubkey = splitted_owner_index_key;
+    update_index_instruction.accounts[2].is_signer = false;
+    let mut update_index_transaction = Transaction::new_with_payer(&[update_index_instruction], Some(&payer.pubkey()));
+    update_index_transaction.partial_sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(update_index_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::InvalidVestingIndexAccount)
+    );
+}
+
+#[tokio::test]
+async fn test_token_vesting_program_created_token_account() {
+
+    // Create program and test environment
+    let program_id = Pubkey::from_str("VestingbGKPFXCWuBvfkegQfZyiNwAJb9Ss623VQ5DA").unwrap();
+    let mint_authority = Keypair::new();
+    let mint = Keypair::new();
+
+    let source_account = Keypair::new();
+    let source_token_account = Keypair::new();
+
+    let destination_account = Keypair::new();
+    let splitted_vesting_owner = Keypair::new();
+
+    let vesting_token_seed = Pubkey::new_unique();
+    let vesting_token_key = get_vesting_token_address(&program_id, &vesting_token_seed);
+    let (vesting_account_key,_) = Pubkey::find_program_address(&[&vesting_token_key.as_ref()], &program_id);
+    let splitted_vesting_token_seed = Pubkey::new_unique();
+    let splitted_vesting_token_key = get_vesting_token_address(&program_id, &splitted_vesting_token_seed);
+    let (splitted_vesting_account_key,_) = Pubkey::find_program_address(&[&splitted_vesting_token_key.as_ref()], &program_id);
+
+    let program_test = ProgramTest::new(
+        "spl_governance_addin_vesting",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    // Start and process transactions on the test network
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    banks_client.process_transaction(mint_init_transaction(
+        &payer,
+        &mint,
+        &mint_authority,
+        recent_blockhash
+    )).await.unwrap();
+
+    banks_client.process_transaction(
+        create_token_account(&payer, &mint, recent_blockhash, &source_token_account, &source_account.pubkey())
+    ).await.unwrap();
+
+    let mut mint_to_transaction = Transaction::new_with_payer(
+        &[
+            token_instruction::mint_to(
+                &spl_token::id(),
+                &mint.pubkey(),
+                &source_token_account.pubkey(),
+                &mint_authority.pubkey(),
+                &[],
+                100
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    mint_to_transaction.partial_sign(&[&payer, &mint_authority], recent_blockhash);
+    banks_client.process_transaction(mint_to_transaction).await.unwrap();
+
+    let deposit_instruction = |vesting_token_key: &Pubkey| vesting_instruction::deposit(
+        &program_id,
+        &spl_token::id(),
+        vesting_token_key,
+        &source_account.pubkey(),
+        &source_token_account.pubkey(),
+        &destination_account.pubkey(),
+        &payer.pubkey(),
+        vec![
+            VestingSchedule {amount: 100, release_time: 1_000_000_000_000, release_proposal: None},
+        ],
+        None,
+    ).unwrap();
+
+    // The vesting token account must be derived from the seed
+    let mut deposit_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::with_created_vesting_token_accounts(
+                deposit_instruction(&splitted_vesting_token_key),
+                &mint.pubkey(),
+                &[vesting_token_seed],
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    deposit_transaction.partial_sign(&[&payer, &source_account], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(deposit_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::InvalidVestingTokenAccount)
+    );
+
+    // Deposit creates the vesting token account without signing by the vesting token account
+    let mut deposit_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::with_created_vesting_token_accounts(
+                deposit_instruction(&vesting_token_key),
+                &mint.pubkey(),
+                &[vesting_token_seed],
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    deposit_transaction.partial_sign(&[&payer, &source_account], recent_blockhash);
+    banks_client.process_transaction(deposit_transaction).await.unwrap();
+
+    let vesting_token = banks_client.get_packed_account_data::<TokenAccount>(vesting_token_key).await.unwrap();
+    assert_eq!(vesting_token.owner, vesting_account_key);
+    assert_eq!(vesting_token.mint, mint.pubkey());
+    assert_eq!(vesting_token.amount, 100);
+
+    let vesting_record = banks_client.get_account_data_with_borsh::<VestingRecord>(vesting_account_key).await.unwrap();
+    assert_eq!(vesting_record.token, vesting_token_key);
+
+    // The same for Split
+    let mut split_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::with_created_vesting_token_accounts(
+                vesting_instruction::split(
+                    &program_id,
+                    &spl_token::id(),
+                    &vesting_token_key,
+                    &destination_account.pubkey(),
+                    &splitted_vesting_token_key,
+                    &splitted_vesting_owner.pubkey(),
+                    &payer.pubkey(),
+                    vec![
+                        VestingSchedule {amount: 40, release_time: 1_000_000_000_000, release_proposal: None},
+                    ],
+                    None,
+                ).unwrap(),
+                &mint.pubkey(),
+                &[splitted_vesting_token_seed],
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    split_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    banks_client.process_transaction(split_transaction).await.unwrap();
+
+    let splitted_vesting_token = banks_client.get_packed_account_data::<TokenAccount>(splitted_vesting_token_key).await.unwrap();
+    assert_eq!(splitted_vesting_token.owner, splitted_vesting_account_key);
+    assert_eq!(splitted_vesting_token.amount, 40);
+
+    let splitted_record = banks_client.get_account_data_with_borsh::<VestingRecord>(splitted_vesting_account_key).await.unwrap();
+    assert_eq!(splitted_record.owner, splitted_vesting_owner.pubkey());
+    assert_eq!(splitted_record.token, splitted_vesting_token_key);
+
+    // And for SplitMany (one seed for every new vesting)
+    let many_seeds = [Pubkey::new_unique(), Pubkey::new_unique()];
+    let many_token_keys: Vec<Pubkey> = many_seeds.iter()
+            .map(|seed| get_vesting_token_address(&program_id, seed))
+            .collect();
+    let mut split_many_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::with_created_vesting_token_accounts(
+                vesting_instruction::split_many(
+                    &program_id,
+                    &spl_token::id(),
+                    &vesting_token_key,
+                    &destination_account.pubkey(),
+                    &payer.pubkey(),
+                    many_token_keys.iter().map(|token_key| (
+                        *token_key,
+                        splitted_vesting_owner.pubkey(),
+                        vec![VestingSchedule {amount: 10, release_time: 1_000_000_000_000, release_proposal: None}],
+                    )).collect(),
+                ).unwrap(),
+                &mint.pubkey(),
+                &many_seeds,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    split_many_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    banks_client.process_transaction(split_many_transaction).await.unwrap();
+
+    for token_key in &many_token_keys {
+        let (many_vesting_account_key, _) = Pubkey::find_program_address(&[token_key.as_ref()], &program_id);
+        let many_vesting_token = banks_client.get_packed_account_data::<TokenAccount>(*token_key).await.unwrap();
+        assert_eq!(many_vesting_token.owner, many_vesting_account_key);
+        assert_eq!(many_vesting_token.amount, 10);
+    }
+}
+
+#[tokio::test]
+async fn test_token_vesting_get_status() {
+
+    // Create program and test environment
+    let program_id = Pubkey::from_str("VestingbGKPFXCWuBvfkegQfZyiNwAJb9Ss623VQ5DA").unwrap();
+    let mint_authority = Keypair::new();
+    let mint = Keypair::new();
+
+    let source_account = Keypair::new();
+    let source_token_account = Keypair::new();
+
+    let destination_account = Keypair::new();
+
+    let vesting_token_account = Keypair::new();
+    let (vesting_account_key,_) = Pubkey::find_program_address(&[&vesting_token_account.pubkey().as_ref()], &program_id);
+
+    let program_test = ProgramTest::new(
+        "spl_governance_addin_vesting",
+        program_id,
+        processor!(process_instruction),
+    );
+
+    // Start and process transactions on the test network
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    banks_client.process_transaction(mint_init_transaction(
+        &payer,
+        &mint,
+        &mint_authority,
+        recent_blockhash
+    )).await.unwrap();
+
+    for (account, owner) in [
+        (&source_token_account, &source_account.pubkey()),
+        (&vesting_token_account, &vesting_account_key),
+    ] {
+        banks_client.process_transaction(
+            create_token_account(&payer, &mint, recent_blockhash, account, owner)
+        ).await.unwrap()
+    }
+
+    let mut deposit_transaction = Transaction::new_with_payer(
+        &[
+            token_instruction::mint_to(
+                &spl_token::id(),
+                &mint.pubkey(),
+                &source_token_account.pubkey(),
+                &mint_authority.pubkey(),
+                &[],
+                100
+            ).unwrap(),
+            vesting_instruction::deposit(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &source_account.pubkey(),
+                &source_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &payer.pubkey(),
+                vec![
+                    VestingSchedule {amount: 30, release_time: 0, release_proposal: None},
+                    VestingSchedule {amount: 70, release_time: 1_000_000_000_000, release_proposal: None},
+                ],
+                None,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    deposit_transaction.partial_sign(&[&payer, &mint_authority, &source_account], recent_blockhash);
+    banks_client.process_transaction(deposit_transaction).await.unwrap();
+
+    // The status is read by the simulation of the transaction
+    let mut get_status_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::get_vesting_status(
+                &program_id,
+                &vesting_token_account.pubkey(),
+                &[],
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    get_status_transaction.partial_sign(&[&payer], recent_blockhash);
+    let simulation = banks_client.simulate_transaction(get_status_transaction).await.unwrap();
+    assert_eq!(simulation.result, Some(Ok(())));
+
+    let return_data = simulation.simulation_details.unwrap().return_data.unwrap();
+    assert_eq!(return_data.program_id, program_id);
+    assert_eq!(
+        try_from_slice_unchecked::<VestingStatus>(&return_data.data).unwrap(),
+        VestingStatus {
+            locked_amount: 70,
+            claimable_amount: 30,
+            next_release_time: Some(1_000_000_000_000),
+            voter_weight: 0,
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_token_vesting_milestone_release() {
+
+    let mut context = VestingTestContext::new(100).await;
+    let realm_address = context.setup_realm("testing realm", true).await;
+
+    // The milestone is voted in the DAO Realm of the same mint which doesn't use the vesting addin
+    let dao_realm_address = context.setup_realm("dao realm", false).await;
+    let voter = Keypair::new();
+    let voter_token_account = context.create_token_account(&voter.pubkey()).await;
+    context.mint_to(&voter_token_account.pubkey(), 1_000).await;
+
+    let destination_account = Keypair::new();
+    let destination_token_account = context.create_token_account(&destination_account.pubkey()).await;
+    let (vesting_token_account, _) = context.create_vesting_token_account().await;
+
+    let VestingTestContext {
+        program_id, governance_id, mut banks_client, payer, recent_blockhash, mint, source_account,
+        source_token_account, ..
+    } = context;
+
+    let voter_record_address = get_token_owner_record_address(
+        &governance_id,
+        &dao_realm_address,
+        &mint.pubkey(),
+        &voter.pubkey(),
+    );
+    let governed_account = Pubkey::new_unique();
+    let governance_address = get_governance_address(&governance_id, &dao_realm_address, &governed_account);
+    let proposal_address = get_proposal_address(&governance_id, &governance_address, &mint.pubkey(), &0u32.to_le_bytes());
+
+    let mut create_governance_transaction = Transaction::new_with_payer(
+        &[
+            governance_instruction::deposit_governing_tokens(
+                &governance_id,
+                &dao_realm_address,
+                &voter_token_account.pubkey(),
+                &voter.pubkey(),
+                &voter.pubkey(),
+                &payer.pubkey(),
+                1_000,
+                &mint.pubkey(),
+            ),
+            governance_instruction::create_governance(
+                &governance_id,
+                &dao_realm_address,
+                &governed_account,
+                &voter_record_address,
+                &payer.pubkey(),
+                &voter.pubkey(),
+                None,
+                GovernanceConfig {
+                    vote_threshold_percentage: VoteThresholdPercentage::YesVote(60),
+                    min_community_weight_to_create_proposal: 1,
+                    min_transaction_hold_up_time: 0,
+                    max_voting_time: 3600,
+                    vote_tipping: VoteTipping::Strict,
+                    proposal_cool_off_time: 0,
+                    min_council_weight_to_create_proposal: 1,
+                },
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    create_governance_transaction.partial_sign(&[&payer, &voter], recent_blockhash);
+    banks_client.process_transaction(create_governance_transaction).await.unwrap();
+
+    let mut create_proposal_transaction = Transaction::new_with_payer(
+        &[
+            governance_instruction::create_proposal(
+                &governance_id,
+                &governance_address,
+                &voter_record_address,
+                &voter.pubkey(),
+                &payer.pubkey(),
+                None,
+                &dao_realm_address,
+                "Milestone".to_string(),
+                "".to_string(),
+                &mint.pubkey(),
+                VoteType::SingleChoice,
+                vec!["Release".to_string()],
+                true,
+                0,
+            ),
+            governance_instruction::add_signatory(
+                &governance_id,
+                &proposal_address,
+                &voter_record_address,
+                &voter.pubkey(),
+                &payer.pubkey(),
+                &voter.pubkey(),
+            ),
+            governance_instruction::sign_off_proposal(
+                &governance_id,
+                &dao_realm_address,
+                &governance_address,
+                &proposal_address,
+                &voter.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    create_proposal_transaction.partial_sign(&[&payer, &voter], recent_blockhash);
+    banks_client.process_transaction(create_proposal_transaction).await.unwrap();
+
+    let schedules = vec![
+        VestingSchedule {amount: 20, release_time: 0, release_proposal: None},
+        VestingSchedule {amount: 80, release_time: 1, release_proposal: Some(proposal_address)},
+    ];
+
+    // Milestones can't be released without the governance program pinned by the Realm
+    let mut deposit_without_realm_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::deposit(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &source_account.pubkey(),
+                &source_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &payer.pubkey(),
+                schedules.clone(),
+                None,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    deposit_without_realm_transaction.partial_sign(&[&payer, &source_account], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(deposit_without_realm_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::MissingRealmAccounts)
+    );
+
+    let mut deposit_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::deposit_with_realm(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &source_account.pubkey(),
+                &source_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &payer.pubkey(),
+                schedules,
+                &realm_address,
+                &mint.pubkey(),
+                &governance_id,
+                None,
+                false,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    deposit_transaction.partial_sign(&[&payer, &source_account], recent_blockhash);
+    banks_client.process_transaction(deposit_transaction).await.unwrap();
+
+    let withdraw_instruction = vesting_instruction::withdraw_with_realm(
+        &program_id,
+        &spl_token::id(),
+        &vesting_token_account.pubkey(),
+        &destination_token_account.pubkey(),
+        &destination_account.pubkey(),
+        &governance_id,
+        &realm_address,
+        &mint.pubkey(),
+        &[proposal_address],
+        None,
+    ).unwrap();
+
+    // The milestone stays locked while the Proposal is voted
+    let mut withdraw_transaction = Transaction::new_with_payer(
+        &[withdraw_instruction.clone()],
+        Some(&payer.pubkey()),
+    );
+    withdraw_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    banks_client.process_transaction(withdraw_transaction).await.unwrap();
+
+    let destination_token_data = banks_client.get_packed_account_data::<TokenAccount>(destination_token_account.pubkey()).await.unwrap();
+    assert_eq!(destination_token_data.amount, 20);
+
+    let recent_blockhash = banks_client.get_new_latest_blockhash(&recent_blockhash).await.unwrap();
+    let mut locked_withdraw_transaction = Transaction::new_with_payer(
+        &[withdraw_instruction.clone()],
+        Some(&payer.pubkey()),
+    );
+    locked_withdraw_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(locked_withdraw_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::NotReachedReleaseTime)
+    );
+
+    // Accounts owned by another program are not accepted as the Proposal
+    let mut foreign_proposal_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::withdraw_with_realm(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &destination_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+                &[voter_token_account.pubkey()],
+                None,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    foreign_proposal_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(foreign_proposal_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::InvalidProposalAccount)
+    );
+
+    // The vote of the whole deposit tips the Proposal to Succeeded state
+    let mut cast_vote_transaction = Transaction::new_with_payer(
+        &[
+            governance_instruction::cast_vote(
+                &governance_id,
+                &dao_realm_address,
+                &governance_address,
+                &proposal_address,
+                &voter_record_address,
+                &voter_record_address,
+                &voter.pubkey(),
+                &mint.pubkey(),
+                &payer.pubkey(),
+                None,
+                None,
+                Vote::Approve(vec![VoteChoice {rank: 0, weight_percentage: 100}]),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    cast_vote_transaction.partial_sign(&[&payer, &voter], recent_blockhash);
+    banks_client.process_transaction(cast_vote_transaction).await.unwrap();
+
+    let proposal = banks_client.get_account_data_with_borsh::<ProposalV2>(proposal_address).await.unwrap();
+    assert_eq!(proposal.state, ProposalState::Succeeded);
+
+    // The released milestone is claimable, the voter weight of the vesting is taken from the VoterWeightRecord
+    let mut get_status_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::get_vesting_status_with_realm(
+                &program_id,
+                &vesting_token_account.pubkey(),
+                &destination_account.pubkey(),
+                &realm_address,
+                &mint.pubkey(),
+                &[proposal_address],
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    get_status_transaction.partial_sign(&[&payer], recent_blockhash);
+    let simulation = banks_client.simulate_transaction(get_status_transaction).await.unwrap();
+    assert_eq!(simulation.result, Some(Ok(())));
+    let return_data = simulation.simulation_details.unwrap().return_data.unwrap();
+    assert_eq!(
+        try_from_slice_unchecked::<VestingStatus>(&return_data.data).unwrap(),
+        VestingStatus {
+            locked_amount: 0,
+            claimable_amount: 80,
+            next_release_time: None,
+            voter_weight: 80,
+        }
+    );
+
+    let recent_blockhash = banks_client.get_new_latest_blockhash(&recent_blockhash).await.unwrap();
+    let mut milestone_withdraw_transaction = Transaction::new_with_payer(
+        &[withdraw_instruction],
+        Some(&payer.pubkey()),
+    );
+    milestone_withdraw_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    banks_client.process_transaction(milestone_withdraw_transaction).await.unwrap();
+
+    let destination_token_data = banks_client.get_packed_account_data::<TokenAccount>(destination_token_account.pubkey()).await.unwrap();
+    assert_eq!(destination_token_data.amount, 100);
+}
+
+#[tokio::test]
+async fn test_token_vesting_snapshot_voting() {
+
+    let mut context = VestingTestContext::new(1_000).await;
+    let realm_address = context.setup_realm("testing realm", true).await;
+
+    let voter = Keypair::new();
+    let (early_vesting_token_account, _) = context.create_vesting_token_account().await;
+    let (late_vesting_token_account, _) = context.create_vesting_token_account().await;
+
+    let set_vesting_config_instruction = vesting_instruction::set_vesting_config(
+        &context.program_id,
+        &context.governance_id,
+        &realm_address,
+        &context.mint_authority.pubkey(),
+        &context.payer.pubkey(),
+        &context.mint.pubkey(),
+        WeightCurve::Linear,
+        10_000,
+        MaxVoterWeightSource::Deposits,
+        false,
+        None,
+        true,
+        10_000,
+    ).unwrap();
+    let set_vesting_config_transaction = context.transaction(&[set_vesting_config_instruction], &[&context.mint_authority]);
+    context.banks_client.process_transaction(set_vesting_config_transaction).await.unwrap();
+
+    let VestingTestContext {
+        program_id, governance_id, mut banks_client, payer, recent_blockhash, mint, source_account,
+        source_token_account, ..
+    } = context;
+
+    let voter_weight_record_address = get_voter_weight_record_address(&program_id, &realm_address, &mint.pubkey(), &voter.pubkey());
+    let max_voter_weight_record_address = get_max_voter_weight_record_address(&program_id, &realm_address, &mint.pubkey());
+    let voter_record_address = get_token_owner_record_address(
+        &governance_id,
+        &realm_address,
+        &mint.pubkey(),
+        &voter.pubkey(),
+    );
+    let governed_account = Pubkey::new_unique();
+    let governance_address = get_governance_address(&governance_id, &realm_address, &governed_account);
+    let proposal_address = get_proposal_address(&governance_id, &governance_address, &mint.pubkey(), &0u32.to_le_bytes());
+
+    let deposit_instruction = |vesting_token_account: &Keypair, amount: u64| {
+        vesting_instruction::deposit_with_realm(
+            &program_id,
+            &spl_token::id(),
+            &vesting_token_account.pubkey(),
+            &source_account.pubkey(),
+            &source_token_account.pubkey(),
+            &voter.pubkey(),
+            &payer.pubkey(),
+            vec![VestingSchedule {amount, release_time: 0, release_proposal: None}],
+            &realm_address,
+            &mint.pubkey(),
+            &governance_id,
+            None,
+            false,
+        ).unwrap()
+    };
+
+    let mut deposit_transaction = Transaction::new_with_payer(
+        &[
+            governance_instruction::create_token_owner_record(
+                &governance_id,
+                &realm_address,
+                &voter.pubkey(),
+                &mint.pubkey(),
+                &payer.pubkey(),
+            ),
+            deposit_instruction(&early_vesting_token_account, 100),
+        ],
+        Some(&payer.pubkey()),
+    );
+    deposit_transaction.partial_sign(&[&payer, &source_account], recent_blockhash);
+    banks_client.process_transaction(deposit_transaction).await.unwrap();
+
+    // The voter weight is not usable until it's reported for the action
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.base.voter_weight, 100);
+    assert_eq!(voter_weight_record.base.weight_action, Some(VoterWeightAction::CastVote));
+    assert_eq!(voter_weight_record.base.weight_action_target, Some(Pubkey::default()));
+
+    // The deposit is counted for the Proposal which enters Voting state in the later slot
+    let deposit_slot = banks_client.get_sysvar::<Clock>().await.unwrap().slot;
+    let mut recent_blockhash = recent_blockhash;
+    while banks_client.get_sysvar::<Clock>().await.unwrap().slot <= deposit_slot {
+        recent_blockhash = banks_client.get_new_latest_blockhash(&recent_blockhash).await.unwrap();
+    }
+
+    let mut create_proposal_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::update_voter_weight_record(
+                &program_id,
+                &voter.pubkey(),
+                &voter.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+                Some(VoterWeightAction::CreateGovernance),
+            ).unwrap(),
+            governance_instruction::create_governance(
+                &governance_id,
+                &realm_address,
+                &governed_account,
+                &voter_record_address,
+                &payer.pubkey(),
+                &voter.pubkey(),
+                Some(voter_weight_record_address),
+                GovernanceConfig {
+                    vote_threshold_percentage: VoteThresholdPercentage::YesVote(60),
+                    min_community_weight_to_create_proposal: 1,
+                    min_transaction_hold_up_time: 0,
+                    max_voting_time: 3600,
+                    vote_tipping: VoteTipping::Strict,
+                    proposal_cool_off_time: 0,
+                    min_council_weight_to_create_proposal: 1,
+                },
+            ),
+            vesting_instruction::update_voter_weight_record(
+                &program_id,
+                &voter.pubkey(),
+                &voter.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+                Some(VoterWeightAction::CreateProposal),
+            ).unwrap(),
+            governance_instruction::create_proposal(
+                &governance_id,
+                &governance_address,
+                &voter_record_address,
+                &voter.pubkey(),
+                &payer.pubkey(),
+                Some(voter_weight_record_address),
+                &realm_address,
+                "Snapshot".to_string(),
+                "".to_string(),
+                &mint.pubkey(),
+                VoteType::SingleChoice,
+                vec!["Approve".to_string()],
+                true,
+                0,
+            ),
+            governance_instruction::add_signatory(
+                &governance_id,
+                &proposal_address,
+                &voter_record_address,
+                &voter.pubkey(),
+                &payer.pubkey(),
+                &voter.pubkey(),
+            ),
+            governance_instruction::sign_off_proposal(
+                &governance_id,
+                &realm_address,
+                &governance_address,
+                &proposal_address,
+                &voter.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    create_proposal_transaction.partial_sign(&[&payer, &voter], recent_blockhash);
+    banks_client.process_transaction(create_proposal_transaction).await.unwrap();
+
+    // The deposit after the voting start is not counted for the Proposal
+    let proposal = banks_client.get_account_data_with_borsh::<ProposalV2>(proposal_address).await.unwrap();
+    let voting_at_slot = proposal.voting_at_slot.unwrap();
+    while banks_client.get_sysvar::<Clock>().await.unwrap().slot <= voting_at_slot {
+        recent_blockhash = banks_client.get_new_latest_blockhash(&recent_blockhash).await.unwrap();
+    }
+
+    let mut late_deposit_transaction = Transaction::new_with_payer(
+        &[deposit_instruction(&late_vesting_token_account, 900)],
+        Some(&payer.pubkey()),
+    );
+    late_deposit_transaction.partial_sign(&[&payer, &source_account], recent_blockhash);
+    banks_client.process_transaction(late_deposit_transaction).await.unwrap();
+
+    let cast_vote_instruction = governance_instruction::cast_vote(
+        &governance_id,
+        &realm_address,
+        &governance_address,
+        &proposal_address,
+        &voter_record_address,
+        &voter_record_address,
+        &voter.pubkey(),
+        &mint.pubkey(),
+        &payer.pubkey(),
+        Some(voter_weight_record_address),
+        Some(max_voter_weight_record_address),
+        Vote::Approve(vec![VoteChoice {rank: 0, weight_percentage: 100}]),
+    );
+
+    let mut current_weight_vote_transaction = Transaction::new_with_payer(
+        &[cast_vote_instruction.clone()],
+        Some(&payer.pubkey()),
+    );
+    current_weight_vote_transaction.partial_sign(&[&payer, &voter], recent_blockhash);
+    assert!(banks_client.process_transaction(current_weight_vote_transaction).await.is_err());
+
+    let mut cast_vote_without_proposal_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::update_voter_weight_record(
+                &program_id,
+                &voter.pubkey(),
+                &voter.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+                Some(VoterWeightAction::CastVote),
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    cast_vote_without_proposal_transaction.partial_sign(&[&payer, &voter], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(cast_vote_without_proposal_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::ProposalRequiredForCastVote)
+    );
+
+    // The third party can't report the voter weight for the action
+    let mut third_party_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::update_voter_weight_record_for_proposal(
+                &program_id,
+                &voter.pubkey(),
+                &payer.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+                &proposal_address,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    third_party_transaction.partial_sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(third_party_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, GovernanceError::GoverningTokenOwnerOrDelegateMustSign)
+    );
+
+    let mut cast_vote_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::update_voter_weight_record_for_proposal(
+                &program_id,
+                &voter.pubkey(),
+                &voter.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+                &proposal_address,
+            ).unwrap(),
+            cast_vote_instruction,
+        ],
+        Some(&payer.pubkey()),
+    );
+    cast_vote_transaction.partial_sign(&[&payer, &voter], recent_blockhash);
+    banks_client.process_transaction(cast_vote_transaction).await.unwrap();
+
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.total_amount, 1_000);
+    assert_eq!(voter_weight_record.base.voter_weight, 100);
+    let proposal = banks_client.get_account_data_with_borsh::<ProposalV2>(proposal_address).await.unwrap();
+    assert_eq!(proposal.options[0].vote_weight, 100);
+}
+
+#[tokio::test]
+async fn test_token_vesting_vote_percentage_expiry() {
+
+    let mut context = VestingTestContext::new(1_000).await;
+    let realm_address = context.setup_realm("testing realm", true).await;
+
+    let voter = Keypair::new();
+    let (vesting_token_account, _) = context.create_vesting_token_account().await;
+
+    let set_vesting_config_instruction = vesting_instruction::set_vesting_config(
+        &context.program_id,
+        &context.governance_id,
+        &realm_address,
+        &context.mint_authority.pubkey(),
+        &context.payer.pubkey(),
+        &context.mint.pubkey(),
+        WeightCurve::Linear,
+        10_000,
+        MaxVoterWeightSource::Deposits,
+        false,
+        None,
+        false,
+        5_000,
+    ).unwrap();
+    let set_vesting_config_transaction = context.transaction(&[set_vesting_config_instruction], &[&context.mint_authority]);
+    context.banks_client.process_transaction(set_vesting_config_transaction).await.unwrap();
+
+    let VestingTestContext {
+        program_id, governance_id, mut banks_client, payer, recent_blockhash, mint, source_account,
+        source_token_account, ..
+    } = context;
+
+    let voter_weight_record_address = get_voter_weight_record_address(&program_id, &realm_address, &mint.pubkey(), &voter.pubkey());
+    let max_voter_weight_record_address = get_max_voter_weight_record_address(&program_id, &realm_address, &mint.pubkey());
+    let voter_record_address = get_token_owner_record_address(
+        &governance_id,
+        &realm_address,
+        &mint.pubkey(),
+        &voter.pubkey(),
+    );
+    let governed_account = Pubkey::new_unique();
+    let governance_address = get_governance_address(&governance_id, &realm_address, &governed_account);
+    let proposal_address = get_proposal_address(&governance_id, &governance_address, &mint.pubkey(), &0u32.to_le_bytes());
+
+    let mut deposit_transaction = Transaction::new_with_payer(
+        &[
+            governance_instruction::create_token_owner_record(
+                &governance_id,
+                &realm_address,
+                &voter.pubkey(),
+                &mint.pubkey(),
+                &payer.pubkey(),
+            ),
+            vesting_instruction::deposit_with_realm(
+                &program_id,
+                &spl_token::id(),
+                &vesting_token_account.pubkey(),
+                &source_account.pubkey(),
+                &source_token_account.pubkey(),
+                &voter.pubkey(),
+                &payer.pubkey(),
+                vec![VestingSchedule {amount: 1_000, release_time: 0, release_proposal: None}],
+                &realm_address,
+                &mint.pubkey(),
+                &governance_id,
+                None,
+                false,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    deposit_transaction.partial_sign(&[&payer, &source_account], recent_blockhash);
+    banks_client.process_transaction(deposit_transaction).await.unwrap();
+
+    let valid_until = banks_client.get_sysvar::<Clock>().await.unwrap().slot + 10;
+    let mut set_vote_percentage_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::set_vote_percentage_with_realm(
+                &program_id,
+                &voter.pubkey(),
+                &voter.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+                30*100,
+                Some(valid_until),
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    set_vote_percentage_transaction.partial_sign(&[&payer, &voter], recent_blockhash);
+    banks_client.process_transaction(set_vote_percentage_transaction).await.unwrap();
+
+    // The voter weight of the temporary vote percentage expires with the vote percentage
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.vote_percentage, 3_000);
+    assert_eq!(voter_weight_record.base.voter_weight, 300);
+    assert_eq!(voter_weight_record.base.voter_weight_expiry, Some(valid_until));
+
+    let mut create_proposal_transaction = Transaction::new_with_payer(
+        &[
+            governance_instruction::create_governance(
+                &governance_id,
+                &realm_address,
+                &governed_account,
+                &voter_record_address,
+                &payer.pubkey(),
+                &voter.pubkey(),
+                Some(voter_weight_record_address),
+                GovernanceConfig {
+                    vote_threshold_percentage: VoteThresholdPercentage::YesVote(60),
+                    min_community_weight_to_create_proposal: 1,
+                    min_transaction_hold_up_time: 0,
+                    max_voting_time: 3600,
+                    vote_tipping: VoteTipping::Strict,
+                    proposal_cool_off_time: 0,
+                    min_council_weight_to_create_proposal: 1,
+                },
+            ),
+            governance_instruction::create_proposal(
+                &governance_id,
+                &governance_address,
+                &voter_record_address,
+                &voter.pubkey(),
+                &payer.pubkey(),
+                Some(voter_weight_record_address),
+                &realm_address,
+                "Expiry".to_string(),
+                "".to_string(),
+                &mint.pubkey(),
+                VoteType::SingleChoice,
+                vec!["Approve".to_string()],
+                true,
+                0,
+            ),
+            governance_instruction::add_signatory(
+                &governance_id,
+                &proposal_address,
+                &voter_record_address,
+                &voter.pubkey(),
+                &payer.pubkey(),
+                &voter.pubkey(),
+            ),
+            governance_instruction::sign_off_proposal(
+                &governance_id,
+                &realm_address,
+                &governance_address,
+                &proposal_address,
+                &voter.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    create_proposal_transaction.partial_sign(&[&payer, &voter], recent_blockhash);
+    banks_client.process_transaction(create_proposal_transaction).await.unwrap();
+
+    let mut recent_blockhash = recent_blockhash;
+    while banks_client.get_sysvar::<Clock>().await.unwrap().slot <= valid_until {
+        recent_blockhash = banks_client.get_new_latest_blockhash(&recent_blockhash).await.unwrap();
+    }
+
+    // The expired voter weight can't be used for the vote
+    let cast_vote_instruction = governance_instruction::cast_vote(
+        &governance_id,
+        &realm_address,
+        &governance_address,
+        &proposal_address,
+        &voter_record_address,
+        &voter_record_address,
+        &voter.pubkey(),
+        &mint.pubkey(),
+        &payer.pubkey(),
+        Some(voter_weight_record_address),
+        Some(max_voter_weight_record_address),
+        Vote::Approve(vec![VoteChoice {rank: 0, weight_percentage: 100}]),
+    );
+    let mut expired_vote_transaction = Transaction::new_with_payer(
+        &[cast_vote_instruction.clone()],
+        Some(&payer.pubkey()),
+    );
+    expired_vote_transaction.partial_sign(&[&payer, &voter], recent_blockhash);
+    assert!(banks_client.process_transaction(expired_vote_transaction).await.is_err());
+
+    // The update applies the default vote percentage of the Realm as of the slot after the expiry
+    let mut update_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::update_voter_weight_record(
+                &program_id,
+                &voter.pubkey(),
+                &voter.pubkey(),
+                &governance_id,
+                &realm_address,
+                &mint.pubkey(),
+                None,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    update_transaction.partial_sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(update_transaction).await.unwrap();
+
+    let mut cast_vote_transaction = Transaction::new_with_payer(
+        &[cast_vote_instruction],
+        Some(&payer.pubkey()),
+    );
+    cast_vote_transaction.partial_sign(&[&payer, &voter], recent_blockhash);
+    banks_client.process_transaction(cast_vote_transaction).await.unwrap();
+
+    let proposal = banks_client.get_account_data_with_borsh::<ProposalV2>(proposal_address).await.unwrap();
+    assert_eq!(proposal.options[0].vote_weight, 500);
+
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.vote_percentage, 5_000);
+    assert_eq!(voter_weight_record.vote_percentage_expiry, None);
+    assert_eq!(voter_weight_record.base.voter_weight, 500);
+    assert_eq!(voter_weight_record.base.voter_weight_expiry, None);
+    assert_eq!(voter_weight_record.get_voter_weight_at(valid_until + 1).unwrap(), 300);
+    assert_eq!(voter_weight_record.get_voter_weight_at(valid_until + 2).unwrap(), 500);
+}
+
+#[tokio::test]
+async fn test_token_vesting_migrate_vesting_record() {
+
+    let program_id = Pubkey::from_str("VestingbGKPFXCWuBvfkegQfZyiNwAJb9Ss623VQ5DA").unwrap();
+    let destination_account = Keypair::new();
+    let vesting_token_account = Keypair::new();
+    let (vesting_account_key, vesting_account_bump_seed) = Pubkey::find_program_address(&[vesting_token_account.pubkey().as_ref()], &program_id);
+
+    // The vesting account created by the previous version of the addin
+    let legacy_vesting_record = VestingRecordV1 {
+        account_type: VestingAccountType::VestingRecordV1,
+        owner: destination_account.pubkey(),
+        mint: Pubkey::new_unique(),
+        token: vesting_token_account.pubkey(),
+        realm: None,
+        schedule: vec![
+            VestingScheduleV1 {release_time: 0, amount: 30},
+            VestingScheduleV1 {release_time: 1_000_000_000_000, amount: 70},
+        ],
+    };
+    let legacy_vesting_data = legacy_vesting_record.try_to_vec().unwrap();
+
+    let mut program_test = ProgramTest::new(
+        "spl_governance_addin_vesting",
+        program_id,
+        processor!(process_instruction),
+    );
+    program_test.add_account(
+        vesting_account_key,
+        Account {
+            lamports: Rent::default().minimum_balance(legacy_vesting_data.len()),
+            data: legacy_vesting_data,
+            owner: program_id,
+            ..Account::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // The legacy vesting account is rejected until it's migrated
+    let mut get_status_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::get_vesting_status(
+                &program_id,
+                &vesting_token_account.pubkey(),
+                &[],
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    get_status_transaction.partial_sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(get_status_transaction.clone()).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::VestingRecordMigrationRequired)
+    );
+
+    let migrate_instruction = vesting_instruction::migrate_vesting_record(
+        &program_id,
+        &vesting_token_account.pubkey(),
+        &destination_account.pubkey(),
+        &payer.pubkey(),
+        None,
+    ).unwrap();
+
+    let mut migrate_transaction = Transaction::new_with_payer(&[migrate_instruction.clone()], Some(&payer.pubkey()));
+    migrate_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    banks_client.process_transaction(migrate_transaction).await.unwrap();
+
+    let vesting_record = banks_client.get_account_data_with_borsh::<VestingRecord>(vesting_account_key).await.unwrap();
+    assert_eq!(vesting_record.account_type, VestingAccountType::VestingRecordV2);
+    assert_eq!(vesting_record.owner, destination_account.pubkey());
+    assert_eq!(vesting_record.grantor, Pubkey::default());
+    assert_eq!(vesting_record.rent_payer, payer.pubkey());
+    assert_eq!(vesting_record.bump_seed, vesting_account_bump_seed);
+    assert_eq!(
+        vesting_record.schedule,
+        vec![
+            VestingSchedule {release_time: 0, amount: 30, release_proposal: None},
+            VestingSchedule {release_time: 1_000_000_000_000, amount: 70, release_proposal: None},
+        ]
+    );
+
+    let simulation = banks_client.simulate_transaction(get_status_transaction).await.unwrap();
+    assert_eq!(simulation.result, Some(Ok(())));
+
+    // The migrated vesting account can't be migrated again
+    let recent_blockhash = banks_client.get_new_latest_blockhash(&recent_blockhash).await.unwrap();
+    let mut migrate_transaction = Transaction::new_with_payer(&[migrate_instruction], Some(&payer.pubkey()));
+    migrate_transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(migrate_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::InvalidVestingAccount)
+    );
+}
+
+#[tokio::test]
+async fn test_token_vesting_migrate_voter_weight_record() {
+
+    let program_id = Pubkey::from_str("VestingbGKPFXCWuBvfkegQfZyiNwAJb9Ss623VQ5DA").unwrap();
+    let realm = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+    let (voter_weight_record_address, voter_weight_record_bump_seed) = Pubkey::find_program_address(
+        &get_voter_weight_record_seeds(&realm, &mint, &owner),
+        &program_id,
+    );
+
+    // The VoterWeightRecord created by the previous version of the addin
+    let legacy_voter_weight_record = ExtendedVoterWeightRecordV1 {
+        base: VoterWeightRecord {
+            account_discriminator: VoterWeightRecord::ACCOUNT_DISCRIMINATOR,
+            realm,
+            governing_token_mint: mint,
+            governing_token_owner: owner,
+            voter_weight: 50,
+            voter_weight_expiry: None,
+            weight_action: None,
+            weight_action_target: None,
+            reserved: [0u8; 8],
+        },
+        account_discriminator: ExtendedVoterWeightRecordV1::ACCOUNT_DISCRIMINATOR,
+        total_amount: 100,
+        vote_percentage: 5_000,
+    };
+    let legacy_voter_weight_data = legacy_voter_weight_record.try_to_vec().unwrap();
+
+    let mut program_test = ProgramTest::new(
+        "spl_governance_addin_vesting",
+        program_id,
+        processor!(process_instruction),
+    );
+    program_test.add_account(
+        voter_weight_record_address,
+        Account {
+            lamports: Rent::default().minimum_balance(legacy_voter_weight_data.len()),
+            data: legacy_voter_weight_data,
+            owner: program_id,
+            ..Account::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // The legacy VoterWeightRecord is rejected until it's migrated
+    let mut update_transaction = Transaction::new_with_payer(
+        &[
+            vesting_instruction::update_voter_weight_record(
+                &program_id,
+                &owner,
+                &owner,
+                &Pubkey::new_unique(),
+                &realm,
+                &mint,
+                None,
+            ).unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    update_transaction.partial_sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(update_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::VoterWeightRecordMigrationRequired)
+    );
+
+    let migrate_instruction = vesting_instruction::migrate_voter_weight_record(
+        &program_id,
+        &owner,
+        &realm,
+        &mint,
+        &payer.pubkey(),
+    ).unwrap();
+
+    let mut migrate_transaction = Transaction::new_with_payer(&[migrate_instruction.clone()], Some(&payer.pubkey()));
+    migrate_transaction.partial_sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(migrate_transaction).await.unwrap();
+
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.base, legacy_voter_weight_record.base);
+    assert_eq!(voter_weight_record.account_discriminator, ExtendedVoterWeightRecord::ACCOUNT_DISCRIMINATOR);
+    assert_eq!(voter_weight_record.total_amount, 100);
+    assert_eq!(voter_weight_record.weighted_amount, 100);
+    assert_eq!(voter_weight_record.vote_percentage, 5_000);
+    assert_eq!(voter_weight_record.vote_percentage_expiry, None);
+    assert_eq!(voter_weight_record.vote_amount, None);
+    assert_eq!(
+        voter_weight_record.checkpoints,
+        [VoterWeightCheckpoint {slot: 0, voter_weight: 50}; VOTER_WEIGHT_CHECKPOINTS]
+    );
+    assert_eq!(voter_weight_record.bump_seed, voter_weight_record_bump_seed);
+
+    // The migrated record can't be migrated again
+    let recent_blockhash = banks_client.get_new_latest_blockhash(&recent_blockhash).await.unwrap();
+    let mut migrate_transaction = Transaction::new_with_payer(&[migrate_instruction], Some(&payer.pubkey()));
+    migrate_transaction.partial_sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(migrate_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::InvalidVoterWeightRecordAccountAddress)
+    );
+}
+
+#[tokio::test]
+async fn test_token_vesting_compute_units() {
+    // Compute budget for the instructions of the vesting under Realm (half of the default budget
+    // of the instruction, so the instructions can be batched). The units are consumed by the BPF build
+    // of the program only (cargo test-bpf), the native processor doesn't consume them
+    const MAX_COMPUTE_UNITS: u64 = 100_000;
+
+    let mut context = VestingTestContext::new(200).await;
+    let realm_address = context.setup_realm("testing realm", true).await;
+
+    let destination_account = Keypair::new();
+    let destination_token_account = context.create_token_account(&destination_account.pubkey()).await;
+    let splitted_vesting_owner = Keypair::new();
+    let (vesting_token_account, vesting_account_key) = context.create_vesting_token_account().await;
+    let (splitted_vesting_token_account, _) = context.create_vesting_token_account().await;
+
+    let VestingTestContext {
+        program_id, governance_id, mut banks_client, payer, recent_blockhash, mint, source_account,
+        source_token_account, ..
+    } = context;
+
+    let deposit_instruction = vesting_instruction::deposit_with_realm(
+        &program_id,
+        &spl_token::id(),
+        &vesting_token_account.pubkey(),
+        &source_account.pubkey(),
+        &source_token_account.pubkey(),
+        &destination_account.pubkey(),
+        &payer.pubkey(),
+        vec![
+            VestingSchedule {amount: 100, release_time: 0, release_proposal: None},
+            VestingSchedule {amount: 100, release_time: 1_000_000_000_000, release_proposal: None},
+        ],
+        &realm_address,
+        &mint.pubkey(),
+        &governance_id,
+        None,
+        false,
+    ).unwrap();
+
+    // Only the canonical bump seed is accepted when the vesting account is created
+    let (_, vesting_account_bump_seed) = Pubkey::find_program_address(&[vesting_token_account.pubkey().as_ref()], &program_id);
+    let (non_canonical_vesting_account_key, non_canonical_bump_seed) = (0..vesting_account_bump_seed).rev()
+            .find_map(|bump_seed| {
+                Pubkey::create_program_address(&[vesting_token_account.pubkey().as_ref(), &[bump_seed]], &program_id)
+                    .ok()
+                    .map(|key| (key, bump_seed))
+            })
+            .unwrap();
+    let mut non_canonical_deposit_instruction = deposit_instruction.clone();
+    for account in non_canonical_deposit_instruction.accounts.iter_mut() {
+        if account.pubkey == vesting_account_key {
+            account.pubkey = non_canonical_vesting_account_key;
+        }
+    }
+    *non_canonical_deposit_instruction.data.last_mut().unwrap() = non_canonical_bump_seed;
+    let mut non_canonical_deposit_transaction = Transaction::new_with_payer(&[non_canonical_deposit_instruction], Some(&payer.pubkey()));
+    non_canonical_deposit_transaction.partial_sign(&[&payer, &source_account], recent_blockhash);
+    assert_eq!(
+        banks_client.process_transaction(non_canonical_deposit_transaction).await.unwrap_err().unwrap(),
+        trx_instruction_error(0, VestingError::InvalidVestingAccount)
+    );
+
+    let mut deposit_transaction = Transaction::new_with_payer(&[deposit_instruction], Some(&payer.pubkey()));
+    deposit_transaction.partial_sign(&[&payer, &source_account], recent_blockhash);
+    banks_client.process_transaction(deposit_transaction).await.unwrap();
+
+    // The bump seeds are stored in the records, so the addresses are checked by create_program_address
+    let voter_weight_record_seeds = get_voter_weight_record_seeds(&realm_address, &mint.pubkey(), &destination_account.pubkey());
+    let (voter_weight_record_address, voter_weight_record_bump_seed) = Pubkey::find_program_address(&voter_weight_record_seeds, &program_id);
+    let vesting_record = banks_client.get_account_data_with_borsh::<VestingRecord>(vesting_account_key).await.unwrap();
+    assert_eq!(vesting_record.bump_seed, vesting_account_bump_seed);
+    let voter_weight_record = get_voter_weight_record(&mut banks_client, voter_weight_record_address).await;
+    assert_eq!(voter_weight_record.bump_seed, voter_weight_record_bump_seed);
+
+    let instructions = [
+        ("Withdraw", vesting_instruction::withdraw_with_realm(
+            &program_id,
+            &spl_token::id(),
+            &vesting_token_account.pubkey(),
+            &destination_token_account.pubkey(),
+            &destination_account.pubkey(),
+            &governance_id,
+            &realm_address,
+            &mint.pubkey(),
+            &[],
+            None,
+        ).unwrap()),
+        ("Split", vesting_instruction::split_with_realm(
+            &program_id,
+            &spl_token::id(),
+            &vesting_token_account.pubkey(),
+            &destination_account.pubkey(),
+            &splitted_vesting_token_account.pubkey(),
+            &splitted_vesting_owner.pubkey(),
+            &payer.pubkey(),
+            vec![
+                VestingSchedule {amount: 50, release_time: 1_000_000_000_000, release_proposal: None},
+            ],
+            None,
+            &governance_id,
+            &realm_address,
+            &mint.pubkey(),
+            None,
+        ).unwrap()),
+    ];
+
+    for (name, instruction) in instructions {
+        let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+        transaction.partial_sign(&[&payer, &destination_account], recent_blockhash);
+        let simulation = banks_client.simulate_transaction(transaction).await.unwrap();
+        assert_eq!(simulation.result, Some(Ok(())));
+
+        let units_consumed = simulation.simulation_details.unwrap().units_consumed;
+        println!("{} consumed {} compute units", name, units_consumed);
+        assert!(units_consumed <= MAX_COMPUTE_UNITS);
+    }
+}
+
+/// Reads ExtendedVoterWeightRecord (the account is allocated for all optional fields)
+async fn get_voter_weight_record(banks_client: &mut BanksClient, address: Pubkey) -> ExtendedVoterWeightRecord {
+    let account = banks_client.get_account(address).await.unwrap().unwrap();
+    try_from_slice_unchecked::<ExtendedVoterWeightRecord>(&account.data).unwrap()
+}
+
+/// Test network with the vesting addin and the governance program,
+/// the mint and the source token account of the depositor
+struct VestingTestContext {
+    program_id: Pubkey,
+    governance_id: Pubkey,
+    banks_client: BanksClient,
+    payer: Keypair,
+    recent_blockhash: Hash,
+    mint_authority: Keypair,
+    mint: Keypair,
+    source_account: Keypair,
+    source_token_account: Keypair,
+}
+
+impl VestingTestContext {
+    /// Starts the test network and mints the amount to the source token account
+    async fn new(amount: u64) -> Self {
+        let program_id = Pubkey::from_str("VestingbGKPFXCWuBvfkegQfZyiNwAJb9Ss623VQ5DA").unwrap();
+        let governance_id = Pubkey::from_str("5ZYgDTqLbYJ2UAtF7rbUboSt9Q6bunCQgGEwxDFrQrXb").unwrap();
+
+        let mut program_test = ProgramTest::new(
+            "spl_governance_addin_vesting",
+            program_id,
+            processor!(process_instruction),
+        );
+
+        program_test.add_program(
+            "spl_governance",
+            governance_id,
+            None,
+        );
+
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+        let mint_authority = Keypair::new();
+        let mint = Keypair::new();
+        banks_client.process_transaction(mint_init_transaction(
+            &payer,
+            &mint,
+            &mint_authority,
+            recent_blockhash
+        )).await.unwrap();
+
+        let source_account = Keypair::new();
+        let source_token_account = Keypair::new();
+        banks_client.process_transaction(
+            create_token_account(&payer, &mint, recent_blockhash, &source_token_account, &source_account.pubkey())
+        ).await.unwrap();
+
+        let mut context = VestingTestContext {
+            program_id,
+            governance_id,
+            banks_client,
+            payer,
+            recent_blockhash,
+            mint_authority,
+            mint,
+            source_account,
+            source_token_account,
+        };
+        let source_token_account = context.source_token_account.pubkey();
+        context.mint_to(&source_token_account, amount).await;
+        context
+    }
+
+    /// Creates the transaction signed by the payer and the signers
+    fn transaction(&self, instructions: &[Instruction], signers: &[&Keypair]) -> Transaction {
+        let mut transaction = Transaction::new_with_payer(instructions, Some(&self.payer.pubkey()));
+        transaction.partial_sign(&[&self.payer], self.recent_blockhash);
+        transaction.partial_sign(signers, self.recent_blockhash);
+        transaction
+    }
+
+    /// Processes the transaction signed by the payer and the signers
+    async fn process_transaction(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), BanksClientError> {
+        let transaction = self.transaction(instructions, signers);
+        self.banks_client.process_transaction(transaction).await
+    }
+
+    /// Mints the amount to the token account
+    async fn mint_to(&mut self, token_account: &Pubkey, amount: u64) {
+        let instruction = token_instruction::mint_to(
+            &spl_token::id(),
+            &self.mint.pubkey(),
+            token_account,
+            &self.mint_authority.pubkey(),
+            &[],
+            amount
+        ).unwrap();
+        let transaction = self.transaction(&[instruction], &[&self.mint_authority]);
+        self.banks_client.process_transaction(transaction).await.unwrap();
+    }
+
+    /// Creates the token account of the mint for the owner
+    async fn create_token_account(&mut self, owner: &Pubkey) -> Keypair {
+        let token_account = Keypair::new();
+        self.banks_client.process_transaction(
+            create_token_account(&self.payer, &self.mint, self.recent_blockhash, &token_account, owner)
+        ).await.unwrap();
+        token_account
+    }
+
+    /// Creates the token account owned by the vesting account PDA,
+    /// returns the token account and the vesting account address
+    async fn create_vesting_token_account(&mut self) -> (Keypair, Pubkey) {
+        let vesting_token_account = Keypair::new();
+        let (vesting_account_key, _) = Pubkey::find_program_address(&[vesting_token_account.pubkey().as_ref()], &self.program_id);
+        self.banks_client.process_transaction(
+            create_token_account(&self.payer, &self.mint, self.recent_blockhash, &vesting_token_account, &vesting_account_key)
+        ).await.unwrap();
+        (vesting_token_account, vesting_account_key)
+    }
+
+    /// Creates the Realm governed by the mint authority with or without the vesting addin,
+    /// returns the Realm address
+    async fn setup_realm(&mut self, name: &str, use_vesting_addin: bool) -> Pubkey {
+        let voter_weight_addin = if use_vesting_addin { Some(self.program_id) } else { None };
+        let instruction = governance_instruction::create_realm(
+            &self.governance_id,
+            &self.mint_authority.pubkey(),
+            &self.mint.pubkey(),
+            &self.payer.pubkey(),
+            None, voter_weight_addin, voter_weight_addin,
+            name.to_string(),
+            1,
+            MintMaxVoteWeightSource::SupplyFraction(10_000_000_000)
+        );
+        self.process_transaction(&[instruction], &[]).await.unwrap();
+        get_realm_address(&self.governance_id, name)
+    }
+
+    /// Sets the vesting addin settings of the Realm signed by the mint authority (the Realm authority)
+    async fn set_vesting_config(
+        &mut self,
+        realm: &Pubkey,
+        weight_curve: WeightCurve,
+        max_voter_weight_share: u16,
+        max_voter_weight_source: MaxVoterWeightSource,
+        count_unvested_only: bool,
+    ) {
+        let instruction = vesting_instruction::set_vesting_config(
+            &self.program_id,
+            &self.governance_id,
+            realm,
+            &self.mint_authority.pubkey(),
+            &self.payer.pubkey(),
+            &self.mint.pubkey(),
+            weight_curve,
+            max_voter_weight_share,
+            max_voter_weight_source,
+            count_unvested_only,
+            None,
+            false,
+            10_000,
+        ).unwrap();
+        let transaction = self.transaction(&[instruction], &[&self.mint_authority]);
+        self.banks_client.process_transaction(transaction).await.unwrap();
+    }
 }
 
 fn mint_init_transaction(